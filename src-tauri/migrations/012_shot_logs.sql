@@ -0,0 +1,18 @@
+-- Migration 012: Per-frame shot log
+-- Film cameras record no EXIF, so the shot log is the only source of
+-- per-frame shooting settings. One row per photo, written to EXIF on demand.
+
+CREATE TABLE shot_logs (
+    photo_id INTEGER PRIMARY KEY,
+    frame_number INTEGER,               -- Frame number as noted while shooting
+    aperture REAL,                      -- f-number, e.g. 2.8
+    shutter_speed TEXT,                 -- e.g. "1/125" or "2"
+    focal_length REAL,                  -- millimetres
+    exposure_compensation REAL,         -- EV, e.g. -0.7
+    metering_mode TEXT,                 -- e.g. "spot", "center-weighted"
+    flash BOOLEAN,
+    notes TEXT,
+    created_at DATETIME DEFAULT CURRENT_TIMESTAMP,
+    updated_at DATETIME DEFAULT CURRENT_TIMESTAMP,
+    FOREIGN KEY (photo_id) REFERENCES photos(id) ON DELETE CASCADE
+);
@@ -0,0 +1,5 @@
+-- Migration 013: Add capture time to shot logs
+-- Logbook apps record when each frame was exposed; written as DateTimeOriginal
+
+-- Capture time in "YYYY-MM-DD HH:MM:SS" (local time)
+ALTER TABLE shot_logs ADD COLUMN shot_at TEXT;
@@ -0,0 +1,7 @@
+-- Migration 014: Content hash per photo
+-- BLAKE3 hash of the original file as delivered, used to detect files that
+-- were already imported (e.g. a lab re-delivering the same scans)
+
+ALTER TABLE photos ADD COLUMN content_hash TEXT;
+
+CREATE INDEX IF NOT EXISTS idx_photos_content_hash ON photos(content_hash);
@@ -0,0 +1,5 @@
+-- Migration 015: Perceptual hash per photo
+-- 64-bit dHash of the thumbnail (16 hex digits), used to find visually
+-- near-identical photos such as re-scans or TIFF + JPEG deliveries
+
+ALTER TABLE photos ADD COLUMN perceptual_hash TEXT;
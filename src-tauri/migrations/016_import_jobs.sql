@@ -0,0 +1,32 @@
+-- Migration 016: Import jobs
+-- Imports are recorded before any file is touched so an import interrupted by
+-- a crash or app exit can be rolled back or finished on the next start.
+
+CREATE TABLE import_jobs (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    roll_id INTEGER NOT NULL,
+    created_roll BOOLEAN NOT NULL DEFAULT 0,   -- The roll was created by this import
+    copy_mode BOOLEAN NOT NULL DEFAULT 1,      -- 1 = copy, 0 = move
+    auto_write_exif BOOLEAN NOT NULL DEFAULT 0,
+    replaced_photo_ids TEXT,                   -- JSON array of photos replaced by this import
+    state TEXT NOT NULL DEFAULT 'pending',     -- pending, copying, generating_derivatives, writing_db, writing_exif, done, cancelled, failed
+    error TEXT,
+    created_at DATETIME DEFAULT CURRENT_TIMESTAMP,
+    updated_at DATETIME DEFAULT CURRENT_TIMESTAMP
+);
+
+CREATE INDEX idx_import_jobs_state ON import_jobs(state);
+
+-- Planned files of a job; removed once the job has finished
+CREATE TABLE import_job_files (
+    job_id INTEGER NOT NULL,
+    position INTEGER NOT NULL,
+    source_path TEXT NOT NULL,
+    content_hash TEXT,
+    filename TEXT NOT NULL,
+    original_path TEXT NOT NULL,
+    thumbnail_path TEXT NOT NULL,
+    preview_path TEXT NOT NULL,
+    PRIMARY KEY (job_id, position),
+    FOREIGN KEY (job_id) REFERENCES import_jobs(id) ON DELETE CASCADE
+);
@@ -0,0 +1,4 @@
+-- Migration 017: Filename template for imported originals
+-- Tokens: {date}, {film}, {camera}, {roll_id}, {roll_name}, {frame}, {original_name}
+-- NULL means the default template ROLL_{roll_id}_{frame}
+ALTER TABLE settings ADD COLUMN filename_template TEXT;
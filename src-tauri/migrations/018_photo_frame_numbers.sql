@@ -0,0 +1,10 @@
+-- Migration 018: Original scan filenames and lab frame numbers
+-- original_filename: name of the file as delivered by the lab/scanner
+-- frame_number: frame label parsed from it ("12", "12A"); photos are ordered by it
+ALTER TABLE photos ADD COLUMN original_filename TEXT;
+ALTER TABLE photos ADD COLUMN frame_number TEXT;
+
+ALTER TABLE import_job_files ADD COLUMN frame_number TEXT;
+
+-- User regexes for frame numbers (JSON array), tried before the built-in scanner patterns
+ALTER TABLE settings ADD COLUMN frame_patterns TEXT;
@@ -0,0 +1,15 @@
+-- Migration 019: Explicit photo order within a roll
+-- sequence: 1-based position in the roll; set on import, changed by reordering
+ALTER TABLE photos ADD COLUMN sequence INTEGER;
+
+-- Keep the order photos were shown in so far: by frame number, then filename
+WITH ordered AS (
+    SELECT id, ROW_NUMBER() OVER (
+        PARTITION BY roll_id
+        ORDER BY frame_number IS NULL, CAST(frame_number AS INTEGER), frame_number, filename, id
+    ) AS position
+    FROM photos
+)
+UPDATE photos SET sequence = (SELECT position FROM ordered WHERE ordered.id = photos.id);
+
+CREATE INDEX IF NOT EXISTS idx_photos_roll_sequence ON photos(roll_id, sequence);
@@ -0,0 +1,28 @@
+-- Migration 020: Store roll and photo paths relative to the library root
+-- Paths inside the root become `<year>/<roll>/...` with `/` separators;
+-- paths outside it stay absolute. They are resolved against the root on read.
+UPDATE rolls SET
+    path = CASE
+        WHEN substr(path, 1, length(r.root) + 1) IN (r.root || '/', r.root || '\')
+        THEN replace(substr(path, length(r.root) + 2), '\', '/')
+        ELSE path
+    END
+FROM (SELECT rtrim(rtrim(value, '/'), '\') AS root FROM settings WHERE key = 'library_root' AND value <> '') AS r;
+
+UPDATE photos SET
+    file_path = CASE
+        WHEN substr(file_path, 1, length(r.root) + 1) IN (r.root || '/', r.root || '\')
+        THEN replace(substr(file_path, length(r.root) + 2), '\', '/')
+        ELSE file_path
+    END,
+    thumbnail_path = CASE
+        WHEN substr(thumbnail_path, 1, length(r.root) + 1) IN (r.root || '/', r.root || '\')
+        THEN replace(substr(thumbnail_path, length(r.root) + 2), '\', '/')
+        ELSE thumbnail_path
+    END,
+    preview_path = CASE
+        WHEN substr(preview_path, 1, length(r.root) + 1) IN (r.root || '/', r.root || '\')
+        THEN replace(substr(preview_path, length(r.root) + 2), '\', '/')
+        ELSE preview_path
+    END
+FROM (SELECT rtrim(rtrim(value, '/'), '\') AS root FROM settings WHERE key = 'library_root' AND value <> '') AS r;
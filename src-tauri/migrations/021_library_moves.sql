@@ -0,0 +1,28 @@
+-- Migration 021: Library moves
+-- Moving the library to a new root copies every roll folder with checksum
+-- verification before the database is switched over, so an interrupted move
+-- can be resumed where it stopped.
+
+CREATE TABLE library_moves (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    source_root TEXT NOT NULL,
+    target_root TEXT NOT NULL,
+    copy_mode BOOLEAN NOT NULL DEFAULT 0,      -- 1 = keep the old folders, 0 = remove them after switching
+    state TEXT NOT NULL DEFAULT 'copying',     -- copying, switched, done, cancelled, failed
+    error TEXT,
+    created_at DATETIME DEFAULT CURRENT_TIMESTAMP,
+    updated_at DATETIME DEFAULT CURRENT_TIMESTAMP
+);
+
+CREATE INDEX idx_library_moves_state ON library_moves(state);
+
+-- Files of a move; content_hash is set once the copy has been verified
+CREATE TABLE library_move_files (
+    move_id INTEGER NOT NULL,
+    source_path TEXT NOT NULL,
+    target_path TEXT NOT NULL,
+    size INTEGER NOT NULL,
+    content_hash TEXT,
+    PRIMARY KEY (move_id, source_path),
+    FOREIGN KEY (move_id) REFERENCES library_moves(id) ON DELETE CASCADE
+);
@@ -0,0 +1,8 @@
+-- Migration 022: Automatic database backups
+-- Backups are written to a `backups` folder next to the database
+
+-- Hours between scheduled backups (default: 24, 0 turns scheduled backups off)
+ALTER TABLE settings ADD COLUMN backup_interval_hours INTEGER DEFAULT 24;
+
+-- Number of automatic backups to keep (default: 10)
+ALTER TABLE settings ADD COLUMN backup_keep INTEGER DEFAULT 10;
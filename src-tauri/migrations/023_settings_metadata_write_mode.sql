@@ -0,0 +1,4 @@
+-- Migration 023: Metadata write mode
+-- 'embedded' writes metadata into the original files,
+-- 'sidecar' writes .xmp sidecars next to them and never modifies the originals
+ALTER TABLE settings ADD COLUMN metadata_write_mode TEXT DEFAULT 'embedded';
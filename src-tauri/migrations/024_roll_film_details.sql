@@ -0,0 +1,8 @@
+-- Migration 024: Film details of a roll
+-- Written to photos as discrete fields in the FilmVault XMP namespace
+
+ALTER TABLE rolls ADD COLUMN film_iso INTEGER;      -- Box speed of the film stock
+ALTER TABLE rolls ADD COLUMN film_format TEXT;      -- e.g. 35mm, 120
+ALTER TABLE rolls ADD COLUMN film_process TEXT;     -- e.g. C-41, E-6, B&W
+ALTER TABLE rolls ADD COLUMN push_pull REAL;        -- Stops pushed (+) or pulled (-) in development
+ALTER TABLE rolls ADD COLUMN scanner TEXT;
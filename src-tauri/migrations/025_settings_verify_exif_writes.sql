@@ -0,0 +1,3 @@
+-- Migration 025: EXIF write verification
+-- When enabled, each roll write is read back and compared with the database
+ALTER TABLE settings ADD COLUMN verify_exif_writes INTEGER DEFAULT 0;
@@ -0,0 +1,4 @@
+-- Migration 026: Remember originals the content hash backfill could not read
+-- Photos imported before migration 014 are hashed once in the background;
+-- a missing or unreadable original is marked here and not tried again
+ALTER TABLE photos ADD COLUMN content_hash_failed_at DATETIME;
@@ -0,0 +1,9 @@
+-- Migration 027: Keep the frame number on the photo only
+-- Shot logs duplicated photos.frame_number; a frame noted only in the
+-- shot log moves to the photo before the shot log column is dropped
+UPDATE photos
+SET frame_number = (SELECT CAST(s.frame_number AS TEXT) FROM shot_logs s WHERE s.photo_id = photos.id)
+WHERE frame_number IS NULL
+  AND EXISTS (SELECT 1 FROM shot_logs s WHERE s.photo_id = photos.id AND s.frame_number IS NOT NULL);
+
+ALTER TABLE shot_logs DROP COLUMN frame_number;
@@ -0,0 +1,337 @@
+/*!
+ * FilmVault Archive Import
+ *
+ * Lab deliveries often arrive as ZIP or TAR downloads. Their images are read
+ * straight out of the archive instead of being unpacked to a temp folder
+ * first. Each image entry is addressed by a virtual path
+ * `<archive path>/<entry name>`, so duplicate checks, import jobs and
+ * progress events treat entries like files on disk.
+ */
+
+use std::collections::BTreeMap;
+use std::fs::{self, File};
+use std::io::{self, BufReader, Read};
+use std::path::{Component, Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use anyhow::{Context, Result};
+
+use crate::image_processor::{is_supported_image, FailedImport, ImportFile, PlannedImport};
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum ArchiveKind {
+    Zip,
+    Tar,
+    TarGz,
+}
+
+fn archive_kind(path: &Path) -> Option<ArchiveKind> {
+    let name = path.file_name()?.to_str()?.to_lowercase();
+    if name.ends_with(".zip") {
+        Some(ArchiveKind::Zip)
+    } else if name.ends_with(".tar") {
+        Some(ArchiveKind::Tar)
+    } else if name.ends_with(".tar.gz") || name.ends_with(".tgz") {
+        Some(ArchiveKind::TarGz)
+    } else {
+        None
+    }
+}
+
+/// Check if a path is a ZIP or TAR (optionally gzipped) archive
+pub fn is_archive(path: &Path) -> bool {
+    path.is_file() && archive_kind(path).is_some()
+}
+
+/// Archive file name without its archive extension ("delivery.tar.gz" -> "delivery")
+pub fn archive_stem(path: &Path) -> String {
+    let name = path.file_name().map(|n| n.to_string_lossy().to_string()).unwrap_or_default();
+    let lower = name.to_lowercase();
+    for ext in [".tar.gz", ".tgz", ".tar", ".zip"] {
+        if lower.ends_with(ext) {
+            return name[..name.len() - ext.len()].to_string();
+        }
+    }
+    name
+}
+
+/// Virtual path of an archive entry
+pub fn entry_path(archive: &Path, entry_name: &str) -> PathBuf {
+    archive.join(entry_name)
+}
+
+/// Folder of an entry inside the archive ("" for the top level)
+fn entry_folder(entry_name: &str) -> &str {
+    entry_name.rsplit_once('/').map(|(folder, _)| folder).unwrap_or("")
+}
+
+/// Normalize an entry path to `/`-separated form; None for paths escaping the archive
+fn normalize_entry_name(path: &Path) -> Option<String> {
+    let mut parts = Vec::new();
+    for component in path.components() {
+        match component {
+            Component::Normal(part) => parts.push(part.to_str()?),
+            Component::CurDir => {}
+            _ => return None,
+        }
+    }
+    (!parts.is_empty()).then(|| parts.join("/"))
+}
+
+/// Skip hidden files and folders and archive metadata (`__MACOSX`, `.DS_Store`, ...)
+fn is_ignored_entry(entry_name: &str) -> bool {
+    entry_name.split('/').any(|part| part.starts_with('.') || part == "__MACOSX")
+}
+
+/// Whether an entry is an image to import (optionally only directly inside `folder`)
+fn is_image_entry(entry_name: &str, folder: Option<&str>) -> bool {
+    !is_ignored_entry(entry_name)
+        && is_supported_image(Path::new(entry_name))
+        && folder.is_none_or(|folder| entry_folder(entry_name) == folder)
+}
+
+/// Visit every file entry of an archive in archive order
+/// The callback gets the normalized entry name and a reader for its contents;
+/// returning false stops the walk.
+fn for_each_entry<F>(archive: &Path, mut visit: F) -> Result<()>
+where
+    F: FnMut(&str, &mut dyn Read) -> Result<bool>,
+{
+    let kind = archive_kind(archive).context("Unsupported archive format")?;
+    let file = File::open(archive).context("Failed to open archive")?;
+
+    match kind {
+        ArchiveKind::Zip => {
+            let mut zip = zip::ZipArchive::new(BufReader::new(file)).context("Failed to read ZIP archive")?;
+            for index in 0..zip.len() {
+                let mut entry = zip.by_index(index).context("Failed to read ZIP entry")?;
+                if !entry.is_file() {
+                    continue;
+                }
+                let Some(name) = entry.enclosed_name().as_deref().and_then(normalize_entry_name) else {
+                    continue;
+                };
+                if !visit(&name, &mut entry)? {
+                    break;
+                }
+            }
+        }
+        ArchiveKind::Tar | ArchiveKind::TarGz => {
+            let reader: Box<dyn Read> = if kind == ArchiveKind::TarGz {
+                Box::new(flate2::read::GzDecoder::new(BufReader::new(file)))
+            } else {
+                Box::new(BufReader::new(file))
+            };
+            let mut tar = tar::Archive::new(reader);
+            for entry in tar.entries().context("Failed to read TAR archive")? {
+                let mut entry = entry.context("Failed to read TAR entry")?;
+                if !entry.header().entry_type().is_file() {
+                    continue;
+                }
+                let Some(name) = entry.path().ok().as_deref().and_then(normalize_entry_name) else {
+                    continue;
+                };
+                if !visit(&name, &mut entry)? {
+                    break;
+                }
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// Names of the image entries of an archive, sorted by path
+/// With `folder`, only images directly inside that folder ("" for the top level)
+pub fn list_archive_images(archive: &Path, folder: Option<&str>) -> Result<Vec<String>> {
+    let mut names = Vec::new();
+    for_each_entry(archive, |name, _| {
+        if is_image_entry(name, folder) {
+            names.push(name.to_string());
+        }
+        Ok(true)
+    })?;
+    names.sort();
+    Ok(names)
+}
+
+/// Hash the image entries of an archive in one pass, sorted by path
+pub fn hash_archive_images(archive: &Path, folder: Option<&str>) -> Result<Vec<ImportFile>> {
+    let mut files = Vec::new();
+    for_each_entry(archive, |name, reader| {
+        if is_image_entry(name, folder) {
+            let mut hasher = blake3::Hasher::new();
+            io::copy(reader, &mut hasher)
+                .with_context(|| format!("Failed to read {} from archive", name))?;
+            files.push(ImportFile {
+                source_path: entry_path(archive, name),
+                content_hash: hasher.finalize().to_hex().to_string(),
+            });
+        }
+        Ok(true)
+    })?;
+    files.sort_by(|a, b| a.source_path.cmp(&b.source_path));
+    Ok(files)
+}
+
+/// Folders inside an archive that directly contain images, with their image counts
+pub fn archive_image_folders(archive: &Path) -> Result<Vec<(String, usize)>> {
+    let mut counts: BTreeMap<String, usize> = BTreeMap::new();
+    for name in list_archive_images(archive, None)? {
+        *counts.entry(entry_folder(&name).to_string()).or_default() += 1;
+    }
+    Ok(counts.into_iter().collect())
+}
+
+/// Extract planned archive entries straight into the roll's `originals/`
+///
+/// Reads the archive once, in archive order, and stops before the next entry
+/// once `cancel` is set. Entries that can't be written or aren't in the
+/// archive are returned as failures; the archive itself is never modified.
+pub fn extract_planned_files<F>(
+    archive: &Path,
+    planned: &[PlannedImport],
+    cancel: &AtomicBool,
+    mut progress_callback: F,
+) -> Result<Vec<FailedImport>>
+where
+    F: FnMut(usize, usize, String),
+{
+    let total = planned.len();
+    let mut pending: BTreeMap<PathBuf, &PlannedImport> = planned
+        .iter()
+        .map(|file| (file.source_path.clone(), file))
+        .collect();
+    let mut failed = Vec::new();
+    let mut extracted = 0;
+
+    for_each_entry(archive, |name, reader| {
+        if cancel.load(Ordering::SeqCst) {
+            return Ok(false);
+        }
+        let Some(file) = pending.remove(&entry_path(archive, name)) else {
+            return Ok(true);
+        };
+
+        // create_new: a file already in the roll is never replaced, nor removed on failure
+        let result = fs::OpenOptions::new()
+            .write(true)
+            .create_new(true)
+            .open(&file.original_path)
+            .and_then(|mut dest| io::copy(reader, &mut dest).inspect_err(|_| {
+                let _ = fs::remove_file(&file.original_path);
+            }));
+        if let Err(e) = result {
+            eprintln!("Warning: Failed to extract {} from {:?}: {}", name, archive, e);
+            failed.push(FailedImport { source_path: file.source_path.clone(), error: e.to_string() });
+        }
+
+        extracted += 1;
+        let display_name = name.rsplit('/').next().unwrap_or(name).to_string();
+        progress_callback(extracted, total, display_name);
+        Ok(!pending.is_empty())
+    })?;
+
+    if !cancel.load(Ordering::SeqCst) {
+        for file in pending.into_values() {
+            failed.push(FailedImport {
+                source_path: file.source_path.clone(),
+                error: "Not found in archive".to_string(),
+            });
+        }
+    }
+
+    Ok(failed)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Write;
+    use crate::image_processor::plan_import_files;
+
+    const ENTRIES: &[(&str, &[u8])] = &[
+        ("delivery/roll_02/002.jpg", b"frame two"),
+        ("delivery/roll_02/001.jpg", b"frame one"),
+        ("delivery/roll_01/001.tif", b"other roll"),
+        ("delivery/contact.jpg", b"contact sheet"),
+        ("delivery/invoice.pdf", b"not an image"),
+        ("__MACOSX/delivery/roll_01/._001.tif", b"resource fork"),
+    ];
+
+    fn write_zip(path: &Path) {
+        let mut zip = zip::ZipWriter::new(File::create(path).unwrap());
+        for (name, data) in ENTRIES {
+            zip.start_file(*name, zip::write::SimpleFileOptions::default()).unwrap();
+            zip.write_all(data).unwrap();
+        }
+        zip.finish().unwrap();
+    }
+
+    fn write_tar(path: &Path) {
+        let mut tar = tar::Builder::new(File::create(path).unwrap());
+        for (name, data) in ENTRIES {
+            let mut header = tar::Header::new_gnu();
+            header.set_size(data.len() as u64);
+            header.set_mode(0o644);
+            header.set_cksum();
+            tar.append_data(&mut header, name, *data).unwrap();
+        }
+        tar.finish().unwrap();
+    }
+
+    #[test]
+    fn test_archive_names() {
+        assert_eq!(archive_stem(Path::new("/dl/Lab Order 42.tar.gz")), "Lab Order 42");
+        assert_eq!(archive_stem(Path::new("scans.ZIP")), "scans");
+        assert_eq!(normalize_entry_name(Path::new("./a/b.jpg")).as_deref(), Some("a/b.jpg"));
+        assert_eq!(normalize_entry_name(Path::new("../evil.jpg")), None);
+        assert_eq!(entry_folder("a/b/c.jpg"), "a/b");
+        assert_eq!(entry_folder("c.jpg"), "");
+    }
+
+    #[test]
+    fn test_zip_and_tar_import() {
+        let base = std::env::temp_dir().join(format!("filmvault_archive_test_{}", std::process::id()));
+        fs::create_dir_all(&base).unwrap();
+
+        for archive in [base.join("delivery.zip"), base.join("delivery.tar")] {
+            if archive_kind(&archive) == Some(ArchiveKind::Zip) {
+                write_zip(&archive);
+            } else {
+                write_tar(&archive);
+            }
+            assert!(is_archive(&archive));
+
+            assert_eq!(list_archive_images(&archive, None).unwrap(), vec![
+                "delivery/contact.jpg", "delivery/roll_01/001.tif",
+                "delivery/roll_02/001.jpg", "delivery/roll_02/002.jpg",
+            ]);
+            assert_eq!(archive_image_folders(&archive).unwrap(), vec![
+                ("delivery".to_string(), 1),
+                ("delivery/roll_01".to_string(), 1),
+                ("delivery/roll_02".to_string(), 2),
+            ]);
+
+            let files = hash_archive_images(&archive, Some("delivery/roll_02")).unwrap();
+            assert_eq!(files.len(), 2);
+            assert_eq!(files[0].source_path, entry_path(&archive, "delivery/roll_02/001.jpg"));
+            assert_eq!(files[0].content_hash, blake3::hash(b"frame one").to_hex().to_string());
+
+            let roll_dir = base.join("roll");
+            fs::create_dir_all(roll_dir.join("originals")).unwrap();
+            let planned = plan_import_files(files, &roll_dir, 0, |frame, _| format!("ROLL_00000001_{:03}", frame));
+            let mut progress = vec![];
+            let failed = extract_planned_files(&archive, &planned, &AtomicBool::new(false), |current, total, name| {
+                progress.push((current, total, name));
+            }).unwrap();
+
+            assert!(failed.is_empty());
+            assert_eq!(fs::read(&planned[0].original_path).unwrap(), b"frame one");
+            assert_eq!(fs::read(&planned[1].original_path).unwrap(), b"frame two");
+            assert_eq!(progress.len(), 2);
+            fs::remove_dir_all(&roll_dir).unwrap();
+        }
+
+        fs::remove_dir_all(&base).unwrap();
+    }
+}
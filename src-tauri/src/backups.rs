@@ -0,0 +1,307 @@
+/*!
+ * FilmVault Database Backups
+ *
+ * Backups are consistent copies of the live database made with `VACUUM INTO`,
+ * so they can be taken while the app is in use. Automatic backups (before
+ * migrations, on a schedule and before a restore) go to a `backups` folder
+ * next to the database and are rotated; manual backups go wherever the user
+ * chooses. A backup is inspected (integrity and schema version) before it is
+ * allowed to replace the live database.
+ */
+
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime};
+use serde::{Deserialize, Serialize};
+use sqlx::SqlitePool;
+use sqlx::sqlite::{SqliteConnectOptions, SqlitePoolOptions};
+use anyhow::{bail, Context, Result};
+
+use crate::library_paths::database_file;
+use crate::migrations::{current_schema_version, latest_schema_version};
+
+/// Folder for automatic backups, next to the database
+pub const BACKUP_DIR: &str = "backups";
+
+pub const DEFAULT_BACKUP_INTERVAL_HOURS: i64 = 24;
+pub const DEFAULT_BACKUP_KEEP: i64 = 10;
+
+/// Why an automatic backup was taken; part of its file name
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum BackupReason {
+    PreMigration,
+    Scheduled,
+    PreRestore,
+}
+
+impl BackupReason {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            BackupReason::PreMigration => "pre-migration",
+            BackupReason::Scheduled => "scheduled",
+            BackupReason::PreRestore => "pre-restore",
+        }
+    }
+}
+
+/// What a backup file contains
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BackupInfo {
+    pub path: String,
+    pub schema_version: i64,
+    pub roll_count: i64,
+    pub photo_count: i64,
+    pub size: u64,
+    pub modified_at: Option<String>,
+}
+
+/// Write a consistent copy of the database to `target`, replacing any file there
+pub async fn backup_database(pool: &SqlitePool, target: &Path) -> Result<()> {
+    if let Some(parent) = target.parent() {
+        fs::create_dir_all(parent).with_context(|| format!("Failed to create {}", parent.display()))?;
+    }
+    if database_file(pool).await?.is_some_and(|live| live == target) {
+        bail!("A backup cannot replace the live database");
+    }
+
+    // VACUUM INTO refuses existing files, so write beside the target and rename
+    let temp_path = target.with_extension("db.part");
+    if temp_path.exists() {
+        fs::remove_file(&temp_path)?;
+    }
+    sqlx::query("VACUUM INTO ?1")
+        .bind(temp_path.to_string_lossy().to_string())
+        .execute(pool)
+        .await
+        .context("Failed to write backup")?;
+    fs::rename(&temp_path, target).with_context(|| format!("Failed to move backup to {}", target.display()))?;
+
+    eprintln!("[Backup] Backed up database to {:?}", target);
+    Ok(())
+}
+
+/// Folder and file name prefix of the automatic backups of the open database
+async fn backup_location(pool: &SqlitePool) -> Result<Option<(PathBuf, String)>> {
+    let Some(db_file) = database_file(pool).await? else {
+        return Ok(None);
+    };
+    let stem = db_file.file_stem().map(|stem| stem.to_string_lossy().to_string()).unwrap_or_default();
+    let dir = db_file.parent().map(|dir| dir.join(BACKUP_DIR)).unwrap_or_else(|| PathBuf::from(BACKUP_DIR));
+    Ok(Some((dir, format!("{}-", stem))))
+}
+
+/// Automatic backups of a database in `dir`, oldest first
+fn list_backup_files(dir: &Path, prefix: &str) -> Vec<PathBuf> {
+    let Ok(entries) = fs::read_dir(dir) else {
+        return Vec::new();
+    };
+    let mut files: Vec<PathBuf> = entries
+        .flatten()
+        .map(|entry| entry.path())
+        .filter(|path| {
+            let name = path.file_name().map(|n| n.to_string_lossy().to_string()).unwrap_or_default();
+            name.starts_with(prefix) && name.ends_with(".db")
+        })
+        .collect();
+    // Names carry a sortable timestamp
+    files.sort();
+    files
+}
+
+/// Delete the oldest automatic backups so at most `keep` remain; returns how many were deleted
+pub fn rotate_backups(dir: &Path, prefix: &str, keep: usize) -> Result<usize> {
+    let files = list_backup_files(dir, prefix);
+    let excess = files.len().saturating_sub(keep.max(1));
+    for file in &files[..excess] {
+        fs::remove_file(file).with_context(|| format!("Failed to remove old backup {}", file.display()))?;
+    }
+    Ok(excess)
+}
+
+/// Take an automatic backup into the backups folder and rotate old ones
+///
+/// Returns None for in-memory databases, which have nothing to back up.
+pub async fn backup_automatic(pool: &SqlitePool, reason: BackupReason, keep: i64) -> Result<Option<PathBuf>> {
+    let Some((dir, prefix)) = backup_location(pool).await? else {
+        return Ok(None);
+    };
+
+    let name = format!("{}{}-{}.db", prefix, chrono::Local::now().format("%Y%m%d-%H%M%S"), reason.as_str());
+    let target = dir.join(name);
+    backup_database(pool, &target).await?;
+
+    let removed = rotate_backups(&dir, &prefix, keep.max(1) as usize)?;
+    if removed > 0 {
+        eprintln!("[Backup] Removed {} old backups", removed);
+    }
+    Ok(Some(target))
+}
+
+/// Back up a database that is about to be migrated
+pub async fn backup_before_migrations(pool: &SqlitePool) -> Result<Option<PathBuf>> {
+    let has_catalog: bool = sqlx::query_scalar(
+        "SELECT COUNT(*) > 0 FROM sqlite_master WHERE type = 'table' AND name = 'rolls'"
+    )
+    .fetch_one(pool)
+    .await?;
+    if !has_catalog || current_schema_version(pool).await? >= latest_schema_version() {
+        return Ok(None);
+    }
+
+    backup_automatic(pool, BackupReason::PreMigration, DEFAULT_BACKUP_KEEP).await
+}
+
+/// Take a scheduled backup if the newest automatic backup is older than `interval_hours`
+pub async fn backup_if_due(pool: &SqlitePool, interval_hours: i64, keep: i64) -> Result<Option<PathBuf>> {
+    if interval_hours <= 0 {
+        return Ok(None);
+    }
+    let Some((dir, prefix)) = backup_location(pool).await? else {
+        return Ok(None);
+    };
+
+    let newest = list_backup_files(&dir, &prefix)
+        .iter()
+        .filter_map(|file| fs::metadata(file).and_then(|meta| meta.modified()).ok())
+        .max();
+    let interval = Duration::from_secs(interval_hours as u64 * 3600);
+    let due = newest.is_none_or(|newest| SystemTime::now().duration_since(newest).unwrap_or_default() >= interval);
+    if !due {
+        return Ok(None);
+    }
+
+    backup_automatic(pool, BackupReason::Scheduled, keep).await
+}
+
+/// Automatic backups of the open database, newest first
+pub async fn list_automatic_backups(pool: &SqlitePool) -> Result<Vec<PathBuf>> {
+    let Some((dir, prefix)) = backup_location(pool).await? else {
+        return Ok(Vec::new());
+    };
+    let mut files = list_backup_files(&dir, &prefix);
+    files.reverse();
+    Ok(files)
+}
+
+/// Open a backup read-only and check it can be restored by this build
+pub async fn inspect_backup(path: &Path) -> Result<BackupInfo> {
+    let metadata = fs::metadata(path).with_context(|| format!("Backup not found: {}", path.display()))?;
+    let options = SqliteConnectOptions::new().filename(path).read_only(true);
+    let pool = SqlitePoolOptions::new()
+        .max_connections(1)
+        .connect_with(options)
+        .await
+        .with_context(|| format!("{} is not a FilmVault database", path.display()))?;
+
+    let info = async {
+        let check: String = sqlx::query_scalar("PRAGMA quick_check").fetch_one(&pool).await?;
+        if check != "ok" {
+            bail!("Backup is damaged: {}", check);
+        }
+
+        let has_catalog: bool = sqlx::query_scalar(
+            "SELECT COUNT(*) > 0 FROM sqlite_master WHERE type = 'table' AND name = 'rolls'"
+        )
+        .fetch_one(&pool)
+        .await?;
+        if !has_catalog {
+            bail!("{} is not a FilmVault database", path.display());
+        }
+
+        let schema_version = current_schema_version(&pool).await?;
+        if schema_version > latest_schema_version() {
+            bail!(
+                "Backup schema version {} is newer than this build supports ({})",
+                schema_version,
+                latest_schema_version()
+            );
+        }
+
+        let roll_count: i64 = sqlx::query_scalar("SELECT COUNT(*) FROM rolls").fetch_one(&pool).await?;
+        let photo_count: i64 = sqlx::query_scalar("SELECT COUNT(*) FROM photos").fetch_one(&pool).await?;
+
+        Ok(BackupInfo {
+            path: path.to_string_lossy().to_string(),
+            schema_version,
+            roll_count,
+            photo_count,
+            size: metadata.len(),
+            modified_at: metadata.modified().ok()
+                .map(|modified| chrono::DateTime::<chrono::Local>::from(modified).to_rfc3339()),
+        })
+    }
+    .await;
+
+    pool.close().await;
+    info
+}
+
+/// Put a backup in place of a closed database file (blocking)
+///
+/// Copies beside the database and renames over it, so an interrupted restore
+/// leaves the old database untouched. Stale WAL files of the old database are
+/// removed so SQLite does not replay them onto the restored one.
+pub fn replace_database_file(backup: &Path, db_file: &Path) -> Result<()> {
+    let temp_path = db_file.with_extension("db.restore");
+    fs::copy(backup, &temp_path).with_context(|| format!("Failed to copy {}", backup.display()))?;
+
+    for suffix in ["-wal", "-shm"] {
+        let mut sidecar = db_file.as_os_str().to_owned();
+        sidecar.push(suffix);
+        let sidecar = PathBuf::from(sidecar);
+        if sidecar.exists() {
+            fs::remove_file(&sidecar).with_context(|| format!("Failed to remove {}", sidecar.display()))?;
+        }
+    }
+
+    fs::rename(&temp_path, db_file).with_context(|| format!("Failed to replace {}", db_file.display()))?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::migrations::run_migrations;
+
+    #[tokio::test]
+    async fn test_backup_rotate_and_inspect() {
+        let dir = std::env::temp_dir().join(format!("filmvault_backup_test_{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let options = SqliteConnectOptions::new().filename(dir.join("film_vault.db")).create_if_missing(true);
+        let pool = SqlitePoolOptions::new().max_connections(1).connect_with(options).await.unwrap();
+        run_migrations(&pool).await.unwrap();
+        sqlx::query("INSERT INTO rolls (name, path, film_stock, camera, shoot_date) VALUES ('Roll', '2024/1', 'HP5', 'FM2', '2024-03-15')")
+            .execute(&pool)
+            .await
+            .unwrap();
+
+        let manual = dir.join("manual.db");
+        backup_database(&pool, &manual).await.unwrap();
+        let info = inspect_backup(&manual).await.unwrap();
+        assert_eq!(info.schema_version, latest_schema_version());
+        assert_eq!(info.roll_count, 1);
+
+        // Up to date, so nothing to back up before migrations
+        assert!(backup_before_migrations(&pool).await.unwrap().is_none());
+
+        let backup_dir = dir.join(BACKUP_DIR);
+        fs::create_dir_all(&backup_dir).unwrap();
+        for stamp in ["20240101-000000", "20240102-000000", "20240103-000000"] {
+            fs::write(backup_dir.join(format!("film_vault-{}-scheduled.db", stamp)), "").unwrap();
+        }
+        fs::write(backup_dir.join("other-20240101-000000-scheduled.db"), "").unwrap();
+        assert!(backup_automatic(&pool, BackupReason::Scheduled, 2).await.unwrap().is_some());
+        let kept = list_automatic_backups(&pool).await.unwrap();
+        assert_eq!(kept.len(), 2);
+        assert!(kept[1].ends_with("film_vault-20240103-000000-scheduled.db"));
+        assert!(backup_dir.join("other-20240101-000000-scheduled.db").exists());
+
+        // Just backed up, so the schedule is not due
+        assert!(backup_if_due(&pool, 24, 2).await.unwrap().is_none());
+
+        assert!(inspect_backup(&backup_dir.join("other-20240101-000000-scheduled.db")).await.is_err());
+
+        pool.close().await;
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
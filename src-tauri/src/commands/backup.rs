@@ -0,0 +1,154 @@
+use tauri::{State, Emitter, AppHandle, Manager};
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use crate::backups::{
+    BackupInfo, BackupReason, backup_automatic, backup_database, backup_if_due, inspect_backup,
+    list_automatic_backups, replace_database_file,
+};
+use crate::config::{get_backup_interval_hours, get_backup_keep};
+use crate::library_paths::database_file;
+use crate::AppState;
+use super::get_pool;
+use super::library::{lock_library, open_library_pool};
+
+/// How often the backup schedule is checked
+const BACKUP_CHECK_INTERVAL: Duration = Duration::from_secs(15 * 60);
+
+/// Write a consistent backup of the open database to a chosen file
+#[tauri::command]
+pub async fn backup_database_command(
+    target_path: String,
+    state: State<'_, AppState>,
+) -> Result<BackupInfo, String> {
+    let pool = get_pool(&state).await?;
+    let target = PathBuf::from(&target_path);
+
+    backup_database(&pool, &target)
+        .await
+        .map_err(|e| format!("Failed to back up database: {:#}", e))?;
+    inspect_backup(&target)
+        .await
+        .map_err(|e| format!("Backup was written but cannot be read back: {:#}", e))
+}
+
+/// List the automatic backups of the open database, newest first
+#[tauri::command]
+pub async fn list_backups_command(state: State<'_, AppState>) -> Result<Vec<BackupInfo>, String> {
+    let pool = get_pool(&state).await?;
+
+    let files = list_automatic_backups(&pool)
+        .await
+        .map_err(|e| format!("Failed to list backups: {}", e))?;
+    let mut backups = Vec::new();
+    for file in files {
+        match inspect_backup(&file).await {
+            Ok(info) => backups.push(info),
+            Err(e) => eprintln!("[Backup] Skipping unreadable backup {:?}: {:#}", file, e),
+        }
+    }
+
+    Ok(backups)
+}
+
+/// Check that a backup can be restored, without restoring it
+#[tauri::command]
+pub async fn inspect_backup_command(backup_path: String) -> Result<BackupInfo, String> {
+    inspect_backup(Path::new(&backup_path))
+        .await
+        .map_err(|e| format!("{:#}", e))
+}
+
+/// Replace the open database with a backup
+///
+/// The backup is validated first and the current database is backed up
+/// before it is replaced. A backup from an older build is migrated when it
+/// is opened; one from a newer build is refused.
+#[tauri::command]
+pub async fn restore_database_command(
+    backup_path: String,
+    state: State<'_, AppState>,
+    app: AppHandle,
+) -> Result<BackupInfo, String> {
+    let _switching = lock_library(&state)?;
+
+    let backup = PathBuf::from(&backup_path);
+    let info = inspect_backup(&backup)
+        .await
+        .map_err(|e| format!("Cannot restore {}: {:#}", backup_path, e))?;
+
+    let pool = get_pool(&state).await?;
+    let db_file = database_file(&pool)
+        .await
+        .map_err(|e| format!("Failed to locate database: {}", e))?
+        .ok_or_else(|| "The open database has no file to restore into".to_string())?;
+    if db_file == backup {
+        return Err("The backup is the open database".to_string());
+    }
+
+    let keep = get_backup_keep(&pool).await?;
+    let safety_backup = backup_automatic(&pool, BackupReason::PreRestore, keep)
+        .await
+        .map_err(|e| format!("Failed to back up the current database: {:#}", e))?;
+    eprintln!("[Backup] Current database saved to {:?} before restoring", safety_backup);
+
+    // Close the live database so its file can be replaced
+    drop(pool);
+    if let Some(previous) = state.db_pool.lock().await.take() {
+        previous.close().await;
+    }
+
+    let replaced = {
+        let (backup, db_file) = (backup.clone(), db_file.clone());
+        tokio::task::spawn_blocking(move || replace_database_file(&backup, &db_file))
+            .await
+            .map_err(|e| anyhow::anyhow!(e))
+            .and_then(|result| result)
+    };
+    if let Err(e) = &replaced {
+        eprintln!("[Backup] Restore failed, reopening current database: {:#}", e);
+    }
+
+    let pool = open_library_pool(&db_file).await?;
+    *state.db_pool.lock().await = Some(pool);
+    replaced.map_err(|e| format!("Failed to restore backup: {:#}", e))?;
+
+    eprintln!("[Backup] Restored {:?} into {:?}", backup, db_file);
+    let _ = app.emit("database-restored", serde_json::json!({
+        "backupPath": backup_path,
+        "schemaVersion": info.schema_version,
+    }));
+    Ok(info)
+}
+
+/// Take scheduled backups of whichever library is open, for as long as the app runs
+pub(crate) async fn run_backup_schedule(app: AppHandle) {
+    let mut interval = tokio::time::interval(BACKUP_CHECK_INTERVAL);
+    loop {
+        interval.tick().await;
+
+        let state = app.state::<AppState>();
+        // Keeps the library from being switched or restored mid-backup
+        let _switching = state.library_switch.read().await;
+        let Some(pool) = state.db_pool.lock().await.clone() else {
+            continue;
+        };
+
+        let settings = async {
+            Ok::<_, String>((get_backup_interval_hours(&pool).await?, get_backup_keep(&pool).await?))
+        };
+        let (interval_hours, keep) = match settings.await {
+            Ok(settings) => settings,
+            Err(e) => {
+                eprintln!("[Backup] {}", e);
+                continue;
+            }
+        };
+
+        match backup_if_due(&pool, interval_hours, keep).await {
+            Ok(Some(path)) => eprintln!("[Backup] Scheduled backup written to {:?}", path),
+            Ok(None) => {}
+            Err(e) => eprintln!("[Backup] Scheduled backup failed: {:#}", e),
+        }
+    }
+}
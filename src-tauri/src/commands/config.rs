@@ -1,8 +1,53 @@
 use tauri::State;
+use serde::{Deserialize, Serialize};
+use std::path::Path;
+use sqlx::SqlitePool;
 
-use crate::config::{AppConfig, get_library_root, set_library_root};
+use crate::config::{
+    AppConfig, get_library_root, set_library_root, get_filename_template, set_filename_template,
+    get_frame_patterns, set_frame_patterns, get_backup_interval_hours, get_backup_keep, set_backup_settings,
+    get_metadata_write_mode, set_metadata_write_mode, get_verify_exif_writes, set_verify_exif_writes,
+};
+use crate::exif_tool::MetadataWriteMode;
+use crate::database::get_stored_roll_paths;
+use crate::library_paths::{LibraryRoot, is_relative};
 use crate::AppState;
 use super::get_pool;
+use super::library::lock_library;
+
+/// Whether the library's rolls can be found under a library root
+#[derive(Debug, Serialize, Deserialize)]
+pub struct LibraryRootCheck {
+    pub library_root: String,
+    pub rolls_found: usize,
+    pub missing_rolls: Vec<String>, // Roll folders (relative to the root) that are not there
+    pub switched: bool,
+}
+
+impl LibraryRootCheck {
+    fn is_complete(&self) -> bool {
+        self.missing_rolls.is_empty()
+    }
+}
+
+/// Look for every roll stored relative to the library root under `path`
+async fn check_library_root(pool: &SqlitePool, path: &str) -> Result<LibraryRootCheck, String> {
+    let root = LibraryRoot::new(path);
+    let mut rolls_found = 0;
+    let mut missing_rolls = Vec::new();
+    for (_, stored) in get_stored_roll_paths(pool).await.map_err(|e| format!("Failed to get rolls: {}", e))? {
+        if !is_relative(&stored) {
+            continue;
+        }
+        if Path::new(&root.resolve(&stored)).is_dir() {
+            rolls_found += 1;
+        } else {
+            missing_rolls.push(stored);
+        }
+    }
+
+    Ok(LibraryRootCheck { library_root: path.to_string(), rolls_found, missing_rolls, switched: false })
+}
 
 /// Get application configuration
 #[tauri::command]
@@ -12,9 +57,21 @@ pub async fn get_config(state: State<'_, AppState>) -> Result<AppConfig, String>
 
     // Get library root from config
     let library_root = get_library_root(&pool).await?;
+    let filename_template = get_filename_template(&pool).await?;
+    let frame_patterns = get_frame_patterns(&pool).await?;
+    let backup_interval_hours = get_backup_interval_hours(&pool).await?;
+    let backup_keep = get_backup_keep(&pool).await?;
+    let metadata_write_mode = get_metadata_write_mode(&pool).await?;
+    let verify_exif_writes = get_verify_exif_writes(&pool).await?;
 
     Ok(AppConfig {
         library_root,
+        filename_template,
+        frame_patterns,
+        backup_interval_hours,
+        backup_keep,
+        metadata_write_mode,
+        verify_exif_writes,
     })
 }
 
@@ -24,11 +81,123 @@ pub async fn update_library_root(
     path: String,
     state: State<'_, AppState>,
 ) -> Result<bool, String> {
+    // Not while an import or repair stores paths relative to the current root
+    let _switching = lock_library(&state)?;
     // Wait for database initialization
     let pool = get_pool(&state).await?;
 
+    // Rolls are stored relative to the root, so they must be under the new one
+    let check = check_library_root(&pool, &path).await?;
+    if !check.is_complete() {
+        return Err(format!(
+            "{} roll folders were not found in {}. Use relocate to check the new location first.",
+            check.missing_rolls.len(), path
+        ));
+    }
+
     // Set library root
     set_library_root(&pool, &path).await?;
 
     Ok(true)
 }
+
+/// Check whether the library's rolls are under `path` without changing anything
+#[tauri::command]
+pub async fn check_library_root_command(
+    path: String,
+    state: State<'_, AppState>,
+) -> Result<LibraryRootCheck, String> {
+    let pool = get_pool(&state).await?;
+    check_library_root(&pool, &path).await
+}
+
+/// Point the library at its new location (moved folder, other drive letter or machine)
+/// Switches only when every roll folder is found under `path`.
+#[tauri::command]
+pub async fn relocate_library_command(
+    path: String,
+    state: State<'_, AppState>,
+) -> Result<LibraryRootCheck, String> {
+    let _switching = lock_library(&state)?;
+    let pool = get_pool(&state).await?;
+
+    if !Path::new(&path).is_dir() {
+        return Err(format!("Library folder does not exist: {}", path));
+    }
+    let mut check = check_library_root(&pool, &path).await?;
+    if check.is_complete() {
+        set_library_root(&pool, &path).await?;
+        check.switched = true;
+        eprintln!("[Config] Library relocated to '{}' ({} rolls)", path, check.rolls_found);
+    } else {
+        eprintln!("[Config] Not relocating to '{}': {} roll folders missing", path, check.missing_rolls.len());
+    }
+
+    Ok(check)
+}
+
+/// Update the filename template used for newly imported photos
+#[tauri::command]
+pub async fn update_filename_template(
+    template: String,
+    state: State<'_, AppState>,
+) -> Result<bool, String> {
+    let pool = get_pool(&state).await?;
+
+    set_filename_template(&pool, &template).await?;
+
+    Ok(true)
+}
+
+/// Update the regexes used to parse frame numbers from scan filenames
+#[tauri::command]
+pub async fn update_frame_patterns(
+    patterns: Vec<String>,
+    state: State<'_, AppState>,
+) -> Result<bool, String> {
+    let pool = get_pool(&state).await?;
+
+    set_frame_patterns(&pool, &patterns).await?;
+
+    Ok(true)
+}
+
+/// Update how often the database is backed up and how many backups are kept
+#[tauri::command]
+pub async fn update_backup_settings(
+    interval_hours: i64,
+    keep: i64,
+    state: State<'_, AppState>,
+) -> Result<bool, String> {
+    let pool = get_pool(&state).await?;
+
+    set_backup_settings(&pool, interval_hours, keep).await?;
+
+    Ok(true)
+}
+
+/// Choose whether metadata is written into the originals or into XMP sidecars
+#[tauri::command]
+pub async fn update_metadata_write_mode(
+    mode: MetadataWriteMode,
+    state: State<'_, AppState>,
+) -> Result<bool, String> {
+    let pool = get_pool(&state).await?;
+
+    set_metadata_write_mode(&pool, mode).await?;
+
+    Ok(true)
+}
+
+/// Choose whether roll EXIF writes are read back and compared with the database
+#[tauri::command]
+pub async fn update_verify_exif_writes(
+    enabled: bool,
+    state: State<'_, AppState>,
+) -> Result<bool, String> {
+    let pool = get_pool(&state).await?;
+
+    set_verify_exif_writes(&pool, enabled).await?;
+
+    Ok(true)
+}
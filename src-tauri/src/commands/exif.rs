@@ -1,14 +1,27 @@
 use tauri::State;
-use serde::Deserialize;
+use serde::{Deserialize, Serialize};
 use futures::stream::{self, StreamExt};
-
-use crate::database::{get_roll_by_id, get_photos_by_roll, get_photo_by_id, mark_photo_exif_synced, update_photo_metadata};
+use sqlx::SqlitePool;
+use std::collections::{HashMap, HashSet};
+
+use crate::config::{get_metadata_write_mode, get_verify_exif_writes};
+use crate::film_metadata::FilmMetadata;
+use crate::database::{
+    NewRoll, Photo, Roll, ShotLog, get_roll_by_id, get_photos_by_roll, get_photo_by_id, get_all_rolls,
+    get_all_photos, get_shot_log, mark_photo_exif_synced, mark_photo_exif_cleared, update_photo_metadata,
+    update_photo_rating, update_photo_location_with_city, update_roll, get_shot_logs_by_roll,
+};
 use crate::exif_tool::{
-    ExifData, ExifWriteResult, extract_exif, write_photo_roll_exif,
-    write_photo_exif, clear_photo_exif, check_exiftool_available, parse_camera_string
+    ExifData, ExifWriteResult, PhotoMetadata, extract_exif, write_photo_roll_exif, roll_exif_hash,
+    write_photo_exif, write_exif_args, clear_photo_exif, check_exiftool_available, parse_camera_string,
+    exiftool_pool,
+};
+use crate::metadata_drift::{
+    DriftField, DriftReport, DriftResolution, DriftWinner, FieldDrift, PhotoDrift, apply_file_value, compare,
+    db_wins_args,
 };
 use crate::AppState;
-use super::get_pool;
+use super::{get_pool, hold_library};
 
 /// Check if ExifTool is available
 #[tauri::command]
@@ -21,6 +34,8 @@ pub async fn check_exiftool_available_command() -> Result<bool, String> {
 pub struct WriteRollExifRequest {
     pub roll_id: i64,
     pub auto_write: bool,
+    #[serde(default)]
+    pub force: bool, // Rewrite photos whose stored exif_data_hash already matches
 }
 
 /// Build UserComment from roll metadata
@@ -55,6 +70,34 @@ fn build_user_comment(
     parts.join(" | ")
 }
 
+/// Metadata to write to a photo of a roll
+/// `photo` is None for photos without a row yet (during import); they get the roll's fields only.
+pub(crate) fn photo_metadata(roll: &Roll, photo: Option<&Photo>, user_comment: String) -> PhotoMetadata {
+    // Parse camera string into make and model
+    let (make, model) = parse_camera_string(&roll.camera);
+
+    // Photo location takes priority over roll location
+    let gps = match photo {
+        Some(Photo { lat: Some(lat), lon: Some(lon), .. }) => Some((*lat, *lon)),
+        _ => roll.lat.zip(roll.lon),
+    };
+
+    PhotoMetadata {
+        make,
+        model,
+        date_time_original: format_shoot_date_for_exif(&roll.shoot_date),
+        user_comment,
+        lens: roll.lens.clone(),
+        rating: photo.map(|photo| photo.rating),
+        gps,
+        city: photo.and_then(|photo| photo.city.clone()).or_else(|| roll.city.clone()),
+        country: photo.and_then(|photo| photo.country.clone()).or_else(|| roll.country.clone()),
+        description: photo.and_then(|photo| photo.exif_description.clone()),
+        keywords: vec![roll.film_stock.clone()],
+        film: Some(FilmMetadata::from_roll(roll)),
+    }
+}
+
 /// Write roll-level EXIF to all photos in a roll
 #[tauri::command]
 pub async fn write_roll_exif_command(
@@ -63,6 +106,7 @@ pub async fn write_roll_exif_command(
 ) -> Result<ExifWriteResult, String> {
     eprintln!("[EXIF] Writing roll EXIF for roll_id: {}", request.roll_id);
 
+    let _library = hold_library(&state)?;
     let pool = get_pool(&state).await?;
 
     // Get roll from database
@@ -85,6 +129,7 @@ pub async fn write_roll_exif_command(
         eprintln!("[EXIF] No photos found in roll");
         return Ok(ExifWriteResult {
             success_count: 0,
+            skipped_count: 0,
             failed_count: 0,
             failed_files: vec![],
         });
@@ -92,9 +137,6 @@ pub async fn write_roll_exif_command(
 
     eprintln!("[EXIF] Found {} photos to process", photos.len());
 
-    // Parse camera string into make and model
-    let (make, model) = parse_camera_string(&roll.camera);
-
     // Build user comment: "Shot on {film_stock} | {city}, {country} | {notes}"
     let user_comment = build_user_comment(
         &roll.film_stock,
@@ -105,33 +147,42 @@ pub async fn write_roll_exif_command(
 
     eprintln!("[EXIF] Built UserComment: '{}'", user_comment);
 
-    // Format date time original from shoot_date
-    let date_time_original = format_shoot_date_for_exif(&roll.shoot_date);
-
-    // Write EXIF to all photos with concurrency control
-    let results = stream::iter(photos)
-        .map(|photo| {
-            let file_path = photo.file_path.clone();
-            let make_clone = make.clone();
-            let model_clone = model.clone();
-            let date_clone = date_time_original.clone();
-            let comment_clone = user_comment.clone();
-
-            async move {
-                eprintln!("[EXIF] Processing photo: {}", file_path);
-                (
-                    file_path.clone(),
-                    write_photo_roll_exif(
-                        &file_path,
-                        &make_clone,
-                        &model_clone,
-                        &date_clone,
-                        &comment_clone,
-                    ).await
-                )
+    let mode = get_metadata_write_mode(&pool).await?;
+    let verify = get_verify_exif_writes(&pool).await?;
+
+    // Per-frame shot logs are written alongside the roll fields
+    let shot_logs: HashMap<i64, ShotLog> = get_shot_logs_by_roll(&pool, request.roll_id).await
+        .map_err(|e| format!("Failed to query shot logs: {}", e))?
+        .into_iter()
+        .map(|log| (log.photo_id, log))
+        .collect();
+
+    // Skip photos whose files already hold exactly this metadata
+    let mut skipped_count = 0;
+    let mut pending = vec![];
+    for photo in photos {
+        let metadata = photo_metadata(&roll, Some(&photo), user_comment.clone());
+        let shot_log = shot_logs.get(&photo.id).cloned();
+        let hash = roll_exif_hash(&metadata, shot_log.as_ref(), mode);
+        if !request.force && photo.exif_data_hash.as_deref() == Some(hash.as_str()) {
+            skipped_count += 1;
+            continue;
+        }
+        pending.push((photo.id, photo.file_path, metadata, shot_log, hash));
+    }
+    eprintln!("[EXIF] {} photos up to date, {} to write", skipped_count, pending.len());
+
+    // Write EXIF to the remaining photos with concurrency control
+    let results = stream::iter(pending)
+        .map(|(photo_id, file_path, metadata, shot_log, hash)| async move {
+            eprintln!("[EXIF] Processing photo: {}", file_path);
+            let mut result = write_photo_roll_exif(&file_path, &metadata, shot_log.as_ref(), mode).await;
+            if verify && result.is_ok() {
+                result = verify_roll_exif(&file_path, &metadata, shot_log.as_ref()).await;
             }
+            (photo_id, file_path, hash, result)
         })
-        .buffer_unordered(4) // Limit concurrency to 4
+        .buffer_unordered(exiftool_pool().size()) // One request per ExifTool worker
         .collect::<Vec<_>>()
         .await;
 
@@ -140,11 +191,14 @@ pub async fn write_roll_exif_command(
     let mut failed_count = 0;
     let mut failed_files = vec![];
 
-    for (file_path, result) in results {
+    for (photo_id, file_path, hash, result) in results {
         match result {
             Ok(_) => {
                 eprintln!("[EXIF] Successfully wrote EXIF to: {}", file_path);
                 success_count += 1;
+                if let Err(e) = mark_photo_exif_synced(&pool, photo_id, Some(&hash)).await {
+                    eprintln!("[EXIF] Failed to mark photo {} as synced: {}", photo_id, e);
+                }
             }
             Err(e) => {
                 eprintln!("[EXIF] Failed to write EXIF to {}: {}", file_path, e);
@@ -154,16 +208,29 @@ pub async fn write_roll_exif_command(
         }
     }
 
-    eprintln!("[EXIF] Roll EXIF write complete: {} success, {} failed",
-        success_count, failed_count);
+    eprintln!("[EXIF] Roll EXIF write complete: {} written, {} skipped, {} failed",
+        success_count, skipped_count, failed_count);
 
     Ok(ExifWriteResult {
         success_count,
+        skipped_count,
         failed_count,
         failed_files,
     })
 }
 
+/// Read a written photo back and check every field holds what was written
+async fn verify_roll_exif(file_path: &str, metadata: &PhotoMetadata, shot_log: Option<&ShotLog>) -> anyhow::Result<()> {
+    let exif = extract_exif(file_path).await?;
+    let drift = compare(metadata, shot_log, &exif);
+    if drift.is_empty() {
+        return Ok(());
+    }
+
+    let fields: Vec<String> = drift.iter().map(|drift| format!("{:?}", drift.field)).collect();
+    Err(anyhow::anyhow!("Verification failed, file differs in: {}", fields.join(", ")))
+}
+
 /// Request structure for writing photo EXIF
 #[derive(Debug, Deserialize)]
 pub struct WritePhotoExifRequest {
@@ -209,8 +276,9 @@ pub async fn write_photo_exif_command(
 
     eprintln!("[EXIF] Built UserComment: {}", user_comment);
 
-    // Write EXIF to the photo file
-    write_photo_exif(&photo.file_path, Some(&user_comment)).await
+    // Write EXIF to the photo file (or its sidecar)
+    let mode = get_metadata_write_mode(&pool).await?;
+    write_photo_exif(&photo.file_path, Some(&user_comment), mode).await
         .map_err(|e| format!("Failed to write EXIF: {}", e))?;
 
     // Update user-editable metadata in database
@@ -222,8 +290,8 @@ pub async fn write_photo_exif_command(
     ).await
     .map_err(|e| format!("Failed to update metadata: {}", e))?;
 
-    // Mark as synced
-    mark_photo_exif_synced(&pool, request.photo_id).await
+    // Mark as synced; only UserComment was written, so a later roll write must not be skipped
+    mark_photo_exif_synced(&pool, request.photo_id, None).await
         .map_err(|e| format!("Failed to mark as synced: {}", e))?;
 
     eprintln!("[EXIF] Photo EXIF write successful");
@@ -245,9 +313,12 @@ pub async fn clear_photo_exif_command(
         .map_err(|e| format!("Failed to query: {}", e))?
         .ok_or_else(|| "Photo not found".to_string())?;
 
-    // Clear EXIF from the photo file
-    clear_photo_exif(&photo.file_path).await
+    // Clear EXIF from the photo file (or remove its sidecar)
+    let mode = get_metadata_write_mode(&pool).await?;
+    clear_photo_exif(&photo.file_path, mode).await
         .map_err(|e| format!("Failed to clear EXIF: {}", e))?;
+    mark_photo_exif_cleared(&pool, photo_id).await
+        .map_err(|e| format!("Failed to update sync status: {}", e))?;
 
     eprintln!("[EXIF] Photo EXIF cleared successfully");
     Ok(true)
@@ -261,6 +332,7 @@ pub async fn clear_roll_exif_command(
 ) -> Result<ExifWriteResult, String> {
     eprintln!("[EXIF] Clearing roll EXIF for roll_id: {}", roll_id);
 
+    let _library = hold_library(&state)?;
     let pool = get_pool(&state).await?;
 
     // Get all photos in the roll
@@ -270,23 +342,21 @@ pub async fn clear_roll_exif_command(
     if photos.is_empty() {
         return Ok(ExifWriteResult {
             success_count: 0,
+            skipped_count: 0,
             failed_count: 0,
             failed_files: vec![],
         });
     }
 
+    let mode = get_metadata_write_mode(&pool).await?;
+
     // Clear EXIF from all photos with concurrency control
     let results = stream::iter(photos)
-        .map(|photo| {
-            let file_path = photo.file_path.clone();
-            async move {
-                (
-                    file_path.clone(),
-                    clear_photo_exif(&file_path).await
-                )
-            }
+        .map(|photo| async move {
+            let result = clear_photo_exif(&photo.file_path, mode).await;
+            (photo.id, photo.file_path, result)
         })
-        .buffer_unordered(4) // Limit concurrency to 4
+        .buffer_unordered(exiftool_pool().size()) // One request per ExifTool worker
         .collect::<Vec<_>>()
         .await;
 
@@ -295,9 +365,14 @@ pub async fn clear_roll_exif_command(
     let mut failed_count = 0;
     let mut failed_files = vec![];
 
-    for (file_path, result) in results {
+    for (photo_id, file_path, result) in results {
         match result {
-            Ok(_) => success_count += 1,
+            Ok(_) => {
+                success_count += 1;
+                if let Err(e) = mark_photo_exif_cleared(&pool, photo_id).await {
+                    eprintln!("[EXIF] Failed to update sync status of photo {}: {}", photo_id, e);
+                }
+            }
             Err(e) => {
                 failed_count += 1;
                 failed_files.push(format!("{}: {}", file_path, e));
@@ -310,12 +385,13 @@ pub async fn clear_roll_exif_command(
 
     Ok(ExifWriteResult {
         success_count,
+        skipped_count: 0,
         failed_count,
         failed_files,
     })
 }
 
-/// Read EXIF from a single photo file
+/// Read EXIF from a single photo file, with its sidecar merged over it
 #[tauri::command]
 pub async fn read_photo_exif_command(
     photo_id: i64,
@@ -331,13 +407,260 @@ pub async fn read_photo_exif_command(
         .ok_or_else(|| "Photo not found".to_string())?;
 
     // Extract EXIF from the photo file using ExifTool
-    let exif = extract_exif(&photo.file_path)
+    let exif = extract_exif(&photo.file_path).await
         .map_err(|e| format!("Failed to extract EXIF: {}", e))?;
 
     eprintln!("[EXIF] Photo EXIF read successfully");
     Ok(exif)
 }
 
+/// Compare the metadata in the files with the database
+///
+/// Scans one roll, or the whole library when `roll_id` is None. Only photos that
+/// were written before are checked unless `include_unwritten` is set.
+#[tauri::command]
+pub async fn scan_metadata_drift_command(
+    roll_id: Option<i64>,
+    include_unwritten: bool,
+    state: State<'_, AppState>,
+) -> Result<DriftReport, String> {
+    let pool = get_pool(&state).await?;
+
+    let rolls: HashMap<i64, Roll> = match roll_id {
+        Some(roll_id) => get_roll_by_id(&pool, roll_id).await
+            .map_err(|e| format!("Failed to query roll: {}", e))?
+            .into_iter()
+            .collect(),
+        None => get_all_rolls(&pool).await
+            .map_err(|e| format!("Failed to get rolls: {}", e))?,
+    }
+    .into_iter()
+    .map(|roll| (roll.id, roll))
+    .collect();
+
+    let photos = match roll_id {
+        Some(roll_id) => get_photos_by_roll(&pool, roll_id).await,
+        None => get_all_photos(&pool).await,
+    }
+    .map_err(|e| format!("Failed to get photos: {}", e))?;
+
+    let mut roll_photo_counts: HashMap<i64, usize> = HashMap::new();
+    for photo in &photos {
+        *roll_photo_counts.entry(photo.roll_id).or_default() += 1;
+    }
+
+    let mut shot_logs: HashMap<i64, ShotLog> = HashMap::new();
+    for roll_id in rolls.keys() {
+        let logs = get_shot_logs_by_roll(&pool, *roll_id).await
+            .map_err(|e| format!("Failed to query shot logs: {}", e))?;
+        shot_logs.extend(logs.into_iter().map(|log| (log.photo_id, log)));
+    }
+
+    let targets: Vec<(Photo, PhotoMetadata)> = photos
+        .into_iter()
+        .filter(|photo| include_unwritten || photo.exif_synced)
+        .filter_map(|photo| {
+            let metadata = photo_metadata(rolls.get(&photo.roll_id)?, Some(&photo), String::new());
+            Some((photo, metadata))
+        })
+        .collect();
+
+    let results = stream::iter(targets)
+        .map(|(photo, metadata)| {
+            let shot_log = shot_logs.get(&photo.id);
+            async move {
+                let result = extract_exif(&photo.file_path).await
+                    .map(|exif| compare(&metadata, shot_log, &exif));
+                (photo, result)
+            }
+        })
+        .buffer_unordered(exiftool_pool().size()) // One request per ExifTool worker
+        .collect::<Vec<_>>()
+        .await;
+
+    let mut report = DriftReport::default();
+    for (photo, result) in results {
+        report.photos_checked += 1;
+        match result {
+            Ok(fields) if fields.is_empty() => {}
+            Ok(fields) => report.drifted.push(PhotoDrift {
+                photo_id: photo.id,
+                roll_id: photo.roll_id,
+                file_path: photo.file_path,
+                fields,
+                roll_photo_count: roll_photo_counts.get(&photo.roll_id).copied().unwrap_or_default(),
+            }),
+            Err(e) => report.errors.push(format!("{}: {}", photo.file_path, e)),
+        }
+    }
+    report.drifted.sort_by_key(|drift| (drift.roll_id, drift.photo_id));
+
+    eprintln!("[EXIF] Drift scan checked {} photos, {} drifted, {} unreadable",
+        report.photos_checked, report.drifted.len(), report.errors.len());
+    Ok(report)
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ResolveDriftResult {
+    pub resolved_count: usize,
+    pub roll_photos_changed: usize, // Photos of rolls that took a file's value; write the roll to update their files
+    pub errors: Vec<String>,
+}
+
+/// Apply drift resolutions; a failing photo does not stop the others
+/// A field only counts as resolved once the file and database are compared again and match.
+#[tauri::command]
+pub async fn resolve_metadata_drift_command(
+    resolutions: Vec<DriftResolution>,
+    state: State<'_, AppState>,
+) -> Result<ResolveDriftResult, String> {
+    let _library = hold_library(&state)?;
+    let pool = get_pool(&state).await?;
+
+    // Photos in order of first appearance, with their chosen fields
+    let mut by_photo: Vec<(i64, Vec<(DriftField, DriftWinner)>)> = Vec::new();
+    for resolution in resolutions {
+        match by_photo.iter_mut().find(|(photo_id, _)| *photo_id == resolution.photo_id) {
+            Some((_, choices)) => choices.push((resolution.field, resolution.winner)),
+            None => by_photo.push((resolution.photo_id, vec![(resolution.field, resolution.winner)])),
+        }
+    }
+
+    let mut resolved_count = 0;
+    let mut changed_rolls = HashSet::new();
+    let mut errors = Vec::new();
+    for (photo_id, choices) in by_photo {
+        match resolve_photo_drift(&pool, photo_id, &choices).await {
+            Ok((unresolved, changed_roll)) => {
+                changed_rolls.extend(changed_roll);
+                resolved_count += choices.len() - unresolved.len();
+                if !unresolved.is_empty() {
+                    errors.push(format!("Photo {}: {:?} still differ after resolving", photo_id, unresolved));
+                }
+            }
+            Err(e) => {
+                eprintln!("[EXIF] Resolving drift of photo {} failed: {}", photo_id, e);
+                errors.push(format!("Photo {}: {}", photo_id, e));
+            }
+        }
+    }
+
+    let mut roll_photos_changed = 0;
+    for roll_id in changed_rolls {
+        roll_photos_changed += get_photos_by_roll(&pool, roll_id).await
+            .map_err(|e| format!("Failed to get photos: {}", e))?
+            .len();
+    }
+
+    Ok(ResolveDriftResult { resolved_count, roll_photos_changed, errors })
+}
+
+/// Take file values into the database first, then write the database values to the file
+/// Returns the chosen fields that still differ afterwards, and the roll if it took a file value
+async fn resolve_photo_drift(
+    pool: &SqlitePool,
+    photo_id: i64,
+    choices: &[(DriftField, DriftWinner)],
+) -> Result<(Vec<DriftField>, Option<i64>), String> {
+    if let Some((field, winner)) = choices.iter().find(|(field, winner)| !winner.allowed_for(*field)) {
+        return Err(match winner {
+            DriftWinner::FileWins => format!("{:?} is stored on the roll; apply the file's value to the roll to change every photo", field),
+            _ => format!("{:?} is stored on the photo; take the file's value instead", field),
+        });
+    }
+
+    let mut photo = get_photo_by_id(pool, photo_id).await
+        .map_err(|e| format!("Failed to query photo: {}", e))?
+        .ok_or_else(|| "Photo not found".to_string())?;
+    let mut roll = get_roll_by_id(pool, photo.roll_id).await
+        .map_err(|e| format!("Failed to query roll: {}", e))?
+        .ok_or_else(|| "Roll not found".to_string())?;
+    let shot_log = get_shot_log(pool, photo_id).await
+        .map_err(|e| format!("Failed to query shot log: {}", e))?;
+
+    // Compare again so the values taken are the ones in the file now
+    let exif = extract_exif(&photo.file_path).await
+        .map_err(|e| format!("Failed to extract EXIF: {}", e))?;
+    let drift: HashMap<DriftField, FieldDrift> = compare(&photo_metadata(&roll, Some(&photo), String::new()), shot_log.as_ref(), &exif)
+        .into_iter()
+        .map(|drift| (drift.field, drift))
+        .collect();
+
+    let file_wins: Vec<&FieldDrift> = choices.iter()
+        .filter(|(_, winner)| *winner != DriftWinner::DbWins)
+        .filter_map(|(field, _)| drift.get(field))
+        .collect();
+    for field_drift in &file_wins {
+        apply_file_value(field_drift.field, field_drift.file_value.as_deref(), &mut roll, &mut photo)
+            .map_err(|e| e.to_string())?;
+    }
+    let roll_changed = file_wins.iter().any(|field_drift| field_drift.field.is_roll_field());
+    if roll_changed {
+        update_roll(pool, roll.id, roll_update(&roll)).await
+            .map_err(|e| format!("Failed to update roll: {}", e))?;
+    }
+    for field_drift in file_wins.iter().filter(|field_drift| !field_drift.field.is_roll_field()) {
+        let result = match field_drift.field {
+            DriftField::Rating => update_photo_rating(pool, photo.id, photo.rating).await,
+            DriftField::Description => {
+                update_photo_metadata(pool, photo.id, photo.exif_user_comment.clone(), photo.exif_description.clone()).await
+            }
+            _ => update_photo_location_with_city(pool, photo.id, photo.lat, photo.lon, photo.city.clone(), photo.country.clone()).await,
+        };
+        result.map_err(|e| format!("Failed to update photo: {}", e))?;
+    }
+
+    let db_wins: Vec<DriftField> = choices.iter()
+        .filter(|(field, winner)| *winner == DriftWinner::DbWins && drift.contains_key(field))
+        .map(|(field, _)| *field)
+        .collect();
+    if !db_wins.is_empty() {
+        let mode = get_metadata_write_mode(pool).await?;
+        let args = db_wins_args(&photo_metadata(&roll, Some(&photo), String::new()), &db_wins);
+        write_exif_args(&photo.file_path, args, mode).await
+            .map_err(|e| format!("Failed to write EXIF: {}", e))?;
+        // Only some fields were written, so the next roll write must not be skipped
+        mark_photo_exif_synced(pool, photo.id, None).await
+            .map_err(|e| format!("Failed to mark as synced: {}", e))?;
+    }
+
+    let exif = if db_wins.is_empty() {
+        exif
+    } else {
+        extract_exif(&photo.file_path).await
+            .map_err(|e| format!("Failed to extract EXIF after writing: {}", e))?
+    };
+    let remaining: Vec<DriftField> = compare(&photo_metadata(&roll, Some(&photo), String::new()), shot_log.as_ref(), &exif)
+        .into_iter()
+        .map(|drift| drift.field)
+        .filter(|field| choices.iter().any(|(chosen, _)| chosen == field))
+        .collect();
+    Ok((remaining, roll_changed.then_some(roll.id)))
+}
+
+/// Roll fields as `update_roll` takes them
+fn roll_update(roll: &Roll) -> NewRoll {
+    NewRoll {
+        name: roll.name.clone(),
+        path: String::new(), // Path won't be updated through update_roll
+        film_stock: roll.film_stock.clone(),
+        camera: roll.camera.clone(),
+        lens: roll.lens.clone(),
+        shoot_date: roll.shoot_date.clone(),
+        lab_info: roll.lab_info.clone(),
+        notes: roll.notes.clone(),
+        city: roll.city.clone(),
+        country: roll.country.clone(),
+        lat: roll.lat,
+        lon: roll.lon,
+        film_iso: roll.film_iso,
+        film_format: roll.film_format.clone(),
+        film_process: roll.film_process.clone(),
+        push_pull: roll.push_pull,
+        scanner: roll.scanner.clone(),
+    }
+}
+
 /// Format shoot date for EXIF (YYYY:MM:DD HH:MM:SS)
 fn format_shoot_date_for_exif(shoot_date: &str) -> String {
     // shoot_date is in format "YYYY-MM-DD", convert to "YYYY:MM:DD 12:00:00"
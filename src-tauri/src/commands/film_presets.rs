@@ -4,7 +4,8 @@ use crate::database::{FilmPreset, NewFilmPreset, get_all_film_presets, create_fi
 use std::path::{Path, PathBuf};
 use std::fs;
 use std::io::BufWriter;
-use image::{ImageReader, imageops::FilterType};
+use image::{DynamicImage, imageops::FilterType};
+use crate::image_processor::{is_supported_image, load_image};
 use super::get_pool;
 
 /// Get all film presets
@@ -92,13 +93,12 @@ pub async fn upload_preset_image_command(
     }
 
     // Verify it's an image
-    let supported_extensions = ["jpg", "jpeg", "png", "webp", "bmp"];
     let ext = source.extension()
         .and_then(|e| e.to_str())
         .map(|e| e.to_lowercase())
         .ok_or_else(|| "Invalid file extension".to_string())?;
 
-    if !supported_extensions.contains(&ext.as_str()) {
+    if !is_supported_image(source) {
         return Err(format!("Unsupported image format: {}", ext));
     }
 
@@ -112,20 +112,19 @@ pub async fn upload_preset_image_command(
 
     let dest_path = presets_dir.join(&new_filename);
 
+    // Decode (RAW files via their embedded preview)
+    let img = load_image(source).await
+        .map_err(|e| format!("Failed to decode image: {}", e))?;
+
     // Process the image: resize to square and convert to WebP
-    process_preset_image(source, &dest_path)
+    process_preset_image(img, &dest_path)
         .map_err(|e| format!("Failed to process preset image: {}", e))?;
 
     Ok(dest_path.to_string_lossy().to_string())
 }
 
 /// Process a preset image: crop to square and save as WebP
-fn process_preset_image(source: &Path, dest: &Path) -> Result<(), String> {
-    let img = ImageReader::open(source)
-        .map_err(|e| format!("Failed to open image: {}", e))?
-        .decode()
-        .map_err(|e| format!("Failed to decode image: {}", e))?;
-
+fn process_preset_image(img: DynamicImage, dest: &Path) -> Result<(), String> {
     let width = img.width();
     let height = img.height();
     let size = width.min(height);
@@ -1,19 +1,80 @@
-use std::path::Path;
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
 use tauri::{State, Emitter, AppHandle};
 use serde::{Deserialize, Serialize};
-use walkdir::WalkDir;
 use futures::stream::{self, StreamExt};
 
-use crate::database::{NewRoll, NewPhoto, create_roll, create_photos, get_roll_by_id, get_photos_by_roll};
-use crate::image_processor::process_images_in_directory_with_progress;
-use crate::exif_tool::{write_photo_roll_exif, parse_camera_string};
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use sqlx::SqlitePool;
+
+use crate::database::{
+    Roll, NewRoll, NewPhoto, create_roll, create_photos, get_roll_by_id, get_photos_by_roll, get_photo_by_id,
+    get_photos_by_content_hash, get_photos_without_content_hash, update_photo_content_hash, mark_photo_content_hash_failed, delete_photos,
+    delete_roll, set_photo_sequence, set_roll_path,
+};
+use crate::image_processor::{
+    ImportFile, PlannedImport, ProcessedPaths, FailedImport, plan_import_files, copy_planned_files, generate_planned_derivatives,
+    rollback_planned_files, create_roll_subdirs, collect_image_files, find_image_folders, hash_file, hash_import_files,
+};
+use crate::import_jobs::{
+    ImportJobState, ImportJobRegistry, NewImportJob, create_import_job, set_import_job_state,
+    get_unfinished_import_jobs, get_import_job_files, roll_dir_of,
+};
+use crate::archive::{
+    is_archive, archive_stem, archive_image_folders, list_archive_images, hash_archive_images, extract_planned_files,
+};
+use crate::config::{get_filename_template, get_frame_patterns, get_library_root, get_metadata_write_mode};
+use crate::filename_template::{FileNamer, validate_template};
+use crate::frame_numbers::{FrameParser, frame_sort_key, merge_by_frame};
+use crate::exif_tool::{MetadataWriteMode, write_photo_roll_exif, exiftool_pool};
 use crate::AppState;
+use super::{get_pool, hold_library};
+use super::exif::photo_metadata;
+use super::rolls::delete_photo_files;
 
 #[derive(Debug, Serialize, Deserialize)]
 pub struct ImportResult {
+    pub job_id: i64,
     pub roll_id: i64,
     pub photos_count: usize,
     pub message: String,
+    pub duplicates: Vec<DuplicateFile>,
+    pub skipped_count: usize,
+    pub replaced_count: usize,
+    pub replaced_roll_ids: Vec<i64>, // Rolls that lost photos to Replace
+    pub files: Vec<ImportFileReport>,
+}
+
+/// Where an incoming file ended up after an import
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ImportFileReport {
+    pub source_path: String,
+    pub library_path: Option<String>, // None when the file is (back) at source_path
+    pub error: Option<String>,
+}
+
+/// How incoming files that are already in the library are handled
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum DuplicateMode {
+    /// Leave the library copy alone and don't import the file
+    #[default]
+    Skip,
+    /// Import the file and remove the existing library copy, in rolls confirmed for replacing only
+    Replace,
+    /// Import the file even though it is already in the library
+    ImportAnyway,
+}
+
+/// An incoming file whose content is already in the library (or repeats another file of the same import)
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DuplicateFile {
+    pub source_path: String,
+    pub content_hash: String,
+    pub photo_id: Option<i64>, // None when the file repeats another file of this import
+    pub roll_id: Option<i64>,
+    pub existing_path: String,
 }
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -23,11 +84,14 @@ pub struct ImportOptions {
     pub camera: String,
     pub lens: Option<String>,
     pub shoot_date: String,
-    pub library_root: String,
     pub roll_name: Option<String>,
     pub notes: Option<String>,
     pub copy_mode: bool, // true = copy, false = move
     pub auto_write_exif: Option<bool>, // Whether to write EXIF to photos on import
+    pub duplicate_mode: Option<DuplicateMode>, // Defaults to skipping files already in the library
+    pub archive_folder: Option<String>, // When source_path is an archive: only import images directly in this folder
+    #[serde(default)]
+    pub replace_roll_ids: Vec<i64>, // Rolls whose photos Replace may remove; duplicates elsewhere are skipped
 }
 
 /// Import a folder of images as a new roll
@@ -38,15 +102,22 @@ pub async fn import_folder(
     app: AppHandle,
 ) -> Result<ImportResult, String> {
     eprintln!("[Import] import_folder called, waiting for database...");
+    let _library = hold_library(&state)?;
+    let pool = wait_for_pool(&state).await?;
+
+    import_folder_as_roll(&pool, &app, &state.import_jobs, options).await
+}
 
-    // Wait for database to be initialized (up to 30 seconds)
+/// Wait for the database to be initialized (up to 30 seconds)
+/// Imports can be started right after launch, before the pool is ready
+async fn wait_for_pool(state: &State<'_, AppState>) -> Result<SqlitePool, String> {
     let mut attempts = 0;
     let max_attempts = 300; // 30 seconds (300 * 100ms)
-    let pool = loop {
+    loop {
         let db_guard = state.db_pool.lock().await;
         if let Some(pool) = db_guard.as_ref() {
             eprintln!("[Import] Database initialized after {} attempts", attempts);
-            break pool.clone();
+            return Ok(pool.clone());
         }
 
         attempts += 1;
@@ -63,8 +134,16 @@ pub async fn import_folder(
         // Release lock before waiting
         drop(db_guard);
         tokio::time::sleep(tokio::time::Duration::from_millis(100)).await;
-    };
+    }
+}
 
+/// Import one source folder as a new roll
+async fn import_folder_as_roll(
+    pool: &SqlitePool,
+    app: &AppHandle,
+    registry: &ImportJobRegistry,
+    options: ImportOptions,
+) -> Result<ImportResult, String> {
     // Validate source path exists
     let source_path = Path::new(&options.source_path);
     if !source_path.exists() {
@@ -75,7 +154,34 @@ pub async fn import_folder(
     let shoot_date = parse_shoot_date(&options.shoot_date)
         .map_err(|e| format!("Invalid shoot date: {}", e))?;
 
+    // Archive entries are always copied; the archive itself is left untouched
+    let archive = is_archive(source_path).then(|| source_path.to_path_buf());
+    let copy_mode = options.copy_mode || archive.is_some();
+
+    // Check incoming files against the library before creating the roll
+    let image_files = hash_source_files(source_path, options.archive_folder.as_deref()).await?;
+    let (files, duplicates) = find_duplicates(pool, image_files).await?;
+    let found_count = files.len();
+    let (selected_files, replaced_photo_ids) =
+        select_import_files(files, &duplicates, options.duplicate_mode.unwrap_or_default(), &options.replace_roll_ids);
+    let replaced_roll_ids = replaced_rolls(&duplicates, &replaced_photo_ids);
+    let skipped_count = found_count - selected_files.len();
+
+    if found_count > 0 && selected_files.is_empty() {
+        return Err(format!("All {} images are already in the library", found_count));
+    }
+    if !duplicates.is_empty() {
+        eprintln!("[Import] {} duplicates found, skipping {} files", duplicates.len(), skipped_count);
+    }
+
     let year = &shoot_date[0..4];
+    let library_root = get_library_root(pool).await?;
+    if library_root.is_empty() {
+        return Err("No library folder is configured".to_string());
+    }
+    let template = get_filename_template(pool).await?;
+    validate_template(&template).map_err(|e| format!("Invalid filename template: {}", e))?;
+    let frame_parser = frame_parser(pool).await?;
 
     // First, create roll in database to get the ID
     let roll_name = options.roll_name.unwrap_or_else(|| format!("Roll - {}", &options.shoot_date));
@@ -92,139 +198,790 @@ pub async fn import_folder(
         country: None,
         lat: None,
         lon: None,
+        film_iso: None,
+        film_format: None,
+        film_process: None,
+        push_pull: None,
+        scanner: None,
     };
 
-    let roll_id = create_roll(&pool, new_roll).await
+    let roll_id = create_roll(pool, new_roll).await
         .map_err(|e| format!("Failed to create roll in database: {}", e))?;
 
     // Generate unique directory name from roll ID (8-character hex code)
     let dir_code = format!("{:08X}", roll_id);
-    let roll_dir = Path::new(&options.library_root)
+    let roll_dir = Path::new(&library_root)
         .join(year)
         .join(&dir_code);
+    let roll_path = roll_dir.to_string_lossy().to_string();
 
-    // Create roll directory
-    std::fs::create_dir_all(&roll_dir)
-        .map_err(|e| format!("Failed to create roll directory: {}", e))?;
+    // Until the import job is persisted nothing else cleans up the new roll
+    let prepared = async {
+        // Create roll directory
+        std::fs::create_dir_all(&roll_dir)
+            .map_err(|e| format!("Failed to create roll directory: {}", e))?;
 
-    // Update roll path in database
-    let roll_path = roll_dir.to_string_lossy().to_string();
-    sqlx::query("UPDATE rolls SET path = ?1 WHERE id = ?2")
-        .bind(&roll_path)
-        .bind(roll_id)
-        .execute(&pool)
-        .await
-        .map_err(|e| format!("Failed to update roll path: {}", e))?;
+        // Update roll path in database
+        set_roll_path(pool, roll_id, &roll_path)
+            .await
+            .map_err(|e| format!("Failed to update roll path: {}", e))?;
+
+        eprintln!("[Import] Created roll directory: {:?}", roll_dir);
+
+        let roll = get_roll_by_id(pool, roll_id).await
+            .map_err(|e| format!("Failed to get roll: {}", e))?
+            .ok_or_else(|| format!("Roll with ID {} not found", roll_id))?;
+        let mut namer = FileNamer::new(&template, &roll)
+            .map_err(|e| format!("Invalid filename template: {}", e))?;
+        // The folder may already hold files, e.g. left behind by a deleted roll with the same ID
+        namer.reserve_roll_files(&roll_dir);
 
-    eprintln!("[Import] Created roll directory: {:?}", roll_dir);
+        Ok::<_, String>((roll, namer))
+    }
+    .await;
+
+    let (roll, mut namer) = match prepared {
+        Ok(prepared) => prepared,
+        Err(e) => {
+            let _ = delete_roll(pool, roll_id).await;
+            let _ = std::fs::remove_dir(&roll_dir);
+            return Err(e);
+        }
+    };
 
-    // Process images with progress callback
-    let app_handle = app.clone();
-    let processed_images = process_images_in_directory_with_progress(
-        source_path,
-        &roll_dir,
+    let outcome = run_import_job(
+        pool,
+        app,
+        registry,
+        &roll,
+        true,
+        plan_roll_import(selected_files, &roll_dir, 0, &mut namer, &frame_parser),
+        archive,
+        copy_mode,
+        options.auto_write_exif.unwrap_or(false),
+        replaced_photo_ids,
+    ).await?;
+
+    // Send completion event
+    let _ = app.emit("import-complete", serde_json::json!({
+        "jobId": outcome.job_id,
+        "rollId": roll_id,
+        "count": outcome.photos_count,
+        "path": roll_path,
+    }));
+
+    Ok(ImportResult {
+        job_id: outcome.job_id,
         roll_id,
-        options.copy_mode,
-        move |current, total, filename| {
+        photos_count: outcome.photos_count,
+        message: format!(
+            "成功导入 {} 张照片到胶卷文件夹{}",
+            outcome.photos_count,
+            skipped_message(skipped_count)
+        ),
+        duplicates,
+        skipped_count,
+        replaced_count: outcome.replaced_count,
+        replaced_roll_ids,
+        files: outcome.files,
+    })
+}
+
+/// Result of a completed import job
+struct ImportJobOutcome {
+    job_id: i64,
+    photos_count: usize,
+    replaced_count: usize,
+    files: Vec<ImportFileReport>,
+}
+
+/// Report where each planned file is: in the library if it was processed,
+/// otherwise at its source (with the error that stopped it, if any)
+fn import_file_reports(
+    planned: &[PlannedImport],
+    processed: &[ProcessedPaths],
+    failed: &[FailedImport],
+) -> Vec<ImportFileReport> {
+    planned
+        .iter()
+        .map(|file| {
+            let imported = processed.iter().any(|p| p.original_path == file.original_path);
+            ImportFileReport {
+                source_path: file.source_path.to_string_lossy().to_string(),
+                library_path: imported.then(|| file.original_path.to_string_lossy().to_string()),
+                error: failed
+                    .iter()
+                    .find(|f| f.source_path == file.source_path)
+                    .map(|f| f.error.clone()),
+            }
+        })
+        .collect()
+}
+
+/// Summarize failed files for an error message
+fn failed_files_message(failed: &[FailedImport]) -> String {
+    failed
+        .iter()
+        .map(|f| {
+            let name = f.source_path.file_name().and_then(|n| n.to_str()).unwrap_or("unknown");
+            format!("{} ({})", name, f.error)
+        })
+        .collect::<Vec<_>>()
+        .join("; ")
+}
+
+/// Run an import as a persisted job: copy originals, generate derivatives,
+/// write photo rows and (optionally) EXIF
+///
+/// The job can be cancelled with `cancel_import_job` until the photo rows are
+/// written; a cancelled or failed job is rolled back, including its roll when
+/// `created_roll` is set. Move-mode imports are all-or-nothing: if any file
+/// fails to move or decode, or the photo rows can't be written, every original
+/// is moved back to its source. Copy-mode imports leave failed files out.
+#[allow(clippy::too_many_arguments)]
+async fn run_import_job(
+    pool: &SqlitePool,
+    app: &AppHandle,
+    registry: &ImportJobRegistry,
+    roll: &Roll,
+    created_roll: bool,
+    planned: Vec<PlannedImport>,
+    archive: Option<PathBuf>,
+    copy_mode: bool,
+    auto_write_exif: bool,
+    replaced_photo_ids: Vec<i64>,
+) -> Result<ImportJobOutcome, String> {
+    let roll_dir = Path::new(&roll.path);
+
+    let job_id = match create_import_job(pool, NewImportJob {
+        roll_id: roll.id,
+        created_roll,
+        copy_mode,
+        auto_write_exif,
+        replaced_photo_ids: &replaced_photo_ids,
+    }, &planned).await {
+        Ok(job_id) => job_id,
+        Err(e) => {
+            if created_roll {
+                let _ = delete_roll(pool, roll.id).await;
+                let _ = std::fs::remove_dir(roll_dir);
+            }
+            return Err(format!("Failed to create import job: {}", e));
+        }
+    };
+
+    let cancel = registry.register(job_id);
+    let total = planned.len();
+    let _ = app.emit("import-job-started", serde_json::json!({
+        "jobId": job_id,
+        "rollId": roll.id,
+        "total": total,
+    }));
+
+    let emit_progress = |phase: ImportJobState| {
+        let app_handle = app.clone();
+        let roll_id = roll.id;
+        move |current: usize, total: usize, filename: String| {
             // Send progress event to frontend
             let _ = app_handle.emit("import-progress", serde_json::json!({
+                "jobId": job_id,
+                "phase": phase,
                 "current": current,
                 "total": total,
                 "filename": filename,
                 "rollId": roll_id,
             }));
         }
-    ).map_err(|e| format!("Failed to process images: {}", e))?;
+    };
 
-    if processed_images.is_empty() {
-        return Err("No images found in source directory".to_string());
-    }
+    // Copy originals, then generate derivatives
+    let mut failed: Vec<FailedImport> = Vec::new();
+    let processed = async {
+        set_import_job_state(pool, job_id, ImportJobState::Copying, None).await
+            .map_err(|e| format!("Failed to update import job: {}", e))?;
+        failed = match &archive {
+            Some(archive) => extract_from_archive(archive, &planned, roll_dir, &cancel, emit_progress(ImportJobState::Copying))
+                .await?,
+            None => copy_planned_files(&planned, roll_dir, copy_mode, &cancel, emit_progress(ImportJobState::Copying))
+                .await
+                .map_err(|e| format!("Failed to copy images: {}", e))?,
+        };
+
+        if cancel.load(Ordering::SeqCst) {
+            return Ok(None);
+        }
+        if !copy_mode && !failed.is_empty() {
+            return Err(format!("Failed to move {}", failed_files_message(&failed)));
+        }
+
+        set_import_job_state(pool, job_id, ImportJobState::GeneratingDerivatives, None).await
+            .map_err(|e| format!("Failed to update import job: {}", e))?;
+        let (processed, derivative_failures) = generate_planned_derivatives(
+            &planned,
+            &cancel,
+            emit_progress(ImportJobState::GeneratingDerivatives),
+        ).await;
+        failed.extend(derivative_failures);
+
+        if cancel.load(Ordering::SeqCst) {
+            return Ok(None);
+        }
+        if !copy_mode && !failed.is_empty() {
+            return Err(format!("Failed to process {}", failed_files_message(&failed)));
+        }
+        if processed.is_empty() {
+            return Err("No images could be processed".to_string());
+        }
+
+        // Copy mode keeps going without failed files; drop whatever they left in the roll
+        if !failed.is_empty() {
+            let leftovers: Vec<PlannedImport> = planned
+                .iter()
+                .filter(|file| failed.iter().any(|f| f.source_path == file.source_path))
+                .cloned()
+                .collect();
+            let dir = roll_dir.to_path_buf();
+            let cleanup = tokio::task::spawn_blocking(move || rollback_planned_files(&leftovers, &dir, true)).await;
+            if !matches!(cleanup, Ok(Ok(()))) {
+                eprintln!("[Import] Failed to clean up files of failed imports in job {}", job_id);
+            }
+        }
+
+        // Photo rows are written in one transaction, so a failure leaves none behind
+        record_import_photos(pool, job_id, roll, &processed).await?;
+        Ok(Some(processed))
+    }.await;
+
+    registry.finish(job_id);
+
+    let processed = match processed {
+        Ok(Some(processed)) => processed,
+        Ok(None) => {
+            eprintln!("[Import] Job {} cancelled, rolling back", job_id);
+            rollback_import_job(pool, job_id, roll.id, created_roll, copy_mode, &planned, roll_dir,
+                ImportJobState::Cancelled, None).await?;
+            let _ = app.emit("import-cancelled", serde_json::json!({ "jobId": job_id, "rollId": roll.id }));
+            return Err("Import cancelled".to_string());
+        }
+        Err(e) => {
+            eprintln!("[Import] Job {} failed, rolling back: {}", job_id, e);
+            rollback_import_job(pool, job_id, roll.id, created_roll, copy_mode, &planned, roll_dir,
+                ImportJobState::Failed, Some(&e)).await?;
+            let _ = app.emit("import-rolled-back", serde_json::json!({
+                "jobId": job_id,
+                "rollId": roll.id,
+                "error": e,
+                "files": import_file_reports(&planned, &[], &failed),
+            }));
+            return Err(format!("{}; no files were imported", e));
+        }
+    };
 
-    eprintln!("[Import] Processed {} images", processed_images.len());
+    eprintln!("[Import] Processed {} images", processed.len());
+
+    let files = import_file_reports(&planned, &processed, &failed);
+    let photos_count = processed.len();
+    let replaced_count =
+        complete_import_job(pool, job_id, roll, processed, replaced_photo_ids, auto_write_exif).await?;
+
+    Ok(ImportJobOutcome { job_id, photos_count, replaced_count, files })
+}
+
+/// Extract planned archive entries into the roll on a blocking thread
+async fn extract_from_archive<F>(
+    archive: &Path,
+    planned: &[PlannedImport],
+    roll_dir: &Path,
+    cancel: &Arc<AtomicBool>,
+    progress_callback: F,
+) -> Result<Vec<FailedImport>, String>
+where
+    F: FnMut(usize, usize, String) + Send + 'static,
+{
+    let archive = archive.to_path_buf();
+    let planned = planned.to_vec();
+    let roll_dir = roll_dir.to_path_buf();
+    let cancel = cancel.clone();
+
+    tokio::task::spawn_blocking(move || {
+        create_roll_subdirs(&roll_dir)?;
+        extract_planned_files(&archive, &planned, &cancel, progress_callback)
+    })
+    .await
+    .map_err(|e| format!("Failed to extract images: {}", e))?
+    .map_err(|e| format!("Failed to extract images: {:#}", e))
+}
+
+/// Finish a job whose files are in place: write photo rows, remove replaced photos and write EXIF
+async fn finish_import_job(
+    pool: &SqlitePool,
+    job_id: i64,
+    roll: &Roll,
+    processed: Vec<ProcessedPaths>,
+    replaced_photo_ids: Vec<i64>,
+    auto_write_exif: bool,
+) -> Result<(usize, usize), String> {
+    record_import_photos(pool, job_id, roll, &processed).await?;
+    let photos_count = processed.len();
+    let replaced_count =
+        complete_import_job(pool, job_id, roll, processed, replaced_photo_ids, auto_write_exif).await?;
+    Ok((photos_count, replaced_count))
+}
+
+/// Write photo rows for processed files
+/// Photos already recorded (the app stopped right after writing them) are not added twice
+async fn record_import_photos(
+    pool: &SqlitePool,
+    job_id: i64,
+    roll: &Roll,
+    processed: &[ProcessedPaths],
+) -> Result<(), String> {
+    set_import_job_state(pool, job_id, ImportJobState::WritingDb, None).await
+        .map_err(|e| format!("Failed to update import job: {}", e))?;
+
+    let existing_photos = get_photos_by_roll(pool, roll.id).await
+        .map_err(|e| format!("Failed to get existing photos: {}", e))?;
+    let existing: std::collections::HashSet<&str> = existing_photos
+        .iter()
+        .map(|photo| photo.file_path.as_str())
+        .collect();
 
     // Create photo records in database
-    let photos_count = processed_images.len();
-    let new_photos: Vec<NewPhoto> = processed_images
+    let new_photos: Vec<NewPhoto> = processed
         .iter()
         .map(|p| NewPhoto {
-            roll_id,
+            roll_id: roll.id,
             filename: p.filename.clone(),
             file_path: p.original_path.to_string_lossy().to_string(),
             thumbnail_path: Some(p.thumbnail_path.to_string_lossy().to_string()),
             preview_path: Some(p.preview_path.to_string_lossy().to_string()),
+            content_hash: p.content_hash.clone(),
+            perceptual_hash: p.perceptual_hash.clone(),
+            original_filename: p.original_filename.clone(),
+            frame_number: p.frame_number.clone(),
         })
+        .filter(|p| !existing.contains(p.file_path.as_str()))
         .collect();
+    let added_frames: Vec<Option<String>> = new_photos.iter().map(|p| p.frame_number.clone()).collect();
 
-    create_photos(&pool, new_photos).await
+    let ids = create_photos(pool, new_photos).await
         .map_err(|e| format!("Failed to create photos in database: {}", e))?;
 
+    // New photos were appended; slot late re-scans in between the frames already there
+    if !existing_photos.is_empty() && added_frames.iter().any(Option::is_some) {
+        let existing_frames: Vec<_> = existing_photos.iter().map(|p| (p.id, p.frame_number.clone())).collect();
+        let added: Vec<_> = ids.into_iter().zip(added_frames).collect();
+        set_photo_sequence(pool, roll.id, &merge_by_frame(&existing_frames, &added)).await
+            .map_err(|e| format!("Failed to order imported photos: {}", e))?;
+    }
+
+    Ok(())
+}
+
+/// Remove replaced photos and write EXIF once photo rows exist; returns the number replaced
+async fn complete_import_job(
+    pool: &SqlitePool,
+    job_id: i64,
+    roll: &Roll,
+    processed: Vec<ProcessedPaths>,
+    replaced_photo_ids: Vec<i64>,
+    auto_write_exif: bool,
+) -> Result<usize, String> {
+    let replaced_count = remove_replaced_photos(pool, replaced_photo_ids).await?;
+
     // Write EXIF if enabled
-    if options.auto_write_exif.unwrap_or(false) {
-        eprintln!("[Import] Auto-writing EXIF to {} photos", photos_count);
+    if auto_write_exif {
+        set_import_job_state(pool, job_id, ImportJobState::WritingExif, None).await
+            .map_err(|e| format!("Failed to update import job: {}", e))?;
+        let mode = get_metadata_write_mode(pool).await?;
+        write_import_exif(roll, processed, mode).await;
+    }
+
+    set_import_job_state(pool, job_id, ImportJobState::Done, None).await
+        .map_err(|e| format!("Failed to update import job: {}", e))?;
 
-        // Parse camera string into make and model
-        let (make, model) = parse_camera_string(&options.camera);
+    Ok(replaced_count)
+}
+
+/// Remove the files of an unfinished job (and its roll if the job created it)
+#[allow(clippy::too_many_arguments)]
+async fn rollback_import_job(
+    pool: &SqlitePool,
+    job_id: i64,
+    roll_id: i64,
+    created_roll: bool,
+    copy_mode: bool,
+    planned: &[PlannedImport],
+    roll_dir: &Path,
+    state: ImportJobState,
+    error: Option<&str>,
+) -> Result<(), String> {
+    let files = planned.to_vec();
+    let dir = roll_dir.to_path_buf();
+    tokio::task::spawn_blocking(move || rollback_planned_files(&files, &dir, copy_mode))
+        .await
+        .map_err(|e| format!("Failed to roll back import: {}", e))?
+        .map_err(|e| format!("Failed to roll back import: {}", e))?;
+
+    if created_roll {
+        delete_roll(pool, roll_id).await
+            .map_err(|e| format!("Failed to delete roll: {}", e))?;
+        // Only succeeds when nothing else was put in the roll directory
+        let _ = std::fs::remove_dir(roll_dir);
+    }
+
+    set_import_job_state(pool, job_id, state, error).await
+        .map_err(|e| format!("Failed to update import job: {}", e))?;
 
-        // Build user comment: "Shot on {film_stock} | {city}, {country} | {notes}"
-        let mut parts = vec![];
-        if !options.film_stock.is_empty() {
-            parts.push(format!("Shot on {}", options.film_stock));
+    eprintln!("[Import] Job {} rolled back", job_id);
+    Ok(())
+}
+
+/// Write roll-level EXIF to freshly imported photos
+/// Failures are only logged so they don't block the import
+async fn write_import_exif(roll: &Roll, processed: Vec<ProcessedPaths>, mode: MetadataWriteMode) {
+    eprintln!("[Import] Auto-writing EXIF to {} photos", processed.len());
+
+    // Build user comment: "Shot on {film_stock} | {notes}"
+    let mut parts = vec![];
+    if !roll.film_stock.is_empty() {
+        parts.push(format!("Shot on {}", roll.film_stock));
+    }
+    if let Some(ref notes) = roll.notes {
+        if !notes.is_empty() {
+            parts.push(notes.clone());
         }
-        if let Some(ref notes) = options.notes {
-            if !notes.is_empty() {
-                parts.push(notes.clone());
+    }
+    let metadata = photo_metadata(roll, None, parts.join(" | "));
+
+    // Write EXIF to all photos with concurrency control
+    let results = stream::iter(processed)
+        .map(|p| {
+            let file_path = p.original_path.clone();
+            let metadata = &metadata;
+
+            async move {
+                (
+                    file_path.clone(),
+                    write_photo_roll_exif(&file_path.to_string_lossy(), metadata, None, mode).await
+                )
             }
+        })
+        .buffer_unordered(exiftool_pool().size()) // One request per ExifTool worker
+        .collect::<Vec<_>>()
+        .await;
+
+    for (file_path, result) in results {
+        if let Err(e) = result {
+            eprintln!("[Import] Failed to write EXIF to {:?}: {}", file_path, e);
         }
-        let user_comment = parts.join(" | ");
-
-        // Format date time original from shoot_date (replace - with :)
-        let date_time_original = format_exif_date(&shoot_date);
-
-        // Write EXIF to all photos with concurrency control
-        let _results = stream::iter(processed_images)
-            .map(|p| {
-                let file_path = p.original_path.clone();
-                let make_clone = make.clone();
-                let model_clone = model.clone();
-                let date_clone = date_time_original.clone();
-                let comment_clone = user_comment.clone();
-
-                async move {
-                    (
-                        file_path.clone(),
-                        write_photo_roll_exif(
-                            &file_path.to_string_lossy(),
-                            &make_clone,
-                            &model_clone,
-                            &date_clone,
-                            &comment_clone,
-                        ).await
-                    )
+    }
+}
+
+/// Finish or roll back imports interrupted by an app exit or crash
+///
+/// Jobs that stopped after all files were in place are finished; earlier
+/// ones are rolled back. Called once at startup after migrations.
+pub async fn resume_interrupted_imports(pool: &SqlitePool) {
+    let jobs = match get_unfinished_import_jobs(pool).await {
+        Ok(jobs) => jobs,
+        Err(e) => {
+            eprintln!("[Import] Failed to query interrupted imports: {}", e);
+            return;
+        }
+    };
+
+    for job in jobs {
+        eprintln!("[Import] Recovering import job {} (state {})", job.id, job.state);
+
+        let result = async {
+            let planned = get_import_job_files(pool, job.id).await
+                .map_err(|e| format!("Failed to get import job files: {}", e))?;
+            let roll = get_roll_by_id(pool, job.roll_id).await
+                .map_err(|e| format!("Failed to get roll: {}", e))?;
+
+            let roll_dir = match (&roll, roll_dir_of(&planned)) {
+                (Some(roll), _) => PathBuf::from(&roll.path),
+                (None, Some(dir)) => dir.to_path_buf(),
+                (None, None) => PathBuf::new(),
+            };
+
+            match roll {
+                Some(roll) if job.job_state().files_complete() => {
+                    let processed: Vec<ProcessedPaths> = planned.iter()
+                        .filter(|file| file.is_complete())
+                        .map(|file| file.to_processed())
+                        .collect();
+                    let (count, _) = finish_import_job(
+                        pool, job.id, &roll, processed, job.replaced_photo_ids(), job.auto_write_exif,
+                    ).await?;
+                    eprintln!("[Import] Finished interrupted job {} ({} photos)", job.id, count);
                 }
-            })
-            .buffer_unordered(4) // Limit concurrency to 4
-            .collect::<Vec<_>>()
-            .await;
+                _ => {
+                    rollback_import_job(
+                        pool, job.id, job.roll_id, job.created_roll, job.copy_mode, &planned, &roll_dir,
+                        ImportJobState::Failed, Some("Interrupted before all files were imported; rolled back"),
+                    ).await?;
+                }
+            }
+            Ok::<(), String>(())
+        }.await;
 
-        // Note: We don't check results here to avoid blocking import on EXIF write failures
-        // Errors are logged to console
+        if let Err(e) = result {
+            eprintln!("[Import] Failed to recover import job {}: {}", job.id, e);
+            let _ = set_import_job_state(pool, job.id, ImportJobState::Failed, Some(&e)).await;
+        }
     }
+}
 
-    // Send completion event
-    let _ = app.emit("import-complete", serde_json::json!({
-        "rollId": roll_id,
-        "count": photos_count,
-        "path": roll_path,
-    }));
+/// Cancel a running import; its files (and new roll) are removed
+#[tauri::command]
+pub async fn cancel_import_job(
+    job_id: i64,
+    state: State<'_, AppState>,
+) -> Result<bool, String> {
+    let cancelled = state.import_jobs.cancel(job_id);
+    eprintln!("[Import] Cancel requested for job {}: {}", job_id, if cancelled { "ok" } else { "not running" });
+    Ok(cancelled)
+}
 
-    Ok(ImportResult {
-        roll_id,
-        photos_count,
-        message: format!(
-            "成功导入 {} 张照片到胶卷文件夹",
-            photos_count
-        ),
-    })
+/// Record content hashes for photos imported before hashes were stored
+///
+/// Runs in the background when a library is opened. Originals that cannot be
+/// read are marked and not tried again. Photos with EXIF already written into
+/// the original are skipped: they no longer hash like the delivered file, so
+/// re-deliveries of them are not detected as duplicates.
+pub(crate) async fn backfill_content_hashes(pool: SqlitePool) {
+    let missing = match get_photos_without_content_hash(&pool).await {
+        Ok(missing) => missing,
+        Err(e) => {
+            eprintln!("[Import] Failed to query photos without content hash: {}", e);
+            return;
+        }
+    };
+    if missing.is_empty() {
+        return;
+    }
+
+    eprintln!("[Import] Hashing {} existing photos for duplicate detection", missing.len());
+
+    let (mut hashed, mut failed) = (0, 0);
+    for (photo_id, file_path) in missing {
+        let path = PathBuf::from(&file_path);
+        let result = match tokio::task::spawn_blocking(move || hash_file(&path)).await {
+            Ok(Ok(hash)) => update_photo_content_hash(&pool, photo_id, &hash).await.map(|_| hashed += 1),
+            Ok(Err(e)) => {
+                eprintln!("[Import] Cannot hash photo {} ({}): {:#}", photo_id, file_path, e);
+                failed += 1;
+                mark_photo_content_hash_failed(&pool, photo_id).await.map(|_| ())
+            }
+            Err(e) => {
+                eprintln!("[Import] Hashing photo {} panicked: {}", photo_id, e);
+                continue;
+            }
+        };
+        // The library was closed, e.g. switched; the rest is hashed next time it is opened
+        if let Err(e) = result {
+            eprintln!("[Import] Stopped hashing existing photos: {}", e);
+            return;
+        }
+    }
+
+    eprintln!("[Import] Hashed {} existing photos, {} could not be read", hashed, failed);
+}
+
+/// Hash the images of a source folder or archive
+/// For archives, `archive_folder` limits the import to images directly in that folder
+async fn hash_source_files(source_path: &Path, archive_folder: Option<&str>) -> Result<Vec<ImportFile>, String> {
+    if is_archive(source_path) {
+        let archive = source_path.to_path_buf();
+        let folder = archive_folder.map(str::to_string);
+        return tokio::task::spawn_blocking(move || hash_archive_images(&archive, folder.as_deref()))
+            .await
+            .map_err(|e| format!("Failed to read archive: {}", e))?
+            .map_err(|e| format!("Failed to read archive: {:#}", e));
+    }
+
+    let image_files = collect_image_files(source_path)
+        .map_err(|e| format!("Failed to read source directory: {}", e))?;
+    hash_import_files(image_files).await
+        .map_err(|e| format!("Failed to hash source files: {}", e))
+}
+
+/// Hash the incoming files and find those already in the library or repeated within the import
+async fn find_duplicates(
+    pool: &sqlx::SqlitePool,
+    files: Vec<ImportFile>,
+) -> Result<(Vec<ImportFile>, Vec<DuplicateFile>), String> {
+    let mut duplicates = vec![];
+    let mut seen: HashMap<&str, &Path> = HashMap::new();
+
+    for file in &files {
+        let source_path = file.source_path.to_string_lossy().to_string();
+
+        let existing = get_photos_by_content_hash(pool, &file.content_hash).await
+            .map_err(|e| format!("Failed to query duplicates: {}", e))?;
+        for photo in existing {
+            duplicates.push(DuplicateFile {
+                source_path: source_path.clone(),
+                content_hash: file.content_hash.clone(),
+                photo_id: Some(photo.id),
+                roll_id: Some(photo.roll_id),
+                existing_path: photo.file_path,
+            });
+        }
+
+        if let Some(first) = seen.get(file.content_hash.as_str()) {
+            duplicates.push(DuplicateFile {
+                source_path,
+                content_hash: file.content_hash.clone(),
+                photo_id: None,
+                roll_id: None,
+                existing_path: first.to_string_lossy().to_string(),
+            });
+        } else {
+            seen.insert(&file.content_hash, &file.source_path);
+        }
+    }
+
+    Ok((files, duplicates))
+}
+
+/// Choose which files to import for a duplicate mode
+/// Returns the files to import and the library photos they replace. Replace only
+/// removes photos of `replace_roll_ids`; a file also in another roll is skipped.
+fn select_import_files(
+    files: Vec<ImportFile>,
+    duplicates: &[DuplicateFile],
+    mode: DuplicateMode,
+    replace_roll_ids: &[i64],
+) -> (Vec<ImportFile>, Vec<i64>) {
+    let is_listed = |file: &ImportFile, in_library: bool| {
+        duplicates.iter().any(|d| {
+            d.photo_id.is_some() == in_library && Path::new(&d.source_path) == file.source_path
+        })
+    };
+
+    match mode {
+        DuplicateMode::ImportAnyway => (files, vec![]),
+        DuplicateMode::Skip => {
+            let selected = files.into_iter()
+                .filter(|f| !is_listed(f, true) && !is_listed(f, false))
+                .collect();
+            (selected, vec![])
+        }
+        DuplicateMode::Replace => {
+            let in_other_roll = |file: &ImportFile| {
+                duplicates.iter().any(|d| {
+                    d.roll_id.is_some_and(|roll_id| !replace_roll_ids.contains(&roll_id))
+                        && Path::new(&d.source_path) == file.source_path
+                })
+            };
+            let selected: Vec<ImportFile> = files.into_iter()
+                .filter(|f| !is_listed(f, false) && !in_other_roll(f))
+                .collect();
+            let mut replaced: Vec<i64> = duplicates.iter()
+                .filter(|d| selected.iter().any(|f| Path::new(&d.source_path) == f.source_path))
+                .filter_map(|d| d.photo_id)
+                .collect();
+            replaced.sort_unstable();
+            replaced.dedup();
+            (selected, replaced)
+        }
+    }
+}
+
+/// Rolls that the replaced photos belong to
+fn replaced_rolls(duplicates: &[DuplicateFile], replaced_photo_ids: &[i64]) -> Vec<i64> {
+    let mut roll_ids: Vec<i64> = duplicates.iter()
+        .filter(|d| d.photo_id.is_some_and(|photo_id| replaced_photo_ids.contains(&photo_id)))
+        .filter_map(|d| d.roll_id)
+        .collect();
+    roll_ids.sort_unstable();
+    roll_ids.dedup();
+    roll_ids
+}
+
+/// Delete library photos (files, then records) superseded by a replacing import
+/// A photo whose files cannot be deleted keeps its record, so nothing is left untracked
+async fn remove_replaced_photos(pool: &sqlx::SqlitePool, photo_ids: Vec<i64>) -> Result<usize, String> {
+    let mut removed = Vec::with_capacity(photo_ids.len());
+    for photo_id in photo_ids {
+        let photo = match get_photo_by_id(pool, photo_id).await {
+            Ok(Some(photo)) => photo,
+            Ok(None) => continue, // Already gone, e.g. replaced by an earlier attempt of the job
+            Err(e) => return Err(format!("Failed to get replaced photo {}: {}", photo_id, e)),
+        };
+        match delete_photo_files(&photo).await {
+            Ok(()) => removed.push(photo_id),
+            Err(e) => eprintln!("[Warning] Keeping replaced photo {}, its files could not be deleted: {}", photo_id, e),
+        }
+    }
+    if removed.is_empty() {
+        return Ok(0);
+    }
+
+    delete_photos(pool, removed).await
+        .map_err(|e| format!("Failed to delete replaced photos: {}", e))
+}
+
+/// Message for files that were not imported because they are already in the library
+fn skipped_message(skipped_count: usize) -> String {
+    if skipped_count > 0 {
+        format!("，跳过 {} 张重复照片", skipped_count)
+    } else {
+        String::new()
+    }
+}
+
+/// Frame number parser with the user's patterns
+pub(crate) async fn frame_parser(pool: &SqlitePool) -> Result<FrameParser, String> {
+    let patterns = get_frame_patterns(pool).await?;
+    FrameParser::new(&patterns).map_err(|e| format!("Invalid frame pattern: {:#}", e))
+}
+
+/// Plan an import into a roll: files are ordered by the frame number parsed
+/// from their name (files without one follow in name order), then named
+fn plan_roll_import(
+    files: Vec<ImportFile>,
+    roll_dir: &Path,
+    start_index: usize,
+    namer: &mut FileNamer,
+    parser: &FrameParser,
+) -> Vec<PlannedImport> {
+    let mut framed: Vec<(ImportFile, Option<String>)> = files
+        .into_iter()
+        .map(|file| {
+            let frame = parser.parse(&source_file_name(&file.source_path));
+            (file, frame)
+        })
+        .collect();
+    framed.sort_by_key(|(_, frame)| (frame.is_none(), frame.as_deref().map(frame_sort_key)));
+
+    let (files, frames): (Vec<_>, Vec<_>) = framed.into_iter().unzip();
+    let mut planned = plan_import_files(files, roll_dir, start_index, |frame, source| {
+        namer.stem(frame, &source_file_name(source))
+    });
+    for (file, frame) in planned.iter_mut().zip(frames) {
+        file.frame_number = frame;
+    }
+    planned
+}
+
+/// File name of an import source (archive entries use their own name)
+fn source_file_name(source_path: &Path) -> String {
+    source_path
+        .file_name()
+        .map(|name| name.to_string_lossy().to_string())
+        .unwrap_or_default()
+}
+
+/// Filename without its extension
+fn file_stem_of(filename: &str) -> String {
+    Path::new(filename)
+        .file_stem()
+        .map(|stem| stem.to_string_lossy().to_string())
+        .unwrap_or_default()
 }
 
 /// Parse and validate shoot date
@@ -236,23 +993,6 @@ fn parse_shoot_date(date_str: &str) -> Result<String, String> {
     Ok(parsed.format("%Y-%m-%d").to_string())
 }
 
-/// Format shoot date for EXIF (YYYY:MM:DD HH:MM:SS)
-fn format_exif_date(shoot_date: &str) -> String {
-    // shoot_date is in format "YYYY-MM-DD", convert to "YYYY:MM:DD 12:00:00"
-    // Replace dashes with colons and add default time
-    shoot_date.replace("-", ":") + " 12:00:00"
-}
-
-/// Sanitize filename by removing/replacing problematic characters
-fn sanitize_filename(name: &str) -> String {
-    name.chars()
-        .map(|c| match c {
-            ' ' | '/' | '\\' | ':' | '*' | '?' | '"' | '<' | '>' | '|' => '_',
-            _ => c
-        })
-        .collect()
-}
-
 /// Get count of images in a directory (without processing)
 #[tauri::command]
 pub async fn preview_import_count(source_path: String) -> Result<usize, String> {
@@ -261,25 +1001,208 @@ pub async fn preview_import_count(source_path: String) -> Result<usize, String>
         return Err("Source path does not exist".to_string());
     }
 
-    let image_extensions = ["jpg", "jpeg", "png", "tif", "tiff", "webp", "bmp"];
-    let mut count = 0;
+    // Same file selection as import_folder, so the preview matches what gets imported
+    if is_archive(path) {
+        let archive = path.to_path_buf();
+        let names = tokio::task::spawn_blocking(move || list_archive_images(&archive, None))
+            .await
+            .map_err(|e| format!("Failed to read archive: {}", e))?
+            .map_err(|e| format!("Failed to read archive: {:#}", e))?;
+        return Ok(names.len());
+    }
+
+    let image_files = collect_image_files(path)
+        .map_err(|e| format!("Failed to read source directory: {}", e))?;
+
+    Ok(image_files.len())
+}
+
+/// A roll proposed for one folder of a delivery tree; editable before importing
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ProposedRoll {
+    pub source_path: String,
+    pub archive_folder: Option<String>, // Folder inside the archive when source_path is an archive
+    pub roll_name: String,
+    pub image_count: usize,
+    pub film_stock: String,
+    pub camera: String,
+    pub lens: Option<String>,
+    pub shoot_date: String,
+    pub notes: Option<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct BatchImportOptions {
+    pub rolls: Vec<ProposedRoll>,
+    pub copy_mode: bool, // true = copy, false = move
+    pub auto_write_exif: Option<bool>,
+    pub duplicate_mode: Option<DuplicateMode>,
+    #[serde(default)]
+    pub replace_roll_ids: Vec<i64>, // Rolls whose photos Replace may remove; duplicates elsewhere are skipped
+}
+
+/// A proposed roll that could not be imported
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BatchImportError {
+    pub source_path: String,
+    pub roll_name: String,
+    pub error: String,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct BatchImportResult {
+    pub imported: Vec<ImportResult>,
+    pub failed: Vec<BatchImportError>,
+}
+
+/// Walk a delivery tree (folder or archive) and propose one roll per folder that contains images
+/// Roll names come from folder names; the shoot date defaults to the folder's modification date
+#[tauri::command]
+pub async fn preview_batch_import(source_path: String) -> Result<Vec<ProposedRoll>, String> {
+    let root = Path::new(&source_path);
+    if !root.exists() {
+        return Err("Source path does not exist".to_string());
+    }
+
+    if is_archive(root) {
+        let archive = root.to_path_buf();
+        let folders = tokio::task::spawn_blocking(move || archive_image_folders(&archive))
+            .await
+            .map_err(|e| format!("Failed to read archive: {}", e))?
+            .map_err(|e| format!("Failed to read archive: {:#}", e))?;
+
+        return Ok(folders
+            .into_iter()
+            .map(|(folder, image_count)| ProposedRoll {
+                source_path: source_path.clone(),
+                roll_name: match folder.rsplit('/').next() {
+                    Some(name) if !name.is_empty() => name.to_string(),
+                    _ => archive_stem(root),
+                },
+                archive_folder: Some(folder),
+                image_count,
+                film_stock: String::new(),
+                camera: String::new(),
+                lens: None,
+                shoot_date: folder_date(root),
+                notes: None,
+            })
+            .collect());
+    }
 
-    for entry in WalkDir::new(path)
-        .max_depth(1)
+    let root_buf = root.to_path_buf();
+    let folders = tokio::task::spawn_blocking(move || find_image_folders(&root_buf))
+        .await
+        .map_err(|e| format!("Failed to scan source directory: {}", e))?
+        .map_err(|e| format!("Failed to scan source directory: {}", e))?;
+
+    Ok(folders
         .into_iter()
-        .filter_map(|e| e.ok())
-    {
-        let file_path = entry.path();
-        if file_path.is_file() {
-            if let Some(ext) = file_path.extension().and_then(|e| e.to_str()) {
-                if image_extensions.contains(&ext.to_lowercase().as_str()) {
-                    count += 1;
-                }
+        .map(|folder| ProposedRoll {
+            source_path: folder.path.to_string_lossy().to_string(),
+            archive_folder: None,
+            roll_name: folder_roll_name(&folder.path),
+            image_count: folder.image_count,
+            film_stock: String::new(),
+            camera: String::new(),
+            lens: None,
+            shoot_date: folder_date(&folder.path),
+            notes: None,
+        })
+        .collect())
+}
+
+/// Import reviewed roll proposals, one roll (and import job) per folder
+/// A folder that fails is rolled back and reported; the other folders are still imported
+#[tauri::command]
+pub async fn import_batch(
+    options: BatchImportOptions,
+    state: State<'_, AppState>,
+    app: AppHandle,
+) -> Result<BatchImportResult, String> {
+    let _library = hold_library(&state)?;
+    let pool = wait_for_pool(&state).await?;
+
+    let total = options.rolls.len();
+    let mut imported = Vec::new();
+    let mut failed = Vec::new();
+
+    for (index, roll) in options.rolls.into_iter().enumerate() {
+        let _ = app.emit("batch-import-progress", serde_json::json!({
+            "current": index + 1,
+            "total": total,
+            "rollName": roll.roll_name,
+        }));
+
+        let result = import_folder_as_roll(&pool, &app, &state.import_jobs, ImportOptions {
+            source_path: roll.source_path.clone(),
+            film_stock: roll.film_stock,
+            camera: roll.camera,
+            lens: roll.lens,
+            shoot_date: roll.shoot_date,
+            roll_name: Some(roll.roll_name.clone()),
+            notes: roll.notes,
+            copy_mode: options.copy_mode,
+            auto_write_exif: options.auto_write_exif,
+            duplicate_mode: options.duplicate_mode,
+            archive_folder: roll.archive_folder,
+            replace_roll_ids: options.replace_roll_ids.clone(),
+        }).await;
+
+        match result {
+            Ok(result) => imported.push(result),
+            Err(error) => {
+                eprintln!("[Import] Batch import of {} failed: {}", roll.source_path, error);
+                failed.push(BatchImportError {
+                    source_path: roll.source_path,
+                    roll_name: roll.roll_name,
+                    error,
+                });
             }
         }
     }
 
-    Ok(count)
+    eprintln!("[Import] Batch import finished: {} rolls imported, {} failed", imported.len(), failed.len());
+    Ok(BatchImportResult { imported, failed })
+}
+
+/// Roll name for a delivery folder (images at the top of the tree use the root's name)
+fn folder_roll_name(folder: &Path) -> String {
+    folder
+        .file_name()
+        .map(|name| name.to_string_lossy().to_string())
+        .unwrap_or_else(|| folder.to_string_lossy().to_string())
+}
+
+/// Modification date of a folder (or archive) as YYYY-MM-DD (today if unavailable)
+fn folder_date(folder: &Path) -> String {
+    let modified = std::fs::metadata(folder)
+        .and_then(|m| m.modified())
+        .map(chrono::DateTime::<chrono::Local>::from)
+        .unwrap_or_else(|_| chrono::Local::now());
+    modified.format("%Y-%m-%d").to_string()
+}
+
+/// Find images in a source directory (or archive folder) that are already in the library
+/// Matches by content hash, so photos whose original has had EXIF written into it are not found
+#[tauri::command]
+pub async fn find_import_duplicates(
+    source_path: String,
+    archive_folder: Option<String>,
+    state: State<'_, AppState>,
+) -> Result<Vec<DuplicateFile>, String> {
+    let pool = get_pool(&state).await?;
+
+    let path = Path::new(&source_path);
+    if !path.exists() {
+        return Err("Source path does not exist".to_string());
+    }
+
+    let image_files = hash_source_files(path, archive_folder.as_deref()).await?;
+    let (_, duplicates) = find_duplicates(&pool, image_files).await?;
+
+    eprintln!("[Import] Found {} duplicates in {}", duplicates.len(), source_path);
+    Ok(duplicates)
 }
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -288,6 +1211,9 @@ pub struct AddPhotosOptions {
     pub source_path: String,
     pub copy_mode: bool,
     pub auto_write_exif: Option<bool>,
+    pub duplicate_mode: Option<DuplicateMode>,
+    #[serde(default)]
+    pub replace_roll_ids: Vec<i64>, // Other rolls whose photos Replace may remove; this roll always may
 }
 
 /// Add photos to an existing roll
@@ -298,27 +1224,8 @@ pub async fn add_photos_to_roll(
     app: AppHandle,
 ) -> Result<ImportResult, String> {
     eprintln!("[AddPhotos] add_photos_to_roll called, waiting for database...");
-
-    // Wait for database to be initialized (up to 30 seconds)
-    let mut attempts = 0;
-    let max_attempts = 300; // 30 seconds (300 * 100ms)
-    let pool = loop {
-        let db_guard = state.db_pool.lock().await;
-        if let Some(pool) = db_guard.as_ref() {
-            eprintln!("[AddPhotos] Database initialized after {} attempts", attempts);
-            break pool.clone();
-        }
-
-        attempts += 1;
-        if attempts >= max_attempts {
-            drop(db_guard);
-            eprintln!("[AddPhotos] Database initialization timeout after {} attempts", attempts);
-            return Err("Database not initialized. Please check the terminal logs for errors.".to_string());
-        }
-
-        drop(db_guard);
-        tokio::time::sleep(tokio::time::Duration::from_millis(100)).await;
-    };
+    let _library = hold_library(&state)?;
+    let pool = wait_for_pool(&state).await?;
 
     // Validate source path exists
     let source_path = Path::new(&options.source_path);
@@ -337,120 +1244,81 @@ pub async fn add_photos_to_roll(
         return Err(format!("Roll directory does not exist: {}. Please edit the roll or recreate it.", roll.path));
     }
 
-    // Get existing photo count to calculate start index
+    // New frames are numbered after the last photo in the roll's sequence
     let existing_photos = get_photos_by_roll(&pool, options.roll_id).await
         .map_err(|e| format!("Failed to get existing photos: {}", e))?;
-    let start_index = existing_photos.len();
+    let start_index = existing_photos.iter()
+        .filter_map(|photo| photo.sequence)
+        .max()
+        .map_or(existing_photos.len(), |sequence| sequence as usize);
+
+    let template = get_filename_template(&pool).await?;
+    let mut namer = FileNamer::new(&template, &roll)
+        .map_err(|e| format!("Invalid filename template: {}", e))?;
+    namer.reserve(existing_photos.iter().map(|photo| file_stem_of(&photo.filename)));
+    // Untracked files in the roll are kept, so new files must not take their names
+    namer.reserve_roll_files(roll_dir);
+    let frame_parser = frame_parser(&pool).await?;
 
     eprintln!("[AddPhotos] Adding photos to roll {} ({}), existing photos: {}, start index: {}",
         roll.id, roll.name, start_index, start_index);
 
-    // Process images with progress callback
-    let app_handle = app.clone();
-    let processed_images = crate::image_processor::process_images_in_directory_with_start_index(
-        source_path,
-        roll_dir,
-        options.roll_id,
-        start_index,
-        options.copy_mode,
-        move |current, total, filename| {
-            // Send progress event to frontend
-            let _ = app_handle.emit("import-progress", serde_json::json!({
-                "current": current,
-                "total": total,
-                "filename": filename,
-                "rollId": options.roll_id,
-            }));
-        }
-    ).map_err(|e| format!("Failed to process images: {}", e))?;
-
-    if processed_images.is_empty() {
-        return Err("No images found in source directory".to_string());
+    // Check incoming files against the library
+    let archive = is_archive(source_path).then(|| source_path.to_path_buf());
+    let copy_mode = options.copy_mode || archive.is_some();
+
+    let image_files = hash_source_files(source_path, None).await?;
+    let (files, duplicates) = find_duplicates(&pool, image_files).await?;
+    let found_count = files.len();
+    let mut replace_roll_ids = options.replace_roll_ids.clone();
+    replace_roll_ids.push(options.roll_id);
+    let (selected_files, replaced_photo_ids) =
+        select_import_files(files, &duplicates, options.duplicate_mode.unwrap_or_default(), &replace_roll_ids);
+    let replaced_roll_ids = replaced_rolls(&duplicates, &replaced_photo_ids);
+    let skipped_count = found_count - selected_files.len();
+
+    if found_count > 0 && selected_files.is_empty() {
+        return Err(format!("All {} images are already in the library", found_count));
     }
-
-    eprintln!("[AddPhotos] Processed {} images", processed_images.len());
-
-    // Create photo records in database
-    let photos_count = processed_images.len();
-    let new_photos: Vec<NewPhoto> = processed_images
-        .iter()
-        .map(|p| NewPhoto {
-            roll_id: options.roll_id,
-            filename: p.filename.clone(),
-            file_path: p.original_path.to_string_lossy().to_string(),
-            thumbnail_path: Some(p.thumbnail_path.to_string_lossy().to_string()),
-            preview_path: Some(p.preview_path.to_string_lossy().to_string()),
-        })
-        .collect();
-
-    create_photos(&pool, new_photos).await
-        .map_err(|e| format!("Failed to create photos in database: {}", e))?;
-
-    // Write EXIF if enabled
-    if options.auto_write_exif.unwrap_or(false) {
-        eprintln!("[AddPhotos] Auto-writing EXIF to {} photos", photos_count);
-
-        // Parse camera string into make and model
-        let (make, model) = parse_camera_string(&roll.camera);
-
-        // Build user comment: "Shot on {film_stock} | {city}, {country} | {notes}"
-        let mut parts = vec![];
-        if !roll.film_stock.is_empty() {
-            parts.push(format!("Shot on {}", roll.film_stock));
-        }
-        if let Some(ref notes) = roll.notes {
-            if !notes.is_empty() {
-                parts.push(notes.clone());
-            }
-        }
-        let user_comment = parts.join(" | ");
-
-        // Format date time original from shoot_date
-        let date_time_original = format_exif_date(&roll.shoot_date);
-
-        // Write EXIF to all photos with concurrency control
-        let _results = stream::iter(processed_images)
-            .map(|p| {
-                let file_path = p.original_path.clone();
-                let make_clone = make.clone();
-                let model_clone = model.clone();
-                let date_clone = date_time_original.clone();
-                let comment_clone = user_comment.clone();
-
-                async move {
-                    (
-                        file_path.clone(),
-                        write_photo_roll_exif(
-                            &file_path.to_string_lossy(),
-                            &make_clone,
-                            &model_clone,
-                            &date_clone,
-                            &comment_clone,
-                        ).await
-                    )
-                }
-            })
-            .buffer_unordered(4) // Limit concurrency to 4
-            .collect::<Vec<_>>()
-            .await;
-
-        // Note: We don't check results here to avoid blocking import on EXIF write failures
+    if !duplicates.is_empty() {
+        eprintln!("[AddPhotos] {} duplicates found, skipping {} files", duplicates.len(), skipped_count);
     }
 
+    let outcome = run_import_job(
+        &pool,
+        &app,
+        &state.import_jobs,
+        &roll,
+        false,
+        plan_roll_import(selected_files, roll_dir, start_index, &mut namer, &frame_parser),
+        archive,
+        copy_mode,
+        options.auto_write_exif.unwrap_or(false),
+        replaced_photo_ids,
+    ).await?;
+
     // Send completion event
     let _ = app.emit("import-complete", serde_json::json!({
+        "jobId": outcome.job_id,
         "rollId": options.roll_id,
-        "count": photos_count,
+        "count": outcome.photos_count,
         "path": roll.path,
     }));
 
     Ok(ImportResult {
+        job_id: outcome.job_id,
         roll_id: options.roll_id,
-        photos_count,
+        photos_count: outcome.photos_count,
         message: format!(
-            "成功添加 {} 张照片到胶卷",
-            photos_count
+            "成功添加 {} 张照片到胶卷{}",
+            outcome.photos_count,
+            skipped_message(skipped_count)
         ),
+        duplicates,
+        skipped_count,
+        replaced_count: outcome.replaced_count,
+        replaced_roll_ids,
+        files: outcome.files,
     })
 }
 
@@ -459,27 +1327,114 @@ mod tests {
     use super::*;
 
     #[test]
-    fn test_sanitize_filename() {
-        assert_eq!(sanitize_filename("Kodak Portra 400"), "Kodak_Portra_400");
-        assert_eq!(sanitize_filename("Camera/Model"), "Camera_Model");
-        assert_eq!(sanitize_filename("Test:Name"), "Test_Name");
+    fn test_plan_roll_import_orders_by_frame() {
+        let roll = Roll {
+            id: 5,
+            name: "Test".to_string(),
+            path: String::new(),
+            film_stock: "HP5".to_string(),
+            camera: "M6".to_string(),
+            lens: None,
+            shoot_date: "2024-01-01".to_string(),
+            lab_info: None,
+            notes: None,
+            city: None,
+            country: None,
+            lat: None,
+            lon: None,
+            film_iso: None,
+            film_format: None,
+            film_process: None,
+            push_pull: None,
+            scanner: None,
+            created_at: String::new(),
+            updated_at: String::new(),
+        };
+        let files = ["contact.jpg", "R1-01-10.jpg", "R1-01-9.jpg", "R1-01-10A.jpg"]
+            .iter()
+            .map(|name| ImportFile { source_path: PathBuf::from("/scans").join(name), content_hash: name.to_string() })
+            .collect();
+        let mut namer = FileNamer::new("{frame}_{original_name}", &roll).unwrap();
+        let parser = FrameParser::new(&[]).unwrap();
+
+        let planned = plan_roll_import(files, Path::new("/library/roll"), 0, &mut namer, &parser);
+        let names: Vec<_> = planned.iter().map(|p| p.filename.as_str()).collect();
+        assert_eq!(names, vec!["001_R1-01-9.jpg", "002_R1-01-10.jpg", "003_R1-01-10A.jpg", "004_contact.jpg"]);
+        let frames: Vec<_> = planned.iter().map(|p| p.frame_number.as_deref()).collect();
+        assert_eq!(frames, vec![Some("9"), Some("10"), Some("10A"), None]);
     }
 
     #[test]
-    fn test_parse_shoot_date() {
-        assert!(parse_shoot_date("2024-01-15").is_ok());
-        assert!(parse_shoot_date("invalid").is_err());
+    fn test_import_file_reports() {
+        let planned = plan_import_files(
+            vec![
+                ImportFile { source_path: PathBuf::from("/scans/a.tif"), content_hash: "a".to_string() },
+                ImportFile { source_path: PathBuf::from("/scans/b.tif"), content_hash: "b".to_string() },
+            ],
+            Path::new("/library/roll"),
+            0,
+            |frame, _| format!("ROLL_00000001_{:03}", frame),
+        );
+        let processed = vec![planned[0].to_processed()];
+        let failed = vec![FailedImport { source_path: PathBuf::from("/scans/b.tif"), error: "corrupt".to_string() }];
+
+        let reports = import_file_reports(&planned, &processed, &failed);
+        assert_eq!(reports[0].library_path.as_deref(), Some("/library/roll/originals/ROLL_00000001_001.tif"));
+        assert!(reports[0].error.is_none());
+        assert!(reports[1].library_path.is_none());
+        assert_eq!(reports[1].error.as_deref(), Some("corrupt"));
+        assert_eq!(failed_files_message(&failed), "b.tif (corrupt)");
     }
 
     #[test]
-    fn test_format_exif_date() {
-        assert_eq!(
-            format_exif_date("2024-01-15"),
-            "2024:01:15 12:00:00"
-        );
-        assert_eq!(
-            format_exif_date("2023-12-31"),
-            "2023:12:31 12:00:00"
-        );
+    fn test_select_import_files() {
+        let file = |name: &str, hash: &str| ImportFile {
+            source_path: PathBuf::from(name),
+            content_hash: hash.to_string(),
+        };
+        let files = vec![file("a.jpg", "h1"), file("b.jpg", "h2"), file("c.jpg", "h2")];
+        let duplicates = vec![
+            DuplicateFile {
+                source_path: "a.jpg".to_string(),
+                content_hash: "h1".to_string(),
+                photo_id: Some(7),
+                roll_id: Some(1),
+                existing_path: "/library/ROLL_00000001_001.jpg".to_string(),
+            },
+            DuplicateFile {
+                source_path: "c.jpg".to_string(),
+                content_hash: "h2".to_string(),
+                photo_id: None,
+                roll_id: None,
+                existing_path: "b.jpg".to_string(),
+            },
+        ];
+
+        let names = |files: &[ImportFile]| -> Vec<String> {
+            files.iter().map(|f| f.source_path.to_string_lossy().to_string()).collect()
+        };
+
+        let (selected, replaced) = select_import_files(files.clone(), &duplicates, DuplicateMode::Skip, &[1]);
+        assert_eq!(names(&selected), vec!["b.jpg"]);
+        assert!(replaced.is_empty());
+
+        let (selected, replaced) = select_import_files(files.clone(), &duplicates, DuplicateMode::Replace, &[1]);
+        assert_eq!(names(&selected), vec!["a.jpg", "b.jpg"]);
+        assert_eq!(replaced, vec![7]);
+        assert_eq!(replaced_rolls(&duplicates, &replaced), vec![1]);
+
+        // Photo 7 is in a roll that was not confirmed for replacing, so a.jpg is skipped
+        let (selected, replaced) = select_import_files(files.clone(), &duplicates, DuplicateMode::Replace, &[2]);
+        assert_eq!(names(&selected), vec!["b.jpg"]);
+        assert!(replaced.is_empty());
+
+        let (selected, _) = select_import_files(files, &duplicates, DuplicateMode::ImportAnyway, &[]);
+        assert_eq!(selected.len(), 3);
+    }
+
+    #[test]
+    fn test_parse_shoot_date() {
+        assert!(parse_shoot_date("2024-01-15").is_ok());
+        assert!(parse_shoot_date("invalid").is_err());
     }
 }
@@ -0,0 +1,356 @@
+use tauri::State;
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+use sqlx::SqlitePool;
+
+use crate::database::{
+    NewPhoto, Photo, PhotoPathUpdate, Roll, get_all_rolls, get_all_photos, get_roll_by_id, get_photo_by_id, get_photos_by_roll,
+    create_photo, delete_photo, delete_roll, update_photo_paths, update_photo_perceptual_hash, set_photo_sequence,
+};
+use crate::config::get_library_root;
+use crate::filename_template::FileNamer;
+use crate::frame_numbers::merge_by_frame;
+use crate::image_processor::{PlannedImport, create_roll_subdirs, generate_derivatives, hash_file, is_supported_image};
+use crate::integrity::{IntegrityReport, scan_library};
+use crate::AppState;
+use super::{get_pool, hold_library};
+use super::import::frame_parser;
+use super::rolls::delete_photo_files;
+
+/// A fix the user picked for an integrity issue
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "action", rename_all = "snake_case")]
+pub enum IntegrityFix {
+    /// Generate thumbnail and preview again from the original
+    RegenerateDerivatives { photo_id: i64 },
+    /// Point a photo at the new location of its original
+    Relink { photo_id: i64, path: String },
+    /// Create a photo for an image in the roll's folder
+    Adopt { roll_id: i64, path: String },
+    /// Remove a photo whose original is gone
+    RemovePhoto { photo_id: i64 },
+    /// Remove a roll whose folder is gone, with its photos
+    RemoveRoll { roll_id: i64 },
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct RepairLibraryResult {
+    pub fixed_count: usize,
+    pub errors: Vec<String>,
+}
+
+/// Compare the database with the files in the library
+#[tauri::command]
+pub async fn check_library_integrity_command(
+    state: State<'_, AppState>,
+) -> Result<IntegrityReport, String> {
+    let pool = get_pool(&state).await?;
+
+    let rolls = get_all_rolls(&pool)
+        .await
+        .map_err(|e| format!("Failed to get rolls: {}", e))?;
+    let photos = get_all_photos(&pool)
+        .await
+        .map_err(|e| format!("Failed to get photos: {}", e))?;
+    let library_root = get_library_root(&pool).await?;
+
+    let report = tokio::task::spawn_blocking(move || {
+        let library_root = (!library_root.is_empty()).then(|| PathBuf::from(library_root));
+        scan_library(library_root.as_deref(), &rolls, &photos)
+    })
+    .await
+    .map_err(|e| format!("Failed to check library: {}", e))?;
+
+    eprintln!("[Integrity] Checked {} rolls and {} photos, found {} issues",
+        report.rolls_checked, report.photos_checked, report.issues.len());
+    Ok(report)
+}
+
+/// Apply fixes from an integrity report; a failing fix does not stop the others
+#[tauri::command]
+pub async fn repair_library_command(
+    fixes: Vec<IntegrityFix>,
+    state: State<'_, AppState>,
+) -> Result<RepairLibraryResult, String> {
+    let _library = hold_library(&state)?;
+    let pool = get_pool(&state).await?;
+
+    let mut fixed_count = 0;
+    let mut errors = Vec::new();
+    for fix in fixes {
+        let result = match &fix {
+            IntegrityFix::RegenerateDerivatives { photo_id } => regenerate_derivatives(&pool, *photo_id).await,
+            IntegrityFix::Relink { photo_id, path } => relink_photo(&pool, *photo_id, Path::new(path)).await,
+            IntegrityFix::Adopt { roll_id, path } => adopt_file(&pool, *roll_id, Path::new(path)).await,
+            IntegrityFix::RemovePhoto { photo_id } => remove_dead_photo(&pool, *photo_id).await,
+            IntegrityFix::RemoveRoll { roll_id } => remove_dead_roll(&pool, *roll_id).await,
+        };
+        match result {
+            Ok(()) => fixed_count += 1,
+            Err(e) => {
+                eprintln!("[Integrity] Fix {:?} failed: {}", fix, e);
+                errors.push(e);
+            }
+        }
+    }
+
+    Ok(RepairLibraryResult { fixed_count, errors })
+}
+
+fn file_stem(path: &Path) -> String {
+    path.file_stem()
+        .map(|stem| stem.to_string_lossy().to_string())
+        .unwrap_or_default()
+}
+
+/// Derivative stem for `original_name` that no other photo's thumbnail or
+/// preview uses and no file in the roll's derivative folders already has
+fn unique_derivative_stem(roll: &Roll, photos: &[Photo], skip_photo_id: Option<i64>, original_name: &str) -> Result<String, String> {
+    let mut namer = FileNamer::new("{original_name}", roll)
+        .map_err(|e| format!("Failed to name derivatives: {}", e))?;
+    namer.reserve(
+        photos.iter()
+            .filter(|photo| Some(photo.id) != skip_photo_id)
+            .flat_map(|photo| [&photo.thumbnail_path, &photo.preview_path])
+            .flatten()
+            .map(|path| file_stem(Path::new(path))),
+    );
+    let roll_dir = Path::new(&roll.path);
+    for dir in ["thumbnails", "previews"] {
+        if let Ok(entries) = std::fs::read_dir(roll_dir.join(dir)) {
+            namer.reserve(entries.flatten().map(|entry| file_stem(&entry.path())));
+        }
+    }
+    Ok(namer.stem(0, original_name))
+}
+
+async fn regenerate_derivatives(pool: &SqlitePool, photo_id: i64) -> Result<(), String> {
+    let photo = get_photo_by_id(pool, photo_id)
+        .await
+        .map_err(|e| format!("Failed to get photo: {}", e))?
+        .ok_or_else(|| format!("Photo with ID {} not found", photo_id))?;
+    let roll = get_roll_by_id(pool, photo.roll_id)
+        .await
+        .map_err(|e| format!("Failed to get roll: {}", e))?
+        .ok_or_else(|| format!("Roll with ID {} not found", photo.roll_id))?;
+
+    let original = PathBuf::from(&photo.file_path);
+    if !original.exists() {
+        return Err(format!("Original of photo {} is missing: {}", photo_id, photo.file_path));
+    }
+
+    let roll_dir = Path::new(&roll.path);
+    create_roll_subdirs(roll_dir).map_err(|e| format!("Failed to create roll folders: {}", e))?;
+    let stem = if photo.thumbnail_path.is_none() || photo.preview_path.is_none() {
+        let photos = get_photos_by_roll(pool, roll.id)
+            .await
+            .map_err(|e| format!("Failed to get photos: {}", e))?;
+        unique_derivative_stem(&roll, &photos, Some(photo_id), &photo.filename)?
+    } else {
+        file_stem(&original)
+    };
+    let derivative_path = |path: &Option<String>, dir: &str| match path {
+        Some(path) => PathBuf::from(path),
+        None => roll_dir.join(dir).join(format!("{}.webp", stem)),
+    };
+
+    let planned = PlannedImport {
+        source_path: original.clone(),
+        content_hash: photo.content_hash.clone(),
+        filename: photo.filename.clone(),
+        original_path: original,
+        thumbnail_path: derivative_path(&photo.thumbnail_path, "thumbnails"),
+        preview_path: derivative_path(&photo.preview_path, "previews"),
+        frame_number: photo.frame_number.clone(),
+    };
+    let processed = generate_derivatives(&planned)
+        .await
+        .map_err(|e| format!("Failed to generate derivatives for photo {}: {}", photo_id, e))?;
+
+    update_photo_paths(pool, &[PhotoPathUpdate {
+        photo_id,
+        filename: photo.filename,
+        file_path: photo.file_path,
+        thumbnail_path: Some(processed.thumbnail_path.to_string_lossy().to_string()),
+        preview_path: Some(processed.preview_path.to_string_lossy().to_string()),
+    }])
+    .await
+    .map_err(|e| format!("Failed to update photo paths: {}", e))?;
+
+    if let Some(perceptual_hash) = processed.perceptual_hash {
+        update_photo_perceptual_hash(pool, photo_id, &perceptual_hash)
+            .await
+            .map_err(|e| format!("Failed to update perceptual hash: {}", e))?;
+    }
+
+    eprintln!("[Integrity] Regenerated derivatives of photo {}", photo_id);
+    Ok(())
+}
+
+async fn relink_photo(pool: &SqlitePool, photo_id: i64, path: &Path) -> Result<(), String> {
+    let photo = get_photo_by_id(pool, photo_id)
+        .await
+        .map_err(|e| format!("Failed to get photo: {}", e))?
+        .ok_or_else(|| format!("Photo with ID {} not found", photo_id))?;
+
+    if !path.is_file() {
+        return Err(format!("File not found: {}", path.display()));
+    }
+    let filename = path.file_name()
+        .map(|name| name.to_string_lossy().to_string())
+        .ok_or_else(|| format!("Invalid file path: {}", path.display()))?;
+
+    let roll = get_roll_by_id(pool, photo.roll_id)
+        .await
+        .map_err(|e| format!("Failed to get roll: {}", e))?
+        .ok_or_else(|| format!("Roll with ID {} not found", photo.roll_id))?;
+    if !path.starts_with(Path::new(&roll.path)) {
+        return Err(format!("{} is not in the folder of roll {}", path.display(), roll.name));
+    }
+
+    let file_path = path.to_string_lossy().to_string();
+    let roll_photos = get_photos_by_roll(pool, roll.id)
+        .await
+        .map_err(|e| format!("Failed to get photos: {}", e))?;
+    if let Some(other) = roll_photos.iter().find(|other| other.id != photo_id && other.file_path == file_path) {
+        return Err(format!("{} already belongs to photo {}", path.display(), other.filename));
+    }
+
+    // Written metadata changes the file, so only files never written to still match the hash taken on import
+    if let (Some(expected), None) = (&photo.content_hash, &photo.exif_written_at) {
+        let hashed = path.to_path_buf();
+        let content_hash = tokio::task::spawn_blocking(move || hash_file(&hashed))
+            .await
+            .map_err(|e| format!("Failed to hash {}: {}", path.display(), e))?
+            .map_err(|e| format!("Failed to hash {}: {}", path.display(), e))?;
+        if &content_hash != expected {
+            return Err(format!("{} is a different file than the one imported as {}", path.display(), photo.filename));
+        }
+    }
+
+    update_photo_paths(pool, &[PhotoPathUpdate {
+        photo_id,
+        filename,
+        file_path,
+        thumbnail_path: photo.thumbnail_path,
+        preview_path: photo.preview_path,
+    }])
+    .await
+    .map_err(|e| format!("Failed to update photo paths: {}", e))?;
+
+    eprintln!("[Integrity] Relinked photo {} to {:?}", photo_id, path);
+    Ok(())
+}
+
+async fn adopt_file(pool: &SqlitePool, roll_id: i64, path: &Path) -> Result<(), String> {
+    let roll = get_roll_by_id(pool, roll_id)
+        .await
+        .map_err(|e| format!("Failed to get roll: {}", e))?
+        .ok_or_else(|| format!("Roll with ID {} not found", roll_id))?;
+
+    let roll_dir = Path::new(&roll.path);
+    if !path.is_file() || !is_supported_image(path) {
+        return Err(format!("Not an image file: {}", path.display()));
+    }
+    if !path.starts_with(roll_dir) {
+        return Err(format!("{} is not in the folder of roll {}", path.display(), roll.name));
+    }
+
+    let existing_photos = get_photos_by_roll(pool, roll_id)
+        .await
+        .map_err(|e| format!("Failed to get photos: {}", e))?;
+    let file_path = path.to_string_lossy().to_string();
+    if existing_photos.iter().any(|photo| photo.file_path == file_path) {
+        return Err(format!("{} already belongs to roll {}", path.display(), roll.name));
+    }
+
+    let hashed = path.to_path_buf();
+    let content_hash = tokio::task::spawn_blocking(move || hash_file(&hashed))
+        .await
+        .map_err(|e| format!("Failed to hash {}: {}", path.display(), e))?
+        .map_err(|e| format!("Failed to hash {}: {}", path.display(), e))?;
+
+    let filename = path.file_name()
+        .map(|name| name.to_string_lossy().to_string())
+        .unwrap_or_default();
+    let frame_number = frame_parser(pool).await?.parse(&filename);
+
+    create_roll_subdirs(roll_dir).map_err(|e| format!("Failed to create roll folders: {}", e))?;
+    // An untracked 001.tif must not take over the derivatives of a tracked 001.jpg
+    let stem = unique_derivative_stem(&roll, &existing_photos, None, &filename)?;
+    let planned = PlannedImport {
+        source_path: path.to_path_buf(),
+        content_hash: Some(content_hash),
+        filename: filename.clone(),
+        original_path: path.to_path_buf(),
+        thumbnail_path: roll_dir.join("thumbnails").join(format!("{}.webp", stem)),
+        preview_path: roll_dir.join("previews").join(format!("{}.webp", stem)),
+        frame_number: frame_number.clone(),
+    };
+    let processed = generate_derivatives(&planned)
+        .await
+        .map_err(|e| format!("Failed to generate derivatives for {}: {}", path.display(), e))?;
+
+    let photo_id = create_photo(pool, NewPhoto {
+        roll_id,
+        filename: filename.clone(),
+        file_path,
+        thumbnail_path: Some(processed.thumbnail_path.to_string_lossy().to_string()),
+        preview_path: Some(processed.preview_path.to_string_lossy().to_string()),
+        content_hash: processed.content_hash,
+        perceptual_hash: processed.perceptual_hash,
+        original_filename: Some(filename),
+        frame_number: frame_number.clone(),
+    })
+    .await
+    .map_err(|e| format!("Failed to create photo: {}", e))?;
+
+    // Put the adopted frame where it belongs on the film, or after the last
+    // photo when it has no frame number
+    let existing: Vec<_> = existing_photos.iter().map(|photo| (photo.id, photo.frame_number.clone())).collect();
+    let order = merge_by_frame(&existing, &[(photo_id, frame_number)]);
+    set_photo_sequence(pool, roll_id, &order)
+        .await
+        .map_err(|e| format!("Failed to order photos: {}", e))?;
+
+    eprintln!("[Integrity] Adopted {:?} into roll {} as photo {}", path, roll_id, photo_id);
+    Ok(())
+}
+
+async fn remove_dead_photo(pool: &SqlitePool, photo_id: i64) -> Result<(), String> {
+    let photo = get_photo_by_id(pool, photo_id)
+        .await
+        .map_err(|e| format!("Failed to get photo: {}", e))?
+        .ok_or_else(|| format!("Photo with ID {} not found", photo_id))?;
+
+    if Path::new(&photo.file_path).exists() {
+        return Err(format!("Original of photo {} still exists: {}", photo_id, photo.file_path));
+    }
+
+    // Leftover derivatives of the lost original
+    delete_photo_files(&photo).await?;
+    delete_photo(pool, photo_id)
+        .await
+        .map_err(|e| format!("Failed to delete photo: {}", e))?;
+
+    eprintln!("[Integrity] Removed photo {} (original missing)", photo_id);
+    Ok(())
+}
+
+async fn remove_dead_roll(pool: &SqlitePool, roll_id: i64) -> Result<(), String> {
+    let roll = get_roll_by_id(pool, roll_id)
+        .await
+        .map_err(|e| format!("Failed to get roll: {}", e))?
+        .ok_or_else(|| format!("Roll with ID {} not found", roll_id))?;
+
+    if !roll.path.is_empty() && Path::new(&roll.path).exists() {
+        return Err(format!("Folder of roll {} still exists: {}", roll.name, roll.path));
+    }
+
+    delete_roll(pool, roll_id)
+        .await
+        .map_err(|e| format!("Failed to delete roll: {}", e))?;
+
+    eprintln!("[Integrity] Removed roll {} (folder missing)", roll_id);
+    Ok(())
+}
@@ -0,0 +1,493 @@
+use tauri::{State, Emitter, AppHandle, Manager};
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+use sqlx::SqlitePool;
+use tokio::sync::RwLockWriteGuard;
+
+use crate::config::{init_default_config, get_exif_concurrent_writes, get_library_root, set_library_root};
+use crate::exif_tool::exiftool_pool;
+use crate::database::init_database;
+use crate::default_presets::initialize_default_presets;
+use crate::libraries::{
+    DATABASE_FILE, REGISTRY_FILE, LibraryRegistry, connection_string, default_library_name, portable_db_path,
+};
+use crate::database::get_stored_roll_paths;
+use crate::library_moves::{
+    LibraryMove, LibraryMoveState, create_library_move, get_library_move, get_unfinished_library_move,
+    set_library_move_state, add_library_move_files, get_library_move_files, mark_library_move_file_copied,
+    moved_roll_path, plan_roll_folder, copy_verified, remove_moved_files, switch_library_root,
+};
+use crate::library_paths::LibraryRoot;
+use crate::AppState;
+use super::{get_pool, hold_library};
+use super::import::{backfill_content_hashes, resume_interrupted_imports};
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct LibraryInfo {
+    pub name: String,
+    pub db_path: String,
+    pub last_opened: String,
+    pub is_current: bool,
+    pub available: bool, // false when the database file cannot be found (e.g. drive not mounted)
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct CreateLibraryRequest {
+    pub name: String,
+    pub library_root: String,
+    pub db_in_root: bool, // true = keep the database inside the library root so it travels with the drive
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct MoveLibraryRequest {
+    pub target_root: String,
+    pub copy_mode: bool, // true = keep the old folders, false = remove them once the library is switched
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct MoveLibraryResult {
+    pub move_id: i64,
+    pub files_copied: usize,
+    pub bytes_copied: u64,
+    pub missing_rolls: Vec<String>, // Roll folders that did not exist and were not moved
+}
+
+/// Move (or copy) every roll folder to a new library root
+///
+/// Resumes the unfinished move to the same root if there is one. Each file is
+/// verified against its source before the database is switched over.
+#[tauri::command]
+pub async fn move_library_command(
+    request: MoveLibraryRequest,
+    state: State<'_, AppState>,
+    app: AppHandle,
+) -> Result<MoveLibraryResult, String> {
+    let _moving = lock_library(&state)?;
+    let pool = get_pool(&state).await?;
+    let target = Path::new(&request.target_root);
+
+    let unfinished = get_unfinished_library_move(&pool)
+        .await
+        .map_err(|e| format!("Failed to get library moves: {}", e))?;
+    if let Some(library_move) = unfinished {
+        if Path::new(&library_move.target_root) != target {
+            return Err(format!(
+                "A move to {} is unfinished. Resume or abandon it first.", library_move.target_root
+            ));
+        }
+        eprintln!("[LibraryMove] Resuming move {} to '{}'", library_move.id, library_move.target_root);
+        return run_library_move(&pool, &app, library_move.id).await;
+    }
+
+    let source_root = get_library_root(&pool).await?;
+    if source_root.is_empty() {
+        return Err("No library folder is configured".to_string());
+    }
+    let source = Path::new(&source_root);
+    if target.starts_with(source) || source.starts_with(target) {
+        return Err("The new library folder must not be the current one, or inside it".to_string());
+    }
+
+    std::fs::create_dir_all(target)
+        .map_err(|e| format!("Failed to create library folder: {}", e))?;
+    let move_id = create_library_move(&pool, &source_root, &request.target_root, request.copy_mode)
+        .await
+        .map_err(|e| format!("Failed to create library move: {}", e))?;
+    eprintln!("[LibraryMove] Moving library from '{}' to '{}'", source_root, request.target_root);
+
+    run_library_move(&pool, &app, move_id).await
+}
+
+/// Get the library move that stopped before finishing, if any
+#[tauri::command]
+pub async fn get_unfinished_library_move_command(
+    state: State<'_, AppState>,
+) -> Result<Option<LibraryMove>, String> {
+    let pool = get_pool(&state).await?;
+    get_unfinished_library_move(&pool)
+        .await
+        .map_err(|e| format!("Failed to get library moves: {}", e))
+}
+
+/// Give up an unfinished move before the switch; removes the copies made so far
+#[tauri::command]
+pub async fn abandon_library_move_command(
+    move_id: i64,
+    state: State<'_, AppState>,
+) -> Result<bool, String> {
+    let _moving = lock_library(&state)?;
+    let pool = get_pool(&state).await?;
+
+    let library_move = get_library_move(&pool, move_id)
+        .await
+        .map_err(|e| format!("Failed to get library move: {}", e))?
+        .ok_or_else(|| format!("Library move {} not found", move_id))?;
+    if library_move.move_state() != LibraryMoveState::Copying {
+        return Err("The library already uses the new folder; run the move again to finish it".to_string());
+    }
+
+    let files = get_library_move_files(&pool, move_id)
+        .await
+        .map_err(|e| format!("Failed to get library move files: {}", e))?;
+    let target_root = PathBuf::from(&library_move.target_root);
+    tokio::task::spawn_blocking(move || {
+        let copied = files.iter().filter(|file| file.content_hash.is_some()).map(|file| file.target_path.as_str());
+        remove_moved_files(copied, &target_root)
+    })
+    .await
+    .map_err(|e| format!("Failed to remove copied files: {}", e))?
+    .map_err(|e| format!("Failed to remove copied files: {:#}", e))?;
+
+    set_library_move_state(&pool, move_id, LibraryMoveState::Cancelled, None)
+        .await
+        .map_err(|e| format!("Failed to update library move: {}", e))?;
+
+    eprintln!("[LibraryMove] Abandoned move {} to '{}'", move_id, library_move.target_root);
+    Ok(true)
+}
+
+/// Plan every roll folder into the move; returns roll folders that do not exist
+async fn plan_library_move(pool: &SqlitePool, library_move: &LibraryMove) -> Result<Vec<String>, String> {
+    let old_root = LibraryRoot::new(&library_move.source_root);
+    let new_root = LibraryRoot::new(&library_move.target_root);
+
+    let mut missing_rolls = Vec::new();
+    for (_, stored) in get_stored_roll_paths(pool).await.map_err(|e| format!("Failed to get rolls: {}", e))? {
+        let source_dir = PathBuf::from(old_root.resolve(&stored));
+        if stored.is_empty() || !source_dir.is_dir() {
+            missing_rolls.push(stored);
+            continue;
+        }
+        let target_dir = PathBuf::from(new_root.resolve(&moved_roll_path(&stored)));
+
+        let files = tokio::task::spawn_blocking(move || plan_roll_folder(&source_dir, &target_dir))
+            .await
+            .map_err(|e| format!("Failed to list roll folder: {}", e))?
+            .map_err(|e| format!("Failed to list roll folder: {:#}", e))?;
+        add_library_move_files(pool, library_move.id, &files)
+            .await
+            .map_err(|e| format!("Failed to record library move files: {}", e))?;
+    }
+
+    Ok(missing_rolls)
+}
+
+/// Copy what is left, switch the database, then remove the old folders
+async fn run_library_move(pool: &SqlitePool, app: &AppHandle, move_id: i64) -> Result<MoveLibraryResult, String> {
+    let library_move = get_library_move(pool, move_id)
+        .await
+        .map_err(|e| format!("Failed to get library move: {}", e))?
+        .ok_or_else(|| format!("Library move {} not found", move_id))?;
+
+    let mut files_copied = 0;
+    let mut bytes_copied = 0u64;
+    let mut missing_rolls = Vec::new();
+
+    if library_move.move_state() == LibraryMoveState::Copying {
+        // Plan again after each pass so files added meanwhile are not left behind
+        loop {
+            missing_rolls = plan_library_move(pool, &library_move).await?;
+            let files = get_library_move_files(pool, move_id)
+                .await
+                .map_err(|e| format!("Failed to get library move files: {}", e))?;
+            let pending: Vec<_> = files.iter().filter(|file| file.content_hash.is_none()).cloned().collect();
+            if pending.is_empty() {
+                break;
+            }
+
+            let files_total = files.len();
+            let bytes_total: i64 = files.iter().map(|file| file.size).sum();
+            let mut files_done = files_total - pending.len();
+            let mut bytes_done: i64 = files.iter().filter(|file| file.content_hash.is_some()).map(|file| file.size).sum();
+
+            for file in pending {
+                let (source, target) = (PathBuf::from(&file.source_path), PathBuf::from(&file.target_path));
+                let copied = tokio::task::spawn_blocking(move || copy_verified(&source, &target))
+                    .await
+                    .map_err(|e| format!("Failed to copy {}: {}", file.source_path, e))?;
+                let content_hash = match copied {
+                    Ok(content_hash) => content_hash,
+                    Err(e) => {
+                        let message = format!("Failed to copy {}: {:#}", file.source_path, e);
+                        eprintln!("[LibraryMove] {}", message);
+                        // The move stays resumable; nothing has been switched or removed
+                        let _ = set_library_move_state(pool, move_id, LibraryMoveState::Copying, Some(&message)).await;
+                        return Err(format!("{}. Run the move again to resume.", message));
+                    }
+                };
+                mark_library_move_file_copied(pool, move_id, &file.source_path, &content_hash)
+                    .await
+                    .map_err(|e| format!("Failed to record copied file: {}", e))?;
+
+                files_done += 1;
+                bytes_done += file.size;
+                files_copied += 1;
+                bytes_copied += file.size as u64;
+                let _ = app.emit("library-move-progress", serde_json::json!({
+                    "moveId": move_id,
+                    "filesDone": files_done,
+                    "filesTotal": files_total,
+                    "bytesDone": bytes_done,
+                    "bytesTotal": bytes_total,
+                    "path": file.target_path,
+                }));
+            }
+        }
+
+        // Stop if the move was abandoned meanwhile: its copies are gone
+        let current = get_library_move(pool, move_id)
+            .await
+            .map_err(|e| format!("Failed to get library move: {}", e))?
+            .ok_or_else(|| format!("Library move {} not found", move_id))?;
+        if current.move_state() != LibraryMoveState::Copying {
+            return Err(format!("Library move {} was stopped before the switch", move_id));
+        }
+
+        switch_library_root(pool, &library_move)
+            .await
+            .map_err(|e| format!("Failed to switch library folder: {}", e))?;
+        eprintln!("[LibraryMove] Library switched to '{}'", library_move.target_root);
+        let _ = app.emit("library-move-switched", serde_json::json!({
+            "moveId": move_id,
+            "libraryRoot": library_move.target_root,
+        }));
+    }
+
+    if !library_move.copy_mode {
+        let files = get_library_move_files(pool, move_id)
+            .await
+            .map_err(|e| format!("Failed to get library move files: {}", e))?;
+        let source_root = PathBuf::from(&library_move.source_root);
+        tokio::task::spawn_blocking(move || {
+            remove_moved_files(files.iter().map(|file| file.source_path.as_str()), &source_root)
+        })
+        .await
+        .map_err(|e| format!("Failed to remove old library files: {}", e))?
+        .map_err(|e| format!("Library moved, but old files could not be removed: {:#}. Run the move again to retry.", e))?;
+    }
+
+    set_library_move_state(pool, move_id, LibraryMoveState::Done, None)
+        .await
+        .map_err(|e| format!("Failed to update library move: {}", e))?;
+
+    eprintln!("[LibraryMove] Move {} done: {} files ({} bytes) copied", move_id, files_copied, bytes_copied);
+    Ok(MoveLibraryResult { move_id, files_copied, bytes_copied, missing_rolls })
+}
+
+fn registry_path(app: &AppHandle) -> Result<PathBuf, String> {
+    let app_data_dir = app.path().app_data_dir()
+        .map_err(|e| format!("Failed to get app data dir: {}", e))?;
+    std::fs::create_dir_all(&app_data_dir)
+        .map_err(|e| format!("Failed to create app data dir: {}", e))?;
+    Ok(app_data_dir.join(REGISTRY_FILE))
+}
+
+fn library_infos(registry: &LibraryRegistry) -> Vec<LibraryInfo> {
+    registry.libraries.iter()
+        .map(|entry| LibraryInfo {
+            name: entry.name.clone(),
+            db_path: entry.db_path.clone(),
+            last_opened: entry.last_opened.clone(),
+            is_current: registry.current.as_deref() == Some(entry.db_path.as_str()),
+            available: Path::new(&entry.db_path).is_file(),
+        })
+        .collect()
+}
+
+/// Open a library database: migrate it and prepare it for use
+pub(crate) async fn open_library_pool(db_path: &Path) -> Result<SqlitePool, String> {
+    let db_connection_string = connection_string(db_path);
+    eprintln!("[Libraries] Opening {:?} ({})", db_path, db_connection_string);
+
+    let pool = init_database(&db_connection_string)
+        .await
+        .map_err(|e| format!("Failed to open library database: {}", e))?;
+
+    // Initialize default configuration
+    if let Err(e) = init_default_config(&pool).await {
+        eprintln!("[Libraries] Failed to initialize default config: {}", e);
+    }
+
+    // Initialize default film presets
+    if let Err(e) = initialize_default_presets(&pool).await {
+        eprintln!("[Libraries] Failed to initialize default presets: {}", e);
+    }
+
+    // Size the ExifTool worker pool from this library's settings
+    match get_exif_concurrent_writes(&pool).await {
+        Ok(workers) => {
+            if let Err(e) = exiftool_pool().resize(workers).await {
+                eprintln!("[Libraries] Failed to resize the ExifTool pool: {}", e);
+            }
+        }
+        Err(e) => eprintln!("[Libraries] Failed to read EXIF concurrency setting: {}", e),
+    }
+
+    // Finish or roll back imports interrupted by the last exit
+    resume_interrupted_imports(&pool).await;
+
+    // Hash photos from before content hashes were stored, for duplicate detection
+    tokio::spawn(backfill_content_hashes(pool.clone()));
+
+    Ok(pool)
+}
+
+/// Database to open at startup: the last library used, or the default one
+pub(crate) fn startup_db_path(app_data_dir: &Path) -> PathBuf {
+    let registry = LibraryRegistry::load(&app_data_dir.join(REGISTRY_FILE));
+    match registry.current.map(PathBuf::from) {
+        Some(db_path) if db_path.is_file() => db_path,
+        Some(db_path) => {
+            eprintln!("[Libraries] Last library {:?} not found, opening the default library", db_path);
+            app_data_dir.join(DATABASE_FILE)
+        }
+        None => app_data_dir.join(DATABASE_FILE),
+    }
+}
+
+/// Remember the library opened at startup
+pub(crate) fn remember_startup_library(app: &AppHandle, db_path: &Path) -> Result<(), String> {
+    let registry_path = registry_path(app)?;
+    let mut registry = LibraryRegistry::load(&registry_path);
+    let db_path_str = db_path.to_string_lossy().to_string();
+    let name = match registry.get(&db_path_str) {
+        Some(entry) => entry.name.clone(),
+        None if registry_path.parent() == db_path.parent() => "Default library".to_string(),
+        None => default_library_name(db_path),
+    };
+    registry.remember(&name, &db_path_str);
+    registry.save(&registry_path).map_err(|e| format!("Failed to save library list: {}", e))
+}
+
+/// Make `pool` the open library and close the previous one
+async fn switch_library(
+    state: &State<'_, AppState>,
+    app: &AppHandle,
+    pool: SqlitePool,
+    name: &str,
+    db_path: &Path,
+) -> Result<LibraryInfo, String> {
+    let library_root = get_library_root(&pool).await?;
+
+    let previous = state.db_pool.lock().await.replace(pool);
+    if let Some(previous) = previous {
+        // Waits for queries still running on the old library
+        previous.close().await;
+    }
+
+    let registry_path = registry_path(app)?;
+    let mut registry = LibraryRegistry::load(&registry_path);
+    let db_path_str = db_path.to_string_lossy().to_string();
+    registry.remember(name, &db_path_str);
+    registry.save(&registry_path).map_err(|e| format!("Failed to save library list: {}", e))?;
+
+    eprintln!("[Libraries] Switched to '{}' ({:?})", name, db_path);
+    let _ = app.emit("library-switched", serde_json::json!({
+        "name": name,
+        "dbPath": db_path_str,
+        "libraryRoot": library_root,
+    }));
+
+    library_infos(&registry)
+        .into_iter()
+        .find(|library| library.db_path == db_path_str)
+        .ok_or_else(|| "Library missing from library list".to_string())
+}
+
+/// Exclusive hold on the open library, for switching, restoring or moving it
+///
+/// Fails instead of waiting while imports, EXIF writes or repairs hold the library.
+pub(crate) fn lock_library<'a>(state: &'a State<'_, AppState>) -> Result<RwLockWriteGuard<'a, ()>, String> {
+    state.library_switch
+        .try_write()
+        .map_err(|_| "Wait for running imports, EXIF writes and repairs to finish first".to_string())
+}
+
+/// Get the recently used libraries, most recent first
+#[tauri::command]
+pub async fn list_libraries_command(app: AppHandle) -> Result<Vec<LibraryInfo>, String> {
+    let registry = LibraryRegistry::load(&registry_path(&app)?);
+    Ok(library_infos(&registry))
+}
+
+/// Open an existing library database and switch to it
+#[tauri::command]
+pub async fn open_library_command(
+    db_path: String,
+    state: State<'_, AppState>,
+    app: AppHandle,
+) -> Result<LibraryInfo, String> {
+    let _switching = lock_library(&state)?;
+
+    let path = PathBuf::from(&db_path);
+    if !path.is_file() {
+        return Err(format!("Library database not found: {}", db_path));
+    }
+
+    let registry = LibraryRegistry::load(&registry_path(&app)?);
+    let name = registry.get(&db_path)
+        .map(|entry| entry.name.clone())
+        .unwrap_or_else(|| default_library_name(&path));
+
+    let pool = open_library_pool(&path).await?;
+    switch_library(&state, &app, pool, &name, &path).await
+}
+
+/// Create a new library and switch to it
+#[tauri::command]
+pub async fn create_library_command(
+    request: CreateLibraryRequest,
+    state: State<'_, AppState>,
+    app: AppHandle,
+) -> Result<LibraryInfo, String> {
+    let _switching = lock_library(&state)?;
+
+    let name = request.name.trim();
+    if name.is_empty() {
+        return Err("Library name cannot be empty".to_string());
+    }
+    let library_root = PathBuf::from(request.library_root.trim());
+    if !library_root.is_absolute() {
+        return Err("Library folder must be an absolute path".to_string());
+    }
+
+    let db_path = if request.db_in_root {
+        portable_db_path(&library_root)
+    } else {
+        let registry_path = registry_path(&app)?;
+        let libraries_dir = registry_path.with_file_name("libraries");
+        std::fs::create_dir_all(&libraries_dir)
+            .map_err(|e| format!("Failed to create libraries folder: {}", e))?;
+        libraries_dir.join(format!("{}.db", chrono::Utc::now().format("%Y%m%d%H%M%S")))
+    };
+    if db_path.exists() {
+        return Err(format!("A library already exists at {}; open it instead", db_path.display()));
+    }
+
+    std::fs::create_dir_all(&library_root)
+        .map_err(|e| format!("Failed to create library folder: {}", e))?;
+    let pool = open_library_pool(&db_path).await?;
+    set_library_root(&pool, &library_root.to_string_lossy()).await?;
+
+    eprintln!("[Libraries] Created '{}' at {:?}", name, db_path);
+    switch_library(&state, &app, pool, name, &db_path).await
+}
+
+/// Remove a library from the recent list; its files are kept
+#[tauri::command]
+pub async fn forget_library_command(
+    db_path: String,
+    state: State<'_, AppState>,
+    app: AppHandle,
+) -> Result<bool, String> {
+    let _library = hold_library(&state)?;
+
+    let registry_path = registry_path(&app)?;
+    let mut registry = LibraryRegistry::load(&registry_path);
+    if registry.current.as_deref() == Some(db_path.as_str()) {
+        return Err("The open library cannot be removed from the list".to_string());
+    }
+    let forgotten = registry.forget(&db_path);
+    registry.save(&registry_path).map_err(|e| format!("Failed to save library list: {}", e))?;
+    Ok(forgotten)
+}
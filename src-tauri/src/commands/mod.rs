@@ -1,12 +1,27 @@
+pub mod backup;
 pub mod config;
 pub mod exif;
 pub mod film_presets;
 pub mod import;
+pub mod integrity;
+pub mod library;
 pub mod rolls;
+pub mod shot_logs;
 
 use tauri::State;
+use tokio::sync::RwLockReadGuard;
 use crate::AppState;
 
+/// Keep the open library from being switched, restored or moved while a
+/// long-running command works on it
+///
+/// Take it before getting the pool and hold it until the command returns.
+pub fn hold_library<'a>(state: &'a State<'_, AppState>) -> Result<RwLockReadGuard<'a, ()>, String> {
+    state.library_switch
+        .try_read()
+        .map_err(|_| "The library is being switched, restored or moved. Try again when it has finished.".to_string())
+}
+
 pub async fn get_pool(state: &State<'_, AppState>) -> Result<sqlx::Pool<sqlx::Sqlite>, String> {
     let mut attempts = 0;
     loop {
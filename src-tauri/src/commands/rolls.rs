@@ -1,8 +1,10 @@
 use tauri::State;
 use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
 use std::fs;
-use std::path::Path;
+use std::path::{Path, PathBuf};
 use base64::prelude::*;
+use sqlx::SqlitePool;
 
 use crate::database::{
     Roll, Photo, NewRoll,
@@ -11,9 +13,16 @@ use crate::database::{
     update_photo_rating, update_photo_location, delete_photo, delete_photos,
     toggle_photo_favorite, update_photo_favorite, get_favorite_photos_by_roll,
     update_roll_location, update_photo_location_with_city, apply_roll_location_to_photos,
+    get_all_photos_with_thumbnails, update_photo_perceptual_hash, PhotoPathUpdate, update_photo_paths,
+    update_photo_frame_numbers, set_photo_sequence,
 };
+use crate::image_processor::{perceptual_hash_file, group_near_duplicates, hamming_distance, rename_files};
+use crate::config::get_filename_template;
+use crate::filename_template::FileNamer;
+use crate::exif_tool::sidecar_path;
+use super::import::frame_parser;
 use crate::AppState;
-use super::get_pool;
+use super::{get_pool, hold_library};
 
 #[derive(Debug, Serialize, Deserialize)]
 pub struct UpdateRollRequest {
@@ -29,6 +38,11 @@ pub struct UpdateRollRequest {
     pub country: Option<String>,
     pub lat: Option<f64>,
     pub lon: Option<f64>,
+    pub film_iso: Option<i64>,
+    pub film_format: Option<String>,
+    pub film_process: Option<String>,
+    pub push_pull: Option<f64>,
+    pub scanner: Option<String>,
 }
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -44,6 +58,41 @@ pub struct DeletePhotosRequest {
     pub delete_files: bool,
 }
 
+/// Default Hamming distance (out of 64 bits) within which photos count as near-identical
+const DEFAULT_NEAR_DUPLICATE_THRESHOLD: u32 = 8;
+
+/// Visually near-identical photos, possibly from different rolls
+#[derive(Debug, Serialize, Deserialize)]
+pub struct NearDuplicateGroup {
+    pub photos: Vec<Photo>,
+    pub max_distance: u32,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct RenameRollFilesRequest {
+    pub roll_id: i64,
+    pub template: Option<String>, // Defaults to the configured filename template
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct RenameRollFilesResult {
+    pub renamed_count: usize,
+    pub unchanged_count: usize,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ReorderPhotosRequest {
+    pub roll_id: i64,
+    pub photo_ids: Vec<i64>, // Every photo of the roll, in the new order
+    pub renumber_files: bool, // Also rename the files so frame numbers follow the new order
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ReorderPhotosResult {
+    pub photos: Vec<Photo>,
+    pub renamed_count: usize,
+}
+
 #[derive(Debug, Serialize, Deserialize)]
 pub struct RollWithPhotos {
     pub roll: Roll,
@@ -127,6 +176,11 @@ pub async fn update_roll_command(
         country: request.country,
         lat: request.lat,
         lon: request.lon,
+        film_iso: request.film_iso,
+        film_format: request.film_format,
+        film_process: request.film_process,
+        push_pull: request.push_pull,
+        scanner: request.scanner,
     };
 
     let result = update_roll(&pool, request.id, new_roll)
@@ -179,7 +233,15 @@ pub async fn delete_roll_command(
 
     eprintln!("[DeleteRoll] Deleting roll {} ({})", request.id, roll.name);
 
-    // 2. Delete from database (photos will be cascade deleted)
+    // 2. Delete physical files first, so a failure leaves the roll in the library
+    if request.delete_files {
+        eprintln!("[DeleteRoll] Deleting physical files (delete_originals: {})", request.delete_originals);
+        delete_roll_files(&roll.path, request.delete_originals)
+            .await
+            .map_err(|e| format!("Roll was not deleted: its folder could not be removed ({}). {}", roll.path, e))?;
+    }
+
+    // 3. Delete from database (photos will be cascade deleted)
     let deleted = delete_roll(&pool, request.id)
         .await
         .map_err(|e| format!("Failed to delete roll from database: {}", e))?;
@@ -190,15 +252,6 @@ pub async fn delete_roll_command(
 
     eprintln!("[DeleteRoll] Database records deleted successfully");
 
-    // 3. Delete physical files if requested
-    if request.delete_files {
-        eprintln!("[DeleteRoll] Deleting physical files (delete_originals: {})", request.delete_originals);
-        if let Err(e) = delete_roll_files(&roll.path, request.delete_originals).await {
-            eprintln!("[Warning] Failed to delete files for roll {}: {}", request.id, e);
-            // Don't return error - database deletion succeeded
-        }
-    }
-
     eprintln!("[DeleteRoll] Roll {} deleted successfully", request.id);
     Ok(true)
 }
@@ -295,7 +348,7 @@ pub async fn read_image_as_base64(path: String) -> Result<String, String> {
 }
 
 /// Delete physical files for a photo
-async fn delete_photo_files(
+pub(crate) async fn delete_photo_files(
     photo: &Photo,
 ) -> Result<(), String> {
     // Delete thumbnail
@@ -318,6 +371,14 @@ async fn delete_photo_files(
         }
     }
 
+    // Delete XMP sidecar
+    let sidecar = sidecar_path(&photo.file_path);
+    if sidecar.exists() {
+        eprintln!("[DeletePhoto] Deleting sidecar: {:?}", sidecar);
+        fs::remove_file(&sidecar)
+            .map_err(|e| format!("Failed to delete sidecar: {}", e))?;
+    }
+
     // Delete original file
     let original_path = Path::new(&photo.file_path);
     if original_path.exists() {
@@ -394,6 +455,73 @@ pub async fn delete_photos_command(
     Ok(count)
 }
 
+/// Find groups of visually near-identical photos across the library
+/// (re-scans, TIFF + JPEG deliveries, corrected versions)
+/// Photos without a perceptual hash are hashed from their thumbnail first
+#[tauri::command]
+pub async fn find_near_duplicate_photos_command(
+    threshold: Option<u32>,
+    state: State<'_, AppState>,
+) -> Result<Vec<NearDuplicateGroup>, String> {
+    let pool = get_pool(&state).await?;
+    let threshold = threshold.unwrap_or(DEFAULT_NEAR_DUPLICATE_THRESHOLD).min(32);
+
+    let mut photos = get_all_photos_with_thumbnails(&pool)
+        .await
+        .map_err(|e| format!("Failed to get photos: {}", e))?;
+
+    // Hash thumbnails of photos imported before perceptual hashes were stored
+    let missing: Vec<(usize, String)> = photos.iter()
+        .enumerate()
+        .filter(|(_, p)| p.perceptual_hash.is_none())
+        .filter_map(|(i, p)| p.thumbnail_path.clone().map(|t| (i, t)))
+        .collect();
+
+    if !missing.is_empty() {
+        eprintln!("[NearDuplicates] Hashing {} thumbnails", missing.len());
+        let hashed = tokio::task::spawn_blocking(move || {
+            missing.into_iter()
+                .filter_map(|(i, thumb)| perceptual_hash_file(Path::new(&thumb)).ok().map(|h| (i, h)))
+                .collect::<Vec<_>>()
+        })
+        .await
+        .map_err(|e| format!("Failed to hash thumbnails: {}", e))?;
+
+        for (i, hash) in hashed {
+            update_photo_perceptual_hash(&pool, photos[i].id, &hash)
+                .await
+                .map_err(|e| format!("Failed to store perceptual hash: {}", e))?;
+            photos[i].perceptual_hash = Some(hash);
+        }
+    }
+
+    let hashes: Vec<(i64, String)> = photos.iter()
+        .filter_map(|p| p.perceptual_hash.clone().map(|h| (p.id, h)))
+        .collect();
+    let groups = group_near_duplicates(&hashes, threshold);
+
+    let mut by_id: std::collections::HashMap<i64, Photo> = photos.into_iter().map(|p| (p.id, p)).collect();
+    let result: Vec<NearDuplicateGroup> = groups.into_iter()
+        .map(|ids| {
+            let photos: Vec<Photo> = ids.iter().filter_map(|id| by_id.remove(id)).collect();
+
+            let mut max_distance = 0;
+            for (i, a) in photos.iter().enumerate() {
+                for b in &photos[i + 1..] {
+                    if let (Some(ha), Some(hb)) = (&a.perceptual_hash, &b.perceptual_hash) {
+                        max_distance = max_distance.max(hamming_distance(ha, hb).unwrap_or(0));
+                    }
+                }
+            }
+
+            NearDuplicateGroup { photos, max_distance }
+        })
+        .collect();
+
+    eprintln!("[NearDuplicates] Found {} groups (threshold {})", result.len(), threshold);
+    Ok(result)
+}
+
 /// Toggle photo favorite status
 #[tauri::command]
 pub async fn toggle_photo_favorite_command(
@@ -480,3 +608,203 @@ pub async fn apply_roll_location_to_photos_command(
         .await
         .map_err(|e| format!("Failed to apply roll location to photos: {}", e))
 }
+
+/// Re-apply a filename template to every photo of a roll
+///
+/// Originals, thumbnails and previews are renamed together and the photo rows
+/// updated in one transaction; if either step fails every file keeps its old name.
+/// Frames are numbered in the roll's current order.
+#[tauri::command]
+pub async fn rename_roll_files_command(
+    request: RenameRollFilesRequest,
+    state: State<'_, AppState>,
+) -> Result<RenameRollFilesResult, String> {
+    let _library = hold_library(&state)?;
+    let pool = get_pool(&state).await?;
+    rename_roll_files(&pool, request.roll_id, request.template).await
+}
+
+/// Put a roll's photos in a new order, optionally renumbering the files on disk
+#[tauri::command]
+pub async fn reorder_roll_photos_command(
+    request: ReorderPhotosRequest,
+    state: State<'_, AppState>,
+) -> Result<ReorderPhotosResult, String> {
+    let _library = hold_library(&state)?;
+    let pool = get_pool(&state).await?;
+
+    let photos = get_photos_by_roll(&pool, request.roll_id)
+        .await
+        .map_err(|e| format!("Failed to get photos: {}", e))?;
+    let previous_order: Vec<i64> = photos.iter().map(|photo| photo.id).collect();
+
+    let mut current = previous_order.clone();
+    let mut requested = request.photo_ids.clone();
+    current.sort_unstable();
+    requested.sort_unstable();
+    if current != requested {
+        return Err(format!("New order must list each of the roll's {} photos exactly once", photos.len()));
+    }
+
+    set_photo_sequence(&pool, request.roll_id, &request.photo_ids)
+        .await
+        .map_err(|e| format!("Failed to reorder photos: {}", e))?;
+
+    let mut renamed_count = 0;
+    if request.renumber_files {
+        match rename_roll_files(&pool, request.roll_id, None).await {
+            Ok(result) => renamed_count = result.renamed_count,
+            Err(e) => {
+                // Files keep their names, so keep the order they were numbered in
+                if let Err(restore_error) = set_photo_sequence(&pool, request.roll_id, &previous_order).await {
+                    eprintln!("[Reorder] Failed to restore order of roll {}: {}", request.roll_id, restore_error);
+                }
+                return Err(e);
+            }
+        }
+    }
+
+    let photos = get_photos_by_roll(&pool, request.roll_id)
+        .await
+        .map_err(|e| format!("Failed to get photos: {}", e))?;
+
+    Ok(ReorderPhotosResult { photos, renamed_count })
+}
+
+/// Rename a roll's files to match `template` (the saved template when None) and its photo order
+async fn rename_roll_files(
+    pool: &SqlitePool,
+    roll_id: i64,
+    template: Option<String>,
+) -> Result<RenameRollFilesResult, String> {
+    let roll = get_roll_by_id(pool, roll_id)
+        .await
+        .map_err(|e| format!("Failed to get roll: {}", e))?
+        .ok_or_else(|| format!("Roll with ID {} not found", roll_id))?;
+    let photos = get_photos_by_roll(pool, roll.id)
+        .await
+        .map_err(|e| format!("Failed to get photos: {}", e))?;
+
+    let template = match template {
+        Some(template) => template,
+        None => get_filename_template(pool).await?,
+    };
+    let mut namer = FileNamer::new(&template, &roll)
+        .map_err(|e| format!("Invalid filename template: {}", e))?;
+    // Untracked files keep their names, so the roll's own files must not be renamed onto them
+    let tracked: HashSet<PathBuf> = photos
+        .iter()
+        .flat_map(|photo| {
+            [Some(photo.file_path.clone()), photo.thumbnail_path.clone(), photo.preview_path.clone()]
+                .into_iter()
+                .flatten()
+                .map(PathBuf::from)
+                .chain([sidecar_path(&photo.file_path)])
+        })
+        .collect();
+    namer.reserve_roll_files_except(Path::new(&roll.path), &tracked);
+
+    let mut moves = Vec::new();
+    let mut updates = Vec::new();
+
+    for (index, photo) in photos.iter().enumerate() {
+        let original_name = photo.original_filename.as_deref().unwrap_or(&photo.filename);
+        let stem = namer.stem(index + 1, original_name);
+        let original = Path::new(&photo.file_path);
+        let filename = match original.extension().and_then(|e| e.to_str()) {
+            Some(ext) => format!("{}.{}", stem, ext),
+            None => stem.clone(),
+        };
+        if filename == photo.filename {
+            continue;
+        }
+
+        let renamed = |path: &Path, name: &str| path.with_file_name(name);
+        let file_path = renamed(original, &filename);
+        moves.push((original.to_path_buf(), file_path.clone()));
+
+        let mut derivative = |path: &Option<String>| {
+            path.as_ref().map(|path| {
+                let old = Path::new(path);
+                let new = renamed(old, &format!("{}.webp", stem));
+                if old.exists() {
+                    moves.push((old.to_path_buf(), new.clone()));
+                }
+                new.to_string_lossy().to_string()
+            })
+        };
+        let thumbnail_path = derivative(&photo.thumbnail_path);
+        let preview_path = derivative(&photo.preview_path);
+
+        // The XMP sidecar keeps following its original
+        let sidecar = sidecar_path(&photo.file_path);
+        if sidecar.exists() {
+            moves.push((sidecar, sidecar_path(&file_path.to_string_lossy())));
+        }
+
+        updates.push(PhotoPathUpdate {
+            photo_id: photo.id,
+            filename,
+            file_path: file_path.to_string_lossy().to_string(),
+            thumbnail_path,
+            preview_path,
+        });
+    }
+
+    let unchanged_count = photos.len() - updates.len();
+    if updates.is_empty() {
+        return Ok(RenameRollFilesResult { renamed_count: 0, unchanged_count });
+    }
+
+    eprintln!("[Rename] Renaming {} photos of roll {} with template '{}'", updates.len(), roll.id, template);
+
+    let file_moves = moves.clone();
+    tokio::task::spawn_blocking(move || rename_files(&file_moves))
+        .await
+        .map_err(|e| format!("Failed to rename files: {}", e))?
+        .map_err(|e| format!("Failed to rename files: {:#}", e))?;
+
+    if let Err(e) = update_photo_paths(pool, &updates).await {
+        // Put the files back so they match the unchanged database rows
+        let reverse: Vec<_> = moves.into_iter().map(|(from, to)| (to, from)).collect();
+        let restored = tokio::task::spawn_blocking(move || rename_files(&reverse)).await;
+        if !matches!(restored, Ok(Ok(()))) {
+            eprintln!("[Rename] Failed to restore file names of roll {}", roll.id);
+        }
+        return Err(format!("Failed to update photo paths: {}", e));
+    }
+
+    Ok(RenameRollFilesResult { renamed_count: updates.len(), unchanged_count })
+}
+
+/// Parse frame numbers again for every photo of a roll (e.g. after adding a frame pattern)
+/// Uses the original filename where known, otherwise the library filename.
+/// Returns the number of photos that got a frame number.
+#[tauri::command]
+pub async fn reparse_frame_numbers_command(
+    roll_id: i64,
+    state: State<'_, AppState>,
+) -> Result<usize, String> {
+    let pool = get_pool(&state).await?;
+    let parser = frame_parser(&pool).await?;
+
+    let photos = get_photos_by_roll(&pool, roll_id)
+        .await
+        .map_err(|e| format!("Failed to get photos: {}", e))?;
+
+    let frames: Vec<(i64, Option<String>)> = photos
+        .iter()
+        .map(|photo| {
+            let name = photo.original_filename.as_deref().unwrap_or(&photo.filename);
+            (photo.id, parser.parse(name))
+        })
+        .collect();
+    let parsed_count = frames.iter().filter(|(_, frame)| frame.is_some()).count();
+
+    update_photo_frame_numbers(&pool, &frames)
+        .await
+        .map_err(|e| format!("Failed to update frame numbers: {}", e))?;
+
+    eprintln!("[FrameNumbers] Roll {}: {} of {} photos have a frame number", roll_id, parsed_count, photos.len());
+    Ok(parsed_count)
+}
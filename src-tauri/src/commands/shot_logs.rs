@@ -0,0 +1,348 @@
+use tauri::State;
+use serde::{Deserialize, Serialize};
+use futures::stream::{self, StreamExt};
+
+use crate::database::{
+    ShotLog, ShotLogImport, ShotLogPatch, get_photo_by_id, get_photos_by_roll, get_shot_log, get_shot_logs_by_roll,
+    save_shot_logs, apply_shot_log_patch, import_shot_logs, mark_photo_exif_synced,
+};
+use crate::config::get_metadata_write_mode;
+use crate::exif_tool::{ExifWriteResult, write_shot_log_exif, exiftool_pool};
+use crate::logbook::{LogbookMatch, MatchMode, MatchPlan, parse_logbook_file, match_entries};
+use crate::AppState;
+use super::{get_pool, hold_library};
+
+/// Request structure for saving complete shot logs
+#[derive(Debug, Deserialize)]
+pub struct UpdateShotLogsRequest {
+    pub logs: Vec<ShotLog>,
+    pub write_exif: Option<bool>,
+}
+
+/// Request structure for setting the same fields on many photos
+#[derive(Debug, Deserialize)]
+pub struct ApplyShotLogFieldsRequest {
+    pub photo_ids: Vec<i64>,
+    pub fields: ShotLogPatch,
+    pub write_exif: Option<bool>,
+}
+
+/// Request structure for applying a reviewed logbook match plan
+#[derive(Debug, Deserialize)]
+pub struct ApplyLogbookImportRequest {
+    pub roll_id: i64,
+    pub matches: Vec<LogbookMatch>,
+    pub write_exif: Option<bool>,
+}
+
+/// Result of a shot log update
+#[derive(Debug, Serialize)]
+pub struct ShotLogUpdateResult {
+    pub updated_count: usize,
+    pub exif: Option<ExifWriteResult>,
+}
+
+/// Normalize a shutter speed to ExifTool's ExposureTime notation
+/// Accepts "1/125", "1/125s", "0.5", "2s" or 2" and returns "1/125", "0.5", "2"
+fn normalize_shutter_speed(value: &str) -> Result<String, String> {
+    let trimmed = value.trim().trim_end_matches(['s', 'S', '"']).trim();
+
+    let valid = match trimmed.split_once('/') {
+        Some((num, den)) => {
+            num.trim().parse::<f64>().map(|n| n > 0.0).unwrap_or(false)
+                && den.trim().parse::<f64>().map(|d| d > 0.0).unwrap_or(false)
+        }
+        None => trimmed.parse::<f64>().map(|v| v > 0.0).unwrap_or(false),
+    };
+
+    if !valid {
+        return Err(format!("Invalid shutter speed: {}", value));
+    }
+
+    Ok(trimmed.replace(' ', ""))
+}
+
+/// Validate numeric shot values and normalize the shutter speed
+fn validate_shot_values(
+    aperture: Option<f64>,
+    shutter_speed: &mut Option<String>,
+    focal_length: Option<f64>,
+    exposure_compensation: Option<f64>,
+) -> Result<(), String> {
+    if matches!(aperture, Some(a) if a <= 0.0) {
+        return Err("Aperture must be a positive f-number".to_string());
+    }
+    if matches!(focal_length, Some(f) if f <= 0.0) {
+        return Err("Focal length must be positive".to_string());
+    }
+    if matches!(exposure_compensation, Some(ev) if !(-10.0..=10.0).contains(&ev)) {
+        return Err("Exposure compensation must be between -10 and +10 EV".to_string());
+    }
+
+    // An empty shutter speed clears the field
+    *shutter_speed = match shutter_speed.as_deref().map(str::trim) {
+        Some("") | None => None,
+        Some(value) => Some(normalize_shutter_speed(value)?),
+    };
+
+    Ok(())
+}
+
+/// Validate the fields a patch sets; an empty shutter speed clears the field
+fn validate_shot_patch(patch: &mut ShotLogPatch) -> Result<(), String> {
+    let mut shutter_speed = patch.shutter_speed.clone().flatten();
+    validate_shot_values(
+        patch.aperture.flatten(),
+        &mut shutter_speed,
+        patch.focal_length.flatten(),
+        patch.exposure_compensation.flatten(),
+    )?;
+    if patch.shutter_speed.is_some() {
+        patch.shutter_speed = Some(shutter_speed);
+    }
+
+    Ok(())
+}
+
+/// Write the stored shot logs of the given photos to their files
+async fn write_shot_logs_to_files(
+    pool: &sqlx::SqlitePool,
+    photo_ids: &[i64],
+) -> Result<ExifWriteResult, String> {
+    let mut targets = vec![];
+    for photo_id in photo_ids {
+        let photo = get_photo_by_id(pool, *photo_id).await
+            .map_err(|e| format!("Failed to query photo: {}", e))?;
+        let log = get_shot_log(pool, *photo_id).await
+            .map_err(|e| format!("Failed to query shot log: {}", e))?;
+        if let (Some(photo), Some(log)) = (photo, log) {
+            targets.push((photo.id, photo.file_path, log));
+        }
+    }
+
+    let mode = get_metadata_write_mode(pool).await?;
+    let results = stream::iter(targets)
+        .map(|(photo_id, file_path, log)| async move {
+            let result = write_shot_log_exif(&file_path, &log, mode).await;
+            (photo_id, file_path, result)
+        })
+        .buffer_unordered(exiftool_pool().size()) // One request per ExifTool worker
+        .collect::<Vec<_>>()
+        .await;
+
+    let mut success_count = 0;
+    let mut failed_count = 0;
+    let mut failed_files = vec![];
+
+    for (photo_id, file_path, result) in results {
+        match result {
+            Ok(_) => {
+                success_count += 1;
+                // Only part of a roll write, so the next roll write must not be skipped
+                if let Err(e) = mark_photo_exif_synced(pool, photo_id, None).await {
+                    eprintln!("[ShotLog] Failed to mark photo {} as synced: {}", photo_id, e);
+                }
+            }
+            Err(e) => {
+                eprintln!("[ShotLog] Failed to write shot log to {}: {}", file_path, e);
+                failed_count += 1;
+                failed_files.push(format!("{}: {}", file_path, e));
+            }
+        }
+    }
+
+    Ok(ExifWriteResult {
+        success_count,
+        skipped_count: 0,
+        failed_count,
+        failed_files,
+    })
+}
+
+/// Get shot logs for all photos in a roll
+#[tauri::command]
+pub async fn get_shot_logs_by_roll_command(
+    roll_id: i64,
+    state: State<'_, AppState>,
+) -> Result<Vec<ShotLog>, String> {
+    let pool = get_pool(&state).await?;
+    get_shot_logs_by_roll(&pool, roll_id)
+        .await
+        .map_err(|e| format!("Failed to get shot logs: {}", e))
+}
+
+/// Save complete shot logs for one or more photos
+/// Optionally writes them to EXIF (FNumber, ExposureTime, FocalLength, ExposureCompensation, ...)
+#[tauri::command]
+pub async fn update_shot_logs_command(
+    request: UpdateShotLogsRequest,
+    state: State<'_, AppState>,
+) -> Result<ShotLogUpdateResult, String> {
+    let _library = hold_library(&state)?;
+    let pool = get_pool(&state).await?;
+
+    let mut logs = request.logs;
+    for log in logs.iter_mut() {
+        validate_shot_values(log.aperture, &mut log.shutter_speed, log.focal_length, log.exposure_compensation)
+            .map_err(|e| format!("Photo {}: {}", log.photo_id, e))?;
+    }
+
+    let updated_count = save_shot_logs(&pool, &logs)
+        .await
+        .map_err(|e| format!("Failed to save shot logs: {}", e))?;
+
+    eprintln!("[ShotLog] Saved {} shot logs", updated_count);
+
+    let exif = if request.write_exif.unwrap_or(false) {
+        let photo_ids: Vec<i64> = logs.iter().map(|log| log.photo_id).collect();
+        Some(write_shot_logs_to_files(&pool, &photo_ids).await?)
+    } else {
+        None
+    };
+
+    Ok(ShotLogUpdateResult { updated_count, exif })
+}
+
+/// Set the same shot log fields on many photos (e.g. one lens for a whole roll)
+/// Fields left out are not changed; fields sent as null are cleared
+#[tauri::command]
+pub async fn apply_shot_log_fields_command(
+    request: ApplyShotLogFieldsRequest,
+    state: State<'_, AppState>,
+) -> Result<ShotLogUpdateResult, String> {
+    let _library = hold_library(&state)?;
+    let pool = get_pool(&state).await?;
+
+    let mut fields = request.fields;
+    validate_shot_patch(&mut fields)?;
+
+    let updated_count = apply_shot_log_patch(&pool, &request.photo_ids, &fields)
+        .await
+        .map_err(|e| format!("Failed to update shot logs: {}", e))?;
+
+    eprintln!("[ShotLog] Updated shot logs for {} photos", updated_count);
+
+    let exif = if request.write_exif.unwrap_or(false) {
+        Some(write_shot_logs_to_files(&pool, &request.photo_ids).await?)
+    } else {
+        None
+    };
+
+    Ok(ShotLogUpdateResult { updated_count, exif })
+}
+
+/// Parse a logbook export (CSV or JSON) and propose which entry belongs to which photo
+/// Nothing is saved; the plan is returned for review
+#[tauri::command]
+pub async fn preview_logbook_import_command(
+    roll_id: i64,
+    logbook_path: String,
+    mode: MatchMode,
+    state: State<'_, AppState>,
+) -> Result<MatchPlan, String> {
+    let pool = get_pool(&state).await?;
+
+    let entries = parse_logbook_file(std::path::Path::new(&logbook_path))
+        .map_err(|e| format!("Failed to read logbook: {}", e))?;
+
+    let photos: Vec<(i64, String, Option<String>)> = get_photos_by_roll(&pool, roll_id)
+        .await
+        .map_err(|e| format!("Failed to get photos: {}", e))?
+        .into_iter()
+        .map(|photo| (photo.id, photo.filename, photo.frame_number))
+        .collect();
+
+    let plan = match_entries(&photos, &entries, mode);
+
+    eprintln!(
+        "[ShotLog] Logbook {}: {} entries, {} matched, {} photos without entry",
+        logbook_path, entries.len(), plan.matches.len(), plan.unmatched_photo_ids.len()
+    );
+
+    Ok(plan)
+}
+
+/// Save a reviewed logbook match plan as shot logs
+/// Entries with coordinates also set the photo location; fields an entry leaves empty are kept
+#[tauri::command]
+pub async fn apply_logbook_import_command(
+    request: ApplyLogbookImportRequest,
+    state: State<'_, AppState>,
+) -> Result<ShotLogUpdateResult, String> {
+    let _library = hold_library(&state)?;
+    let pool = get_pool(&state).await?;
+
+    let mut entries = Vec::with_capacity(request.matches.len());
+    for m in &request.matches {
+        let mut patch = m.entry.to_patch();
+        validate_shot_patch(&mut patch).map_err(|e| format!("{}: {}", m.filename, e))?;
+        entries.push(ShotLogImport {
+            photo_id: m.photo_id,
+            patch,
+            frame_number: m.entry.frame_number,
+            location: m.entry.lat.zip(m.entry.lon),
+        });
+    }
+
+    let updated_count = import_shot_logs(&pool, request.roll_id, &entries)
+        .await
+        .map_err(|e| format!("Failed to save shot logs: {}", e))?;
+
+    eprintln!("[ShotLog] Imported {} shot logs from logbook", updated_count);
+
+    let exif = if request.write_exif.unwrap_or(false) {
+        let photo_ids: Vec<i64> = entries.iter().map(|entry| entry.photo_id).collect();
+        Some(write_shot_logs_to_files(&pool, &photo_ids).await?)
+    } else {
+        None
+    };
+
+    Ok(ShotLogUpdateResult { updated_count, exif })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_normalize_shutter_speed() {
+        assert_eq!(normalize_shutter_speed("1/125").unwrap(), "1/125");
+        assert_eq!(normalize_shutter_speed(" 1/60s ").unwrap(), "1/60");
+        assert_eq!(normalize_shutter_speed("2\"").unwrap(), "2");
+        assert_eq!(normalize_shutter_speed("0.5").unwrap(), "0.5");
+        assert!(normalize_shutter_speed("fast").is_err());
+        assert!(normalize_shutter_speed("1/0").is_err());
+    }
+
+    #[test]
+    fn test_validate_shot_values() {
+        let mut shutter = Some("1/250s".to_string());
+        assert!(validate_shot_values(Some(8.0), &mut shutter, Some(35.0), Some(0.3)).is_ok());
+        assert_eq!(shutter.as_deref(), Some("1/250"));
+
+        let mut empty = Some("  ".to_string());
+        assert!(validate_shot_values(None, &mut empty, None, None).is_ok());
+        assert!(empty.is_none());
+
+        assert!(validate_shot_values(Some(0.0), &mut None, None, None).is_err());
+        assert!(validate_shot_values(None, &mut None, None, Some(12.0)).is_err());
+    }
+
+    #[test]
+    fn test_validate_shot_patch() {
+        // Missing fields stay unchanged, null clears
+        let mut patch: ShotLogPatch = serde_json::from_str(r#"{"aperture": 4, "shutter_speed": "1/60s", "notes": null}"#).unwrap();
+        assert!(validate_shot_patch(&mut patch).is_ok());
+        assert_eq!(patch.aperture, Some(Some(4.0)));
+        assert_eq!(patch.shutter_speed, Some(Some("1/60".to_string())));
+        assert_eq!(patch.notes, Some(None));
+        assert_eq!(patch.focal_length, None);
+
+        let mut patch: ShotLogPatch = serde_json::from_str(r#"{"shutter_speed": ""}"#).unwrap();
+        assert!(validate_shot_patch(&mut patch).is_ok());
+        assert_eq!(patch.shutter_speed, Some(None));
+
+        let mut patch: ShotLogPatch = serde_json::from_str(r#"{"aperture": -1}"#).unwrap();
+        assert!(validate_shot_patch(&mut patch).is_err());
+    }
+}
@@ -3,10 +3,19 @@ use tokio::sync::Mutex;
 use sqlx::SqlitePool;
 use serde::{Deserialize, Serialize};
 
+use crate::exif_tool::MetadataWriteMode;
+use crate::library_paths::{LibraryRoot, database_dir, root_setting};
+
 /// Application configuration
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct AppConfig {
     pub library_root: String,
+    pub filename_template: String,
+    pub frame_patterns: Vec<String>,
+    pub backup_interval_hours: i64,
+    pub backup_keep: i64,
+    pub metadata_write_mode: MetadataWriteMode,
+    pub verify_exif_writes: bool,
 }
 
 /// Application state for configuration
@@ -54,35 +63,27 @@ pub async fn init_default_config(pool: &SqlitePool) -> Result<(), String> {
     }
 }
 
-/// Get library root from configuration
+/// Get library root from configuration (absolute, or empty when not configured)
 pub async fn get_library_root(pool: &SqlitePool) -> Result<String, String> {
-    let result = sqlx::query_as::<_, (String,)>(
-        "SELECT value FROM settings WHERE key = 'library_root'"
-    )
-    .fetch_optional(pool)
-    .await
-    .map_err(|e| format!("Failed to get library_root from config: {}", e))?;
+    let root = LibraryRoot::load(pool)
+        .await
+        .map_err(|e| format!("Failed to get library_root from config: {}", e))?;
 
-    match result {
-        Some(row) => {
-            eprintln!("[Config] Retrieved library_root from database: '{}'", row.0);
-            Ok(row.0)
-        },
-        None => {
-            eprintln!("[Config] No library_root found in database, returning empty string");
-            Ok(String::new()) // Return empty string if not found
-        }
-    }
+    eprintln!("[Config] Retrieved library_root from database: '{}'", root);
+    Ok(root.to_string())
 }
 
 /// Set library root in configuration
 pub async fn set_library_root(pool: &SqlitePool, path: &str) -> Result<(), String> {
     eprintln!("[Config] Setting library_root to: '{}'", path);
+    let database_dir = database_dir(pool)
+        .await
+        .map_err(|e| format!("Failed to set library_root: {}", e))?;
     sqlx::query(
         "INSERT INTO settings (key, value) VALUES ('library_root', ?1)
         ON CONFLICT(key) DO UPDATE SET value = ?1, updated_at = CURRENT_TIMESTAMP"
     )
-    .bind(path)
+    .bind(root_setting(path, database_dir.as_deref()))
     .execute(pool)
     .await
     .map_err(|e| format!("Failed to set library_root: {}", e))?;
@@ -90,3 +91,184 @@ pub async fn set_library_root(pool: &SqlitePool, path: &str) -> Result<(), Strin
     eprintln!("[Config] library_root saved successfully to database");
     Ok(())
 }
+
+/// Get the number of concurrent EXIF operations (ExifTool workers), clamped to 1-8
+pub async fn get_exif_concurrent_writes(pool: &SqlitePool) -> Result<usize, String> {
+    let result = sqlx::query_scalar::<_, Option<i64>>(
+        "SELECT exif_concurrent_writes FROM settings WHERE key = 'library_root'"
+    )
+    .fetch_optional(pool)
+    .await
+    .map_err(|e| format!("Failed to get exif_concurrent_writes from config: {}", e))?;
+
+    let value = result.flatten().unwrap_or(crate::exif_tool::DEFAULT_EXIFTOOL_WORKERS as i64);
+    Ok(value.clamp(1, 8) as usize)
+}
+
+/// Get the filename template for imported originals (default `ROLL_{roll_id}_{frame}`)
+pub async fn get_filename_template(pool: &SqlitePool) -> Result<String, String> {
+    let result = sqlx::query_scalar::<_, Option<String>>(
+        "SELECT filename_template FROM settings WHERE key = 'library_root'"
+    )
+    .fetch_optional(pool)
+    .await
+    .map_err(|e| format!("Failed to get filename_template from config: {}", e))?;
+
+    Ok(result
+        .flatten()
+        .filter(|template| !template.is_empty())
+        .unwrap_or_else(|| crate::filename_template::DEFAULT_FILENAME_TEMPLATE.to_string()))
+}
+
+/// Set the filename template for imported originals
+pub async fn set_filename_template(pool: &SqlitePool, template: &str) -> Result<(), String> {
+    crate::filename_template::validate_template(template)
+        .map_err(|e| format!("Invalid filename template: {}", e))?;
+
+    eprintln!("[Config] Setting filename_template to: '{}'", template);
+    sqlx::query(
+        "UPDATE settings SET filename_template = ?1, updated_at = CURRENT_TIMESTAMP WHERE key = 'library_root'"
+    )
+    .bind(template)
+    .execute(pool)
+    .await
+    .map_err(|e| format!("Failed to set filename_template: {}", e))?;
+
+    Ok(())
+}
+
+/// Get the user's frame number regexes (tried before the built-in scanner patterns)
+pub async fn get_frame_patterns(pool: &SqlitePool) -> Result<Vec<String>, String> {
+    let result = sqlx::query_scalar::<_, Option<String>>(
+        "SELECT frame_patterns FROM settings WHERE key = 'library_root'"
+    )
+    .fetch_optional(pool)
+    .await
+    .map_err(|e| format!("Failed to get frame_patterns from config: {}", e))?;
+
+    Ok(result
+        .flatten()
+        .and_then(|patterns| serde_json::from_str(&patterns).ok())
+        .unwrap_or_default())
+}
+
+/// Set the user's frame number regexes; each needs a capture group for the frame
+pub async fn set_frame_patterns(pool: &SqlitePool, patterns: &[String]) -> Result<(), String> {
+    for pattern in patterns {
+        crate::frame_numbers::compile_user_pattern(pattern).map_err(|e| format!("{:#}", e))?;
+    }
+    let value = serde_json::to_string(patterns)
+        .map_err(|e| format!("Failed to encode frame_patterns: {}", e))?;
+
+    eprintln!("[Config] Setting frame_patterns to: {}", value);
+    sqlx::query(
+        "UPDATE settings SET frame_patterns = ?1, updated_at = CURRENT_TIMESTAMP WHERE key = 'library_root'"
+    )
+    .bind(value)
+    .execute(pool)
+    .await
+    .map_err(|e| format!("Failed to set frame_patterns: {}", e))?;
+
+    Ok(())
+}
+
+/// Get the hours between scheduled database backups (0 = off)
+pub async fn get_backup_interval_hours(pool: &SqlitePool) -> Result<i64, String> {
+    let result = sqlx::query_scalar::<_, Option<i64>>(
+        "SELECT backup_interval_hours FROM settings WHERE key = 'library_root'"
+    )
+    .fetch_optional(pool)
+    .await
+    .map_err(|e| format!("Failed to get backup_interval_hours from config: {}", e))?;
+
+    Ok(result.flatten().unwrap_or(crate::backups::DEFAULT_BACKUP_INTERVAL_HOURS).max(0))
+}
+
+/// Get the number of automatic database backups to keep
+pub async fn get_backup_keep(pool: &SqlitePool) -> Result<i64, String> {
+    let result = sqlx::query_scalar::<_, Option<i64>>(
+        "SELECT backup_keep FROM settings WHERE key = 'library_root'"
+    )
+    .fetch_optional(pool)
+    .await
+    .map_err(|e| format!("Failed to get backup_keep from config: {}", e))?;
+
+    Ok(result.flatten().unwrap_or(crate::backups::DEFAULT_BACKUP_KEEP).clamp(1, 100))
+}
+
+/// Set the backup schedule; `interval_hours` 0 turns scheduled backups off
+pub async fn set_backup_settings(pool: &SqlitePool, interval_hours: i64, keep: i64) -> Result<(), String> {
+    if interval_hours < 0 {
+        return Err("Backup interval cannot be negative".to_string());
+    }
+    if !(1..=100).contains(&keep) {
+        return Err("Number of backups to keep must be between 1 and 100".to_string());
+    }
+
+    eprintln!("[Config] Setting backups to every {} hours, keeping {}", interval_hours, keep);
+    sqlx::query(
+        "UPDATE settings SET backup_interval_hours = ?1, backup_keep = ?2, updated_at = CURRENT_TIMESTAMP WHERE key = 'library_root'"
+    )
+    .bind(interval_hours)
+    .bind(keep)
+    .execute(pool)
+    .await
+    .map_err(|e| format!("Failed to set backup settings: {}", e))?;
+
+    Ok(())
+}
+
+/// Get where metadata writes go (default: into the originals)
+pub async fn get_metadata_write_mode(pool: &SqlitePool) -> Result<MetadataWriteMode, String> {
+    let result = sqlx::query_scalar::<_, Option<String>>(
+        "SELECT metadata_write_mode FROM settings WHERE key = 'library_root'"
+    )
+    .fetch_optional(pool)
+    .await
+    .map_err(|e| format!("Failed to get metadata_write_mode from config: {}", e))?;
+
+    Ok(result
+        .flatten()
+        .and_then(|mode| MetadataWriteMode::parse(&mode))
+        .unwrap_or(MetadataWriteMode::Embedded))
+}
+
+/// Set where metadata writes go
+pub async fn set_metadata_write_mode(pool: &SqlitePool, mode: MetadataWriteMode) -> Result<(), String> {
+    eprintln!("[Config] Setting metadata_write_mode to: {}", mode.as_str());
+    sqlx::query(
+        "UPDATE settings SET metadata_write_mode = ?1, updated_at = CURRENT_TIMESTAMP WHERE key = 'library_root'"
+    )
+    .bind(mode.as_str())
+    .execute(pool)
+    .await
+    .map_err(|e| format!("Failed to set metadata_write_mode: {}", e))?;
+
+    Ok(())
+}
+
+/// Get whether roll EXIF writes are read back and checked (default: off)
+pub async fn get_verify_exif_writes(pool: &SqlitePool) -> Result<bool, String> {
+    let result = sqlx::query_scalar::<_, Option<bool>>(
+        "SELECT verify_exif_writes FROM settings WHERE key = 'library_root'"
+    )
+    .fetch_optional(pool)
+    .await
+    .map_err(|e| format!("Failed to get verify_exif_writes from config: {}", e))?;
+
+    Ok(result.flatten().unwrap_or(false))
+}
+
+/// Set whether roll EXIF writes are read back and checked
+pub async fn set_verify_exif_writes(pool: &SqlitePool, enabled: bool) -> Result<(), String> {
+    eprintln!("[Config] Setting verify_exif_writes to: {}", enabled);
+    sqlx::query(
+        "UPDATE settings SET verify_exif_writes = ?1, updated_at = CURRENT_TIMESTAMP WHERE key = 'library_root'"
+    )
+    .bind(enabled)
+    .execute(pool)
+    .await
+    .map_err(|e| format!("Failed to set verify_exif_writes: {}", e))?;
+
+    Ok(())
+}
@@ -1,7 +1,11 @@
 use sqlx::SqlitePool;
-use serde::{Deserialize, Serialize};
+use serde::{Deserialize, Deserializer, Serialize};
 use anyhow::Result;
 use anyhow::anyhow;
+use anyhow::Context;
+
+use crate::migrations::{run_migrations, current_schema_version};
+use crate::library_paths::LibraryRoot;
 
 #[derive(Debug, Serialize, Deserialize, Clone, sqlx::FromRow)]
 pub struct Roll {
@@ -18,10 +22,23 @@ pub struct Roll {
     pub country: Option<String>,
     pub lat: Option<f64>,
     pub lon: Option<f64>,
+    pub film_iso: Option<i64>,
+    pub film_format: Option<String>,
+    pub film_process: Option<String>,
+    pub push_pull: Option<f64>,
+    pub scanner: Option<String>,
     pub created_at: String,
     pub updated_at: String,
 }
 
+impl Roll {
+    /// The roll path is stored relative to the library root; make it absolute
+    fn resolved(mut self, root: &LibraryRoot) -> Self {
+        self.path = root.resolve(&self.path);
+        self
+    }
+}
+
 #[derive(Debug, Serialize, Deserialize, Clone, sqlx::FromRow)]
 pub struct Photo {
     pub id: i64,
@@ -45,6 +62,25 @@ pub struct Photo {
     // User-editable metadata (stored in database, not EXIF)
     pub exif_user_comment: Option<String>,
     pub exif_description: Option<String>,
+    // BLAKE3 hash of the original file as delivered (duplicate detection)
+    pub content_hash: Option<String>,
+    // dHash of the thumbnail (near-duplicate detection)
+    pub perceptual_hash: Option<String>,
+    // Filename as delivered, and the frame number parsed from it
+    pub original_filename: Option<String>,
+    pub frame_number: Option<String>,
+    // 1-based position in the roll; photos are listed in this order
+    pub sequence: Option<i64>,
+}
+
+impl Photo {
+    /// Paths are stored relative to the library root; make them absolute
+    fn resolved(mut self, root: &LibraryRoot) -> Self {
+        self.file_path = root.resolve(&self.file_path);
+        self.thumbnail_path = root.resolve_opt(self.thumbnail_path);
+        self.preview_path = root.resolve_opt(self.preview_path);
+        self
+    }
 }
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -61,6 +97,11 @@ pub struct NewRoll {
     pub country: Option<String>,
     pub lat: Option<f64>,
     pub lon: Option<f64>,
+    pub film_iso: Option<i64>,
+    pub film_format: Option<String>,
+    pub film_process: Option<String>,
+    pub push_pull: Option<f64>,
+    pub scanner: Option<String>,
 }
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -70,6 +111,69 @@ pub struct NewPhoto {
     pub file_path: String,
     pub thumbnail_path: Option<String>,
     pub preview_path: Option<String>,
+    pub content_hash: Option<String>,
+    pub perceptual_hash: Option<String>,
+    pub original_filename: Option<String>,
+    pub frame_number: Option<String>,
+}
+
+/// Per-frame shot log: the shooting settings noted for one photo
+#[derive(Debug, Serialize, Deserialize, Clone, Default, PartialEq, sqlx::FromRow)]
+pub struct ShotLog {
+    pub photo_id: i64,
+    /// The photo's frame number (`photos.frame_number`) as written to ImageNumber; not saved with the log
+    #[serde(default, skip_deserializing)]
+    pub frame_number: Option<i64>,
+    pub aperture: Option<f64>,
+    pub shutter_speed: Option<String>,
+    pub focal_length: Option<f64>,
+    pub exposure_compensation: Option<f64>,
+    pub metering_mode: Option<String>,
+    pub flash: Option<bool>,
+    pub notes: Option<String>,
+    pub shot_at: Option<String>,
+}
+
+/// Fields to set on many shot logs at once
+///
+/// A missing field (`None`) is left unchanged; `null` (`Some(None)`) clears it.
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
+pub struct ShotLogPatch {
+    #[serde(default, deserialize_with = "double_option", skip_serializing_if = "Option::is_none")]
+    pub aperture: Option<Option<f64>>,
+    #[serde(default, deserialize_with = "double_option", skip_serializing_if = "Option::is_none")]
+    pub shutter_speed: Option<Option<String>>,
+    #[serde(default, deserialize_with = "double_option", skip_serializing_if = "Option::is_none")]
+    pub focal_length: Option<Option<f64>>,
+    #[serde(default, deserialize_with = "double_option", skip_serializing_if = "Option::is_none")]
+    pub exposure_compensation: Option<Option<f64>>,
+    #[serde(default, deserialize_with = "double_option", skip_serializing_if = "Option::is_none")]
+    pub metering_mode: Option<Option<String>>,
+    #[serde(default, deserialize_with = "double_option", skip_serializing_if = "Option::is_none")]
+    pub flash: Option<Option<bool>>,
+    #[serde(default, deserialize_with = "double_option", skip_serializing_if = "Option::is_none")]
+    pub notes: Option<Option<String>>,
+    #[serde(default, deserialize_with = "double_option", skip_serializing_if = "Option::is_none")]
+    pub shot_at: Option<Option<String>>,
+}
+
+/// Tell a `null` field (`Some(None)`) apart from a missing one (`None`, via `#[serde(default)]`)
+fn double_option<'de, T, D>(deserializer: D) -> std::result::Result<Option<Option<T>>, D::Error>
+where
+    T: Deserialize<'de>,
+    D: Deserializer<'de>,
+{
+    Option::<T>::deserialize(deserializer).map(Some)
+}
+
+/// A logbook entry matched to a photo, ready to be saved
+#[derive(Debug, Clone)]
+pub struct ShotLogImport {
+    pub photo_id: i64,
+    pub patch: ShotLogPatch,
+    /// Frame noted in the logbook; only set on photos without a frame number
+    pub frame_number: Option<i64>,
+    pub location: Option<(f64, f64)>,
 }
 
 #[derive(Debug, Serialize, Deserialize, Clone, sqlx::FromRow)]
@@ -159,227 +263,48 @@ pub async fn init_database(db_path: &str) -> Result<SqlitePool> {
     let pool = SqlitePool::connect(db_path).await?;
     eprintln!("[DB] Connected successfully");
 
-    // Run migrations
-    eprintln!("[DB] Running migrations...");
-
-    // Migration 001: Initial schema
-    let migration_001 = include_str!("../migrations/001_initial.sql");
-    match sqlx::query(migration_001).execute(&pool).await {
-        Ok(_) => eprintln!("[DB] Migration 001 executed successfully"),
-        Err(e) => {
-            let error_msg = e.to_string().to_lowercase();
-            if error_msg.contains("already exists") {
-                eprintln!("[DB] Migration 001: Tables already exist, skipping");
-            } else {
-                eprintln!("[DB] Migration 001 error: {}", e);
-                return Err(e.into());
-            }
-        }
-    }
-
-    // Migration 002: Settings table
-    let migration_002 = include_str!("../migrations/002_settings.sql");
-    match sqlx::query(migration_002).execute(&pool).await {
-        Ok(_) => eprintln!("[DB] Migration 002 executed successfully"),
-        Err(e) => {
-            let error_msg = e.to_string().to_lowercase();
-            if error_msg.contains("already exists") {
-                eprintln!("[DB] Migration 002: Settings table already exist, skipping");
-            } else {
-                eprintln!("[DB] Migration 002 error: {}", e);
-                return Err(e.into());
-            }
-        }
+    // Keep a copy of the catalog as it was before this build changes its schema
+    if let Some(backup) = crate::backups::backup_before_migrations(&pool)
+        .await
+        .context("Failed to back up the database before migrating it")?
+    {
+        eprintln!("[DB] Backed up database before migrating to {:?}", backup);
     }
 
-    // Migration 003: Add is_favorite column to photos
-    let migration_003 = include_str!("../migrations/003_add_photo_favorite.sql");
-    match sqlx::query(migration_003).execute(&pool).await {
-        Ok(_) => eprintln!("[DB] Migration 003 executed successfully"),
-        Err(e) => {
-            let error_msg = e.to_string().to_lowercase();
-            if error_msg.contains("duplicate column") {
-                eprintln!("[DB] Migration 003: is_favorite column already exists, skipping");
-            } else {
-                eprintln!("[DB] Migration 003 error: {}", e);
-                return Err(e.into());
-            }
-        }
-    }
-
-    // Migration 004: Add EXIF write tracking and photo-level EXIF fields
-    let migration_004 = include_str!("../migrations/004_exif_write_tracking.sql");
-    match sqlx::query(migration_004).execute(&pool).await {
-        Ok(_) => eprintln!("[DB] Migration 004 executed successfully"),
-        Err(e) => {
-            let error_msg = e.to_string().to_lowercase();
-            if error_msg.contains("duplicate column") {
-                eprintln!("[DB] Migration 004: EXIF columns already exist, skipping");
-            } else {
-                eprintln!("[DB] Migration 004 error: {}", e);
-                return Err(e.into());
-            }
-        }
-    }
-
-    // Migration 005: Add EXIF and UI settings
-    let migration_005 = include_str!("../migrations/005_settings_exif.sql");
-    match sqlx::query(migration_005).execute(&pool).await {
-        Ok(_) => eprintln!("[DB] Migration 005 executed successfully"),
-        Err(e) => {
-            let error_msg = e.to_string().to_lowercase();
-            if error_msg.contains("duplicate column") {
-                eprintln!("[DB] Migration 005: Settings columns already exist, skipping");
-            } else {
-                eprintln!("[DB] Migration 005 error: {}", e);
-                return Err(e.into());
-            }
-        }
-    }
-
-    // Migration 006: Remove photo-level EXIF fields
-    // Note: DROP COLUMN requires SQLite 3.35.0+. If it fails, the app will still work
-    // - Columns remain in DB but won't be used (EXIF read from files instead)
-    // - New installations: columns never existed, error is acceptable
-    // - Old SQLite: DROP COLUMN not supported, columns remain but unused
-    //
-    // Execute each DROP COLUMN separately since sqlx::query only handles one statement
-    let drop_columns = vec![
-        "ALTER TABLE photos DROP COLUMN exif_iso",
-        "ALTER TABLE photos DROP COLUMN exif_aperture",
-        "ALTER TABLE photos DROP COLUMN exif_shutter_speed",
-        "ALTER TABLE photos DROP COLUMN exif_focal_length",
-        "ALTER TABLE photos DROP COLUMN exif_altitude",
-    ];
-
-    let mut migration_006_success = true;
-    for drop_stmt in drop_columns {
-        match sqlx::query(drop_stmt).execute(&pool).await {
-            Ok(_) => {
-                eprintln!("[DB] Migration 006: Executed {}", drop_stmt);
-            }
-            Err(e) => {
-                let error_msg = e.to_string().to_lowercase();
-                // Ignore errors related to:
-                // - Columns not existing ("no such column")
-                // - SQLite version too old ("near drop", "syntax error")
-                // - Duplicate operations ("duplicate")
-                if error_msg.contains("no such column")
-                    || error_msg.contains("near drop")
-                    || error_msg.contains("syntax error")
-                    || error_msg.contains("duplicate") {
-                    eprintln!("[DB] Migration 006: Skipped {} (column doesn't exist or SQLite version too old)", drop_stmt);
-                    migration_006_success = false;
-                } else {
-                    eprintln!("[DB] Migration 006 error on '{}': {}", drop_stmt, e);
-                    return Err(e.into());
-                }
-            }
-        }
-    }
-
-    if migration_006_success {
-        eprintln!("[DB] Migration 006 executed successfully");
-    } else {
-        eprintln!("[DB] Migration 006: Partial execution (some columns didn't exist or SQLite version too old)");
-        eprintln!("[DB] Note: App will function normally - EXIF will be read from files");
-    }
-
-    // Migration 007: Add city and country to photos table
-    let migration_007 = include_str!("../migrations/007_add_photo_city.sql");
-    match sqlx::query(migration_007).execute(&pool).await {
-        Ok(_) => eprintln!("[DB] Migration 007 executed successfully"),
-        Err(e) => {
-            let error_msg = e.to_string().to_lowercase();
-            if error_msg.contains("duplicate column") {
-                eprintln!("[DB] Migration 007: city/country columns already exist, skipping");
-            } else {
-                eprintln!("[DB] Migration 007 error: {}", e);
-                return Err(e.into());
-            }
-        }
-    }
-
-    // Migration 008: Add location fields to rolls table
-    let migration_008 = include_str!("../migrations/008_add_roll_location.sql");
-    match sqlx::query(migration_008).execute(&pool).await {
-        Ok(_) => eprintln!("[DB] Migration 008 executed successfully"),
-        Err(e) => {
-            let error_msg = e.to_string().to_lowercase();
-            if error_msg.contains("duplicate column") {
-                eprintln!("[DB] Migration 008: location columns already exist, skipping");
-            } else {
-                eprintln!("[DB] Migration 008 error: {}", e);
-                return Err(e.into());
-            }
-        }
-    }
-
-    // Migration 009: Create film_presets table
-    let migration_009 = include_str!("../migrations/009_film_presets.sql");
-    match sqlx::query(migration_009).execute(&pool).await {
-        Ok(_) => eprintln!("[DB] Migration 009 executed successfully"),
-        Err(e) => {
-            let error_msg = e.to_string().to_lowercase();
-            if error_msg.contains("already exists") {
-                eprintln!("[DB] Migration 009: film_presets table already exists, skipping");
-            } else {
-                eprintln!("[DB] Migration 009 error: {}", e);
-                return Err(e.into());
-            }
-        }
-    }
-
-    // Migration 010: Add brand field to film_presets table
-    let migration_010 = include_str!("../migrations/010_add_preset_brand.sql");
-    match sqlx::query(migration_010).execute(&pool).await {
-        Ok(_) => eprintln!("[DB] Migration 010 executed successfully"),
-        Err(e) => {
-            let error_msg = e.to_string().to_lowercase();
-            if error_msg.contains("duplicate column") {
-                eprintln!("[DB] Migration 010: brand column already exists, skipping");
-            } else {
-                eprintln!("[DB] Migration 010 error: {}", e);
-                return Err(e.into());
-            }
-        }
-    }
-
-    // Migration 011: Remove iso column from film_presets table
-    let migration_011 = include_str!("../migrations/011_remove_iso.sql");
-    match sqlx::query(migration_011).execute(&pool).await {
-        Ok(_) => eprintln!("[DB] Migration 011 executed successfully"),
-        Err(e) => {
-            let error_msg = e.to_string().to_lowercase();
-            if error_msg.contains("no such column") || error_msg.contains("duplicate") {
-                eprintln!("[DB] Migration 011: iso column already removed or doesn't exist, skipping");
-            } else {
-                eprintln!("[DB] Migration 011 error: {}", e);
-                return Err(e.into());
-            }
-        }
-    }
+    eprintln!("[DB] Running migrations...");
+    run_migrations(&pool).await.map_err(|e| {
+        eprintln!("[DB] Migration error: {}", e);
+        e
+    })?;
 
-    eprintln!("[DB] All migrations completed");
+    let version = current_schema_version(&pool).await?;
+    eprintln!("[DB] All migrations completed (schema version {})", version);
     Ok(pool)
 }
 
 /// Create a new roll
 pub async fn create_roll(pool: &SqlitePool, roll: NewRoll) -> Result<i64> {
+    let root = LibraryRoot::load(pool).await?;
     let result = sqlx::query(
         r#"
-        INSERT INTO rolls (name, path, film_stock, camera, lens, shoot_date, lab_info, notes)
-        VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8)
+        INSERT INTO rolls (name, path, film_stock, camera, lens, shoot_date, lab_info, notes,
+            film_iso, film_format, film_process, push_pull, scanner)
+        VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12, ?13)
         "#
     )
     .bind(&roll.name)
-    .bind(&roll.path)
+    .bind(root.to_stored(&roll.path))
     .bind(&roll.film_stock)
     .bind(&roll.camera)
     .bind(&roll.lens)
     .bind(&roll.shoot_date)
     .bind(&roll.lab_info)
     .bind(&roll.notes)
+    .bind(roll.film_iso)
+    .bind(&roll.film_format)
+    .bind(&roll.film_process)
+    .bind(roll.push_pull)
+    .bind(&roll.scanner)
     .execute(pool)
     .await?;
 
@@ -389,24 +314,47 @@ pub async fn create_roll(pool: &SqlitePool, roll: NewRoll) -> Result<i64> {
 /// Get all rolls
 pub async fn get_all_rolls(pool: &SqlitePool) -> Result<Vec<Roll>> {
     let rolls = sqlx::query_as::<_, Roll>(
-        "SELECT id, name, path, film_stock, camera, lens, shoot_date, lab_info, notes, city, country, lat, lon, created_at, updated_at FROM rolls ORDER BY shoot_date DESC"
+        "SELECT id, name, path, film_stock, camera, lens, shoot_date, lab_info, notes, city, country, lat, lon, film_iso, film_format, film_process, push_pull, scanner, created_at, updated_at FROM rolls ORDER BY shoot_date DESC"
     )
     .fetch_all(pool)
     .await?;
 
-    Ok(rolls)
+    let root = LibraryRoot::load(pool).await?;
+    Ok(rolls.into_iter().map(|roll| roll.resolved(&root)).collect())
 }
 
 /// Get roll by ID
 pub async fn get_roll_by_id(pool: &SqlitePool, id: i64) -> Result<Option<Roll>> {
     let roll = sqlx::query_as::<_, Roll>(
-        "SELECT id, name, path, film_stock, camera, lens, shoot_date, lab_info, notes, city, country, lat, lon, created_at, updated_at FROM rolls WHERE id = ?1"
+        "SELECT id, name, path, film_stock, camera, lens, shoot_date, lab_info, notes, city, country, lat, lon, film_iso, film_format, film_process, push_pull, scanner, created_at, updated_at FROM rolls WHERE id = ?1"
     )
     .bind(id)
     .fetch_optional(pool)
     .await?;
 
-    Ok(roll)
+    let root = LibraryRoot::load(pool).await?;
+    Ok(roll.map(|roll| roll.resolved(&root)))
+}
+
+/// Roll folders as stored: relative to the library root, or absolute when outside it
+pub async fn get_stored_roll_paths(pool: &SqlitePool) -> Result<Vec<(i64, String)>> {
+    let rows = sqlx::query_as::<_, (i64, String)>("SELECT id, path FROM rolls ORDER BY id")
+        .fetch_all(pool)
+        .await?;
+
+    Ok(rows)
+}
+
+/// Set the folder of a roll
+pub async fn set_roll_path(pool: &SqlitePool, id: i64, path: &str) -> Result<bool> {
+    let root = LibraryRoot::load(pool).await?;
+    let result = sqlx::query("UPDATE rolls SET path = ?1, updated_at = CURRENT_TIMESTAMP WHERE id = ?2")
+        .bind(root.to_stored(path))
+        .bind(id)
+        .execute(pool)
+        .await?;
+
+    Ok(result.rows_affected() > 0)
 }
 
 /// Update roll metadata (including location)
@@ -417,8 +365,9 @@ pub async fn update_roll(pool: &SqlitePool, id: i64, roll: NewRoll) -> Result<bo
         SET name = ?1, film_stock = ?2, camera = ?3, lens = ?4,
             shoot_date = ?5, lab_info = ?6, notes = ?7,
             city = ?8, country = ?9, lat = ?10, lon = ?11,
+            film_iso = ?12, film_format = ?13, film_process = ?14, push_pull = ?15, scanner = ?16,
             updated_at = CURRENT_TIMESTAMP
-        WHERE id = ?12
+        WHERE id = ?17
         "#
     )
     .bind(&roll.name)
@@ -432,6 +381,11 @@ pub async fn update_roll(pool: &SqlitePool, id: i64, roll: NewRoll) -> Result<bo
     .bind(&roll.country)
     .bind(roll.lat)
     .bind(roll.lon)
+    .bind(roll.film_iso)
+    .bind(&roll.film_format)
+    .bind(&roll.film_process)
+    .bind(roll.push_pull)
+    .bind(&roll.scanner)
     .bind(id)
     .execute(pool)
     .await?;
@@ -451,17 +405,22 @@ pub async fn delete_roll(pool: &SqlitePool, id: i64) -> Result<bool> {
 
 /// Create a new photo
 pub async fn create_photo(pool: &SqlitePool, photo: NewPhoto) -> Result<i64> {
+    let root = LibraryRoot::load(pool).await?;
     let result = sqlx::query(
         r#"
-        INSERT INTO photos (roll_id, filename, file_path, thumbnail_path, preview_path)
-        VALUES (?1, ?2, ?3, ?4, ?5)
+        INSERT INTO photos (roll_id, filename, file_path, thumbnail_path, preview_path, content_hash, perceptual_hash, original_filename, frame_number, sequence)
+        VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, (SELECT COALESCE(MAX(sequence), 0) + 1 FROM photos WHERE roll_id = ?1))
         "#
     )
     .bind(photo.roll_id)
     .bind(&photo.filename)
-    .bind(&photo.file_path)
-    .bind(&photo.thumbnail_path)
-    .bind(&photo.preview_path)
+    .bind(root.to_stored(&photo.file_path))
+    .bind(root.to_stored_opt(photo.thumbnail_path.as_deref()))
+    .bind(root.to_stored_opt(photo.preview_path.as_deref()))
+    .bind(&photo.content_hash)
+    .bind(&photo.perceptual_hash)
+    .bind(&photo.original_filename)
+    .bind(&photo.frame_number)
     .execute(pool)
     .await?;
 
@@ -470,21 +429,26 @@ pub async fn create_photo(pool: &SqlitePool, photo: NewPhoto) -> Result<i64> {
 
 /// Batch create photos in a single transaction
 pub async fn create_photos_in_transaction(pool: &SqlitePool, photos: Vec<NewPhoto>) -> Result<Vec<i64>> {
+    let root = LibraryRoot::load(pool).await?;
     let mut ids = Vec::new();
     let mut tx = pool.begin().await?;
 
     for photo in photos {
         let result = sqlx::query(
             r#"
-            INSERT INTO photos (roll_id, filename, file_path, thumbnail_path, preview_path)
-            VALUES (?1, ?2, ?3, ?4, ?5)
+            INSERT INTO photos (roll_id, filename, file_path, thumbnail_path, preview_path, content_hash, perceptual_hash, original_filename, frame_number, sequence)
+            VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, (SELECT COALESCE(MAX(sequence), 0) + 1 FROM photos WHERE roll_id = ?1))
             "#
         )
         .bind(photo.roll_id)
         .bind(&photo.filename)
-        .bind(&photo.file_path)
-        .bind(&photo.thumbnail_path)
-        .bind(&photo.preview_path)
+        .bind(root.to_stored(&photo.file_path))
+        .bind(root.to_stored_opt(photo.thumbnail_path.as_deref()))
+        .bind(root.to_stored_opt(photo.preview_path.as_deref()))
+        .bind(&photo.content_hash)
+        .bind(&photo.perceptual_hash)
+        .bind(&photo.original_filename)
+        .bind(&photo.frame_number)
         .execute(&mut *tx)
         .await?;
         ids.push(result.last_insert_rowid());
@@ -502,37 +466,214 @@ pub async fn create_photos(pool: &SqlitePool, photos: Vec<NewPhoto>) -> Result<V
 /// Get photos by roll ID
 pub async fn get_photos_by_roll(pool: &SqlitePool, roll_id: i64) -> Result<Vec<Photo>> {
     let photos = sqlx::query_as::<_, Photo>(
-        "SELECT id, roll_id, filename, file_path, thumbnail_path, preview_path, rating, is_cover, is_favorite, lat, lon, city, country, exif_synced, created_at, exif_written_at, exif_data_hash, exif_user_comment, exif_description FROM photos WHERE roll_id = ?1 ORDER BY filename"
+        "SELECT id, roll_id, filename, file_path, thumbnail_path, preview_path, rating, is_cover, is_favorite, lat, lon, city, country, exif_synced, created_at, exif_written_at, exif_data_hash, exif_user_comment, exif_description, content_hash, perceptual_hash, original_filename, frame_number, sequence FROM photos WHERE roll_id = ?1 ORDER BY sequence, id"
     )
     .bind(roll_id)
     .fetch_all(pool)
     .await?;
 
-    Ok(photos)
+    let root = LibraryRoot::load(pool).await?;
+    Ok(photos.into_iter().map(|photo| photo.resolved(&root)).collect())
 }
 
 /// Get a single photo by ID
 pub async fn get_photo_by_id(pool: &SqlitePool, photo_id: i64) -> Result<Option<Photo>> {
     let photo = sqlx::query_as::<_, Photo>(
-        "SELECT id, roll_id, filename, file_path, thumbnail_path, preview_path, rating, is_cover, is_favorite, lat, lon, city, country, exif_synced, created_at, exif_written_at, exif_data_hash, exif_user_comment, exif_description FROM photos WHERE id = ?1"
+        "SELECT id, roll_id, filename, file_path, thumbnail_path, preview_path, rating, is_cover, is_favorite, lat, lon, city, country, exif_synced, created_at, exif_written_at, exif_data_hash, exif_user_comment, exif_description, content_hash, perceptual_hash, original_filename, frame_number, sequence FROM photos WHERE id = ?1"
     )
     .bind(photo_id)
     .fetch_optional(pool)
     .await?;
 
-    Ok(photo)
+    let root = LibraryRoot::load(pool).await?;
+    Ok(photo.map(|photo| photo.resolved(&root)))
+}
+
+/// Get all photos (library-wide) whose original has the given content hash
+pub async fn get_photos_by_content_hash(pool: &SqlitePool, content_hash: &str) -> Result<Vec<Photo>> {
+    let photos = sqlx::query_as::<_, Photo>(
+        "SELECT id, roll_id, filename, file_path, thumbnail_path, preview_path, rating, is_cover, is_favorite, lat, lon, city, country, exif_synced, created_at, exif_written_at, exif_data_hash, exif_user_comment, exif_description, content_hash, perceptual_hash, original_filename, frame_number, sequence FROM photos WHERE content_hash = ?1 ORDER BY id"
+    )
+    .bind(content_hash)
+    .fetch_all(pool)
+    .await?;
+
+    let root = LibraryRoot::load(pool).await?;
+    Ok(photos.into_iter().map(|photo| photo.resolved(&root)).collect())
+}
+
+/// Get (id, file_path) of photos imported before content hashes were recorded
+/// Skips originals that could not be hashed before, and those with EXIF written
+/// into them: they no longer hash like the delivered file.
+pub async fn get_photos_without_content_hash(pool: &SqlitePool) -> Result<Vec<(i64, String)>> {
+    let rows = sqlx::query_as::<_, (i64, String)>(
+        "SELECT id, file_path FROM photos WHERE content_hash IS NULL AND content_hash_failed_at IS NULL AND exif_written_at IS NULL"
+    )
+    .fetch_all(pool)
+    .await?;
+
+    let root = LibraryRoot::load(pool).await?;
+    Ok(rows.into_iter().map(|(id, file_path)| (id, root.resolve(&file_path))).collect())
+}
+
+/// Set the content hash of a photo
+pub async fn update_photo_content_hash(pool: &SqlitePool, photo_id: i64, content_hash: &str) -> Result<bool> {
+    let result = sqlx::query("UPDATE photos SET content_hash = ?1 WHERE id = ?2")
+        .bind(content_hash)
+        .bind(photo_id)
+        .execute(pool)
+        .await?;
+
+    Ok(result.rows_affected() > 0)
+}
+
+/// Remember that a photo's original could not be hashed
+pub async fn mark_photo_content_hash_failed(pool: &SqlitePool, photo_id: i64) -> Result<bool> {
+    let result = sqlx::query("UPDATE photos SET content_hash_failed_at = CURRENT_TIMESTAMP WHERE id = ?1")
+        .bind(photo_id)
+        .execute(pool)
+        .await?;
+
+    Ok(result.rows_affected() > 0)
+}
+
+/// Get every photo of the library, for integrity checks
+pub async fn get_all_photos(pool: &SqlitePool) -> Result<Vec<Photo>> {
+    let photos = sqlx::query_as::<_, Photo>(
+        "SELECT id, roll_id, filename, file_path, thumbnail_path, preview_path, rating, is_cover, is_favorite, lat, lon, city, country, exif_synced, created_at, exif_written_at, exif_data_hash, exif_user_comment, exif_description, content_hash, perceptual_hash, original_filename, frame_number, sequence FROM photos ORDER BY roll_id, sequence, id"
+    )
+    .fetch_all(pool)
+    .await?;
+
+    let root = LibraryRoot::load(pool).await?;
+    Ok(photos.into_iter().map(|photo| photo.resolved(&root)).collect())
+}
+
+/// Get all photos that have a thumbnail, for near-duplicate search
+pub async fn get_all_photos_with_thumbnails(pool: &SqlitePool) -> Result<Vec<Photo>> {
+    let photos = sqlx::query_as::<_, Photo>(
+        "SELECT id, roll_id, filename, file_path, thumbnail_path, preview_path, rating, is_cover, is_favorite, lat, lon, city, country, exif_synced, created_at, exif_written_at, exif_data_hash, exif_user_comment, exif_description, content_hash, perceptual_hash, original_filename, frame_number, sequence FROM photos WHERE thumbnail_path IS NOT NULL ORDER BY roll_id, sequence, id"
+    )
+    .fetch_all(pool)
+    .await?;
+
+    let root = LibraryRoot::load(pool).await?;
+    Ok(photos.into_iter().map(|photo| photo.resolved(&root)).collect())
+}
+
+/// Set the perceptual hash of a photo
+pub async fn update_photo_perceptual_hash(pool: &SqlitePool, photo_id: i64, perceptual_hash: &str) -> Result<bool> {
+    let result = sqlx::query("UPDATE photos SET perceptual_hash = ?1 WHERE id = ?2")
+        .bind(perceptual_hash)
+        .bind(photo_id)
+        .execute(pool)
+        .await?;
+
+    Ok(result.rows_affected() > 0)
+}
+
+/// New filename and library paths of a photo
+#[derive(Debug, Clone)]
+pub struct PhotoPathUpdate {
+    pub photo_id: i64,
+    pub filename: String,
+    pub file_path: String,
+    pub thumbnail_path: Option<String>,
+    pub preview_path: Option<String>,
+}
+
+/// Update the filename and file paths of several photos in one transaction
+pub async fn update_photo_paths(pool: &SqlitePool, updates: &[PhotoPathUpdate]) -> Result<usize> {
+    let root = LibraryRoot::load(pool).await?;
+    let mut tx = pool.begin().await?;
+    let mut updated = 0;
+
+    for update in updates {
+        let result = sqlx::query(
+            "UPDATE photos SET filename = ?1, file_path = ?2, thumbnail_path = ?3, preview_path = ?4 WHERE id = ?5"
+        )
+        .bind(&update.filename)
+        .bind(root.to_stored(&update.file_path))
+        .bind(root.to_stored_opt(update.thumbnail_path.as_deref()))
+        .bind(root.to_stored_opt(update.preview_path.as_deref()))
+        .bind(update.photo_id)
+        .execute(&mut *tx)
+        .await?;
+        updated += result.rows_affected() as usize;
+    }
+
+    tx.commit().await?;
+    Ok(updated)
+}
+
+/// Set the frame numbers of several photos in one transaction
+pub async fn update_photo_frame_numbers(pool: &SqlitePool, frames: &[(i64, Option<String>)]) -> Result<usize> {
+    let mut tx = pool.begin().await?;
+    let mut updated = 0;
+
+    for (photo_id, frame_number) in frames {
+        let result = sqlx::query("UPDATE photos SET frame_number = ?1 WHERE id = ?2")
+            .bind(frame_number)
+            .bind(photo_id)
+            .execute(&mut *tx)
+            .await?;
+        updated += result.rows_affected() as usize;
+    }
+
+    tx.commit().await?;
+    Ok(updated)
+}
+
+/// Store a new order for a roll's photos: `photo_ids[i]` gets sequence `i + 1`
+/// Returns the number of photos updated; ids of other rolls are ignored.
+pub async fn set_photo_sequence(pool: &SqlitePool, roll_id: i64, photo_ids: &[i64]) -> Result<usize> {
+    let mut tx = pool.begin().await?;
+    let mut updated = 0;
+
+    for (index, photo_id) in photo_ids.iter().enumerate() {
+        let result = sqlx::query("UPDATE photos SET sequence = ?1 WHERE id = ?2 AND roll_id = ?3")
+            .bind(index as i64 + 1)
+            .bind(photo_id)
+            .bind(roll_id)
+            .execute(&mut *tx)
+            .await?;
+        updated += result.rows_affected() as usize;
+    }
+
+    tx.commit().await?;
+    Ok(updated)
+}
+
+/// Renumber a roll's sequence to 1..n, closing gaps left by deleted photos
+pub async fn compact_photo_sequence(pool: &SqlitePool, roll_id: i64) -> Result<()> {
+    sqlx::query(
+        r#"
+        WITH ordered AS (
+            SELECT id, ROW_NUMBER() OVER (ORDER BY sequence, id) AS position
+            FROM photos WHERE roll_id = ?1
+        )
+        UPDATE photos SET sequence = (SELECT position FROM ordered WHERE ordered.id = photos.id)
+        WHERE roll_id = ?1
+        "#
+    )
+    .bind(roll_id)
+    .execute(pool)
+    .await?;
+
+    Ok(())
 }
 
-/// Get cover photo for a roll
+/// Get cover photo for a roll (the first photo in sequence if none is marked)
 pub async fn get_roll_cover(pool: &SqlitePool, roll_id: i64) -> Result<Option<Photo>> {
     let photo = sqlx::query_as::<_, Photo>(
-        "SELECT id, roll_id, filename, file_path, thumbnail_path, preview_path, rating, is_cover, is_favorite, lat, lon, city, country, exif_synced, created_at, exif_written_at, exif_data_hash, exif_user_comment, exif_description FROM photos WHERE roll_id = ?1 AND is_cover = 1 LIMIT 1"
+        "SELECT id, roll_id, filename, file_path, thumbnail_path, preview_path, rating, is_cover, is_favorite, lat, lon, city, country, exif_synced, created_at, exif_written_at, exif_data_hash, exif_user_comment, exif_description, content_hash, perceptual_hash, original_filename, frame_number, sequence FROM photos WHERE roll_id = ?1 ORDER BY is_cover DESC, sequence, id LIMIT 1"
     )
     .bind(roll_id)
     .fetch_optional(pool)
     .await?;
 
-    Ok(photo)
+    let root = LibraryRoot::load(pool).await?;
+    Ok(photo.map(|photo| photo.resolved(&root)))
 }
 
 /// Set photo as cover in a single transaction
@@ -591,12 +732,18 @@ pub async fn update_photo_location(
 
 /// Delete a single photo by ID
 pub async fn delete_photo(pool: &SqlitePool, photo_id: i64) -> Result<bool> {
-    let result = sqlx::query("DELETE FROM photos WHERE id = ?1")
+    let roll_id = sqlx::query_scalar::<_, i64>("DELETE FROM photos WHERE id = ?1 RETURNING roll_id")
         .bind(photo_id)
-        .execute(pool)
+        .fetch_optional(pool)
         .await?;
 
-    Ok(result.rows_affected() > 0)
+    match roll_id {
+        Some(roll_id) => {
+            compact_photo_sequence(pool, roll_id).await?;
+            Ok(true)
+        }
+        None => Ok(false),
+    }
 }
 
 /// Batch delete photos by IDs
@@ -638,12 +785,17 @@ pub async fn delete_photos(pool: &SqlitePool, photo_ids: Vec<i64>) -> Result<usi
 
     let result = query_builder.execute(pool).await?;
 
+    let rolls: std::collections::HashSet<i64> = ids_with_rolls.iter().map(|(_, roll_id, _)| *roll_id).collect();
+    for roll_id in rolls {
+        compact_photo_sequence(pool, roll_id).await?;
+    }
+
     // If any cover photos were deleted, set new covers for affected rolls
     if !affected_rolls.is_empty() {
         for roll_id in affected_rolls {
             // Set the first remaining photo as cover
             if let Some(first_photo) = sqlx::query_as::<_, Photo>(
-                "SELECT id, roll_id, filename, file_path, thumbnail_path, preview_path, rating, is_cover, is_favorite, lat, lon, city, country, exif_synced, created_at, exif_written_at, exif_data_hash, exif_user_comment, exif_description FROM photos WHERE roll_id = ?1 ORDER BY id LIMIT 1"
+                "SELECT id, roll_id, filename, file_path, thumbnail_path, preview_path, rating, is_cover, is_favorite, lat, lon, city, country, exif_synced, created_at, exif_written_at, exif_data_hash, exif_user_comment, exif_description, content_hash, perceptual_hash, original_filename, frame_number, sequence FROM photos WHERE roll_id = ?1 ORDER BY sequence, id LIMIT 1"
             )
             .bind(roll_id)
             .fetch_optional(pool)
@@ -700,13 +852,14 @@ pub async fn update_photo_favorite(pool: &SqlitePool, photo_id: i64, is_favorite
 /// Get favorite photos by roll ID
 pub async fn get_favorite_photos_by_roll(pool: &SqlitePool, roll_id: i64) -> Result<Vec<Photo>> {
     let photos = sqlx::query_as::<_, Photo>(
-        "SELECT id, roll_id, filename, file_path, thumbnail_path, preview_path, rating, is_cover, is_favorite, lat, lon, city, country, exif_synced, created_at, exif_written_at, exif_data_hash, exif_user_comment, exif_description FROM photos WHERE roll_id = ?1 AND is_favorite = 1 ORDER BY filename"
+        "SELECT id, roll_id, filename, file_path, thumbnail_path, preview_path, rating, is_cover, is_favorite, lat, lon, city, country, exif_synced, created_at, exif_written_at, exif_data_hash, exif_user_comment, exif_description, content_hash, perceptual_hash, original_filename, frame_number, sequence FROM photos WHERE roll_id = ?1 AND is_favorite = 1 ORDER BY sequence, id"
     )
     .bind(roll_id)
     .fetch_all(pool)
     .await?;
 
-    Ok(photos)
+    let root = LibraryRoot::load(pool).await?;
+    Ok(photos.into_iter().map(|photo| photo.resolved(&root)).collect())
 }
 
 /// Update photo metadata (description, comment, sync status)
@@ -735,15 +888,41 @@ pub async fn update_photo_metadata(
 }
 
 /// Update photo EXIF sync status after writing to file
+///
+/// `exif_data_hash` is the hash of a full roll write; partial writes pass None
+/// so the next roll write is not skipped.
 pub async fn mark_photo_exif_synced(
     pool: &SqlitePool,
     photo_id: i64,
+    exif_data_hash: Option<&str>,
 ) -> Result<bool> {
     let result = sqlx::query(
         r#"
         UPDATE photos
         SET exif_synced = 1,
-            exif_written_at = CURRENT_TIMESTAMP
+            exif_written_at = CURRENT_TIMESTAMP,
+            exif_data_hash = ?1
+        WHERE id = ?2
+        "#
+    )
+    .bind(exif_data_hash)
+    .bind(photo_id)
+    .execute(pool)
+    .await?;
+
+    Ok(result.rows_affected() > 0)
+}
+
+/// Forget the written EXIF of a photo after its metadata was cleared from the file
+pub async fn mark_photo_exif_cleared(
+    pool: &SqlitePool,
+    photo_id: i64,
+) -> Result<bool> {
+    let result = sqlx::query(
+        r#"
+        UPDATE photos
+        SET exif_synced = 0,
+            exif_data_hash = NULL
         WHERE id = ?1
         "#
     )
@@ -847,3 +1026,185 @@ pub async fn apply_roll_location_to_photos(
 
     Ok(result.rows_affected() as usize)
 }
+
+/// Get the shot log for a photo
+pub async fn get_shot_log(pool: &SqlitePool, photo_id: i64) -> Result<Option<ShotLog>> {
+    let log = sqlx::query_as::<_, ShotLog>(
+        r#"
+        SELECT s.photo_id, CAST(p.frame_number AS INTEGER) AS frame_number, s.aperture, s.shutter_speed,
+               s.focal_length, s.exposure_compensation, s.metering_mode, s.flash, s.notes, s.shot_at
+        FROM shot_logs s
+        JOIN photos p ON p.id = s.photo_id
+        WHERE s.photo_id = ?1
+        "#
+    )
+    .bind(photo_id)
+    .fetch_optional(pool)
+    .await?;
+
+    Ok(log)
+}
+
+/// Get shot logs for all photos in a roll (photos without a log are omitted)
+pub async fn get_shot_logs_by_roll(pool: &SqlitePool, roll_id: i64) -> Result<Vec<ShotLog>> {
+    let logs = sqlx::query_as::<_, ShotLog>(
+        r#"
+        SELECT s.photo_id, CAST(p.frame_number AS INTEGER) AS frame_number, s.aperture, s.shutter_speed,
+               s.focal_length, s.exposure_compensation, s.metering_mode, s.flash, s.notes, s.shot_at
+        FROM shot_logs s
+        JOIN photos p ON p.id = s.photo_id
+        WHERE p.roll_id = ?1
+        ORDER BY p.sequence, p.id
+        "#
+    )
+    .bind(roll_id)
+    .fetch_all(pool)
+    .await?;
+
+    Ok(logs)
+}
+
+/// Insert or replace shot logs in a single transaction
+/// The frame number belongs to the photo and is not saved here
+pub async fn save_shot_logs(pool: &SqlitePool, logs: &[ShotLog]) -> Result<usize> {
+    let mut tx = pool.begin().await?;
+
+    for log in logs {
+        sqlx::query(
+            r#"
+            INSERT INTO shot_logs (photo_id, aperture, shutter_speed, focal_length,
+                                   exposure_compensation, metering_mode, flash, notes, shot_at)
+            VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9)
+            ON CONFLICT(photo_id) DO UPDATE SET
+                aperture = ?2, shutter_speed = ?3, focal_length = ?4,
+                exposure_compensation = ?5, metering_mode = ?6, flash = ?7, notes = ?8,
+                shot_at = ?9, updated_at = CURRENT_TIMESTAMP
+            "#
+        )
+        .bind(log.photo_id)
+        .bind(log.aperture)
+        .bind(&log.shutter_speed)
+        .bind(log.focal_length)
+        .bind(log.exposure_compensation)
+        .bind(&log.metering_mode)
+        .bind(log.flash)
+        .bind(&log.notes)
+        .bind(&log.shot_at)
+        .execute(&mut *tx)
+        .await?;
+    }
+
+    tx.commit().await?;
+    Ok(logs.len())
+}
+
+/// Set the given fields on the shot logs of many photos in a single transaction
+/// Photos without a shot log get one created
+pub async fn apply_shot_log_patch(
+    pool: &SqlitePool,
+    photo_ids: &[i64],
+    patch: &ShotLogPatch,
+) -> Result<usize> {
+    let mut tx = pool.begin().await?;
+    let mut updated = 0;
+
+    for photo_id in photo_ids {
+        updated += patch_shot_log(&mut tx, *photo_id, patch).await?;
+    }
+
+    tx.commit().await?;
+    Ok(updated)
+}
+
+/// Save logbook entries as shot logs, and entry coordinates as photo locations, in a single transaction
+/// Fields an entry leaves empty keep their current value; photos outside the roll are rejected
+pub async fn import_shot_logs(
+    pool: &SqlitePool,
+    roll_id: i64,
+    entries: &[ShotLogImport],
+) -> Result<usize> {
+    let mut tx = pool.begin().await?;
+    let mut updated = 0;
+
+    for entry in entries {
+        let photo_roll_id = sqlx::query_scalar::<_, i64>("SELECT roll_id FROM photos WHERE id = ?1")
+            .bind(entry.photo_id)
+            .fetch_optional(&mut *tx)
+            .await?;
+        if photo_roll_id != Some(roll_id) {
+            return Err(anyhow!("Photo {} is not in roll {}", entry.photo_id, roll_id));
+        }
+
+        updated += patch_shot_log(&mut tx, entry.photo_id, &entry.patch).await?;
+
+        if let Some(frame_number) = entry.frame_number {
+            sqlx::query("UPDATE photos SET frame_number = ?1 WHERE id = ?2 AND frame_number IS NULL")
+                .bind(frame_number.to_string())
+                .bind(entry.photo_id)
+                .execute(&mut *tx)
+                .await?;
+        }
+
+        if let Some((lat, lon)) = entry.location {
+            sqlx::query("UPDATE photos SET lat = ?1, lon = ?2 WHERE id = ?3")
+                .bind(lat)
+                .bind(lon)
+                .bind(entry.photo_id)
+                .execute(&mut *tx)
+                .await?;
+        }
+    }
+
+    tx.commit().await?;
+    Ok(updated)
+}
+
+/// Set the given fields on one photo's shot log, creating the log if the photo has none
+async fn patch_shot_log(
+    tx: &mut sqlx::Transaction<'_, sqlx::Sqlite>,
+    photo_id: i64,
+    patch: &ShotLogPatch,
+) -> Result<usize> {
+    sqlx::query("INSERT INTO shot_logs (photo_id) SELECT id FROM photos WHERE id = ?1 ON CONFLICT(photo_id) DO NOTHING")
+        .bind(photo_id)
+        .execute(&mut **tx)
+        .await?;
+
+    // Each field is a (set?, value) pair so a patch can clear a field as well as set it
+    let result = sqlx::query(
+        r#"
+        UPDATE shot_logs
+        SET aperture = CASE WHEN ?1 THEN ?2 ELSE aperture END,
+            shutter_speed = CASE WHEN ?3 THEN ?4 ELSE shutter_speed END,
+            focal_length = CASE WHEN ?5 THEN ?6 ELSE focal_length END,
+            exposure_compensation = CASE WHEN ?7 THEN ?8 ELSE exposure_compensation END,
+            metering_mode = CASE WHEN ?9 THEN ?10 ELSE metering_mode END,
+            flash = CASE WHEN ?11 THEN ?12 ELSE flash END,
+            notes = CASE WHEN ?13 THEN ?14 ELSE notes END,
+            shot_at = CASE WHEN ?15 THEN ?16 ELSE shot_at END,
+            updated_at = CURRENT_TIMESTAMP
+        WHERE photo_id = ?17
+        "#
+    )
+    .bind(patch.aperture.is_some())
+    .bind(patch.aperture.flatten())
+    .bind(patch.shutter_speed.is_some())
+    .bind(patch.shutter_speed.clone().flatten())
+    .bind(patch.focal_length.is_some())
+    .bind(patch.focal_length.flatten())
+    .bind(patch.exposure_compensation.is_some())
+    .bind(patch.exposure_compensation.flatten())
+    .bind(patch.metering_mode.is_some())
+    .bind(patch.metering_mode.clone().flatten())
+    .bind(patch.flash.is_some())
+    .bind(patch.flash.flatten())
+    .bind(patch.notes.is_some())
+    .bind(patch.notes.clone().flatten())
+    .bind(patch.shot_at.is_some())
+    .bind(patch.shot_at.clone().flatten())
+    .bind(photo_id)
+    .execute(&mut **tx)
+    .await?;
+
+    Ok(result.rows_affected() as usize)
+}
@@ -5,10 +5,25 @@
  * Handles EXIF reading and writing operations via ExifTool command-line.
  */
 
-use std::process::Command;
-use std::path::Path;
+use std::process::{Command, Stdio};
+use std::path::{Path, PathBuf};
+use std::sync::OnceLock;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::Duration;
 use serde::{Deserialize, Serialize};
 use anyhow::Result;
+use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
+use tokio::process::{Child, ChildStderr, ChildStdin, ChildStdout};
+use tokio::sync::{Mutex, Semaphore};
+
+use crate::database::ShotLog;
+use crate::film_metadata::{FilmMetadata, legacy_film_stock};
+
+/// Default number of ExifTool worker processes
+pub const DEFAULT_EXIFTOOL_WORKERS: usize = 4;
+
+/// Maximum time a single ExifTool request may take before the worker is restarted
+const EXIFTOOL_TIMEOUT: Duration = Duration::from_secs(60);
 
 /// EXIF data structure for reading and writing
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -65,16 +80,72 @@ pub struct ExifData {
 
     #[serde(skip_serializing_if = "Option::is_none")]
     pub description: Option<String>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub keywords: Option<Vec<String>>,
+
+    // Film details from the FilmVault XMP namespace
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub film: Option<FilmMetadata>,
 }
 
 /// Result of EXIF write operation
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ExifWriteResult {
     pub success_count: usize,
+    #[serde(default)]
+    pub skipped_count: usize, // Already up to date (matching exif_data_hash)
     pub failed_count: usize,
     pub failed_files: Vec<String>,
 }
 
+/// Where metadata writes go
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum MetadataWriteMode {
+    /// Into the original files
+    Embedded,
+    /// Into `.xmp` sidecars next to the originals, which are never modified
+    Sidecar,
+}
+
+impl MetadataWriteMode {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            MetadataWriteMode::Embedded => "embedded",
+            MetadataWriteMode::Sidecar => "sidecar",
+        }
+    }
+
+    pub fn parse(value: &str) -> Option<Self> {
+        match value {
+            "embedded" => Some(MetadataWriteMode::Embedded),
+            "sidecar" => Some(MetadataWriteMode::Sidecar),
+            _ => None,
+        }
+    }
+}
+
+/// XMP sidecar of a photo: same folder and name, `.xmp` extension
+pub fn sidecar_path(file_path: &str) -> PathBuf {
+    Path::new(file_path).with_extension("xmp")
+}
+
+/// File a metadata write goes to; the original must exist either way
+///
+/// ExifTool creates a missing sidecar from scratch when tags are written to it.
+fn write_target(file_path: &str, mode: MetadataWriteMode) -> Result<String> {
+    if !Path::new(file_path).exists() {
+        eprintln!("[EXIF] ERROR: File not found: {}", file_path);
+        return Err(anyhow::anyhow!("File not found: {}", file_path));
+    }
+
+    Ok(match mode {
+        MetadataWriteMode::Embedded => file_path.to_string(),
+        MetadataWriteMode::Sidecar => sidecar_path(file_path).to_string_lossy().to_string(),
+    })
+}
+
 /// Check if ExifTool is available
 pub fn check_exiftool_available() -> bool {
     // IMPORTANT: Check our bundled exiftool FIRST (before system PATH)
@@ -212,8 +283,281 @@ fn get_exiftool_files_dir() -> Option<std::path::PathBuf> {
     None
 }
 
+/// Apply PATH/PERL5LIB so the bundled Perl can find its DLLs and modules
+fn apply_exiftool_env(cmd: &mut tokio::process::Command) {
+    if let Some(files_dir) = get_exiftool_files_dir() {
+        // Add exiftool_files to PATH so perl532.dll can be found
+        cmd.env("PATH", format!("{};{}", files_dir.display(), std::env::var("PATH").unwrap_or_default()));
+
+        // Set PERL5LIB to point to the lib directory
+        cmd.env("PERL5LIB", files_dir.join("lib"));
+    }
+}
+
+/// Output of a single ExifTool request
+#[derive(Debug, Clone, Default)]
+pub struct ExifToolOutput {
+    pub stdout: String,
+    pub stderr: String,
+    /// Raw stdout bytes, for binary extraction (`-b`)
+    pub data: Vec<u8>,
+}
+
+impl ExifToolOutput {
+    /// Turn ExifTool error messages into an error (warnings are ignored)
+    pub fn check_errors(&self) -> Result<()> {
+        let errors: Vec<&str> = self.stderr
+            .lines()
+            .filter(|line| line.trim_start().starts_with("Error"))
+            .collect();
+
+        if !errors.is_empty() {
+            return Err(anyhow::anyhow!("ExifTool error: {}", errors.join("; ")));
+        }
+
+        if self.stdout.contains("weren't updated due to errors") {
+            return Err(anyhow::anyhow!("ExifTool error: {}", self.stdout.trim()));
+        }
+
+        Ok(())
+    }
+}
+
+/// Whether an argument assigns a tag value (`-TAG=VALUE`)
+fn is_tag_assignment(arg: &str) -> bool {
+    arg.starts_with('-') && arg.contains('=')
+}
+
+/// C-style escape of a tag assignment, undone by ExifTool's `-ec` option
+fn escape_assignment(arg: &str) -> String {
+    arg.replace('\\', "\\\\").replace('\r', "\\r").replace('\n', "\\n")
+}
+
+/// Build the argument block for one `-stay_open` request
+///
+/// Arguments are newline-separated. Tag values with line breaks (a multi-line
+/// description) are sent C-escaped with `-ec`, so the file gets them intact;
+/// line breaks anywhere else are flattened. `-echo4` marks the end of stderr
+/// output and `-executeN` the end of stdout.
+fn build_request(args: &[String], seq: u64) -> String {
+    let multi_line = args.iter().any(|arg| is_tag_assignment(arg) && arg.contains(['\r', '\n']));
+
+    let mut request = String::new();
+    request.push_str("-charset\nfilename=utf8\n");
+    if multi_line {
+        request.push_str("-ec\n");
+    }
+    for arg in args {
+        if multi_line && is_tag_assignment(arg) {
+            request.push_str(&escape_assignment(arg));
+        } else {
+            request.push_str(&arg.replace("\r\n", " ").replace(['\r', '\n'], " "));
+        }
+        request.push('\n');
+    }
+    request.push_str(&format!("-echo4\n{{ready{}}}\n-execute{}\n", seq, seq));
+    request
+}
+
+/// Read until the `{readyN}` sentinel, returning everything before it
+///
+/// Works on bytes because `-b` output is binary and is not newline-terminated,
+/// so the sentinel may directly follow the data.
+async fn read_until_ready<R>(reader: &mut BufReader<R>, seq: u64) -> Result<Vec<u8>>
+where
+    R: tokio::io::AsyncRead + Unpin,
+{
+    let sentinel = format!("{{ready{}}}", seq);
+    let mut output = Vec::new();
+
+    loop {
+        let read = reader.read_until(b'\n', &mut output).await?;
+        if read == 0 {
+            return Err(anyhow::anyhow!("ExifTool process exited unexpectedly"));
+        }
+
+        let line_end = output.len()
+            - if output.ends_with(b"\r\n") { 2 } else if output.ends_with(b"\n") { 1 } else { 0 };
+        if output[..line_end].ends_with(sentinel.as_bytes()) {
+            output.truncate(line_end - sentinel.len());
+            return Ok(output);
+        }
+    }
+}
+
+/// A long-lived ExifTool process driven through `-stay_open True -@ -`
+struct ExifToolSession {
+    _child: Child,
+    stdin: ChildStdin,
+    stdout: BufReader<ChildStdout>,
+    stderr: BufReader<ChildStderr>,
+    seq: u64,
+}
+
+impl ExifToolSession {
+    fn spawn(config: Option<&Path>) -> Result<Self> {
+        let exiftool_path = get_exiftool_path();
+        let mut cmd = tokio::process::Command::new(&exiftool_path);
+        apply_exiftool_env(&mut cmd);
+        // Must come first: defines the FilmVault XMP namespace
+        if let Some(config) = config {
+            cmd.arg("-config").arg(config);
+        }
+        cmd.arg("-stay_open")
+            .arg("True")
+            .arg("-@")
+            .arg("-")
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .kill_on_drop(true);
+
+        let mut child = cmd.spawn()
+            .map_err(|e| anyhow::anyhow!("Failed to start exiftool: {}", e))?;
+
+        let stdin = child.stdin.take()
+            .ok_or_else(|| anyhow::anyhow!("Failed to open exiftool stdin"))?;
+        let stdout = child.stdout.take()
+            .ok_or_else(|| anyhow::anyhow!("Failed to open exiftool stdout"))?;
+        let stderr = child.stderr.take()
+            .ok_or_else(|| anyhow::anyhow!("Failed to open exiftool stderr"))?;
+
+        eprintln!("[EXIF] Started ExifTool worker (pid {:?})", child.id());
+
+        Ok(ExifToolSession {
+            _child: child,
+            stdin,
+            stdout: BufReader::new(stdout),
+            stderr: BufReader::new(stderr),
+            seq: 0,
+        })
+    }
+
+    async fn execute(&mut self, args: &[String]) -> Result<ExifToolOutput> {
+        self.seq += 1;
+        let seq = self.seq;
+
+        self.stdin.write_all(build_request(args, seq).as_bytes()).await?;
+        self.stdin.flush().await?;
+
+        let (stdout, stderr) = tokio::try_join!(
+            read_until_ready(&mut self.stdout, seq),
+            read_until_ready(&mut self.stderr, seq),
+        )?;
+
+        Ok(ExifToolOutput {
+            stdout: String::from_utf8_lossy(&stdout).to_string(),
+            stderr: String::from_utf8_lossy(&stderr).to_string(),
+            data: stdout,
+        })
+    }
+}
+
+/// Pool of ExifTool workers, sized by the open library's `exif_concurrent_writes` setting
+///
+/// Workers are started on demand, reused between requests and discarded
+/// (and replaced on the next request) if they crash or time out.
+pub struct ExifToolPool {
+    size: AtomicUsize,
+    resizing: Mutex<()>,
+    permits: Semaphore,
+    idle: Mutex<Vec<ExifToolSession>>,
+    config: Option<PathBuf>, // ExifTool config defining the FilmVault XMP namespace
+}
+
+impl ExifToolPool {
+    fn new(size: usize, config: Option<PathBuf>) -> Self {
+        let size = size.clamp(1, 8);
+        ExifToolPool {
+            size: AtomicUsize::new(size),
+            resizing: Mutex::new(()),
+            permits: Semaphore::new(size),
+            idle: Mutex::new(Vec::new()),
+            config,
+        }
+    }
+
+    /// Change the number of workers; shrinking waits for running requests to free their workers
+    pub async fn resize(&self, size: usize) -> Result<()> {
+        let size = size.clamp(1, 8);
+        let _resizing = self.resizing.lock().await;
+        let current = self.size.load(Ordering::Relaxed);
+
+        if size > current {
+            self.permits.add_permits(size - current);
+        } else if size < current {
+            self.permits.acquire_many((current - size) as u32).await?.forget();
+            self.idle.lock().await.truncate(size);
+        }
+        self.size.store(size, Ordering::Relaxed);
+
+        if size != current {
+            eprintln!("[EXIF] ExifTool pool resized from {} to {} workers", current, size);
+        }
+        Ok(())
+    }
+
+    /// Run one ExifTool request on an idle (or freshly started) worker
+    pub async fn run(&self, args: Vec<String>) -> Result<ExifToolOutput> {
+        let _permit = self.permits.acquire().await?;
+
+        // A crashed worker is replaced once before giving up
+        for attempt in 0..2 {
+            let idle = self.idle.lock().await.pop();
+            let mut session = match idle {
+                Some(session) => session,
+                None => ExifToolSession::spawn(self.config.as_deref())?,
+            };
+
+            match tokio::time::timeout(EXIFTOOL_TIMEOUT, session.execute(&args)).await {
+                Ok(Ok(output)) => {
+                    self.idle.lock().await.push(session);
+                    return Ok(output);
+                }
+                Ok(Err(e)) if attempt == 0 => {
+                    eprintln!("[EXIF] ExifTool worker failed, restarting: {}", e);
+                }
+                Ok(Err(e)) => return Err(e),
+                Err(_) => {
+                    eprintln!("[EXIF] ExifTool request timed out, discarding worker");
+                    return Err(anyhow::anyhow!(
+                        "ExifTool timed out after {} seconds",
+                        EXIFTOOL_TIMEOUT.as_secs()
+                    ));
+                }
+            }
+        }
+
+        Err(anyhow::anyhow!("ExifTool worker could not be restarted"))
+    }
+
+    pub fn size(&self) -> usize {
+        self.size.load(Ordering::Relaxed)
+    }
+}
+
+static EXIFTOOL_POOL: OnceLock<ExifToolPool> = OnceLock::new();
+
+/// Configure the ExifTool config file; only the first call takes effect
+///
+/// The pool starts with the default size and is resized whenever a library is opened.
+pub fn init_exiftool_pool(config: Option<PathBuf>) {
+    if EXIFTOOL_POOL.set(ExifToolPool::new(DEFAULT_EXIFTOOL_WORKERS, config)).is_ok() {
+        eprintln!("[EXIF] ExifTool pool configured with {} workers", exiftool_pool().size());
+    }
+}
+
+/// Get the shared ExifTool worker pool
+///
+/// Without `init_exiftool_pool` the workers have no config, so the FilmVault XMP fields cannot be written.
+pub fn exiftool_pool() -> &'static ExifToolPool {
+    EXIFTOOL_POOL.get_or_init(|| ExifToolPool::new(DEFAULT_EXIFTOOL_WORKERS, None))
+}
+
 /// Extract EXIF data from a file using ExifTool
-pub fn extract_exif(file_path: &str) -> Result<ExifData> {
+///
+/// When the file has an XMP sidecar its fields win over the embedded ones.
+pub async fn extract_exif(file_path: &str) -> Result<ExifData> {
     eprintln!("[EXIF] Extracting EXIF from: {}", file_path);
 
     // Check if file exists
@@ -222,46 +566,69 @@ pub fn extract_exif(file_path: &str) -> Result<ExifData> {
         return Ok(ExifData::default());
     }
 
-    // Call ExifTool with JSON output for structured data
-    let exiftool_path = get_exiftool_path();
-    let output = Command::new(&exiftool_path)
-        .arg("-j")                     // JSON output
-        .arg("-coordFormat")           // GPS coordinates format
-        .arg("%f")                     // Use decimal format for GPS
-        .arg(file_path)
-        .output()
+    let embedded = match read_exif_object(file_path).await? {
+        Some(exif_obj) => parse_exif_object(&exif_obj),
+        None => ExifData::default(),
+    };
+
+    let sidecar = sidecar_path(file_path);
+    let result = if sidecar.exists() {
+        eprintln!("[EXIF] Merging sidecar: {:?}", sidecar);
+        match read_exif_object(&sidecar.to_string_lossy()).await? {
+            Some(exif_obj) => parse_exif_object(&exif_obj).merged_over(embedded),
+            None => embedded,
+        }
+    } else {
+        embedded
+    };
+
+    eprintln!("[EXIF] Extracted data: Make={:?}, Model={:?}, ISO={:?}",
+        result.make, result.model, result.iso);
+
+    Ok(result)
+}
+
+/// Run ExifTool on one file and get its JSON object (None if it has no readable metadata)
+async fn read_exif_object(file_path: &str) -> Result<Option<serde_json::Value>> {
+    // Request JSON output for structured data
+    let output = exiftool_pool()
+        .run(vec![
+            "-j".to_string(),                 // JSON output
+            "-coordFormat".to_string(),       // GPS coordinates format
+            "%+f".to_string(),                // Use signed decimal format for GPS
+            file_path.to_string(),
+        ])
+        .await
         .map_err(|e| {
             eprintln!("[EXIF] Failed to execute exiftool: {}", e);
-            anyhow::anyhow!("Failed to execute exiftool: {}", e)
+            e
         })?;
 
-    if !output.status.success() {
-        let stderr = String::from_utf8_lossy(&output.stderr);
-        eprintln!("[EXIF] ExifTool returned error: {}", stderr);
-        return Ok(ExifData::default());
+    if let Err(e) = output.check_errors() {
+        eprintln!("[EXIF] ExifTool returned error: {}", e);
+        return Ok(None);
     }
 
-    let json_str = String::from_utf8_lossy(&output.stdout);
+    let json_str = &output.stdout;
     eprintln!("[EXIF] ExifTool output: {}", json_str);
 
     if json_str.trim().is_empty() {
         eprintln!("[EXIF] No EXIF data found (empty output)");
-        return Ok(ExifData::default());
+        return Ok(None);
     }
 
     // Parse JSON output
-    let exif_array: Vec<serde_json::Value> = serde_json::from_str(&json_str)
+    let exif_array: Vec<serde_json::Value> = serde_json::from_str(json_str)
         .map_err(|e| {
             eprintln!("[EXIF] Failed to parse EXIF JSON: {}", e);
             anyhow::anyhow!("Failed to parse EXIF JSON: {}", e)
         })?;
 
-    if exif_array.is_empty() {
-        return Ok(ExifData::default());
-    }
-
-    let exif_obj = &exif_array[0];
+    Ok(exif_array.into_iter().next())
+}
 
+/// Map ExifTool's JSON output for one file to ExifData
+fn parse_exif_object(exif_obj: &serde_json::Value) -> ExifData {
     // Extract fields from ExifTool output
     // Note: ExifTool uses different field names
     let make = exif_obj["Make"].as_str().map(String::from);
@@ -274,11 +641,11 @@ pub fn extract_exif(file_path: &str) -> Result<ExifData> {
         .or_else(|| exif_obj["CreateDate"].as_str())
         .map(String::from);
 
-    // Try to extract film stock from UserComment
-    let user_comment_raw = exif_obj["UserComment"].as_str().map(String::from);
-    let film_stock = user_comment_raw.as_ref().and_then(|s| {
-        s.split("Shot on ").nth(1).map(String::from)
-    });
+    // Film stock from the FilmVault XMP fields, or from an older "Shot on ..." UserComment
+    let film = FilmMetadata::from_exif_object(exif_obj);
+    let film_stock = film.as_ref()
+        .and_then(|film| film.film_stock.clone())
+        .or_else(|| exif_obj["UserComment"].as_str().and_then(legacy_film_stock));
 
     let iso = exif_obj["ISO"].as_i64()
         .or_else(|| exif_obj["ISOSpeedRatings"].as_i64())
@@ -305,6 +672,11 @@ pub fn extract_exif(file_path: &str) -> Result<ExifData> {
     let gps_longitude = exif_obj["GPSLongitude"].as_f64();
     let gps_altitude = exif_obj["GPSAltitude"].as_f64();
 
+    let gps_city = exif_obj["City"].as_str().map(String::from);
+    let gps_country = exif_obj["Country"].as_str()
+        .or_else(|| exif_obj["Country-PrimaryLocationName"].as_str())
+        .map(String::from);
+
     let rating = exif_obj["Rating"].as_i64().map(|v| v as i32);
 
     let user_comment = exif_obj["UserComment"].as_str().map(String::from);
@@ -312,7 +684,16 @@ pub fn extract_exif(file_path: &str) -> Result<ExifData> {
         .or_else(|| exif_obj["ImageDescription"].as_str())
         .map(String::from);
 
-    let result = ExifData {
+    // A single keyword comes back as a plain string
+    let keywords = match &exif_obj["Subject"] {
+        serde_json::Value::Array(items) => Some(items.iter()
+            .map(|item| item.as_str().map(String::from).unwrap_or_else(|| item.to_string()))
+            .collect()),
+        serde_json::Value::String(item) => Some(vec![item.clone()]),
+        _ => None,
+    };
+
+    ExifData {
         make,
         model,
         lens_model,
@@ -325,17 +706,38 @@ pub fn extract_exif(file_path: &str) -> Result<ExifData> {
         gps_latitude,
         gps_longitude,
         gps_altitude,
-        gps_city: None,
-        gps_country: None,
+        gps_city,
+        gps_country,
         rating,
         user_comment,
         description,
-    };
+        keywords,
+        film,
+    }
+}
 
-    eprintln!("[EXIF] Extracted data: Make={:?}, Model={:?}, ISO={:?}",
-        result.make, result.model, result.iso);
+/// Extract the largest embedded JPEG preview from a RAW/DNG file
+///
+/// Tries the full-size tags first and falls back to smaller previews.
+pub async fn extract_embedded_preview(file_path: &str) -> Result<Vec<u8>> {
+    eprintln!("[EXIF] Extracting embedded preview from: {}", file_path);
 
-    Ok(result)
+    if !Path::new(file_path).exists() {
+        return Err(anyhow::anyhow!("File not found: {}", file_path));
+    }
+
+    for tag in ["-JpgFromRaw", "-PreviewImage", "-OtherImage", "-ThumbnailImage"] {
+        let output = exiftool_pool()
+            .run(vec!["-b".to_string(), tag.to_string(), file_path.to_string()])
+            .await?;
+
+        if !output.data.is_empty() {
+            eprintln!("[EXIF] Found embedded preview via {} ({} bytes)", tag, output.data.len());
+            return Ok(output.data);
+        }
+    }
+
+    Err(anyhow::anyhow!("No embedded preview found in {}", file_path))
 }
 
 /// Parse camera string into Make and Model
@@ -351,162 +753,337 @@ pub fn parse_camera_string(camera: &str) -> (String, String) {
     }
 }
 
-/// Write roll-level EXIF to a single photo file
+/// Map a metering mode name to its EXIF MeteringMode code
+fn metering_mode_code(mode: &str) -> Option<u8> {
+    match mode.trim().to_lowercase().replace(['_', ' '], "-").as_str() {
+        "average" => Some(1),
+        "center-weighted" | "center-weighted-average" | "centre-weighted" => Some(2),
+        "spot" => Some(3),
+        "multi-spot" => Some(4),
+        "matrix" | "multi-segment" | "evaluative" | "pattern" => Some(5),
+        "partial" => Some(6),
+        _ => None,
+    }
+}
+
+/// Build ExifTool arguments for a shot log
 ///
-/// Simplified version - only writes: Make, Model, DateTimeOriginal, UserComment
-/// UserComment contains: film stock + location + user notes
-pub async fn write_photo_roll_exif(
-    file_path: &str,
-    make: &str,
-    model: &str,
-    date_time_original: &str,
-    user_comment: &str,
-) -> Result<()> {
-    eprintln!("[EXIF] Writing roll EXIF to: {}", file_path);
-    eprintln!("[EXIF]   Make: {}, Model: {}", make, model);
-    eprintln!("[EXIF]   Date: {}", date_time_original);
-    eprintln!("[EXIF]   UserComment: {}", user_comment);
+/// Writes FNumber, ExposureTime, FocalLength, ExposureCompensation, MeteringMode,
+/// Flash, the frame number (ImageNumber) and capture time. Unset fields are left untouched.
+pub fn shot_log_exif_args(log: &ShotLog) -> Vec<String> {
+    let mut args = vec![];
+
+    if let Some(aperture) = log.aperture {
+        args.push(format!("-FNumber={}", aperture));
+        args.push(format!("-ApertureValue={}", aperture));
+    }
 
-    if !Path::new(file_path).exists() {
-        eprintln!("[EXIF] ERROR: File not found: {}", file_path);
-        return Err(anyhow::anyhow!("File not found: {}", file_path));
+    if let Some(ref shutter_speed) = log.shutter_speed {
+        if !shutter_speed.is_empty() {
+            args.push(format!("-ExposureTime={}", shutter_speed));
+            args.push(format!("-ShutterSpeedValue={}", shutter_speed));
+        }
     }
 
-    let exiftool_path = get_exiftool_path();
-    let mut cmd = Command::new(&exiftool_path);
+    if let Some(focal_length) = log.focal_length {
+        args.push(format!("-FocalLength={}", focal_length));
+    }
 
-    // Set environment variables for ExifTool/Perl
-    if let Some(files_dir) = get_exiftool_files_dir() {
-        // Add exiftool_files to PATH so perl532.dll can be found
-        eprintln!("[EXIF] Adding to PATH: {}", files_dir.display());
-        cmd.env("PATH", format!("{};{}", files_dir.display(), std::env::var("PATH").unwrap_or_default()));
+    if let Some(ev) = log.exposure_compensation {
+        args.push(format!("-ExposureCompensation={}", ev));
+    }
 
-        // Set PERL5LIB to point to the lib directory
-        let lib_dir = files_dir.join("lib");
-        eprintln!("[EXIF] Setting PERL5LIB to: {}", lib_dir.display());
-        cmd.env("PERL5LIB", &lib_dir);
+    if let Some(code) = log.metering_mode.as_deref().and_then(metering_mode_code) {
+        args.push(format!("-MeteringMode#={}", code));
     }
 
-    // Overwrite original (don't create backup)
-    cmd.arg("-overwrite_original");
+    if let Some(flash) = log.flash {
+        // 0x01 = Fired, 0x10 = Off, Did not fire
+        args.push(format!("-Flash#={}", if flash { 1 } else { 16 }));
+    }
+
+    if let Some(frame_number) = log.frame_number {
+        args.push(format!("-ImageNumber={}", frame_number));
+    }
+
+    // Exact capture time overrides the roll's shoot date
+    if let Some(ref shot_at) = log.shot_at {
+        if !shot_at.is_empty() {
+            let exif_time = shot_at.replacen('-', ":", 2);
+            args.push(format!("-DateTimeOriginal={}", exif_time));
+            args.push(format!("-CreateDate={}", exif_time));
+        }
+    }
+
+    args
+}
+
+/// Write a shot log to a single photo file (or its sidecar)
+pub async fn write_shot_log_exif(file_path: &str, log: &ShotLog, mode: MetadataWriteMode) -> Result<()> {
+    eprintln!("[EXIF] Writing shot log to: {}", file_path);
+
+    let target = write_target(file_path, mode)?;
+
+    let shot_args = shot_log_exif_args(log);
+    if shot_args.is_empty() {
+        eprintln!("[EXIF] Shot log is empty, skipping");
+        return Ok(());
+    }
+
+    let mut args = vec!["-overwrite_original".to_string()];
+    args.extend(shot_args);
+    args.push(target);
+
+    exiftool_pool().run(args).await?.check_errors()?;
+
+    eprintln!("[EXIF] Successfully wrote shot log");
+    Ok(())
+}
+
+/// Metadata a photo gets from its roll and its own fields
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct PhotoMetadata {
+    pub make: String,
+    pub model: String,
+    pub date_time_original: String,
+    pub user_comment: String,
+    pub lens: Option<String>,
+    pub rating: Option<i32>,
+    pub gps: Option<(f64, f64)>, // (lat, lon)
+    pub city: Option<String>,
+    pub country: Option<String>,
+    pub description: Option<String>,
+    pub keywords: Vec<String>,
+    pub film: Option<FilmMetadata>,
+}
+
+/// Lens maker from a lens name, if it starts with a brand ("Canon FD 50mm f/1.4" -> "Canon")
+pub fn parse_lens_make(lens: &str) -> Option<String> {
+    let first = lens.split_whitespace().next()?;
+    let is_brand = lens.split_whitespace().count() > 1
+        && first.chars().next().is_some_and(char::is_alphabetic)
+        && !first.chars().any(|c| c.is_ascii_digit());
+    is_brand.then(|| first.to_string())
+}
+
+/// Build ExifTool arguments for a photo's metadata; empty fields are left untouched
+pub fn photo_metadata_args(metadata: &PhotoMetadata) -> Vec<String> {
+    let mut args = vec![];
 
     // Write Make (camera manufacturer)
-    if !make.is_empty() {
-        cmd.arg(format!("-Make={}", make));
+    if !metadata.make.is_empty() {
+        args.push(format!("-Make={}", metadata.make));
     }
 
     // Write Model (camera model)
-    if !model.is_empty() {
-        cmd.arg(format!("-Model={}", model));
+    if !metadata.model.is_empty() {
+        args.push(format!("-Model={}", metadata.model));
     }
 
     // Write DateTimeOriginal and CreateDate
-    if !date_time_original.is_empty() {
-        cmd.arg(format!("-DateTimeOriginal={}", date_time_original));
-        cmd.arg(format!("-CreateDate={}", date_time_original));
+    if !metadata.date_time_original.is_empty() {
+        args.push(format!("-DateTimeOriginal={}", metadata.date_time_original));
+        args.push(format!("-CreateDate={}", metadata.date_time_original));
     }
 
     // Write UserComment (contains film stock + location + notes)
-    if !user_comment.is_empty() {
-        cmd.arg(format!("-UserComment={}", user_comment));
+    if !metadata.user_comment.is_empty() {
+        args.push(format!("-UserComment={}", metadata.user_comment));
+    }
+
+    // Write the lens; the maker only when the name starts with one
+    if let Some(lens) = metadata.lens.as_deref().filter(|lens| !lens.is_empty()) {
+        args.push(format!("-LensModel={}", lens));
+        if let Some(lens_make) = parse_lens_make(lens) {
+            args.push(format!("-LensMake={}", lens_make));
+        }
+    }
+
+    if let Some(rating) = metadata.rating {
+        args.push(format!("-XMP:Rating={}", rating));
+    }
+
+    if let Some(description) = metadata.description.as_deref().filter(|description| !description.is_empty()) {
+        args.push(format!("-ImageDescription={}", description));
+        args.push(format!("-XMP-dc:Description={}", description));
     }
 
-    cmd.arg(file_path);
+    // Signed values; ExifTool derives the N/S and E/W references from the sign
+    if let Some((lat, lon)) = metadata.gps {
+        args.push(format!("-GPSLatitude={}", lat));
+        args.push(format!("-GPSLatitudeRef={}", lat));
+        args.push(format!("-GPSLongitude={}", lon));
+        args.push(format!("-GPSLongitudeRef={}", lon));
+    }
+
+    // Location names for tools that show places rather than coordinates
+    let city = metadata.city.as_deref().filter(|city| !city.is_empty());
+    let country = metadata.country.as_deref().filter(|country| !country.is_empty());
+    if city.is_some() || country.is_some() {
+        args.push("-IPTC:CodedCharacterSet=UTF8".to_string());
+    }
+    if let Some(city) = city {
+        args.push(format!("-XMP-photoshop:City={}", city));
+        args.push(format!("-IPTC:City={}", city));
+    }
+    if let Some(country) = country {
+        args.push(format!("-XMP-photoshop:Country={}", country));
+        args.push(format!("-IPTC:Country-PrimaryLocationName={}", country));
+    }
+
+    // Assigning each keyword in one call replaces the whole list
+    for keyword in metadata.keywords.iter().filter(|keyword| !keyword.is_empty()) {
+        args.push(format!("-XMP-dc:Subject={}", keyword));
+    }
+
+    if let Some(film) = &metadata.film {
+        args.extend(film.exiftool_args());
+    }
+
+    args
+}
+
+/// Write roll-level metadata to a single photo file (or its sidecar)
+///
+/// Tag assignments of a roll write: the photo's metadata plus its per-frame shooting settings
+fn roll_exif_args(metadata: &PhotoMetadata, shot_log: Option<&ShotLog>) -> Vec<String> {
+    let mut args = photo_metadata_args(metadata);
+    if let Some(log) = shot_log {
+        args.extend(shot_log_exif_args(log));
+    }
+    args
+}
+
+/// Hash of what a roll write would put in a photo, stored in `photos.exif_data_hash`
+///
+/// The tag assignments are already in a fixed order, so hashing them (and the
+/// write mode, since switching modes moves the data) gives a canonical value.
+/// A photo whose stored hash matches does not need to be written again.
+pub fn roll_exif_hash(metadata: &PhotoMetadata, shot_log: Option<&ShotLog>, mode: MetadataWriteMode) -> String {
+    let mut hasher = blake3::Hasher::new();
+    hasher.update(mode.as_str().as_bytes());
+    for arg in roll_exif_args(metadata, shot_log) {
+        // Length-prefixed so neighbouring values cannot run into each other
+        hasher.update(&(arg.len() as u64).to_le_bytes());
+        hasher.update(arg.as_bytes());
+    }
+    hasher.finalize().to_hex().to_string()
+}
+
+/// Writes camera, lens, date, rating, GPS, location names, description,
+/// keywords and the photo's shot log.
+/// UserComment contains: film stock + location + user notes
+pub async fn write_photo_roll_exif(
+    file_path: &str,
+    metadata: &PhotoMetadata,
+    shot_log: Option<&ShotLog>,
+    mode: MetadataWriteMode,
+) -> Result<()> {
+    eprintln!("[EXIF] Writing roll EXIF to: {} ({})", file_path, mode.as_str());
+    eprintln!("[EXIF]   Make: {}, Model: {}", metadata.make, metadata.model);
+    eprintln!("[EXIF]   Date: {}", metadata.date_time_original);
+    eprintln!("[EXIF]   UserComment: {}", metadata.user_comment);
+
+    let target = write_target(file_path, mode)?;
+
+    // Overwrite original (don't create backup)
+    let mut args = vec!["-overwrite_original".to_string()];
+    args.extend(roll_exif_args(metadata, shot_log));
+    args.push(target);
 
     // Debug: Print the command
-    eprintln!("[EXIF] Command: exiftool {:?}", cmd.get_args().collect::<Vec<_>>());
+    eprintln!("[EXIF] Command: exiftool {:?}", args);
 
-    let output = cmd.output()
+    let output = exiftool_pool().run(args).await
         .map_err(|e| {
             eprintln!("[EXIF] ERROR: Failed to execute exiftool: {}", e);
-            anyhow::anyhow!("Failed to execute exiftool: {}", e)
+            e
         })?;
 
-    if !output.status.success() {
-        let error = String::from_utf8_lossy(&output.stderr);
-        let stdout = String::from_utf8_lossy(&output.stdout);
-        eprintln!("[EXIF] ERROR: ExifTool returned error code: {:?}", output.status.code());
-        eprintln!("[EXIF]   stderr: {}", error);
-        eprintln!("[EXIF]   stdout: {}", stdout);
-        return Err(anyhow::anyhow!("ExifTool error: {}", error));
+    if let Err(e) = output.check_errors() {
+        eprintln!("[EXIF] ERROR: {}", e);
+        eprintln!("[EXIF]   stdout: {}", output.stdout);
+        return Err(e);
     }
 
     eprintln!("[EXIF] Successfully wrote roll EXIF");
     Ok(())
 }
 
-/// Write photo-level EXIF to a single photo file
+/// Write the given tag assignments to a single photo file (or its sidecar)
+pub async fn write_exif_args(file_path: &str, tag_args: Vec<String>, mode: MetadataWriteMode) -> Result<()> {
+    if tag_args.is_empty() {
+        return Ok(());
+    }
+
+    let target = write_target(file_path, mode)?;
+
+    let mut args = vec!["-overwrite_original".to_string()];
+    args.extend(tag_args);
+    args.push(target);
+
+    exiftool_pool().run(args).await?.check_errors()?;
+    Ok(())
+}
+
+/// Write photo-level EXIF to a single photo file (or its sidecar)
 ///
 /// Simplified version - only writes UserComment (notes)
 pub async fn write_photo_exif(
     file_path: &str,
     user_comment: Option<&str>,
+    mode: MetadataWriteMode,
 ) -> Result<()> {
     eprintln!("[EXIF] Writing photo EXIF to: {}", file_path);
 
-    if !Path::new(file_path).exists() {
-        return Err(anyhow::anyhow!("File not found: {}", file_path));
-    }
+    let target = write_target(file_path, mode)?;
 
     // If no comment to write, skip
-    if user_comment.is_none() {
-        eprintln!("[EXIF] No user comment to write, skipping");
-        return Ok(());
-    }
-
-    let exiftool_path = get_exiftool_path();
-    let mut cmd = Command::new(&exiftool_path);
-    cmd.arg("-overwrite_original");
-
-    // Set environment variables for ExifTool/Perl
-    if let Some(files_dir) = get_exiftool_files_dir() {
-        eprintln!("[EXIF] Adding to PATH: {}", files_dir.display());
-        cmd.env("PATH", format!("{};{}", files_dir.display(), std::env::var("PATH").unwrap_or_default()));
-
-        let lib_dir = files_dir.join("lib");
-        eprintln!("[EXIF] Setting PERL5LIB to: {}", lib_dir.display());
-        cmd.env("PERL5LIB", &lib_dir);
-    }
+    let comment = match user_comment {
+        Some(comment) => comment,
+        None => {
+            eprintln!("[EXIF] No user comment to write, skipping");
+            return Ok(());
+        }
+    };
 
     // Write UserComment only
-    if let Some(comment) = user_comment {
-        cmd.arg(format!("-UserComment={}", comment));
-    }
-
-    cmd.arg(file_path);
+    let args = vec![
+        "-overwrite_original".to_string(),
+        format!("-UserComment={}", comment),
+        target,
+    ];
 
-    let output = cmd.output()
-        .map_err(|e| anyhow::anyhow!("Failed to execute exiftool: {}", e))?;
-
-    if !output.status.success() {
-        let error = String::from_utf8_lossy(&output.stderr);
-        return Err(anyhow::anyhow!("ExifTool error: {}", error));
-    }
+    exiftool_pool().run(args).await?.check_errors()?;
 
     eprintln!("[EXIF] Successfully wrote photo EXIF");
     Ok(())
 }
 
-/// Clear all EXIF data from a photo file
-pub async fn clear_photo_exif(file_path: &str) -> Result<()> {
-    eprintln!("[EXIF] Clearing EXIF from: {}", file_path);
+/// Clear written metadata from a photo
+///
+/// Embedded mode strips all metadata from the file; sidecar mode only
+/// removes the sidecar and leaves the original alone.
+pub async fn clear_photo_exif(file_path: &str, mode: MetadataWriteMode) -> Result<()> {
+    eprintln!("[EXIF] Clearing EXIF from: {} ({})", file_path, mode.as_str());
 
-    if !Path::new(file_path).exists() {
-        return Err(anyhow::anyhow!("File not found: {}", file_path));
+    let target = write_target(file_path, mode)?;
+
+    if mode == MetadataWriteMode::Sidecar {
+        if Path::new(&target).exists() {
+            std::fs::remove_file(&target)?;
+        }
+        eprintln!("[EXIF] Successfully removed sidecar");
+        return Ok(());
     }
 
-    let exiftool_path = get_exiftool_path();
-    let output = Command::new(&exiftool_path)
-        .arg("-overwrite_original")
-        .arg("-all=")
-        .arg(file_path)
-        .output()
-        .map_err(|e| anyhow::anyhow!("Failed to execute exiftool: {}", e))?;
+    let args = vec![
+        "-overwrite_original".to_string(),
+        "-all=".to_string(),
+        target,
+    ];
 
-    if !output.status.success() {
-        let error = String::from_utf8_lossy(&output.stderr);
-        return Err(anyhow::anyhow!("ExifTool error: {}", error));
-    }
+    exiftool_pool().run(args).await?.check_errors()?;
 
     eprintln!("[EXIF] Successfully cleared EXIF");
     Ok(())
@@ -533,6 +1110,35 @@ impl Default for ExifData {
             rating: None,
             user_comment: None,
             description: None,
+            keywords: None,
+            film: None,
+        }
+    }
+}
+
+impl ExifData {
+    /// Fields set in `self` win over those in `base` (sidecar over embedded)
+    pub fn merged_over(self, base: ExifData) -> ExifData {
+        ExifData {
+            make: self.make.or(base.make),
+            model: self.model.or(base.model),
+            lens_model: self.lens_model.or(base.lens_model),
+            date_time_original: self.date_time_original.or(base.date_time_original),
+            film_stock: self.film_stock.or(base.film_stock),
+            iso: self.iso.or(base.iso),
+            aperture: self.aperture.or(base.aperture),
+            shutter_speed: self.shutter_speed.or(base.shutter_speed),
+            focal_length: self.focal_length.or(base.focal_length),
+            gps_latitude: self.gps_latitude.or(base.gps_latitude),
+            gps_longitude: self.gps_longitude.or(base.gps_longitude),
+            gps_altitude: self.gps_altitude.or(base.gps_altitude),
+            gps_city: self.gps_city.or(base.gps_city),
+            gps_country: self.gps_country.or(base.gps_country),
+            rating: self.rating.or(base.rating),
+            user_comment: self.user_comment.or(base.user_comment),
+            description: self.description.or(base.description),
+            keywords: self.keywords.or(base.keywords),
+            film: self.film.or(base.film),
         }
     }
 }
@@ -557,6 +1163,176 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_build_request() {
+        let args = vec!["-j".to_string(), "line one\nline two".to_string()];
+        assert_eq!(
+            build_request(&args, 7),
+            "-charset\nfilename=utf8\n-j\nline one line two\n-echo4\n{ready7}\n-execute7\n"
+        );
+
+        // A multi-line value is escaped, not flattened; so are backslashes, as -ec unescapes them
+        let args = vec!["-ImageDescription=Harbour\nat dusk \\ f/8".to_string(), "/photos/a.jpg".to_string()];
+        assert_eq!(
+            build_request(&args, 8),
+            "-charset\nfilename=utf8\n-ec\n-ImageDescription=Harbour\\nat dusk \\\\ f/8\n/photos/a.jpg\n-echo4\n{ready8}\n-execute8\n"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_read_until_ready() {
+        let text: &[u8] = b"[{\"Make\": \"Canon\"}]\n{ready3}\n";
+        let mut reader = BufReader::new(text);
+        assert_eq!(read_until_ready(&mut reader, 3).await.unwrap(), b"[{\"Make\": \"Canon\"}]\n");
+
+        // Binary output is followed directly by the sentinel
+        let binary: &[u8] = b"\xff\xd8\n\x00\xff\xd9{ready4}\r\n";
+        let mut reader = BufReader::new(binary);
+        assert_eq!(read_until_ready(&mut reader, 4).await.unwrap(), b"\xff\xd8\n\x00\xff\xd9");
+    }
+
+    #[test]
+    fn test_check_errors() {
+        let ok = ExifToolOutput {
+            stdout: "    1 image files updated\n".to_string(),
+            stderr: "Warning: [minor] Fixed incorrect URI for xmlns:MicrosoftPhoto\n".to_string(),
+            data: Vec::new(),
+        };
+        assert!(ok.check_errors().is_ok());
+
+        let failed = ExifToolOutput {
+            stdout: "    1 files weren't updated due to errors\n".to_string(),
+            stderr: "Error: File not found - missing.jpg\n".to_string(),
+            data: Vec::new(),
+        };
+        assert!(failed.check_errors().is_err());
+    }
+
+    #[test]
+    fn test_shot_log_exif_args() {
+        let log = ShotLog {
+            photo_id: 1,
+            frame_number: Some(12),
+            aperture: Some(2.8),
+            shutter_speed: Some("1/125".to_string()),
+            focal_length: Some(50.0),
+            exposure_compensation: Some(-0.7),
+            metering_mode: Some("Center weighted".to_string()),
+            flash: Some(false),
+            notes: Some("backlit".to_string()),
+            shot_at: Some("2024-05-01 14:32:10".to_string()),
+        };
+        assert_eq!(
+            shot_log_exif_args(&log),
+            vec![
+                "-FNumber=2.8",
+                "-ApertureValue=2.8",
+                "-ExposureTime=1/125",
+                "-ShutterSpeedValue=1/125",
+                "-FocalLength=50",
+                "-ExposureCompensation=-0.7",
+                "-MeteringMode#=2",
+                "-Flash#=16",
+                "-ImageNumber=12",
+                "-DateTimeOriginal=2024:05:01 14:32:10",
+                "-CreateDate=2024:05:01 14:32:10",
+            ]
+        );
+
+        assert!(shot_log_exif_args(&ShotLog::default()).is_empty());
+    }
+
+    #[test]
+    fn test_photo_metadata_args() {
+        let metadata = PhotoMetadata {
+            make: "Nikon".to_string(),
+            model: "FM2".to_string(),
+            date_time_original: "2024:05:01 12:00:00".to_string(),
+            user_comment: "Shot on Kodak Portra 400".to_string(),
+            lens: Some("Nikkor 50mm f/1.4".to_string()),
+            rating: Some(4),
+            gps: Some((-33.8568, 151.2153)),
+            city: Some("Sydney".to_string()),
+            country: Some("Australia".to_string()),
+            description: Some("Opera House at dusk".to_string()),
+            keywords: vec!["Kodak Portra 400".to_string(), String::new()],
+            film: None,
+        };
+        assert_eq!(
+            photo_metadata_args(&metadata),
+            vec![
+                "-Make=Nikon",
+                "-Model=FM2",
+                "-DateTimeOriginal=2024:05:01 12:00:00",
+                "-CreateDate=2024:05:01 12:00:00",
+                "-UserComment=Shot on Kodak Portra 400",
+                "-LensModel=Nikkor 50mm f/1.4",
+                "-LensMake=Nikkor",
+                "-XMP:Rating=4",
+                "-ImageDescription=Opera House at dusk",
+                "-XMP-dc:Description=Opera House at dusk",
+                "-GPSLatitude=-33.8568",
+                "-GPSLatitudeRef=-33.8568",
+                "-GPSLongitude=151.2153",
+                "-GPSLongitudeRef=151.2153",
+                "-IPTC:CodedCharacterSet=UTF8",
+                "-XMP-photoshop:City=Sydney",
+                "-IPTC:City=Sydney",
+                "-XMP-photoshop:Country=Australia",
+                "-IPTC:Country-PrimaryLocationName=Australia",
+                "-XMP-dc:Subject=Kodak Portra 400",
+            ]
+        );
+
+        assert_eq!(parse_lens_make("Canon FD 50mm f/1.4"), Some("Canon".to_string()));
+        assert_eq!(parse_lens_make("50mm f/1.8"), None);
+        assert_eq!(parse_lens_make("Summicron"), None);
+
+        assert!(photo_metadata_args(&PhotoMetadata::default()).is_empty());
+    }
+
+    #[test]
+    fn test_roll_exif_hash() {
+        let metadata = PhotoMetadata {
+            make: "Nikon".to_string(),
+            model: "FM2".to_string(),
+            rating: Some(3),
+            ..Default::default()
+        };
+        let hash = roll_exif_hash(&metadata, None, MetadataWriteMode::Embedded);
+        assert_eq!(hash, roll_exif_hash(&metadata.clone(), None, MetadataWriteMode::Embedded));
+
+        // Any change to what would be written changes the hash
+        let rated = PhotoMetadata { rating: Some(4), ..metadata.clone() };
+        assert_ne!(hash, roll_exif_hash(&rated, None, MetadataWriteMode::Embedded));
+        assert_ne!(hash, roll_exif_hash(&metadata, None, MetadataWriteMode::Sidecar));
+        let log = ShotLog { aperture: Some(2.8), ..Default::default() };
+        assert_ne!(hash, roll_exif_hash(&metadata, Some(&log), MetadataWriteMode::Embedded));
+        // An empty shot log writes nothing
+        assert_eq!(hash, roll_exif_hash(&metadata, Some(&ShotLog::default()), MetadataWriteMode::Embedded));
+    }
+
+    #[test]
+    fn test_sidecar_merged_over_embedded() {
+        assert_eq!(sidecar_path("/film/2024/00000001/originals/001.jpg"), PathBuf::from("/film/2024/00000001/originals/001.xmp"));
+
+        let embedded = ExifData {
+            make: Some("Epson".to_string()),
+            model: Some("Perfection V600".to_string()),
+            rating: Some(1),
+            ..ExifData::default()
+        };
+        let sidecar = ExifData {
+            make: Some("Nikon".to_string()),
+            rating: Some(4),
+            ..ExifData::default()
+        };
+        let merged = sidecar.merged_over(embedded);
+        assert_eq!(merged.make.as_deref(), Some("Nikon"));
+        assert_eq!(merged.model.as_deref(), Some("Perfection V600"));
+        assert_eq!(merged.rating, Some(4));
+    }
+
     #[test]
     fn test_exif_data_default() {
         let exif = ExifData::default();
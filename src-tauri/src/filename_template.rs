@@ -0,0 +1,254 @@
+/*!
+ * FilmVault Filename Templates
+ *
+ * Library filenames of originals (and their derivatives) are rendered from a
+ * template such as `{date}_{film}_{frame}`. Token values are sanitized so the
+ * result is always a single valid filename stem.
+ */
+
+use std::collections::HashSet;
+use std::path::{Path, PathBuf};
+use anyhow::{bail, Result};
+
+use crate::database::Roll;
+
+/// Reproduces the original `ROLL_A3F80001_001` naming
+pub const DEFAULT_FILENAME_TEMPLATE: &str = "ROLL_{roll_id}_{frame}";
+
+/// Tokens a template may use
+pub const TEMPLATE_TOKENS: &[&str] = &["date", "film", "camera", "roll_id", "roll_name", "frame", "original_name"];
+
+/// Replace characters that are invalid or awkward in filenames
+pub fn sanitize_filename(name: &str) -> String {
+    name.chars()
+        .map(|c| match c {
+            ' ' | '/' | '\\' | ':' | '*' | '?' | '"' | '<' | '>' | '|' => '_',
+            _ => c
+        })
+        .collect()
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Part {
+    Literal(String),
+    Token(String),
+}
+
+fn parse_template(template: &str) -> Result<Vec<Part>> {
+    let mut parts = Vec::new();
+    let mut rest = template;
+
+    while let Some(open) = rest.find(['{', '}']) {
+        if rest[open..].starts_with('}') {
+            bail!("Unmatched '}}' in filename template");
+        }
+        if open > 0 {
+            parts.push(Part::Literal(rest[..open].to_string()));
+        }
+        let Some(close) = rest[open..].find('}') else {
+            bail!("Unclosed '{{' in filename template");
+        };
+        let token = &rest[open + 1..open + close];
+        if !TEMPLATE_TOKENS.contains(&token) {
+            bail!("Unknown token {{{}}} in filename template", token);
+        }
+        parts.push(Part::Token(token.to_string()));
+        rest = &rest[open + close + 1..];
+    }
+    if !rest.is_empty() {
+        parts.push(Part::Literal(rest.to_string()));
+    }
+
+    if !parts.iter().any(|part| matches!(part, Part::Token(t) if t == "frame" || t == "original_name")) {
+        bail!("Filename template must contain {{frame}} or {{original_name}}");
+    }
+
+    Ok(parts)
+}
+
+/// Check a template before saving it
+pub fn validate_template(template: &str) -> Result<()> {
+    parse_template(template).map(|_| ())
+}
+
+/// Renders library filename stems for one roll
+///
+/// Stems are unique per namer: a repeated stem (e.g. two scans with the same
+/// `{original_name}` in different folders) gets a `_2`, `_3`, ... suffix.
+#[derive(Debug, Clone)]
+pub struct FileNamer {
+    parts: Vec<Part>,
+    roll_id: i64,
+    roll_name: String,
+    film: String,
+    camera: String,
+    date: String,
+    used: HashSet<String>,
+}
+
+impl FileNamer {
+    pub fn new(template: &str, roll: &Roll) -> Result<Self> {
+        Ok(Self {
+            parts: parse_template(template)?,
+            roll_id: roll.id,
+            roll_name: roll.name.clone(),
+            film: roll.film_stock.clone(),
+            camera: roll.camera.clone(),
+            date: roll.shoot_date.clone(),
+            used: HashSet::new(),
+        })
+    }
+
+    /// Treat existing stems as taken (files already in the roll)
+    pub fn reserve<I, S>(&mut self, stems: I)
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        self.used.extend(stems.into_iter().map(|s| s.into().to_lowercase()));
+    }
+
+    /// Treat the stems of every file in a roll's folders as taken, tracked or not
+    pub fn reserve_roll_files(&mut self, roll_dir: &Path) {
+        self.reserve_roll_files_except(roll_dir, &HashSet::new());
+    }
+
+    /// Like `reserve_roll_files`, but leave out `skip` (e.g. the files a rename is about to move)
+    pub fn reserve_roll_files_except(&mut self, roll_dir: &Path, skip: &HashSet<PathBuf>) {
+        for dir in ["originals", "thumbnails", "previews"] {
+            if let Ok(entries) = std::fs::read_dir(roll_dir.join(dir)) {
+                let stems = entries.flatten()
+                    .map(|entry| entry.path())
+                    .filter(|path| !skip.contains(path))
+                    .filter_map(|path| path.file_stem().map(|stem| stem.to_string_lossy().to_string()));
+                self.reserve(stems);
+            }
+        }
+    }
+
+    /// Stem for frame number `frame` (1-based) of a file originally called `original_name`
+    pub fn stem(&mut self, frame: usize, original_name: &str) -> String {
+        let original_stem = Path::new(original_name)
+            .file_stem()
+            .map(|s| s.to_string_lossy().to_string())
+            .unwrap_or_default();
+
+        let rendered: String = self.parts
+            .iter()
+            .map(|part| match part {
+                Part::Literal(text) => sanitize_filename(text),
+                Part::Token(token) => sanitize_filename(&match token.as_str() {
+                    "date" => self.date.clone(),
+                    "film" => self.film.clone(),
+                    "camera" => self.camera.clone(),
+                    "roll_id" => format!("{:08X}", self.roll_id),
+                    "roll_name" => self.roll_name.clone(),
+                    "frame" => format!("{:03}", frame),
+                    _ => original_stem.clone(),
+                }),
+            })
+            .collect();
+        let base = match rendered.trim_matches(['.', '_', ' ']) {
+            "" => format!("{:03}", frame),
+            trimmed => trimmed.to_string(),
+        };
+
+        // Filesystems may be case-insensitive, so compare lowercased
+        let mut stem = base.clone();
+        let mut n = 2;
+        while !self.used.insert(stem.to_lowercase()) {
+            stem = format!("{}_{}", base, n);
+            n += 1;
+        }
+        stem
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn roll() -> Roll {
+        Roll {
+            id: 0xA3F8_0001,
+            name: "Tokyo Trip".to_string(),
+            path: String::new(),
+            film_stock: "Portra 400".to_string(),
+            camera: "Nikon F3".to_string(),
+            lens: None,
+            shoot_date: "2024-03-15".to_string(),
+            lab_info: None,
+            notes: None,
+            city: None,
+            country: None,
+            lat: None,
+            lon: None,
+            film_iso: None,
+            film_format: None,
+            film_process: None,
+            push_pull: None,
+            scanner: None,
+            created_at: String::new(),
+            updated_at: String::new(),
+        }
+    }
+
+    #[test]
+    fn test_sanitize_filename() {
+        assert_eq!(sanitize_filename("Test Roll"), "Test_Roll");
+        assert_eq!(sanitize_filename("Roll/2024"), "Roll_2024");
+        assert_eq!(sanitize_filename("Roll:Test"), "Roll_Test");
+        assert_eq!(sanitize_filename("Kodak Portra 400"), "Kodak_Portra_400");
+        assert_eq!(sanitize_filename("Camera/Model"), "Camera_Model");
+        assert_eq!(sanitize_filename("Test:Name"), "Test_Name");
+    }
+
+    #[test]
+    fn test_default_template_matches_legacy_names() {
+        let mut namer = FileNamer::new(DEFAULT_FILENAME_TEMPLATE, &roll()).unwrap();
+        assert_eq!(namer.stem(1, "scan.tif"), "ROLL_A3F80001_001");
+        assert_eq!(namer.stem(12, "scan.tif"), "ROLL_A3F80001_012");
+    }
+
+    #[test]
+    fn test_template_tokens() {
+        let mut namer = FileNamer::new("{date}_{film}_{camera}_{frame}", &roll()).unwrap();
+        assert_eq!(namer.stem(7, "x.jpg"), "2024-03-15_Portra_400_Nikon_F3_007");
+
+        let mut namer = FileNamer::new("{roll_name}/{original_name}", &roll()).unwrap();
+        assert_eq!(namer.stem(1, "DSC_0001.NEF"), "Tokyo_Trip_DSC_0001");
+        assert_eq!(namer.stem(2, "DSC_0001.jpg"), "Tokyo_Trip_DSC_0001_2");
+
+        let mut namer = FileNamer::new("{original_name}", &roll()).unwrap();
+        namer.reserve(["IMG_1"]);
+        assert_eq!(namer.stem(1, "img_1.jpg"), "img_1_2");
+    }
+
+    #[test]
+    fn test_untracked_files_are_reserved() {
+        let roll_dir = std::env::temp_dir().join(format!("filmvault_namer_test_{}", std::process::id()));
+        std::fs::create_dir_all(roll_dir.join("originals")).unwrap();
+        std::fs::write(roll_dir.join("originals").join("scan_01.tif"), b"untracked").unwrap();
+
+        let mut namer = FileNamer::new("{original_name}", &roll()).unwrap();
+        namer.reserve_roll_files(&roll_dir);
+        assert_eq!(namer.stem(1, "scan_01.jpg"), "scan_01_2");
+
+        // A file that is about to be renamed does not block its own name
+        let skip = HashSet::from([roll_dir.join("originals").join("scan_01.tif")]);
+        let mut namer = FileNamer::new("{original_name}", &roll()).unwrap();
+        namer.reserve_roll_files_except(&roll_dir, &skip);
+        assert_eq!(namer.stem(1, "scan_01.jpg"), "scan_01");
+
+        std::fs::remove_dir_all(&roll_dir).unwrap();
+    }
+
+    #[test]
+    fn test_invalid_templates() {
+        assert!(validate_template("{date}_{frame}").is_ok());
+        assert!(validate_template("{date}").is_err());
+        assert!(validate_template("{frame}_{lens}").is_err());
+        assert!(validate_template("{frame").is_err());
+        assert!(validate_template("frame}").is_err());
+    }
+}
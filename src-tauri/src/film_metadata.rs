@@ -0,0 +1,198 @@
+/*!
+ * FilmVault Film Metadata
+ *
+ * Film details of a roll (stock, box speed, format, process, push/pull, lab,
+ * scanner, roll name) are written as discrete properties in a FilmVault XMP
+ * namespace, next to the human-readable UserComment. ExifTool learns the
+ * namespace from a config file in the app data dir passed to every worker, and reading the
+ * properties back gives exactly what was written.
+ */
+
+use std::fs;
+use std::path::{Path, PathBuf};
+use serde::{Deserialize, Serialize};
+use anyhow::{Context, Result};
+
+use crate::database::Roll;
+
+/// ExifTool config defining the `XMP-filmvault` group
+const EXIFTOOL_CONFIG: &str = r#"
+%Image::ExifTool::UserDefined = (
+    'Image::ExifTool::XMP::Main' => {
+        filmvault => {
+            SubDirectory => { TagTable => 'Image::ExifTool::UserDefined::filmvault' },
+        },
+    },
+);
+
+%Image::ExifTool::UserDefined::filmvault = (
+    GROUPS    => { 0 => 'XMP', 1 => 'XMP-filmvault', 2 => 'Image' },
+    NAMESPACE => { 'filmvault' => 'http://ns.filmvault.app/film/1.0/' },
+    WRITABLE  => 'string',
+    FilmStock    => { },
+    FilmSpeed    => { Writable => 'integer' },
+    FilmFormat   => { },
+    FilmProcess  => { },
+    FilmPushPull => { Writable => 'real' },
+    FilmLab      => { },
+    FilmScanner  => { },
+    FilmRollName => { },
+);
+
+1;
+"#;
+
+/// Film details of the roll a photo was shot on
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct FilmMetadata {
+    pub film_stock: Option<String>,
+    pub iso: Option<i64>,
+    pub format: Option<String>,
+    pub process: Option<String>,
+    pub push_pull: Option<f64>, // Stops pushed (+) or pulled (-)
+    pub lab: Option<String>,
+    pub scanner: Option<String>,
+    pub roll_name: Option<String>,
+}
+
+fn non_empty(value: &str) -> Option<String> {
+    let value = value.trim();
+    (!value.is_empty()).then(|| value.to_string())
+}
+
+impl FilmMetadata {
+    pub fn from_roll(roll: &Roll) -> Self {
+        FilmMetadata {
+            film_stock: non_empty(&roll.film_stock),
+            iso: roll.film_iso,
+            format: roll.film_format.as_deref().and_then(non_empty),
+            process: roll.film_process.as_deref().and_then(non_empty),
+            push_pull: roll.push_pull,
+            lab: roll.lab_info.as_deref().and_then(non_empty),
+            scanner: roll.scanner.as_deref().and_then(non_empty),
+            roll_name: non_empty(&roll.name),
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        *self == FilmMetadata::default()
+    }
+
+    /// ExifTool arguments for every property; unset ones are deleted so the file matches exactly
+    pub fn exiftool_args(&self) -> Vec<String> {
+        let text = |value: &Option<String>| value.clone().unwrap_or_default();
+        let number = |value: Option<String>| value.unwrap_or_default();
+        vec![
+            format!("-XMP-filmvault:FilmStock={}", text(&self.film_stock)),
+            format!("-XMP-filmvault:FilmSpeed={}", number(self.iso.map(|iso| iso.to_string()))),
+            format!("-XMP-filmvault:FilmFormat={}", text(&self.format)),
+            format!("-XMP-filmvault:FilmProcess={}", text(&self.process)),
+            format!("-XMP-filmvault:FilmPushPull={}", number(self.push_pull.map(|stops| stops.to_string()))),
+            format!("-XMP-filmvault:FilmLab={}", text(&self.lab)),
+            format!("-XMP-filmvault:FilmScanner={}", text(&self.scanner)),
+            format!("-XMP-filmvault:FilmRollName={}", text(&self.roll_name)),
+        ]
+    }
+
+    /// Read the properties from ExifTool's JSON output (None if the file has none)
+    pub fn from_exif_object(exif_obj: &serde_json::Value) -> Option<Self> {
+        // Numbers and number-like strings come back as JSON numbers
+        let text = |key: &str| match &exif_obj[key] {
+            serde_json::Value::String(value) => non_empty(value),
+            serde_json::Value::Number(value) => Some(value.to_string()),
+            _ => None,
+        };
+
+        let film = FilmMetadata {
+            film_stock: text("FilmStock"),
+            iso: exif_obj["FilmSpeed"].as_i64(),
+            format: text("FilmFormat"),
+            process: text("FilmProcess"),
+            push_pull: exif_obj["FilmPushPull"].as_f64(),
+            lab: text("FilmLab"),
+            scanner: text("FilmScanner"),
+            roll_name: text("FilmRollName"),
+        };
+        (!film.is_empty()).then_some(film)
+    }
+}
+
+/// Film stock from a UserComment written before the XMP properties existed
+/// ("Shot on {film_stock} | {city}, {country} | {notes}")
+pub fn legacy_film_stock(user_comment: &str) -> Option<String> {
+    user_comment
+        .split(" | ")
+        .find_map(|part| part.trim().strip_prefix("Shot on "))
+        .and_then(non_empty)
+}
+
+/// ExifTool config file in the app data dir
+pub const EXIFTOOL_CONFIG_FILE: &str = "exiftool_filmvault.config";
+
+/// Write the ExifTool config defining the namespace into the app data dir
+///
+/// ExifTool runs the config as Perl, so it must live in a directory only this
+/// user can write to, never in a shared temp dir. Written beside the target and
+/// renamed over it, which replaces rather than follows whatever was there.
+pub fn write_exiftool_config(app_data_dir: &Path) -> Result<PathBuf> {
+    let path = app_data_dir.join(EXIFTOOL_CONFIG_FILE);
+    let temp_path = path.with_extension("config.part");
+    fs::write(&temp_path, EXIFTOOL_CONFIG)
+        .with_context(|| format!("Failed to write {}", temp_path.display()))?;
+    fs::rename(&temp_path, &path)
+        .with_context(|| format!("Failed to move ExifTool config to {}", path.display()))?;
+    Ok(path)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_round_trip_through_exiftool_json() {
+        let film = FilmMetadata {
+            film_stock: Some("Kodak Portra 400".to_string()),
+            iso: Some(400),
+            format: Some("120".to_string()),
+            process: Some("C-41".to_string()),
+            push_pull: Some(1.0),
+            lab: Some("Carmencita".to_string()),
+            scanner: None,
+            roll_name: Some("Lisbon | day 2".to_string()),
+        };
+        assert_eq!(film.exiftool_args()[1], "-XMP-filmvault:FilmSpeed=400");
+        assert_eq!(film.exiftool_args()[6], "-XMP-filmvault:FilmScanner=");
+
+        // What `exiftool -j` prints for the written properties
+        let exif_obj = serde_json::json!({
+            "FilmStock": "Kodak Portra 400",
+            "FilmSpeed": 400,
+            "FilmFormat": 120,
+            "FilmProcess": "C-41",
+            "FilmPushPull": 1,
+            "FilmLab": "Carmencita",
+            "FilmRollName": "Lisbon | day 2",
+        });
+        assert_eq!(FilmMetadata::from_exif_object(&exif_obj), Some(film));
+        assert_eq!(FilmMetadata::from_exif_object(&serde_json::json!({ "Make": "Nikon" })), None);
+    }
+
+    #[test]
+    fn test_legacy_film_stock() {
+        assert_eq!(legacy_film_stock("Shot on Kodak Portra 400 | Tokyo, Japan | Sunny day"), Some("Kodak Portra 400".to_string()));
+        assert_eq!(legacy_film_stock("Shot on Ilford HP5"), Some("Ilford HP5".to_string()));
+        assert_eq!(legacy_film_stock("Tokyo, Japan | Sunny day"), None);
+    }
+
+    #[test]
+    fn test_write_exiftool_config_replaces_existing_file() {
+        let dir = std::env::temp_dir().join(format!("filmvault_exiftool_config_test_{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        fs::write(dir.join(EXIFTOOL_CONFIG_FILE), "planted").unwrap();
+
+        let path = write_exiftool_config(&dir).unwrap();
+        assert_eq!(fs::read_to_string(&path).unwrap(), EXIFTOOL_CONFIG);
+        assert!(!path.with_extension("config.part").exists());
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
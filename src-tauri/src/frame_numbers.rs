@@ -0,0 +1,166 @@
+/*!
+ * FilmVault Frame Numbers
+ *
+ * Lab scanners encode the negative's frame number in the delivered filename.
+ * Frame numbers are parsed from the original filename with user-supplied
+ * regexes first, then the known scanner patterns below, so photos can be
+ * ordered the way they sit on the film.
+ */
+
+use anyhow::{bail, Context, Result};
+use regex::Regex;
+
+/// Known scanner naming schemes, tried in order
+/// Each pattern matches the filename stem and captures the frame in `frame`.
+const SCANNER_PATTERNS: &[&str] = &[
+    // Fuji Frontier: R1-07-12A (roll, strip, frame with optional half-frame/edge suffix)
+    r"^R\d+-\d+-(?P<frame>\d+[A-Za-z]?)$",
+    // Noritsu: 000123450012 (8-digit order number, 4-digit frame)
+    r"^\d{8}(?P<frame>\d{4})$",
+    // Known prefix, with or without a separator (IMG_0012, CNV00012, scan-12A)
+    r"(?i)^(?:img|cnv|scan|frame)[_\- ]?0*(?P<frame>\d{1,2}[A-Za-z]?)$",
+    // Trailing frame after a separator (Roll3_012, harbour-07). At most two digits
+    // once zeros are stripped, so names like Portra_400 or Tokyo_2024 have no frame.
+    r"[_\-]0*(?P<frame>\d{1,2}[A-Za-z]?)$",
+    // Nothing but the frame (012, 12A)
+    r"^0*(?P<frame>\d{1,2}[A-Za-z]?)$",
+];
+
+/// Parses frame numbers from original filenames
+#[derive(Debug, Clone)]
+pub struct FrameParser {
+    patterns: Vec<Regex>,
+}
+
+impl FrameParser {
+    /// Build a parser; `user_patterns` are tried before the scanner patterns
+    pub fn new(user_patterns: &[String]) -> Result<Self> {
+        let mut patterns = Vec::new();
+        for pattern in user_patterns {
+            patterns.push(compile_user_pattern(pattern)?);
+        }
+        for pattern in SCANNER_PATTERNS {
+            patterns.push(Regex::new(pattern).expect("valid scanner pattern"));
+        }
+        Ok(Self { patterns })
+    }
+
+    /// Frame label of a filename ("12", "12A", "0"), if a pattern matches its stem
+    pub fn parse(&self, filename: &str) -> Option<String> {
+        let stem = std::path::Path::new(filename).file_stem()?.to_str()?;
+
+        self.patterns.iter().find_map(|pattern| {
+            let captures = pattern.captures(stem)?;
+            let frame = captures.name("frame").or_else(|| captures.get(1))?;
+            normalize_frame(frame.as_str())
+        })
+    }
+}
+
+/// Check a user pattern: it must compile and capture the frame (`(?P<frame>...)` or group 1)
+pub fn compile_user_pattern(pattern: &str) -> Result<Regex> {
+    let regex = Regex::new(pattern).with_context(|| format!("Invalid frame pattern '{}'", pattern))?;
+    if regex.captures_len() < 2 {
+        bail!("Frame pattern '{}' needs a capture group for the frame number", pattern);
+    }
+    Ok(regex)
+}
+
+/// Strip leading zeros and uppercase the suffix ("0012a" -> "12A")
+fn normalize_frame(frame: &str) -> Option<String> {
+    let digits_end = frame.find(|c: char| !c.is_ascii_digit()).unwrap_or(frame.len());
+    let (digits, suffix) = frame.split_at(digits_end);
+    if digits.is_empty() {
+        return None;
+    }
+    let number: u32 = digits.parse().ok()?;
+    Some(format!("{}{}", number, suffix.to_uppercase()))
+}
+
+/// Sort key for a frame label: numeric part, then suffix ("9" < "12" < "12A")
+pub fn frame_sort_key(frame: &str) -> (u32, String) {
+    let digits_end = frame.find(|c: char| !c.is_ascii_digit()).unwrap_or(frame.len());
+    let (digits, suffix) = frame.split_at(digits_end);
+    (digits.parse().unwrap_or(u32::MAX), suffix.to_string())
+}
+
+/// Order of a roll after adding photos: each added photo with a frame number
+/// goes right after the last photo with a lower or equal frame (or before the
+/// first one with a higher frame), the rest go last. Existing photos keep
+/// their relative order. Items are `(photo_id, frame)`.
+pub fn merge_by_frame(existing: &[(i64, Option<String>)], added: &[(i64, Option<String>)]) -> Vec<i64> {
+    let mut order: Vec<(i64, Option<(u32, String)>)> = existing
+        .iter()
+        .map(|(id, frame)| (*id, frame.as_deref().map(frame_sort_key)))
+        .collect();
+
+    for (id, frame) in added {
+        let key = frame.as_deref().map(frame_sort_key);
+        let position = key.as_ref()
+            .and_then(|key| {
+                let after = order.iter().rposition(|(_, other)| other.as_ref().is_some_and(|other| other <= key));
+                match after {
+                    Some(index) => Some(index + 1),
+                    None => order.iter().position(|(_, other)| other.is_some()),
+                }
+            })
+            .unwrap_or(order.len());
+        order.insert(position, (*id, key));
+    }
+
+    order.into_iter().map(|(id, _)| id).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_scanner_patterns() {
+        let parser = FrameParser::new(&[]).unwrap();
+        assert_eq!(parser.parse("000123450012.jpg").as_deref(), Some("12"));
+        assert_eq!(parser.parse("R1-07-12A.jpg").as_deref(), Some("12A"));
+        assert_eq!(parser.parse("R1-07-00.jpg").as_deref(), Some("0"));
+        assert_eq!(parser.parse("scan_036.tif").as_deref(), Some("36"));
+        assert_eq!(parser.parse("CNV00012.JPG").as_deref(), Some("12"));
+        assert_eq!(parser.parse("IMG_0012.jpg").as_deref(), Some("12"));
+        assert_eq!(parser.parse("harbour-07a.jpg").as_deref(), Some("7A"));
+        assert_eq!(parser.parse("12A.jpg").as_deref(), Some("12A"));
+        assert_eq!(parser.parse("contact_sheet.jpg"), None);
+
+        // Numbers that are part of a name are not frames
+        assert_eq!(parser.parse("Portra400.jpg"), None);
+        assert_eq!(parser.parse("Portra_400.jpg"), None);
+        assert_eq!(parser.parse("Tokyo 2024.jpg"), None);
+        assert_eq!(parser.parse("Tokyo_2024.jpg"), None);
+        assert_eq!(parser.parse("trip2.jpg"), None);
+        assert_eq!(parser.parse("IMG_4711.jpg"), None);
+    }
+
+    #[test]
+    fn test_user_patterns_first() {
+        // Lab that puts the frame first: 12_order4711.jpg
+        let parser = FrameParser::new(&[r"^(\d+)_order\d+$".to_string()]).unwrap();
+        assert_eq!(parser.parse("12_order4711.jpg").as_deref(), Some("12"));
+        assert_eq!(parser.parse("scan_03.jpg").as_deref(), Some("3"));
+
+        assert!(FrameParser::new(&["no_group".to_string()]).is_err());
+        assert!(FrameParser::new(&["(unclosed".to_string()]).is_err());
+    }
+
+    #[test]
+    fn test_frame_sort_key() {
+        let mut frames = vec!["12A", "9", "12", "0"];
+        frames.sort_by_key(|f| frame_sort_key(f));
+        assert_eq!(frames, vec!["0", "9", "12", "12A"]);
+    }
+
+    #[test]
+    fn test_merge_by_frame() {
+        let frame = |f: &str| Some(f.to_string());
+        // Frames 1, 2, 4 and an unnumbered scan; a late re-scan of 3 and another unnumbered one arrive
+        let existing = vec![(10, frame("1")), (11, frame("2")), (12, frame("4")), (13, None)];
+        let added = vec![(20, frame("3")), (21, None), (22, frame("5"))];
+        assert_eq!(merge_by_frame(&existing, &added), vec![10, 11, 20, 12, 22, 13, 21]);
+    }
+}
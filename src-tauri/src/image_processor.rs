@@ -1,24 +1,183 @@
 use std::path::{Path, PathBuf};
 use std::fs::{self, File};
 use anyhow::{Result, Context};
-use image::{ImageReader, imageops::FilterType};
+use image::{DynamicImage, ImageReader, imageops::FilterType};
 use std::io::BufWriter;
+use std::sync::atomic::{AtomicBool, Ordering};
+use futures::stream::{self, StreamExt};
+use walkdir::WalkDir;
+
+use crate::exif_tool::extract_embedded_preview;
 
 const THUMBNAIL_WIDTH: u32 = 300;
 const THUMBNAIL_QUALITY: u8 = 85;
 const PREVIEW_WIDTH: u32 = 1920;
 const PREVIEW_QUALITY: u8 = 90;
 
+/// Upper bound on images decoded at once during import; a decoded 100 MB TIFF
+/// scan takes several hundred MB, so this is kept below typical core counts
+const MAX_DERIVATIVE_WORKERS: usize = 4;
+
+/// Image formats decoded directly by the `image` crate
+pub const RASTER_EXTENSIONS: &[&str] = &["jpg", "jpeg", "png", "tif", "tiff", "webp", "bmp"];
+
+/// Camera RAW and DNG formats; derivatives come from their embedded preview JPEG
+pub const RAW_EXTENSIONS: &[&str] = &[
+    "dng", "3fr", "fff", "iiq", "nef", "nrw", "cr2", "cr3", "crw", "arw", "srf", "sr2",
+    "raf", "orf", "rw2", "pef", "srw", "rwl", "erf", "mef", "mos", "kdc", "x3f",
+];
+
+/// Lowercased extension of a path
+fn extension_lower(path: &Path) -> Option<String> {
+    path.extension()
+        .and_then(|e| e.to_str())
+        .map(|e| e.to_lowercase())
+}
+
+/// Check if a file is a RAW/DNG original
+pub fn is_raw_file(path: &Path) -> bool {
+    extension_lower(path)
+        .map(|ext| RAW_EXTENSIONS.contains(&ext.as_str()))
+        .unwrap_or(false)
+}
+
+/// Check if a file is any supported image (raster or RAW)
+pub fn is_supported_image(path: &Path) -> bool {
+    extension_lower(path)
+        .map(|ext| RASTER_EXTENSIONS.contains(&ext.as_str()) || RAW_EXTENSIONS.contains(&ext.as_str()))
+        .unwrap_or(false)
+}
+
+/// Collect supported image files directly inside a directory
+pub fn collect_image_files(source_dir: &Path) -> Result<Vec<PathBuf>> {
+    let mut image_files = Vec::new();
+    for entry in fs::read_dir(source_dir)? {
+        let path = entry?.path();
+        if path.is_file() && is_supported_image(&path) {
+            image_files.push(path);
+        }
+    }
+    // Scans are numbered by filename, so import them in that order
+    image_files.sort();
+    Ok(image_files)
+}
+
+/// A folder of a delivery tree that directly contains images
+#[derive(Debug, Clone, PartialEq)]
+pub struct ImageFolder {
+    pub path: PathBuf,
+    pub image_count: usize,
+}
+
+/// Skip hidden folders and archive metadata (`.thumbnails`, `__MACOSX`, ...)
+fn is_ignored_folder(name: &str) -> bool {
+    name.starts_with('.') || name == "__MACOSX"
+}
+
+/// Find every folder under `root` (including `root`) that directly contains images
+///
+/// Each folder becomes one roll in a batch import; folders are returned in
+/// path order. Counts match `collect_image_files` for the same folder.
+pub fn find_image_folders(root: &Path) -> Result<Vec<ImageFolder>> {
+    let mut counts: std::collections::BTreeMap<PathBuf, usize> = std::collections::BTreeMap::new();
+
+    let walker = WalkDir::new(root)
+        .into_iter()
+        .filter_entry(|entry| {
+            entry.depth() == 0
+                || !entry.file_type().is_dir()
+                || !entry.file_name().to_str().is_some_and(is_ignored_folder)
+        });
+
+    for entry in walker {
+        let entry = entry.context("Failed to read source directory")?;
+        if !entry.file_type().is_file() || !is_supported_image(entry.path()) {
+            continue;
+        }
+        if let Some(parent) = entry.path().parent() {
+            *counts.entry(parent.to_path_buf()).or_default() += 1;
+        }
+    }
+
+    Ok(counts
+        .into_iter()
+        .map(|(path, image_count)| ImageFolder { path, image_count })
+        .collect())
+}
+
+/// Decode an image for derivative generation
+///
+/// RAW/DNG files are decoded from their embedded preview JPEG via ExifTool.
+pub async fn load_image(path: &Path) -> Result<DynamicImage> {
+    if is_raw_file(path) {
+        let preview = extract_embedded_preview(&path.to_string_lossy()).await?;
+        return image::load_from_memory(&preview)
+            .context("Failed to decode embedded preview");
+    }
+
+    decode_image_file(path)
+}
+
+/// Decode a raster image file (blocking)
+fn decode_image_file(path: &Path) -> Result<DynamicImage> {
+    ImageReader::open(path)?
+        .with_guessed_format()?
+        .decode()
+        .context("Failed to decode image")
+}
+
+/// Number of images processed concurrently during import
+pub fn derivative_workers() -> usize {
+    std::thread::available_parallelism()
+        .map(|n| n.get())
+        .unwrap_or(1)
+        .clamp(1, MAX_DERIVATIVE_WORKERS)
+}
+
+/// BLAKE3 hash (hex) of a file's contents
+pub fn hash_file(path: &Path) -> Result<String> {
+    let mut hasher = blake3::Hasher::new();
+    let mut file = File::open(path)
+        .with_context(|| format!("Failed to open {:?} for hashing", path))?;
+    std::io::copy(&mut file, &mut hasher)
+        .with_context(|| format!("Failed to read {:?} for hashing", path))?;
+    Ok(hasher.finalize().to_hex().to_string())
+}
+
+/// A source file selected for import, with the content hash of the file as delivered
+#[derive(Debug, Clone)]
+pub struct ImportFile {
+    pub source_path: PathBuf,
+    pub content_hash: String,
+}
+
+/// Hash source files for import without blocking the async runtime
+pub async fn hash_import_files(paths: Vec<PathBuf>) -> Result<Vec<ImportFile>> {
+    tokio::task::spawn_blocking(move || {
+        paths.into_iter()
+            .map(|source_path| {
+                let content_hash = hash_file(&source_path)?;
+                Ok(ImportFile { source_path, content_hash })
+            })
+            .collect()
+    })
+    .await?
+}
+
 #[derive(Debug, Clone)]
 pub struct ProcessedPaths {
     pub filename: String,
     pub original_path: PathBuf,
     pub thumbnail_path: PathBuf,
     pub preview_path: PathBuf,
+    pub content_hash: Option<String>,
+    pub perceptual_hash: Option<String>,
+    pub original_filename: Option<String>,
+    pub frame_number: Option<String>,
 }
 
 /// Process a single image: generate thumbnail and preview
-pub fn process_image(
+pub async fn process_image(
     original_path: &Path,
     roll_dir: &Path,
 ) -> Result<ProcessedPaths> {
@@ -44,28 +203,106 @@ pub fn process_image(
             .unwrap_or("jpg")
     );
 
+    let img = load_image(original_path).await?;
+
     // Generate thumbnail
     let thumbnail_path = thumbnail_dir.join(format!("{}.webp", file_stem));
-    generate_thumbnail(original_path, &thumbnail_path)?;
+    generate_thumbnail(&img, &thumbnail_path)?;
+    let perceptual_hash = perceptual_hash_file(&thumbnail_path).ok();
 
     // Generate preview
     let preview_path = preview_dir.join(format!("{}.webp", file_stem));
-    generate_preview(original_path, &preview_path)?;
+    generate_preview(&img, original_path, &preview_path)?;
 
     Ok(ProcessedPaths {
         filename,
         original_path: original_path.to_path_buf(),
         thumbnail_path,
         preview_path,
+        content_hash: None,
+        perceptual_hash,
+        original_filename: None,
+        frame_number: None,
     })
 }
 
-/// Generate a thumbnail image
-fn generate_thumbnail(source: &Path, dest: &Path) -> Result<()> {
-    let img = ImageReader::open(source)?
+/// 64-bit difference hash (dHash) of an image as 16 hex digits
+///
+/// The image is reduced to 9x8 grayscale and each bit records whether a pixel
+/// is brighter than its right neighbour, so re-scans and re-encodes of the same
+/// frame land within a few bits of each other.
+pub fn dhash(img: &DynamicImage) -> String {
+    let small = img.resize_exact(9, 8, FilterType::Triangle).to_luma8();
+    let mut hash: u64 = 0;
+    for y in 0..8 {
+        for x in 0..8 {
+            let left = small.get_pixel(x, y)[0];
+            let right = small.get_pixel(x + 1, y)[0];
+            hash = (hash << 1) | u64::from(left > right);
+        }
+    }
+    format!("{:016x}", hash)
+}
+
+/// Perceptual hash of an already-generated thumbnail
+pub fn perceptual_hash_file(thumbnail_path: &Path) -> Result<String> {
+    let img = ImageReader::open(thumbnail_path)?
+        .with_guessed_format()?
         .decode()
-        .context("Failed to decode image")?;
+        .context("Failed to decode thumbnail")?;
+    Ok(dhash(&img))
+}
+
+/// Number of differing bits between two perceptual hashes
+pub fn hamming_distance(a: &str, b: &str) -> Option<u32> {
+    let a = u64::from_str_radix(a, 16).ok()?;
+    let b = u64::from_str_radix(b, 16).ok()?;
+    Some((a ^ b).count_ones())
+}
 
+/// Group photos whose perceptual hashes are within `threshold` bits of each other
+///
+/// Takes `(photo_id, hash)` pairs; similarity is transitive within a group.
+/// Only groups with at least two photos are returned.
+pub fn group_near_duplicates(hashes: &[(i64, String)], threshold: u32) -> Vec<Vec<i64>> {
+    let parsed: Vec<(i64, u64)> = hashes.iter()
+        .filter_map(|(id, hash)| u64::from_str_radix(hash, 16).ok().map(|h| (*id, h)))
+        .collect();
+
+    // Union-find over all pairs within the threshold
+    let mut parent: Vec<usize> = (0..parsed.len()).collect();
+    fn find(parent: &mut [usize], mut i: usize) -> usize {
+        while parent[i] != i {
+            parent[i] = parent[parent[i]];
+            i = parent[i];
+        }
+        i
+    }
+
+    for i in 0..parsed.len() {
+        for j in (i + 1)..parsed.len() {
+            if (parsed[i].1 ^ parsed[j].1).count_ones() <= threshold {
+                let (a, b) = (find(&mut parent, i), find(&mut parent, j));
+                if a != b {
+                    parent[b] = a;
+                }
+            }
+        }
+    }
+
+    let mut groups: std::collections::BTreeMap<usize, Vec<i64>> = std::collections::BTreeMap::new();
+    for (i, (photo_id, _)) in parsed.iter().enumerate() {
+        let root = find(&mut parent, i);
+        groups.entry(root).or_default().push(*photo_id);
+    }
+
+    groups.into_values()
+        .filter(|group| group.len() > 1)
+        .collect()
+}
+
+/// Generate a thumbnail image
+fn generate_thumbnail(img: &DynamicImage, dest: &Path) -> Result<()> {
     // Calculate new dimensions maintaining aspect ratio
     let original_width = img.width();
     let original_height = img.height();
@@ -87,11 +324,7 @@ fn generate_thumbnail(source: &Path, dest: &Path) -> Result<()> {
 }
 
 /// Generate a preview image
-fn generate_preview(source: &Path, dest: &Path) -> Result<()> {
-    let img = ImageReader::open(source)?
-        .decode()
-        .context("Failed to decode image")?;
-
+fn generate_preview(img: &DynamicImage, source: &Path, dest: &Path) -> Result<()> {
     // Only resize if the image is larger than preview width
     if img.width() > PREVIEW_WIDTH {
         let original_width = img.width();
@@ -107,6 +340,14 @@ fn generate_preview(source: &Path, dest: &Path) -> Result<()> {
         let encoder = image::codecs::webp::WebPEncoder::new_lossless(buffered_writer);
         preview.write_with_encoder(encoder)
             .context("Failed to save preview")?;
+    } else if is_raw_file(source) {
+        // A small embedded preview still has to be encoded; the RAW itself is not viewable
+        let dest_file = File::create(dest)
+            .context("Failed to create preview file")?;
+        let buffered_writer = BufWriter::new(dest_file);
+        let encoder = image::codecs::webp::WebPEncoder::new_lossless(buffered_writer);
+        img.write_with_encoder(encoder)
+            .context("Failed to save preview")?;
     } else {
         // If image is smaller, just copy it
         fs::copy(source, dest)
@@ -117,30 +358,17 @@ fn generate_preview(source: &Path, dest: &Path) -> Result<()> {
 }
 
 /// Process multiple images in a directory
-pub fn process_images_in_directory(
+pub async fn process_images_in_directory(
     source_dir: &Path,
     roll_dir: &Path,
 ) -> Result<Vec<ProcessedPaths>> {
     let mut results = Vec::new();
 
-    // Supported image extensions
-    let image_extensions = ["jpg", "jpeg", "png", "tif", "tiff", "webp", "bmp"];
-
-    for entry in fs::read_dir(source_dir)? {
-        let entry = entry?;
-        let path = entry.path();
-
-        if path.is_file() {
-            if let Some(ext) = path.extension().and_then(|e| e.to_str()) {
-                let ext_lower = ext.to_lowercase();
-                if image_extensions.contains(&ext_lower.as_str()) {
-                    match process_image(&path, roll_dir) {
-                        Ok(processed) => results.push(processed),
-                        Err(e) => {
-                            eprintln!("Warning: Failed to process {:?}: {}", path, e);
-                        }
-                    }
-                }
+    for path in collect_image_files(source_dir)? {
+        match process_image(&path, roll_dir).await {
+            Ok(processed) => results.push(processed),
+            Err(e) => {
+                eprintln!("Warning: Failed to process {:?}: {}", path, e);
             }
         }
     }
@@ -148,140 +376,381 @@ pub fn process_images_in_directory(
     Ok(results)
 }
 
-/// Process a single image with file renaming and copy/move
-pub fn process_image_with_copy(
-    source_path: &Path,
-    roll_dir: &Path,
-    new_filename: &str,
-    copy_mode: bool, // true = copy, false = move
-) -> Result<ProcessedPaths> {
-    // Create subdirectories
-    let originals_dir = roll_dir.join("originals");
-    let thumbnail_dir = roll_dir.join("thumbnails");
-    let preview_dir = roll_dir.join("previews");
+/// A file planned for import: where it comes from and where its files will go
+///
+/// Paths are fixed before anything is copied so an interrupted import can be
+/// rolled back or finished from the plan alone.
+#[derive(Debug, Clone, PartialEq)]
+pub struct PlannedImport {
+    pub source_path: PathBuf,
+    pub content_hash: Option<String>,
+    pub filename: String,
+    pub original_path: PathBuf,
+    pub thumbnail_path: PathBuf,
+    pub preview_path: PathBuf,
+    pub frame_number: Option<String>, // Parsed from the source filename
+}
 
-    fs::create_dir_all(&originals_dir)
-        .context("Failed to create originals directory")?;
-    fs::create_dir_all(&thumbnail_dir)
-        .context("Failed to create thumbnail directory")?;
-    fs::create_dir_all(&preview_dir)
-        .context("Failed to create preview directory")?;
+impl PlannedImport {
+    /// Paths of a processed import (the perceptual hash is read from the thumbnail)
+    pub fn to_processed(&self) -> ProcessedPaths {
+        ProcessedPaths {
+            filename: self.filename.clone(),
+            original_path: self.original_path.clone(),
+            thumbnail_path: self.thumbnail_path.clone(),
+            preview_path: self.preview_path.clone(),
+            content_hash: self.content_hash.clone(),
+            perceptual_hash: perceptual_hash_file(&self.thumbnail_path).ok(),
+            original_filename: self.original_filename(),
+            frame_number: self.frame_number.clone(),
+        }
+    }
 
-    // Get file extension
-    let extension = source_path
-        .extension()
-        .and_then(|e| e.to_str())
-        .ok_or_else(|| anyhow::anyhow!("Invalid file extension"))?;
+    /// Name of the file as delivered (archive entries use their own name)
+    pub fn original_filename(&self) -> Option<String> {
+        self.source_path.file_name().map(|name| name.to_string_lossy().to_string())
+    }
 
-    // Destination path for original image (in originals/ subdirectory)
-    let dest_original_path = originals_dir.join(new_filename);
+    /// Whether the original and both derivatives exist
+    pub fn is_complete(&self) -> bool {
+        self.original_path.exists() && self.thumbnail_path.exists() && self.preview_path.exists()
+    }
+}
+
+/// A planned file that could not be copied or processed
+#[derive(Debug, Clone, PartialEq)]
+pub struct FailedImport {
+    pub source_path: PathBuf,
+    pub error: String,
+}
 
-    // Copy or move the original image
+/// Assign library filenames and paths to import files
+/// Files are numbered in order after `start_index`; `stem_for(frame, source_path)`
+/// gives the filename stem of each (see `filename_template::FileNamer`)
+pub fn plan_import_files<F>(
+    image_files: Vec<ImportFile>,
+    roll_dir: &Path,
+    start_index: usize,
+    mut stem_for: F,
+) -> Vec<PlannedImport>
+where
+    F: FnMut(usize, &Path) -> String,
+{
+    image_files.into_iter()
+        .enumerate()
+        .map(|(index, file)| {
+            // Get file extension
+            let extension = file.source_path
+                .extension()
+                .and_then(|e| e.to_str())
+                .unwrap_or("jpg")
+                .to_string();
+
+            let file_stem = stem_for(start_index + index + 1, &file.source_path);
+            let filename = format!("{}.{}", file_stem, extension);
+
+            PlannedImport {
+                source_path: file.source_path,
+                content_hash: Some(file.content_hash),
+                original_path: roll_dir.join("originals").join(&filename),
+                thumbnail_path: roll_dir.join("thumbnails").join(format!("{}.webp", file_stem)),
+                preview_path: roll_dir.join("previews").join(format!("{}.webp", file_stem)),
+                filename,
+                frame_number: None,
+            }
+        })
+        .collect()
+}
+
+/// Copy a file to a path that must not exist yet; a partial copy is removed
+fn copy_new(source_path: &Path, dest_path: &Path) -> std::io::Result<()> {
+    let mut source = File::open(source_path)?;
+    let mut dest = fs::OpenOptions::new().write(true).create_new(true).open(dest_path)?;
+    if let Err(e) = std::io::copy(&mut source, &mut dest) {
+        let _ = fs::remove_file(dest_path);
+        return Err(e);
+    }
+    Ok(())
+}
+
+/// Copy or move an original into the library (blocking)
+/// Never replaces a file already at `dest_path`, e.g. an untracked scan in the roll.
+fn transfer_original(source_path: &Path, dest_path: &Path, copy_mode: bool) -> Result<()> {
     if copy_mode {
-        fs::copy(source_path, &dest_original_path)
+        copy_new(source_path, dest_path)
             .context("Failed to copy original image")?;
     } else {
-        fs::rename(source_path, &dest_original_path)
+        if dest_path.exists() {
+            anyhow::bail!("Failed to move original image: {:?} already exists", dest_path);
+        }
+        fs::rename(source_path, dest_path)
             .or_else(|_| {
                 // If rename fails (cross-device), try copy + delete
-                fs::copy(source_path, &dest_original_path)?;
+                copy_new(source_path, dest_path)?;
                 fs::remove_file(source_path)
             })
             .context("Failed to move original image")?;
     }
+    Ok(())
+}
 
-    // Get filename without extension for thumbnail/preview
-    let file_stem = new_filename.trim_end_matches(&format!(".{}", extension));
-
-    // Generate thumbnail
-    let thumbnail_path = thumbnail_dir.join(format!("{}.webp", file_stem));
-    generate_thumbnail(&dest_original_path, &thumbnail_path)?;
-
-    // Generate preview
-    let preview_path = preview_dir.join(format!("{}.webp", file_stem));
-    generate_preview(&dest_original_path, &preview_path)?;
-
-    Ok(ProcessedPaths {
-        filename: new_filename.to_string(),
-        original_path: dest_original_path,
-        thumbnail_path,
-        preview_path,
-    })
+/// Create the originals/thumbnails/previews directories of a roll
+pub fn create_roll_subdirs(roll_dir: &Path) -> Result<()> {
+    fs::create_dir_all(roll_dir.join("originals"))
+        .context("Failed to create originals directory")?;
+    fs::create_dir_all(roll_dir.join("thumbnails"))
+        .context("Failed to create thumbnail directory")?;
+    fs::create_dir_all(roll_dir.join("previews"))
+        .context("Failed to create preview directory")?;
+    Ok(())
 }
 
-/// Process multiple images in a directory with progress callback and file renaming
-pub fn process_images_in_directory_with_progress<F>(
-    source_dir: &Path,
+/// Copy (or move) planned originals into the roll, one file at a time
+///
+/// Stops before the next file once `cancel` is set. Files that fail to copy
+/// are returned and skipped by the derivative phase; in move mode copying
+/// stops at the first failure since the whole import will be rolled back.
+pub async fn copy_planned_files<F>(
+    planned: &[PlannedImport],
     roll_dir: &Path,
-    roll_id: i64,
     copy_mode: bool,
+    cancel: &AtomicBool,
     mut progress_callback: F,
-) -> Result<Vec<ProcessedPaths>>
+) -> Result<Vec<FailedImport>>
 where
     F: FnMut(usize, usize, String),
 {
-    let mut results = Vec::new();
+    create_roll_subdirs(roll_dir)?;
 
-    // Supported image extensions
-    let image_extensions = ["jpg", "jpeg", "png", "tif", "tiff", "webp", "bmp"];
+    let total = planned.len();
+    let mut failed = Vec::new();
 
-    // First, collect all image files
-    let mut image_files: Vec<PathBuf> = Vec::new();
-    for entry in fs::read_dir(source_dir)? {
-        let entry = entry?;
-        let path = entry.path();
-
-        if path.is_file() {
-            if let Some(ext) = path.extension().and_then(|e| e.to_str()) {
-                let ext_lower = ext.to_lowercase();
-                if image_extensions.contains(&ext_lower.as_str()) {
-                    image_files.push(path);
+    for (index, file) in planned.iter().enumerate() {
+        if cancel.load(Ordering::SeqCst) {
+            break;
+        }
+
+        // Get original filename for display
+        let original_filename = file.source_path
+            .file_name()
+            .and_then(|n| n.to_str())
+            .unwrap_or("unknown")
+            .to_string();
+
+        let source = file.source_path.clone();
+        let dest = file.original_path.clone();
+        match tokio::task::spawn_blocking(move || transfer_original(&source, &dest, copy_mode)).await? {
+            Ok(()) => {}
+            Err(e) => {
+                eprintln!("Warning: Failed to copy {:?}: {}", file.source_path, e);
+                failed.push(FailedImport { source_path: file.source_path.clone(), error: format!("{:#}", e) });
+                // A move-mode import is rolled back as a whole, so stop moving files
+                if !copy_mode {
+                    break;
                 }
             }
         }
+
+        progress_callback(index + 1, total, original_filename);
     }
 
-    let total = image_files.len();
-    eprintln!("[Import] Found {} images to process", total);
+    Ok(failed)
+}
+
+/// Generate thumbnail and preview for an original already in the library
+///
+/// Decoding, resizing and encoding run on the blocking thread pool so several
+/// images can be processed in parallel without stalling the runtime.
+pub async fn generate_derivatives(file: &PlannedImport) -> Result<ProcessedPaths> {
+    // RAW files are decoded from the embedded preview, which ExifTool extracts
+    let embedded_preview = if is_raw_file(&file.original_path) {
+        Some(extract_embedded_preview(&file.original_path.to_string_lossy()).await?)
+    } else {
+        None
+    };
+
+    let perceptual_hash = {
+        let original = file.original_path.clone();
+        let thumbnail = file.thumbnail_path.clone();
+        let preview = file.preview_path.clone();
+        tokio::task::spawn_blocking(move || -> Result<Option<String>> {
+            let img = match embedded_preview {
+                Some(bytes) => image::load_from_memory(&bytes)
+                    .context("Failed to decode embedded preview")?,
+                None => decode_image_file(&original)?,
+            };
+
+            generate_thumbnail(&img, &thumbnail)?;
+            let perceptual_hash = perceptual_hash_file(&thumbnail).ok();
+            generate_preview(&img, &original, &preview)?;
+
+            Ok(perceptual_hash)
+        })
+        .await??
+    };
+
+    Ok(ProcessedPaths {
+        filename: file.filename.clone(),
+        original_path: file.original_path.clone(),
+        thumbnail_path: file.thumbnail_path.clone(),
+        preview_path: file.preview_path.clone(),
+        content_hash: file.content_hash.clone(),
+        perceptual_hash,
+        original_filename: file.original_filename(),
+        frame_number: file.frame_number.clone(),
+    })
+}
+
+/// Generate derivatives for copied originals on up to `derivative_workers()` images at a time
+///
+/// `buffered` yields results in plan order, so progress events stay ordered
+/// while several images are in flight. Once `cancel` is set no new image is
+/// started; images already in flight are finished so nothing is written after return.
+/// Originals that were never copied are skipped without being reported again.
+pub async fn generate_planned_derivatives<F>(
+    planned: &[PlannedImport],
+    cancel: &AtomicBool,
+    mut progress_callback: F,
+) -> (Vec<ProcessedPaths>, Vec<FailedImport>)
+where
+    F: FnMut(usize, usize, String),
+{
+    let total = planned.len();
+    let workers = derivative_workers();
+    eprintln!("[Import] Generating derivatives for {} images ({} workers)", total, workers);
+
+    let mut processing = stream::iter(planned.iter().enumerate())
+        .take_while(|_| futures::future::ready(!cancel.load(Ordering::SeqCst)))
+        .map(|(index, file)| async move {
+            // Originals that failed to copy are skipped
+            let result = if file.original_path.exists() {
+                Some(generate_derivatives(file).await)
+            } else {
+                None
+            };
+            (index, file, result)
+        })
+        .buffered(workers);
 
-    // Generate roll code for filename prefix
-    let roll_code = format!("ROLL_{:08X}", roll_id);
+    let mut results = Vec::new();
+    let mut failed = Vec::new();
 
-    // Process each image with a new sequential filename
-    for (index, source_path) in image_files.iter().enumerate() {
+    while let Some((index, file, result)) = processing.next().await {
         let current = index + 1;
+        eprintln!("[Import] Processed {}/{}: {:?} -> {}", current, total, file.source_path, file.filename);
+
+        match result {
+            Some(Ok(processed)) => results.push(processed),
+            None => {}
+            Some(Err(e)) => {
+                eprintln!("Warning: Failed to process {:?}: {}", file.source_path, e);
+                failed.push(FailedImport { source_path: file.source_path.clone(), error: format!("{:#}", e) });
+            }
+        }
 
-        // Get original filename for display
-        let original_filename = source_path
+        // Send progress update
+        let original_filename = file.source_path
             .file_name()
             .and_then(|n| n.to_str())
             .unwrap_or("unknown")
             .to_string();
+        progress_callback(current, total, original_filename);
+    }
 
-        // Get file extension
-        let extension = source_path
-            .extension()
-            .and_then(|e| e.to_str())
-            .unwrap_or("jpg");
+    (results, failed)
+}
+
+/// Remove the files of an unfinished import
+///
+/// Derivatives are deleted; originals are deleted (copy mode) or moved back to
+/// their source (move mode). Empty roll subdirectories are removed afterwards.
+pub fn rollback_planned_files(planned: &[PlannedImport], roll_dir: &Path, copy_mode: bool) -> Result<()> {
+    for file in planned {
+        for derivative in [&file.thumbnail_path, &file.preview_path] {
+            if derivative.exists() {
+                fs::remove_file(derivative)
+                    .with_context(|| format!("Failed to delete {:?}", derivative))?;
+            }
+        }
 
-        // Generate new filename (ROLL_A3F80001_001.jpg, ROLL_A3F80001_002.jpg, etc.)
-        let new_filename = format!("{}_{:03}.{}", roll_code, current, extension);
+        if !file.original_path.exists() {
+            continue;
+        }
+        if copy_mode || file.source_path.exists() {
+            // A file that was already there (the copy refused to replace it) is not ours to delete
+            let copied = match &file.content_hash {
+                Some(content_hash) => hash_file(&file.original_path).is_ok_and(|hash| &hash == content_hash),
+                None => true,
+            };
+            if !copied {
+                eprintln!("[Import] Keeping {:?}: it is not the file this import copied", file.original_path);
+                continue;
+            }
+            fs::remove_file(&file.original_path)
+                .with_context(|| format!("Failed to delete {:?}", file.original_path))?;
+        } else {
+            transfer_original(&file.original_path, &file.source_path, false)
+                .with_context(|| format!("Failed to move {:?} back", file.original_path))?;
+        }
+    }
 
-        eprintln!("[Import] Processing {}/{}: {} -> {}", current, total, original_filename, new_filename);
+    for subdir in ["originals", "thumbnails", "previews"] {
+        // Only succeeds when the directory is empty
+        let _ = fs::remove_dir(roll_dir.join(subdir));
+    }
 
-        // Send progress update
-        progress_callback(current, total, original_filename.clone());
+    Ok(())
+}
 
-        // Process the image
-        match process_image_with_copy(source_path, roll_dir, &new_filename, copy_mode) {
-            Ok(processed) => results.push(processed),
-            Err(e) => {
-                eprintln!("Warning: Failed to process {:?}: {}", source_path, e);
-            }
+/// Rename files as one unit: either every file ends up at its new path or none moves
+///
+/// Files go through temporary names first, so renames that swap names within
+/// the set don't clash. A target that exists outside the set is an error.
+pub fn rename_files(moves: &[(PathBuf, PathBuf)]) -> Result<()> {
+    let sources: std::collections::HashSet<&PathBuf> = moves.iter().map(|(from, _)| from).collect();
+    for (from, to) in moves {
+        if !from.exists() {
+            anyhow::bail!("File not found: {:?}", from);
+        }
+        if to.exists() && !sources.contains(to) {
+            anyhow::bail!("Target already exists: {:?}", to);
         }
     }
 
-    Ok(results)
+    let temp_path = |path: &Path| {
+        let mut name = path.file_name().unwrap_or_default().to_os_string();
+        name.push(".renaming");
+        path.with_file_name(name)
+    };
+
+    // Undo the first `done` steps of a phase
+    let undo = |pairs: &[(&Path, &Path)]| {
+        for (from, to) in pairs.iter().rev() {
+            let _ = fs::rename(to, from);
+        }
+    };
+
+    let to_temp: Vec<(PathBuf, PathBuf)> = moves.iter().map(|(from, _)| (from.clone(), temp_path(from))).collect();
+    let from_temp: Vec<(PathBuf, PathBuf)> = moves.iter().map(|(from, to)| (temp_path(from), to.clone())).collect();
+
+    for (index, (from, to)) in to_temp.iter().enumerate() {
+        if let Err(e) = fs::rename(from, to) {
+            let done: Vec<_> = to_temp[..index].iter().map(|(a, b)| (a.as_path(), b.as_path())).collect();
+            undo(&done);
+            return Err(e).with_context(|| format!("Failed to rename {:?}", from));
+        }
+    }
+    for (index, (from, to)) in from_temp.iter().enumerate() {
+        if let Err(e) = fs::rename(from, to) {
+            let done: Vec<_> = from_temp[..index].iter().map(|(a, b)| (a.as_path(), b.as_path())).collect();
+            undo(&done);
+            let all: Vec<_> = to_temp.iter().map(|(a, b)| (a.as_path(), b.as_path())).collect();
+            undo(&all);
+            return Err(e).with_context(|| format!("Failed to rename {:?}", from));
+        }
+    }
+
+    Ok(())
 }
 
 /// Get file size in MB
@@ -291,87 +760,227 @@ pub fn get_file_size_mb(path: &Path) -> Result<f64> {
     Ok(bytes as f64 / (1024.0 * 1024.0))
 }
 
-/// Process multiple images in a directory with progress callback, file renaming, and starting index
-/// This function is used when adding photos to an existing roll
-pub fn process_images_in_directory_with_start_index<F>(
-    source_dir: &Path,
-    roll_dir: &Path,
-    roll_id: i64,
-    start_index: usize,
-    copy_mode: bool,
-    mut progress_callback: F,
-) -> Result<Vec<ProcessedPaths>>
-where
-    F: FnMut(usize, usize, String),
-{
-    let mut results = Vec::new();
+#[cfg(test)]
+mod tests {
+    use super::*;
 
-    // Supported image extensions
-    let image_extensions = ["jpg", "jpeg", "png", "tif", "tiff", "webp", "bmp"];
+    #[test]
+    fn test_supported_image_extensions() {
+        assert!(is_supported_image(Path::new("scan_001.JPG")));
+        assert!(is_supported_image(Path::new("scan_001.tif")));
+        assert!(is_supported_image(Path::new("DSC_0001.NEF")));
+        assert!(is_supported_image(Path::new("frame.dng")));
+        assert!(!is_supported_image(Path::new("notes.txt")));
+        assert!(!is_supported_image(Path::new("no_extension")));
+
+        assert!(is_raw_file(Path::new("B0001234.3FR")));
+        assert!(is_raw_file(Path::new("capture.iiq")));
+        assert!(!is_raw_file(Path::new("scan.tiff")));
+    }
 
-    // First, collect all image files
-    let mut image_files: Vec<PathBuf> = Vec::new();
-    for entry in fs::read_dir(source_dir)? {
-        let entry = entry?;
-        let path = entry.path();
-
-        if path.is_file() {
-            if let Some(ext) = path.extension().and_then(|e| e.to_str()) {
-                let ext_lower = ext.to_lowercase();
-                if image_extensions.contains(&ext_lower.as_str()) {
-                    image_files.push(path);
-                }
+    #[test]
+    fn test_find_image_folders() {
+        let base = std::env::temp_dir().join(format!("filmvault_delivery_test_{}", std::process::id()));
+        for (dir, files) in [
+            ("", vec!["contact_sheet.jpg", "invoice.pdf", "z_index.png"]),
+            ("roll_02", vec!["02.tif", "01.tif"]),
+            ("roll_01/scans", vec!["a.jpg", "b.JPG", "c.dng"]),
+            ("roll_01/.thumbnails", vec!["a.jpg"]),
+            ("__MACOSX/roll_01", vec!["a.jpg"]),
+            ("empty", vec![]),
+        ] {
+            let folder = base.join(dir);
+            fs::create_dir_all(&folder).unwrap();
+            for file in files {
+                fs::write(folder.join(file), b"").unwrap();
             }
         }
-    }
-
-    let total = image_files.len();
-    eprintln!("[AddPhotos] Found {} images to process, starting from index {}", total, start_index);
-
-    // Generate roll code for filename prefix
-    let roll_code = format!("ROLL_{:08X}", roll_id);
 
-    // Process each image with a new sequential filename starting from start_index
-    for (index, source_path) in image_files.iter().enumerate() {
-        let current = index + 1;
-        let photo_index = start_index + index + 1;
+        let folders = find_image_folders(&base).unwrap();
+        assert_eq!(folders, vec![
+            ImageFolder { path: base.clone(), image_count: 2 },
+            ImageFolder { path: base.join("roll_01/scans"), image_count: 3 },
+            ImageFolder { path: base.join("roll_02"), image_count: 2 },
+        ]);
+        assert_eq!(
+            collect_image_files(&base.join("roll_02")).unwrap(),
+            vec![base.join("roll_02/01.tif"), base.join("roll_02/02.tif")]
+        );
+
+        fs::remove_dir_all(&base).unwrap();
+    }
 
-        // Get original filename for display
-        let original_filename = source_path
-            .file_name()
-            .and_then(|n| n.to_str())
-            .unwrap_or("unknown")
-            .to_string();
+    #[test]
+    fn test_dhash_near_duplicates() {
+        // A gradient and a slightly brightened, upscaled copy hash within a few bits
+        let img = DynamicImage::ImageLuma8(image::GrayImage::from_fn(90, 80, |x, y| {
+            image::Luma([((x * 2 + y) % 256) as u8])
+        }));
+        let rescan = DynamicImage::ImageLuma8(image::GrayImage::from_fn(180, 160, |x, y| {
+            image::Luma([(((x / 2) * 2 + y / 2) % 256).min(245) as u8 + 10])
+        }));
+        let flipped = img.fliph();
+
+        let (a, b, c) = (dhash(&img), dhash(&rescan), dhash(&flipped));
+        assert_eq!(a.len(), 16);
+        assert!(hamming_distance(&a, &b).unwrap() <= 8);
+        assert!(hamming_distance(&a, &c).unwrap() > 8);
+    }
 
-        // Get file extension
-        let extension = source_path
-            .extension()
-            .and_then(|e| e.to_str())
-            .unwrap_or("jpg");
+    #[test]
+    fn test_group_near_duplicates() {
+        let hashes = vec![
+            (1, "ff00ff00ff00ff00".to_string()),
+            (2, "ff00ff00ff00ff01".to_string()),
+            (3, "00ff00ff00ff00ff".to_string()),
+            (4, "ff00ff00ff00ff03".to_string()),
+        ];
+        assert_eq!(group_near_duplicates(&hashes, 2), vec![vec![1, 2, 4]]);
+        assert!(group_near_duplicates(&hashes, 0).is_empty());
+    }
 
-        // Generate new filename (ROLL_A3F80001_006.jpg for start_index=5)
-        let new_filename = format!("{}_{:03}.{}", roll_code, photo_index, extension);
+    #[tokio::test]
+    async fn test_parallel_import_keeps_order() {
+        let base = std::env::temp_dir().join(format!("filmvault_import_test_{}", std::process::id()));
+        let source_dir = base.join("source");
+        let roll_dir = base.join("roll");
+        fs::create_dir_all(&source_dir).unwrap();
+
+        let mut files = vec![];
+        for (i, size) in [400u32, 20, 300, 40, 250].iter().enumerate() {
+            let path = source_dir.join(format!("scan_{}.png", i));
+            image::RgbImage::from_pixel(*size, *size, image::Rgb([i as u8 * 40, 80, 120]))
+                .save(&path)
+                .unwrap();
+            files.push(ImportFile { source_path: path, content_hash: format!("hash{}", i) });
+        }
 
-        eprintln!("[AddPhotos] Processing {}/{}: {} -> {}", current, total, original_filename, new_filename);
+        let planned = plan_import_files(files, &roll_dir, 2, |frame, _| format!("ROLL_0000002A_{:03}", frame));
+        let cancel = AtomicBool::new(false);
+
+        // Move mode, so the rollback below has to restore the sources
+        let failed = copy_planned_files(&planned, &roll_dir, false, &cancel, |_, _, _| {}).await.unwrap();
+        assert!(failed.is_empty());
+        assert!(!planned[0].source_path.exists());
+
+        let mut progress = vec![];
+        let (results, failed) = generate_planned_derivatives(&planned, &cancel, |current, _, name| {
+            progress.push((current, name));
+        })
+        .await;
+        assert!(failed.is_empty());
+
+        let names: Vec<_> = results.iter().map(|r| r.filename.as_str()).collect();
+        assert_eq!(names, vec![
+            "ROLL_0000002A_003.png", "ROLL_0000002A_004.png", "ROLL_0000002A_005.png",
+            "ROLL_0000002A_006.png", "ROLL_0000002A_007.png",
+        ]);
+        assert_eq!(results[3].content_hash.as_deref(), Some("hash3"));
+        assert!(planned.iter().all(|p| p.is_complete()));
+
+        let order: Vec<_> = progress.iter().map(|(current, name)| (*current, name.as_str())).collect();
+        assert_eq!(order, vec![
+            (1, "scan_0.png"), (2, "scan_1.png"), (3, "scan_2.png"), (4, "scan_3.png"), (5, "scan_4.png"),
+        ]);
+
+        rollback_planned_files(&planned, &roll_dir, false).unwrap();
+        assert!(planned.iter().all(|p| p.source_path.exists() && !p.original_path.exists()));
+        assert!(!roll_dir.join("thumbnails").exists());
+
+        fs::remove_dir_all(&base).unwrap();
+    }
 
-        // Send progress update
-        progress_callback(current, total, original_filename.clone());
+    #[tokio::test]
+    async fn test_cancelled_import_stops() {
+        let cancel = AtomicBool::new(true);
+        let planned = plan_import_files(
+            vec![ImportFile { source_path: PathBuf::from("/nonexistent/a.jpg"), content_hash: "h".to_string() }],
+            Path::new("/nonexistent/roll"),
+            0,
+            |frame, _| format!("{:03}", frame),
+        );
+        let (results, failed) = generate_planned_derivatives(&planned, &cancel, |_, _, _| {}).await;
+        assert!(results.is_empty() && failed.is_empty());
+    }
 
-        // Process the image
-        match process_image_with_copy(source_path, roll_dir, &new_filename, copy_mode) {
-            Ok(processed) => results.push(processed),
-            Err(e) => {
-                eprintln!("Warning: Failed to process {:?}: {}", source_path, e);
-            }
-        }
+    #[tokio::test]
+    async fn test_failed_move_is_restored() {
+        let base = std::env::temp_dir().join(format!("filmvault_move_test_{}", std::process::id()));
+        let source_dir = base.join("source");
+        let roll_dir = base.join("roll");
+        fs::create_dir_all(&source_dir).unwrap();
+
+        // The second file is not a decodable image
+        let good = source_dir.join("scan_0.png");
+        image::RgbImage::from_pixel(40, 40, image::Rgb([10, 20, 30])).save(&good).unwrap();
+        let broken = source_dir.join("scan_1.jpg");
+        fs::write(&broken, b"not a jpeg").unwrap();
+
+        let files = vec![
+            ImportFile { source_path: good.clone(), content_hash: "a".to_string() },
+            ImportFile { source_path: broken.clone(), content_hash: "b".to_string() },
+        ];
+        let planned = plan_import_files(files, &roll_dir, 0, |frame, _| format!("{:03}", frame));
+        let cancel = AtomicBool::new(false);
+
+        let failed = copy_planned_files(&planned, &roll_dir, false, &cancel, |_, _, _| {}).await.unwrap();
+        assert!(failed.is_empty());
+        assert!(!good.exists() && !broken.exists());
+
+        let (results, failed) = generate_planned_derivatives(&planned, &cancel, |_, _, _| {}).await;
+        assert_eq!(results.len(), 1);
+        assert_eq!(failed.len(), 1);
+        assert_eq!(failed[0].source_path, broken);
+
+        rollback_planned_files(&planned, &roll_dir, false).unwrap();
+        assert!(good.exists() && broken.exists());
+        assert!(!roll_dir.join("originals").exists());
+
+        fs::remove_dir_all(&base).unwrap();
     }
 
-    Ok(results)
-}
+    #[test]
+    fn test_copy_keeps_existing_original() {
+        let base = std::env::temp_dir().join(format!("filmvault_copy_test_{}", std::process::id()));
+        let roll_dir = base.join("roll");
+        fs::create_dir_all(roll_dir.join("originals")).unwrap();
+        let source = base.join("scan.png");
+        fs::write(&source, b"scan").unwrap();
+        let untracked = roll_dir.join("originals").join("001.png");
+        fs::write(&untracked, b"untracked").unwrap();
+
+        let files = vec![ImportFile { source_path: source.clone(), content_hash: hash_file(&source).unwrap() }];
+        let planned = plan_import_files(files, &roll_dir, 0, |frame, _| format!("{:03}", frame));
+        assert!(transfer_original(&source, &planned[0].original_path, true).is_err());
+        assert!(transfer_original(&source, &planned[0].original_path, false).is_err());
+
+        // Rolling back the failed copy leaves the file that was already there
+        rollback_planned_files(&planned, &roll_dir, true).unwrap();
+        assert_eq!(fs::read(&untracked).unwrap(), b"untracked");
+        assert!(source.exists());
+
+        fs::remove_dir_all(&base).unwrap();
+    }
 
-#[cfg(test)]
-mod tests {
-    use super::*;
+    #[test]
+    fn test_rename_files_swaps_names() {
+        let base = std::env::temp_dir().join(format!("filmvault_rename_test_{}", std::process::id()));
+        fs::create_dir_all(&base).unwrap();
+        let (a, b, c) = (base.join("a.jpg"), base.join("b.jpg"), base.join("c.jpg"));
+        fs::write(&a, b"a").unwrap();
+        fs::write(&b, b"b").unwrap();
+
+        rename_files(&[(a.clone(), b.clone()), (b.clone(), a.clone())]).unwrap();
+        assert_eq!(fs::read(&a).unwrap(), b"b");
+        assert_eq!(fs::read(&b).unwrap(), b"a");
+
+        // A target outside the set stops the whole rename
+        fs::write(&c, b"c").unwrap();
+        assert!(rename_files(&[(a.clone(), base.join("d.jpg")), (b.clone(), c.clone())]).is_err());
+        assert!(a.exists() && b.exists() && !base.join("d.jpg").exists());
+
+        fs::remove_dir_all(&base).unwrap();
+    }
 
     #[test]
     fn test_calculate_dimensions() {
@@ -0,0 +1,329 @@
+/*!
+ * FilmVault Import Jobs
+ *
+ * Every import is persisted as a job with its planned files before anything is
+ * copied. The job state records how far the import got, so a cancelled or
+ * interrupted import can be rolled back (or finished, once all files are in
+ * place) without leaving half a roll behind.
+ */
+
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use serde::{Deserialize, Serialize};
+use sqlx::SqlitePool;
+use anyhow::Result;
+
+use crate::image_processor::PlannedImport;
+
+/// Progress of an import job
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ImportJobState {
+    Pending,
+    Copying,
+    GeneratingDerivatives,
+    WritingDb,
+    WritingExif,
+    Done,
+    Cancelled,
+    Failed,
+}
+
+impl ImportJobState {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ImportJobState::Pending => "pending",
+            ImportJobState::Copying => "copying",
+            ImportJobState::GeneratingDerivatives => "generating_derivatives",
+            ImportJobState::WritingDb => "writing_db",
+            ImportJobState::WritingExif => "writing_exif",
+            ImportJobState::Done => "done",
+            ImportJobState::Cancelled => "cancelled",
+            ImportJobState::Failed => "failed",
+        }
+    }
+
+    pub fn parse(value: &str) -> Option<Self> {
+        let state = match value {
+            "pending" => ImportJobState::Pending,
+            "copying" => ImportJobState::Copying,
+            "generating_derivatives" => ImportJobState::GeneratingDerivatives,
+            "writing_db" => ImportJobState::WritingDb,
+            "writing_exif" => ImportJobState::WritingExif,
+            "done" => ImportJobState::Done,
+            "cancelled" => ImportJobState::Cancelled,
+            "failed" => ImportJobState::Failed,
+            _ => return None,
+        };
+        Some(state)
+    }
+
+    /// Whether the job no longer needs attention
+    pub fn is_finished(&self) -> bool {
+        matches!(self, ImportJobState::Done | ImportJobState::Cancelled | ImportJobState::Failed)
+    }
+
+    /// Whether all originals and derivatives were in place when the job stopped,
+    /// so it can be finished instead of rolled back
+    pub fn files_complete(&self) -> bool {
+        matches!(self, ImportJobState::WritingDb | ImportJobState::WritingExif)
+    }
+}
+
+/// A persisted import job
+#[derive(Debug, Clone, Serialize, Deserialize, sqlx::FromRow)]
+pub struct ImportJob {
+    pub id: i64,
+    pub roll_id: i64,
+    pub created_roll: bool,
+    pub copy_mode: bool,
+    pub auto_write_exif: bool,
+    pub replaced_photo_ids: Option<String>,
+    pub state: String,
+    pub error: Option<String>,
+    pub created_at: String,
+    pub updated_at: String,
+}
+
+impl ImportJob {
+    pub fn job_state(&self) -> ImportJobState {
+        ImportJobState::parse(&self.state).unwrap_or(ImportJobState::Failed)
+    }
+
+    pub fn replaced_photo_ids(&self) -> Vec<i64> {
+        self.replaced_photo_ids
+            .as_deref()
+            .and_then(|ids| serde_json::from_str(ids).ok())
+            .unwrap_or_default()
+    }
+}
+
+/// Settings of a new import job
+pub struct NewImportJob<'a> {
+    pub roll_id: i64,
+    pub created_roll: bool,
+    pub copy_mode: bool,
+    pub auto_write_exif: bool,
+    pub replaced_photo_ids: &'a [i64],
+}
+
+/// Record a job and its planned files (state `pending`)
+pub async fn create_import_job(
+    pool: &SqlitePool,
+    job: NewImportJob<'_>,
+    planned: &[PlannedImport],
+) -> Result<i64> {
+    let mut tx = pool.begin().await?;
+
+    let result = sqlx::query(
+        r#"
+        INSERT INTO import_jobs (roll_id, created_roll, copy_mode, auto_write_exif, replaced_photo_ids, state)
+        VALUES (?1, ?2, ?3, ?4, ?5, 'pending')
+        "#
+    )
+    .bind(job.roll_id)
+    .bind(job.created_roll)
+    .bind(job.copy_mode)
+    .bind(job.auto_write_exif)
+    .bind(serde_json::to_string(job.replaced_photo_ids)?)
+    .execute(&mut *tx)
+    .await?;
+    let job_id = result.last_insert_rowid();
+
+    for (position, file) in planned.iter().enumerate() {
+        sqlx::query(
+            r#"
+            INSERT INTO import_job_files
+                (job_id, position, source_path, content_hash, filename, original_path, thumbnail_path, preview_path, frame_number)
+            VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9)
+            "#
+        )
+        .bind(job_id)
+        .bind(position as i64)
+        .bind(file.source_path.to_string_lossy().to_string())
+        .bind(&file.content_hash)
+        .bind(&file.filename)
+        .bind(file.original_path.to_string_lossy().to_string())
+        .bind(file.thumbnail_path.to_string_lossy().to_string())
+        .bind(file.preview_path.to_string_lossy().to_string())
+        .bind(&file.frame_number)
+        .execute(&mut *tx)
+        .await?;
+    }
+
+    tx.commit().await?;
+    Ok(job_id)
+}
+
+/// Update the state of a job; finished jobs drop their file list
+pub async fn set_import_job_state(
+    pool: &SqlitePool,
+    job_id: i64,
+    state: ImportJobState,
+    error: Option<&str>,
+) -> Result<()> {
+    sqlx::query("UPDATE import_jobs SET state = ?1, error = ?2, updated_at = CURRENT_TIMESTAMP WHERE id = ?3")
+        .bind(state.as_str())
+        .bind(error)
+        .bind(job_id)
+        .execute(pool)
+        .await?;
+
+    if state.is_finished() {
+        sqlx::query("DELETE FROM import_job_files WHERE job_id = ?1")
+            .bind(job_id)
+            .execute(pool)
+            .await?;
+    }
+
+    Ok(())
+}
+
+/// Get jobs that stopped before finishing (app closed or crashed mid-import)
+pub async fn get_unfinished_import_jobs(pool: &SqlitePool) -> Result<Vec<ImportJob>> {
+    let jobs = sqlx::query_as::<_, ImportJob>(
+        "SELECT id, roll_id, created_roll, copy_mode, auto_write_exif, replaced_photo_ids, state, error, created_at, updated_at FROM import_jobs WHERE state NOT IN ('done', 'cancelled', 'failed') ORDER BY id"
+    )
+    .fetch_all(pool)
+    .await?;
+
+    Ok(jobs)
+}
+
+/// Get the planned files of a job in import order
+pub async fn get_import_job_files(pool: &SqlitePool, job_id: i64) -> Result<Vec<PlannedImport>> {
+    let rows = sqlx::query_as::<_, (String, Option<String>, String, String, String, String, Option<String>)>(
+        "SELECT source_path, content_hash, filename, original_path, thumbnail_path, preview_path, frame_number FROM import_job_files WHERE job_id = ?1 ORDER BY position"
+    )
+    .bind(job_id)
+    .fetch_all(pool)
+    .await?;
+
+    Ok(rows.into_iter()
+        .map(|(source_path, content_hash, filename, original_path, thumbnail_path, preview_path, frame_number)| PlannedImport {
+            source_path: PathBuf::from(source_path),
+            content_hash,
+            filename,
+            original_path: PathBuf::from(original_path),
+            thumbnail_path: PathBuf::from(thumbnail_path),
+            preview_path: PathBuf::from(preview_path),
+            frame_number,
+        })
+        .collect())
+}
+
+/// Roll directory of a planned import (parent of `originals/`)
+pub fn roll_dir_of(planned: &[PlannedImport]) -> Option<&Path> {
+    planned.first()
+        .and_then(|file| file.original_path.parent())
+        .and_then(|originals| originals.parent())
+}
+
+/// Cancellation flags of running import jobs
+#[derive(Default)]
+pub struct ImportJobRegistry {
+    running: Mutex<HashMap<i64, Arc<AtomicBool>>>,
+}
+
+impl ImportJobRegistry {
+    /// Register a running job and get its cancellation flag
+    pub fn register(&self, job_id: i64) -> Arc<AtomicBool> {
+        let flag = Arc::new(AtomicBool::new(false));
+        self.running.lock().unwrap().insert(job_id, flag.clone());
+        flag
+    }
+
+    /// Request cancellation; false if the job is not running
+    pub fn cancel(&self, job_id: i64) -> bool {
+        match self.running.lock().unwrap().get(&job_id) {
+            Some(flag) => {
+                flag.store(true, Ordering::SeqCst);
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Forget a job once it has stopped
+    pub fn finish(&self, job_id: i64) {
+        self.running.lock().unwrap().remove(&job_id);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::migrations::run_migrations;
+    use sqlx::sqlite::SqlitePoolOptions;
+
+    #[test]
+    fn test_state_round_trip() {
+        for state in [
+            ImportJobState::Pending, ImportJobState::Copying, ImportJobState::GeneratingDerivatives,
+            ImportJobState::WritingDb, ImportJobState::WritingExif, ImportJobState::Done,
+            ImportJobState::Cancelled, ImportJobState::Failed,
+        ] {
+            assert_eq!(ImportJobState::parse(state.as_str()), Some(state));
+        }
+        assert!(ImportJobState::WritingExif.files_complete());
+        assert!(!ImportJobState::GeneratingDerivatives.files_complete());
+    }
+
+    #[tokio::test]
+    async fn test_job_lifecycle() {
+        let pool = SqlitePoolOptions::new()
+            .max_connections(1)
+            .connect("sqlite::memory:")
+            .await
+            .unwrap();
+        run_migrations(&pool).await.unwrap();
+
+        let roll_dir = Path::new("/library/2024/0000002A");
+        let planned = vec![PlannedImport {
+            source_path: PathBuf::from("/scans/001.tif"),
+            content_hash: Some("abc".to_string()),
+            filename: "ROLL_0000002A_001.tif".to_string(),
+            original_path: roll_dir.join("originals/ROLL_0000002A_001.tif"),
+            thumbnail_path: roll_dir.join("thumbnails/ROLL_0000002A_001.webp"),
+            preview_path: roll_dir.join("previews/ROLL_0000002A_001.webp"),
+            frame_number: Some("1".to_string()),
+        }];
+
+        let job_id = create_import_job(&pool, NewImportJob {
+            roll_id: 42,
+            created_roll: true,
+            copy_mode: false,
+            auto_write_exif: false,
+            replaced_photo_ids: &[7, 9],
+        }, &planned).await.unwrap();
+
+        set_import_job_state(&pool, job_id, ImportJobState::Copying, None).await.unwrap();
+
+        let unfinished = get_unfinished_import_jobs(&pool).await.unwrap();
+        assert_eq!(unfinished.len(), 1);
+        assert_eq!(unfinished[0].job_state(), ImportJobState::Copying);
+        assert_eq!(unfinished[0].replaced_photo_ids(), vec![7, 9]);
+        assert!(!unfinished[0].copy_mode);
+
+        let files = get_import_job_files(&pool, job_id).await.unwrap();
+        assert_eq!(files, planned);
+        assert_eq!(roll_dir_of(&files), Some(roll_dir));
+
+        set_import_job_state(&pool, job_id, ImportJobState::Cancelled, None).await.unwrap();
+        assert!(get_unfinished_import_jobs(&pool).await.unwrap().is_empty());
+        assert!(get_import_job_files(&pool, job_id).await.unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_registry_cancel() {
+        let registry = ImportJobRegistry::default();
+        let flag = registry.register(3);
+        assert!(!registry.cancel(4));
+        assert!(registry.cancel(3));
+        assert!(flag.load(Ordering::SeqCst));
+        registry.finish(3);
+        assert!(!registry.cancel(3));
+    }
+}
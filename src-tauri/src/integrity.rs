@@ -0,0 +1,284 @@
+/*!
+ * FilmVault Library Integrity
+ *
+ * Compares roll and photo rows with the library folders: rows whose files are
+ * gone, derivatives that are missing or older than their original, images in
+ * a roll's originals folder without a photo row, and roll folders no roll
+ * points to. Fixes are applied by `commands::integrity`.
+ */
+
+use std::collections::{HashMap, HashSet};
+use std::fs;
+use std::path::{Path, PathBuf};
+use serde::{Deserialize, Serialize};
+
+use crate::database::{Photo, Roll};
+use crate::image_processor::{collect_image_files, hash_file};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum DerivativeKind {
+    Thumbnail,
+    Preview,
+}
+
+/// One mismatch between the database and the files on disk
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum IntegrityIssue {
+    /// The roll's folder does not exist
+    MissingRollFolder { roll_id: i64, path: String },
+    /// The original is gone; `candidate` is an unlinked file that looks like it moved there
+    MissingOriginal { photo_id: i64, roll_id: i64, path: String, candidate: Option<String> },
+    /// No derivative recorded, or the recorded file does not exist
+    MissingDerivative { photo_id: i64, roll_id: i64, derivative: DerivativeKind, path: Option<String> },
+    /// The derivative is older than the original it was made from
+    StaleDerivative { photo_id: i64, roll_id: i64, derivative: DerivativeKind, path: String },
+    /// An image in a roll's originals folder without a photo row
+    OrphanFile { roll_id: i64, path: String },
+    /// A roll folder in the library that no roll points to
+    OrphanFolder { path: String },
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct IntegrityReport {
+    pub rolls_checked: usize,
+    pub photos_checked: usize,
+    pub issues: Vec<IntegrityIssue>,
+}
+
+fn modified(path: &Path) -> Option<std::time::SystemTime> {
+    fs::metadata(path).and_then(|meta| meta.modified()).ok()
+}
+
+/// Roll folders of the library: `<root>/<year>/<roll code>` with an `originals` folder
+fn library_roll_folders(library_root: &Path) -> Vec<PathBuf> {
+    let Ok(years) = fs::read_dir(library_root) else {
+        return Vec::new();
+    };
+
+    let mut folders: Vec<PathBuf> = years
+        .flatten()
+        .filter(|year| year.path().is_dir())
+        .filter_map(|year| fs::read_dir(year.path()).ok())
+        .flat_map(|rolls| rolls.flatten().map(|roll| roll.path()))
+        .filter(|path| path.join("originals").is_dir())
+        .collect();
+    folders.sort();
+    folders
+}
+
+/// Check every roll and photo against the filesystem (blocking)
+///
+/// `library_root` is only needed to find orphan roll folders; pass None to skip that.
+pub fn scan_library(library_root: Option<&Path>, rolls: &[Roll], photos: &[Photo]) -> IntegrityReport {
+    let mut issues = Vec::new();
+
+    let linked: HashSet<PathBuf> = photos.iter().map(|photo| PathBuf::from(&photo.file_path)).collect();
+
+    // Images in roll folders that no photo points to
+    let mut unlinked: Vec<(i64, PathBuf)> = Vec::new();
+    for roll in rolls {
+        let roll_dir = Path::new(&roll.path);
+        if roll.path.is_empty() || !roll_dir.is_dir() {
+            issues.push(IntegrityIssue::MissingRollFolder { roll_id: roll.id, path: roll.path.clone() });
+            continue;
+        }
+        let files = collect_image_files(&roll_dir.join("originals")).unwrap_or_default();
+        unlinked.extend(files.into_iter().filter(|path| !linked.contains(path)).map(|path| (roll.id, path)));
+    }
+
+    // Missing originals are matched to unlinked files by name, then by content hash
+    let mut claimed: HashSet<PathBuf> = HashSet::new();
+    let mut unlinked_hashes: Option<HashMap<String, PathBuf>> = None;
+
+    for photo in photos {
+        let original = Path::new(&photo.file_path);
+        if !original.exists() {
+            let names: Vec<&str> = [Some(photo.filename.as_str()), photo.original_filename.as_deref()]
+                .into_iter()
+                .flatten()
+                .collect();
+            let by_name = unlinked.iter()
+                .map(|(_, path)| path)
+                .find(|path| {
+                    !claimed.contains(*path)
+                        && path.file_name().and_then(|n| n.to_str()).is_some_and(|n| names.contains(&n))
+                })
+                .cloned();
+            let candidate = by_name.or_else(|| {
+                let hash = photo.content_hash.as_ref()?;
+                let hashes = unlinked_hashes.get_or_insert_with(|| {
+                    unlinked.iter()
+                        .filter_map(|(_, path)| Some((hash_file(path).ok()?, path.clone())))
+                        .collect()
+                });
+                hashes.get(hash).filter(|path| !claimed.contains(*path)).cloned()
+            });
+            if let Some(candidate) = &candidate {
+                claimed.insert(candidate.clone());
+            }
+
+            issues.push(IntegrityIssue::MissingOriginal {
+                photo_id: photo.id,
+                roll_id: photo.roll_id,
+                path: photo.file_path.clone(),
+                candidate: candidate.map(|path| path.to_string_lossy().to_string()),
+            });
+        }
+
+        let original_modified = modified(original);
+        for (derivative, path) in [
+            (DerivativeKind::Thumbnail, &photo.thumbnail_path),
+            (DerivativeKind::Preview, &photo.preview_path),
+        ] {
+            let derivative_modified = path.as_deref().and_then(|path| modified(Path::new(path)));
+            match (path, derivative_modified) {
+                (Some(path), Some(derivative_modified)) => {
+                    if original_modified.is_some_and(|original_modified| derivative_modified < original_modified) {
+                        issues.push(IntegrityIssue::StaleDerivative {
+                            photo_id: photo.id,
+                            roll_id: photo.roll_id,
+                            derivative,
+                            path: path.clone(),
+                        });
+                    }
+                }
+                _ => issues.push(IntegrityIssue::MissingDerivative {
+                    photo_id: photo.id,
+                    roll_id: photo.roll_id,
+                    derivative,
+                    path: path.clone(),
+                }),
+            }
+        }
+    }
+
+    issues.extend(unlinked
+        .into_iter()
+        .filter(|(_, path)| !claimed.contains(path))
+        .map(|(roll_id, path)| IntegrityIssue::OrphanFile { roll_id, path: path.to_string_lossy().to_string() }));
+
+    if let Some(library_root) = library_root {
+        let roll_paths: HashSet<PathBuf> = rolls.iter().map(|roll| PathBuf::from(&roll.path)).collect();
+        issues.extend(library_roll_folders(library_root)
+            .into_iter()
+            .filter(|folder| !roll_paths.contains(folder))
+            .map(|folder| IntegrityIssue::OrphanFolder { path: folder.to_string_lossy().to_string() }));
+    }
+
+    IntegrityReport {
+        rolls_checked: rolls.len(),
+        photos_checked: photos.len(),
+        issues,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn roll(id: i64, path: &Path) -> Roll {
+        Roll {
+            id,
+            name: format!("Roll {}", id),
+            path: path.to_string_lossy().to_string(),
+            film_stock: String::new(),
+            camera: String::new(),
+            lens: None,
+            shoot_date: "2024-03-15".to_string(),
+            lab_info: None,
+            notes: None,
+            city: None,
+            country: None,
+            lat: None,
+            lon: None,
+            film_iso: None,
+            film_format: None,
+            film_process: None,
+            push_pull: None,
+            scanner: None,
+            created_at: String::new(),
+            updated_at: String::new(),
+        }
+    }
+
+    fn photo(id: i64, roll_dir: &Path, stem: &str) -> Photo {
+        let path = |dir: &str, name: String| roll_dir.join(dir).join(name).to_string_lossy().to_string();
+        Photo {
+            id,
+            roll_id: 1,
+            filename: format!("{}.jpg", stem),
+            file_path: path("originals", format!("{}.jpg", stem)),
+            thumbnail_path: Some(path("thumbnails", format!("{}.webp", stem))),
+            preview_path: Some(path("previews", format!("{}.webp", stem))),
+            rating: 0,
+            is_cover: false,
+            is_favorite: false,
+            lat: None,
+            lon: None,
+            city: None,
+            country: None,
+            exif_synced: false,
+            created_at: String::new(),
+            exif_written_at: None,
+            exif_data_hash: None,
+            exif_user_comment: None,
+            exif_description: None,
+            content_hash: None,
+            perceptual_hash: None,
+            original_filename: None,
+            frame_number: None,
+            sequence: None,
+        }
+    }
+
+    #[test]
+    fn test_scan_library() {
+        let root = std::env::temp_dir().join(format!("filmvault_integrity_test_{}", std::process::id()));
+        let roll_dir = root.join("2024").join("00000001");
+        let stray_dir = root.join("2024").join("000000FF");
+        for dir in ["originals", "thumbnails", "previews"] {
+            fs::create_dir_all(roll_dir.join(dir)).unwrap();
+        }
+        fs::create_dir_all(stray_dir.join("originals")).unwrap();
+
+        // 001 is intact, 002 lost its preview, 003 was renamed on disk, 004 is not in the database
+        for stem in ["001", "002"] {
+            fs::write(roll_dir.join("originals").join(format!("{}.jpg", stem)), stem).unwrap();
+            fs::write(roll_dir.join("thumbnails").join(format!("{}.webp", stem)), stem).unwrap();
+        }
+        fs::write(roll_dir.join("previews").join("001.webp"), "001").unwrap();
+        fs::write(roll_dir.join("originals").join("003_renamed.jpg"), "003").unwrap();
+        fs::write(roll_dir.join("thumbnails").join("003.webp"), "003").unwrap();
+        fs::write(roll_dir.join("previews").join("003.webp"), "003").unwrap();
+        fs::write(roll_dir.join("originals").join("004.jpg"), "004").unwrap();
+
+        let mut renamed = photo(3, &roll_dir, "003");
+        renamed.content_hash = Some(hash_file(&roll_dir.join("originals").join("003_renamed.jpg")).unwrap());
+        let photos = vec![photo(1, &roll_dir, "001"), photo(2, &roll_dir, "002"), renamed];
+        let rolls = vec![roll(1, &roll_dir), roll(2, &root.join("2023").join("00000002"))];
+
+        let report = scan_library(Some(&root), &rolls, &photos);
+        let path = |dir: &str, name: &str| roll_dir.join(dir).join(name).to_string_lossy().to_string();
+        assert_eq!(report.issues, vec![
+            IntegrityIssue::MissingRollFolder { roll_id: 2, path: rolls[1].path.clone() },
+            IntegrityIssue::MissingDerivative {
+                photo_id: 2,
+                roll_id: 1,
+                derivative: DerivativeKind::Preview,
+                path: Some(path("previews", "002.webp")),
+            },
+            IntegrityIssue::MissingOriginal {
+                photo_id: 3,
+                roll_id: 1,
+                path: path("originals", "003.jpg"),
+                candidate: Some(path("originals", "003_renamed.jpg")),
+            },
+            IntegrityIssue::OrphanFile { roll_id: 1, path: path("originals", "004.jpg") },
+            IntegrityIssue::OrphanFolder { path: stray_dir.to_string_lossy().to_string() },
+        ]);
+
+        fs::remove_dir_all(&root).unwrap();
+    }
+}
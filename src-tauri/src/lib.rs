@@ -3,13 +3,25 @@
 
 mod config;
 mod database;
+mod migrations;
 mod exif_tool;
+mod logbook;
+mod archive;
+mod backups;
+mod filename_template;
+mod film_metadata;
+mod frame_numbers;
 mod image_processor;
+mod import_jobs;
+mod integrity;
+mod libraries;
+mod library_moves;
+mod library_paths;
+mod metadata_drift;
 mod commands;
 mod default_presets;
 
-use config::init_default_config;
-use database::init_database;
+use import_jobs::ImportJobRegistry;
 use sqlx::SqlitePool;
 use std::sync::Arc;
 use tauri::Manager;
@@ -17,6 +29,8 @@ use tauri::Manager;
 // Application state
 struct AppState {
     db_pool: Arc<tokio::sync::Mutex<Option<SqlitePool>>>,
+    import_jobs: Arc<ImportJobRegistry>,
+    library_switch: Arc<tokio::sync::RwLock<()>>, // Written to switch, restore or move the library; read by long-running commands
 }
 
 #[tokio::main]
@@ -28,6 +42,9 @@ pub async fn main() {
         .setup(|app| {
             let handle = app.handle().clone();
 
+            // Check the backup schedule in the background
+            tokio::spawn(commands::backup::run_backup_schedule(handle.clone()));
+
             // Spawn async task to initialize database
             tokio::spawn(async move {
                 eprintln!("[FilmVault] Starting database initialization...");
@@ -48,43 +65,27 @@ pub async fn main() {
 
                 eprintln!("[FilmVault] App data dir: {:?}", app_data_dir);
 
-                let db_path = app_data_dir.join("film_vault.db");
-                eprintln!("[FilmVault] Database file path: {:?}", db_path);
-                eprintln!("[FilmVault] File exists: {}", db_path.exists());
-
-                // Check parent directory exists
-                if let Some(parent) = db_path.parent() {
-                    eprintln!("[FilmVault] Parent directory: {:?}", parent);
-                    eprintln!("[FilmVault] Parent exists: {}", parent.exists());
-                }
-
-                // For Windows SQLx SQLite, use file:// URI format with mode=rwc
-                let db_path_str = db_path.canonicalize()
-                    .unwrap_or(db_path.clone())
-                    .to_string_lossy()
-                    .replace('\\', "/")
-                    .trim_start_matches("//?/")
-                    .to_string();
+                // Kept in the app data dir: ExifTool executes it
+                let exiftool_config = match film_metadata::write_exiftool_config(&app_data_dir) {
+                    Ok(path) => Some(path),
+                    Err(e) => {
+                        eprintln!("[FilmVault] {:#}, film fields will not be written", e);
+                        None
+                    }
+                };
 
-                // Use file:// URI format with read-write-create mode
-                let db_connection_string = format!("sqlite://file:/{}?mode=rwc", db_path_str);
+                exif_tool::init_exiftool_pool(exiftool_config);
 
+                let db_path = commands::library::startup_db_path(&app_data_dir);
                 eprintln!("[FilmVault] Database file path: {:?}", db_path);
-                eprintln!("[FilmVault] Normalized path: {}", db_path_str);
-                eprintln!("[FilmVault] Connection string: {}", db_connection_string);
+                eprintln!("[FilmVault] File exists: {}", db_path.exists());
 
-                match init_database(&db_connection_string).await {
+                match commands::library::open_library_pool(&db_path).await {
                     Ok(pool) => {
                         eprintln!("[FilmVault] Database initialized successfully");
 
-                        // Initialize default configuration
-                        if let Err(e) = init_default_config(&pool).await {
-                            eprintln!("[FilmVault] Failed to initialize default config: {}", e);
-                        }
-
-                        // Initialize default film presets
-                        if let Err(e) = default_presets::initialize_default_presets(&pool).await {
-                            eprintln!("[FilmVault] Failed to initialize default presets: {}", e);
+                        if let Err(e) = commands::library::remember_startup_library(&handle, &db_path) {
+                            eprintln!("[FilmVault] {}", e);
                         }
 
                         let state = handle.state::<AppState>();
@@ -94,7 +95,6 @@ pub async fn main() {
                     }
                     Err(e) => {
                         eprintln!("[FilmVault] Failed to initialize database: {}", e);
-                        eprintln!("[FilmVault] Error details: {:?}", e);
                     }
                 }
             });
@@ -103,15 +103,41 @@ pub async fn main() {
         })
         .manage(AppState {
             db_pool: Arc::new(tokio::sync::Mutex::new(None)),
+            import_jobs: Arc::new(ImportJobRegistry::default()),
+            library_switch: Arc::new(tokio::sync::RwLock::new(())),
         })
         .invoke_handler(tauri::generate_handler![
             // Config commands
             commands::config::get_config,
             commands::config::update_library_root,
+            commands::config::check_library_root_command,
+            commands::config::relocate_library_command,
+            commands::config::update_filename_template,
+            commands::config::update_frame_patterns,
+            commands::config::update_backup_settings,
+            commands::config::update_metadata_write_mode,
+            commands::config::update_verify_exif_writes,
+            // Library commands
+            commands::library::list_libraries_command,
+            commands::library::open_library_command,
+            commands::library::create_library_command,
+            commands::library::forget_library_command,
+            commands::library::move_library_command,
+            commands::library::get_unfinished_library_move_command,
+            commands::library::abandon_library_move_command,
+            // Backup commands
+            commands::backup::backup_database_command,
+            commands::backup::list_backups_command,
+            commands::backup::inspect_backup_command,
+            commands::backup::restore_database_command,
             // Import commands
             commands::import::import_folder,
             commands::import::preview_import_count,
+            commands::import::find_import_duplicates,
             commands::import::add_photos_to_roll,
+            commands::import::cancel_import_job,
+            commands::import::preview_batch_import,
+            commands::import::import_batch,
             // Roll commands
             commands::rolls::get_all_rolls_command,
             commands::rolls::get_roll_by_id_command,
@@ -125,6 +151,13 @@ pub async fn main() {
             commands::rolls::read_image_as_base64,
             commands::rolls::delete_photo_command,
             commands::rolls::delete_photos_command,
+            commands::rolls::find_near_duplicate_photos_command,
+            commands::rolls::rename_roll_files_command,
+            commands::rolls::reorder_roll_photos_command,
+            commands::rolls::reparse_frame_numbers_command,
+            // Library integrity commands
+            commands::integrity::check_library_integrity_command,
+            commands::integrity::repair_library_command,
             // Favorite commands
             commands::rolls::toggle_photo_favorite_command,
             commands::rolls::update_photo_favorite_command,
@@ -140,6 +173,14 @@ pub async fn main() {
             commands::exif::clear_photo_exif_command,
             commands::exif::clear_roll_exif_command,
             commands::exif::read_photo_exif_command,
+            commands::exif::scan_metadata_drift_command,
+            commands::exif::resolve_metadata_drift_command,
+            // Shot log commands
+            commands::shot_logs::get_shot_logs_by_roll_command,
+            commands::shot_logs::update_shot_logs_command,
+            commands::shot_logs::apply_shot_log_fields_command,
+            commands::shot_logs::preview_logbook_import_command,
+            commands::shot_logs::apply_logbook_import_command,
             // Film Preset commands
             commands::film_presets::get_film_presets_command,
             commands::film_presets::create_film_preset_command,
@@ -0,0 +1,147 @@
+/*!
+ * FilmVault Libraries
+ *
+ * A library is a database plus the library root it points to. The app keeps
+ * a small registry (`libraries.json` in the app data dir) of the libraries
+ * used recently and which one is open, so it reopens the same one on the next
+ * start. A library's database can live inside its own root, in which case the
+ * root is stored relative to the database and the library travels with its
+ * drive. Switching is done by `commands::library`.
+ */
+
+use std::fs;
+use std::path::{Path, PathBuf};
+use serde::{Deserialize, Serialize};
+use anyhow::Result;
+
+/// Registry file in the app data dir
+pub const REGISTRY_FILE: &str = "libraries.json";
+
+/// Database file name, both for the default library and inside a library root
+pub const DATABASE_FILE: &str = "film_vault.db";
+
+/// How many libraries the registry remembers
+pub const MAX_RECENT_LIBRARIES: usize = 10;
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct LibraryEntry {
+    pub name: String,
+    pub db_path: String,
+    pub last_opened: String,
+}
+
+/// Recently used libraries, most recent first
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct LibraryRegistry {
+    pub current: Option<String>,
+    pub libraries: Vec<LibraryEntry>,
+}
+
+impl LibraryRegistry {
+    /// Read the registry; a missing or unreadable file gives an empty one
+    pub fn load(path: &Path) -> Self {
+        let Ok(contents) = fs::read_to_string(path) else {
+            return Self::default();
+        };
+        serde_json::from_str(&contents).unwrap_or_else(|e| {
+            eprintln!("[Libraries] Ignoring unreadable registry {:?}: {}", path, e);
+            Self::default()
+        })
+    }
+
+    /// Write the registry through a temporary file so a crash never leaves half a file
+    pub fn save(&self, path: &Path) -> Result<()> {
+        let temp_path = path.with_extension("json.part");
+        fs::write(&temp_path, serde_json::to_string_pretty(self)?)?;
+        fs::rename(&temp_path, path)?;
+        Ok(())
+    }
+
+    pub fn get(&self, db_path: &str) -> Option<&LibraryEntry> {
+        self.libraries.iter().find(|entry| entry.db_path == db_path)
+    }
+
+    /// Make a library the current one and move it to the front
+    pub fn remember(&mut self, name: &str, db_path: &str) {
+        self.libraries.retain(|entry| entry.db_path != db_path);
+        self.libraries.insert(0, LibraryEntry {
+            name: name.to_string(),
+            db_path: db_path.to_string(),
+            last_opened: chrono::Utc::now().to_rfc3339(),
+        });
+        self.libraries.truncate(MAX_RECENT_LIBRARIES);
+        self.current = Some(db_path.to_string());
+    }
+
+    /// Drop a library from the list; the files are left alone
+    pub fn forget(&mut self, db_path: &str) -> bool {
+        let count = self.libraries.len();
+        self.libraries.retain(|entry| entry.db_path != db_path);
+        self.libraries.len() != count
+    }
+}
+
+/// Name for a library that was opened without one: its root's folder name
+pub fn default_library_name(db_path: &Path) -> String {
+    db_path.parent()
+        .and_then(|dir| dir.file_name())
+        .map(|name| name.to_string_lossy().to_string())
+        .unwrap_or_else(|| "Library".to_string())
+}
+
+/// Database of a library whose database lives inside its root
+pub fn portable_db_path(library_root: &Path) -> PathBuf {
+    library_root.join(DATABASE_FILE)
+}
+
+/// SQLx connection string for a database file, created if missing
+pub fn connection_string(db_path: &Path) -> String {
+    // For Windows SQLx SQLite, use file:// URI format with mode=rwc
+    let db_path_str = db_path.canonicalize()
+        .unwrap_or(db_path.to_path_buf())
+        .to_string_lossy()
+        .replace('\\', "/")
+        .trim_start_matches("//?/")
+        .to_string();
+
+    format!("sqlite://file:/{}?mode=rwc", db_path_str)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_registry_keeps_recent_libraries() {
+        let mut registry = LibraryRegistry::default();
+        registry.remember("Personal", "/data/film_vault.db");
+        registry.remember("Clients", "/Volumes/Clients/film_vault.db");
+        registry.remember("Personal", "/data/film_vault.db");
+
+        let paths: Vec<_> = registry.libraries.iter().map(|entry| entry.db_path.as_str()).collect();
+        assert_eq!(paths, vec!["/data/film_vault.db", "/Volumes/Clients/film_vault.db"]);
+        assert_eq!(registry.current.as_deref(), Some("/data/film_vault.db"));
+
+        for i in 0..MAX_RECENT_LIBRARIES {
+            registry.remember("Roll", &format!("/tmp/{}/film_vault.db", i));
+        }
+        assert_eq!(registry.libraries.len(), MAX_RECENT_LIBRARIES);
+        assert!(registry.get("/data/film_vault.db").is_none());
+
+        assert!(registry.forget("/tmp/0/film_vault.db"));
+        assert!(!registry.forget("/tmp/0/film_vault.db"));
+    }
+
+    #[test]
+    fn test_registry_file_round_trip() {
+        let path = std::env::temp_dir().join(format!("filmvault_libraries_test_{}.json", std::process::id()));
+        let mut registry = LibraryRegistry::default();
+        registry.remember("Studio", "/Volumes/Studio/film_vault.db");
+        registry.save(&path).unwrap();
+        assert_eq!(LibraryRegistry::load(&path), registry);
+
+        fs::write(&path, "not json").unwrap();
+        assert_eq!(LibraryRegistry::load(&path), LibraryRegistry::default());
+        fs::remove_file(&path).unwrap();
+    }
+}
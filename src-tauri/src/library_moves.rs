@@ -0,0 +1,424 @@
+/*!
+ * FilmVault Library Moves
+ *
+ * Moving the library to a new root copies every file of every roll folder,
+ * verifying each copy against the BLAKE3 hash of its source, before the
+ * database is switched to the new root in one transaction. Copied files are
+ * recorded as they are verified, so a move interrupted partway through
+ * (app closed, drive unplugged) resumes where it stopped. The old folders
+ * are removed only after the switch, unless the move keeps them (copy mode).
+ */
+
+use std::fs::{self, File};
+use std::io::{Read, Write};
+use std::path::{Path, PathBuf};
+use serde::{Deserialize, Serialize};
+use sqlx::SqlitePool;
+use anyhow::{bail, Context, Result};
+use walkdir::WalkDir;
+
+use crate::image_processor::hash_file;
+use crate::library_paths::{LibraryRoot, database_dir, is_relative, root_setting};
+
+/// Progress of a library move
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum LibraryMoveState {
+    /// Files are being copied; the database still points at the old root
+    Copying,
+    /// The database points at the new root; old files are being removed
+    Switched,
+    Done,
+    Cancelled,
+    Failed,
+}
+
+impl LibraryMoveState {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            LibraryMoveState::Copying => "copying",
+            LibraryMoveState::Switched => "switched",
+            LibraryMoveState::Done => "done",
+            LibraryMoveState::Cancelled => "cancelled",
+            LibraryMoveState::Failed => "failed",
+        }
+    }
+
+    pub fn parse(value: &str) -> Option<Self> {
+        let state = match value {
+            "copying" => LibraryMoveState::Copying,
+            "switched" => LibraryMoveState::Switched,
+            "done" => LibraryMoveState::Done,
+            "cancelled" => LibraryMoveState::Cancelled,
+            "failed" => LibraryMoveState::Failed,
+            _ => return None,
+        };
+        Some(state)
+    }
+
+    /// Whether the move no longer needs attention
+    pub fn is_finished(&self) -> bool {
+        matches!(self, LibraryMoveState::Done | LibraryMoveState::Cancelled | LibraryMoveState::Failed)
+    }
+}
+
+/// A persisted library move
+#[derive(Debug, Clone, Serialize, Deserialize, sqlx::FromRow)]
+pub struct LibraryMove {
+    pub id: i64,
+    pub source_root: String,
+    pub target_root: String,
+    pub copy_mode: bool,
+    pub state: String,
+    pub error: Option<String>,
+    pub created_at: String,
+    pub updated_at: String,
+}
+
+impl LibraryMove {
+    pub fn move_state(&self) -> LibraryMoveState {
+        LibraryMoveState::parse(&self.state).unwrap_or(LibraryMoveState::Failed)
+    }
+}
+
+/// A file of a library move
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, sqlx::FromRow)]
+pub struct LibraryMoveFile {
+    pub source_path: String,
+    pub target_path: String,
+    pub size: i64,
+    pub content_hash: Option<String>, // Set once the copy has been verified
+}
+
+/// Record a new move (state `copying`)
+pub async fn create_library_move(pool: &SqlitePool, source_root: &str, target_root: &str, copy_mode: bool) -> Result<i64> {
+    let result = sqlx::query(
+        "INSERT INTO library_moves (source_root, target_root, copy_mode, state) VALUES (?1, ?2, ?3, 'copying')"
+    )
+    .bind(source_root)
+    .bind(target_root)
+    .bind(copy_mode)
+    .execute(pool)
+    .await?;
+
+    Ok(result.last_insert_rowid())
+}
+
+pub async fn get_library_move(pool: &SqlitePool, move_id: i64) -> Result<Option<LibraryMove>> {
+    let library_move = sqlx::query_as::<_, LibraryMove>(
+        "SELECT id, source_root, target_root, copy_mode, state, error, created_at, updated_at FROM library_moves WHERE id = ?1"
+    )
+    .bind(move_id)
+    .fetch_optional(pool)
+    .await?;
+
+    Ok(library_move)
+}
+
+/// Get the move that stopped before finishing, if any
+pub async fn get_unfinished_library_move(pool: &SqlitePool) -> Result<Option<LibraryMove>> {
+    let library_move = sqlx::query_as::<_, LibraryMove>(
+        "SELECT id, source_root, target_root, copy_mode, state, error, created_at, updated_at FROM library_moves WHERE state NOT IN ('done', 'cancelled', 'failed') ORDER BY id DESC LIMIT 1"
+    )
+    .fetch_optional(pool)
+    .await?;
+
+    Ok(library_move)
+}
+
+/// Update the state of a move; finished moves drop their file list
+pub async fn set_library_move_state(
+    pool: &SqlitePool,
+    move_id: i64,
+    state: LibraryMoveState,
+    error: Option<&str>,
+) -> Result<()> {
+    sqlx::query("UPDATE library_moves SET state = ?1, error = ?2, updated_at = CURRENT_TIMESTAMP WHERE id = ?3")
+        .bind(state.as_str())
+        .bind(error)
+        .bind(move_id)
+        .execute(pool)
+        .await?;
+
+    if state.is_finished() {
+        sqlx::query("DELETE FROM library_move_files WHERE move_id = ?1")
+            .bind(move_id)
+            .execute(pool)
+            .await?;
+    }
+
+    Ok(())
+}
+
+/// Add files to a move; files already recorded are kept as they are. Returns the number added.
+pub async fn add_library_move_files(pool: &SqlitePool, move_id: i64, files: &[LibraryMoveFile]) -> Result<usize> {
+    let mut tx = pool.begin().await?;
+    let mut added = 0;
+
+    for file in files {
+        let result = sqlx::query(
+            "INSERT OR IGNORE INTO library_move_files (move_id, source_path, target_path, size) VALUES (?1, ?2, ?3, ?4)"
+        )
+        .bind(move_id)
+        .bind(&file.source_path)
+        .bind(&file.target_path)
+        .bind(file.size)
+        .execute(&mut *tx)
+        .await?;
+        added += result.rows_affected() as usize;
+    }
+
+    tx.commit().await?;
+    Ok(added)
+}
+
+/// Get the files of a move in the order they were planned
+pub async fn get_library_move_files(pool: &SqlitePool, move_id: i64) -> Result<Vec<LibraryMoveFile>> {
+    let files = sqlx::query_as::<_, LibraryMoveFile>(
+        "SELECT source_path, target_path, size, content_hash FROM library_move_files WHERE move_id = ?1 ORDER BY rowid"
+    )
+    .bind(move_id)
+    .fetch_all(pool)
+    .await?;
+
+    Ok(files)
+}
+
+/// Record that a file was copied and verified
+pub async fn mark_library_move_file_copied(pool: &SqlitePool, move_id: i64, source_path: &str, content_hash: &str) -> Result<()> {
+    sqlx::query("UPDATE library_move_files SET content_hash = ?1 WHERE move_id = ?2 AND source_path = ?3")
+        .bind(content_hash)
+        .bind(move_id)
+        .bind(source_path)
+        .execute(pool)
+        .await?;
+
+    Ok(())
+}
+
+/// Roll folder as stored once the library lives under the new root
+///
+/// Rolls inside the old root keep their relative path; rolls stored outside
+/// it are brought into the new root as `<year>/<roll code>`.
+pub fn moved_roll_path(stored: &str) -> String {
+    if is_relative(stored) {
+        return stored.to_string();
+    }
+    let path = Path::new(stored);
+    let name = |path: Option<&Path>| path.and_then(|p| p.file_name()).map(|n| n.to_string_lossy().to_string());
+    match (name(path.parent()), name(Some(path))) {
+        (Some(year), Some(code)) => format!("{}/{}", year, code),
+        (None, Some(code)) => code,
+        _ => stored.to_string(),
+    }
+}
+
+/// Every file below a roll folder and where it goes (blocking)
+pub fn plan_roll_folder(source_dir: &Path, target_dir: &Path) -> Result<Vec<LibraryMoveFile>> {
+    let mut files = Vec::new();
+    for entry in WalkDir::new(source_dir).sort_by_file_name() {
+        let entry = entry.with_context(|| format!("Failed to read {:?}", source_dir))?;
+        if !entry.file_type().is_file() {
+            continue;
+        }
+        let relative = entry.path().strip_prefix(source_dir)?;
+        let size = entry.metadata().map(|meta| meta.len()).unwrap_or(0);
+        files.push(LibraryMoveFile {
+            source_path: entry.path().to_string_lossy().to_string(),
+            target_path: target_dir.join(relative).to_string_lossy().to_string(),
+            size: size as i64,
+            content_hash: None,
+        });
+    }
+    Ok(files)
+}
+
+/// Copy a file and verify the copy against its source (blocking); returns the BLAKE3 hash
+///
+/// The copy is written to `<target>.part` and only renamed into place once its
+/// hash matches, so a complete-looking target is always a verified one. A
+/// target left by an earlier, interrupted run is accepted if it matches.
+pub fn copy_verified(source: &Path, target: &Path) -> Result<String> {
+    if target.exists() {
+        let source_hash = hash_file(source)?;
+        if hash_file(target)? == source_hash {
+            return Ok(source_hash);
+        }
+        bail!("{:?} already exists with different content", target);
+    }
+
+    if let Some(parent) = target.parent() {
+        fs::create_dir_all(parent).with_context(|| format!("Failed to create {:?}", parent))?;
+    }
+    let mut part_name = target.file_name().unwrap_or_default().to_os_string();
+    part_name.push(".part");
+    let part = target.with_file_name(part_name);
+
+    // Hash the source while copying it so it is read only once
+    let source_hash = {
+        let mut reader = File::open(source).with_context(|| format!("Failed to open {:?}", source))?;
+        let mut writer = File::create(&part).with_context(|| format!("Failed to create {:?}", part))?;
+        let mut hasher = blake3::Hasher::new();
+        let mut buffer = vec![0u8; 1 << 20];
+        loop {
+            let read = reader.read(&mut buffer).with_context(|| format!("Failed to read {:?}", source))?;
+            if read == 0 {
+                break;
+            }
+            hasher.update(&buffer[..read]);
+            writer.write_all(&buffer[..read]).with_context(|| format!("Failed to write {:?}", part))?;
+        }
+        writer.sync_all().with_context(|| format!("Failed to write {:?}", part))?;
+        hasher.finalize().to_hex().to_string()
+    };
+
+    if hash_file(&part)? != source_hash {
+        let _ = fs::remove_file(&part);
+        bail!("Copy of {:?} does not match the original", source);
+    }
+    fs::rename(&part, target).with_context(|| format!("Failed to rename {:?}", part))?;
+
+    Ok(source_hash)
+}
+
+/// Remove files (blocking), then any folders left empty up to `stop_at`
+pub fn remove_moved_files<'a, I>(paths: I, stop_at: &Path) -> Result<()>
+where
+    I: IntoIterator<Item = &'a str>,
+{
+    let mut parents = std::collections::BTreeSet::new();
+    for path in paths {
+        let path = Path::new(path);
+        if path.exists() {
+            fs::remove_file(path).with_context(|| format!("Failed to remove {:?}", path))?;
+        }
+        if let Some(parent) = path.parent() {
+            parents.insert(parent.to_path_buf());
+        }
+    }
+
+    // Deepest folders first; a folder that still has files stops the climb
+    for parent in parents.into_iter().rev() {
+        let mut dir: Option<PathBuf> = Some(parent);
+        while let Some(current) = dir {
+            if current == stop_at || !current.starts_with(stop_at) || fs::remove_dir(&current).is_err() {
+                break;
+            }
+            dir = current.parent().map(Path::to_path_buf);
+        }
+    }
+    Ok(())
+}
+
+/// Point the database at the new root in one transaction (state becomes `switched`)
+pub async fn switch_library_root(pool: &SqlitePool, library_move: &LibraryMove) -> Result<()> {
+    let database_dir = database_dir(pool).await?;
+    let mut tx = pool.begin().await?;
+
+    // Only a move that is still copying may switch; an abandoned one has lost its copies
+    let claimed = sqlx::query("UPDATE library_moves SET state = 'switched', updated_at = CURRENT_TIMESTAMP WHERE id = ?1 AND state = 'copying'")
+        .bind(library_move.id)
+        .execute(&mut *tx)
+        .await?;
+    if claimed.rows_affected() == 0 {
+        bail!("Library move {} is no longer copying", library_move.id);
+    }
+
+    sqlx::query("UPDATE settings SET value = ?1, updated_at = CURRENT_TIMESTAMP WHERE key = 'library_root'")
+        .bind(root_setting(&library_move.target_root, database_dir.as_deref()))
+        .execute(&mut *tx)
+        .await?;
+
+    // Rolls stored outside the old root were copied into the new one
+    let rolls = sqlx::query_as::<_, (i64, String)>("SELECT id, path FROM rolls")
+        .fetch_all(&mut *tx)
+        .await?;
+    for (roll_id, stored) in rolls.into_iter().filter(|(_, stored)| !stored.is_empty() && !is_relative(stored)) {
+        let moved = moved_roll_path(&stored);
+        let new_root = LibraryRoot::new(&library_move.target_root);
+        let new_dir = new_root.resolve(&moved);
+        let rebase = |path: &str| match Path::new(path).strip_prefix(&stored) {
+            Ok(rest) => new_root.to_stored(&Path::new(&new_dir).join(rest).to_string_lossy()),
+            Err(_) => path.to_string(),
+        };
+
+        sqlx::query("UPDATE rolls SET path = ?1, updated_at = CURRENT_TIMESTAMP WHERE id = ?2")
+            .bind(&moved)
+            .bind(roll_id)
+            .execute(&mut *tx)
+            .await?;
+
+        let photos = sqlx::query_as::<_, (i64, String, Option<String>, Option<String>)>(
+            "SELECT id, file_path, thumbnail_path, preview_path FROM photos WHERE roll_id = ?1"
+        )
+        .bind(roll_id)
+        .fetch_all(&mut *tx)
+        .await?;
+        for (photo_id, file_path, thumbnail_path, preview_path) in photos {
+            sqlx::query("UPDATE photos SET file_path = ?1, thumbnail_path = ?2, preview_path = ?3 WHERE id = ?4")
+                .bind(rebase(&file_path))
+                .bind(thumbnail_path.as_deref().map(rebase))
+                .bind(preview_path.as_deref().map(rebase))
+                .bind(photo_id)
+                .execute(&mut *tx)
+                .await?;
+        }
+    }
+
+    tx.commit().await?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_state_round_trip() {
+        for state in [
+            LibraryMoveState::Copying, LibraryMoveState::Switched, LibraryMoveState::Done,
+            LibraryMoveState::Cancelled, LibraryMoveState::Failed,
+        ] {
+            assert_eq!(LibraryMoveState::parse(state.as_str()), Some(state));
+        }
+    }
+
+    #[test]
+    fn test_moved_roll_path() {
+        assert_eq!(moved_roll_path("2024/00000001"), "2024/00000001");
+        assert_eq!(moved_roll_path("/Volumes/Old/2023/0000000A"), "2023/0000000A");
+    }
+
+    #[test]
+    fn test_copy_plan_and_remove() {
+        let base = std::env::temp_dir().join(format!("filmvault_library_move_test_{}", std::process::id()));
+        let source_root = base.join("old");
+        let roll_dir = source_root.join("2024").join("00000001");
+        fs::create_dir_all(roll_dir.join("originals")).unwrap();
+        fs::create_dir_all(roll_dir.join("thumbnails")).unwrap();
+        fs::write(roll_dir.join("originals").join("001.jpg"), b"frame one").unwrap();
+        fs::write(roll_dir.join("thumbnails").join("001.webp"), b"thumb").unwrap();
+
+        let target_dir = base.join("new").join("2024").join("00000001");
+        let files = plan_roll_folder(&roll_dir, &target_dir).unwrap();
+        assert_eq!(files.len(), 2);
+        assert_eq!(files[0].target_path, target_dir.join("originals").join("001.jpg").to_string_lossy());
+
+        for file in &files {
+            let hash = copy_verified(Path::new(&file.source_path), Path::new(&file.target_path)).unwrap();
+            assert_eq!(hash, hash_file(Path::new(&file.source_path)).unwrap());
+        }
+        assert_eq!(fs::read(target_dir.join("originals").join("001.jpg")).unwrap(), b"frame one");
+
+        // Re-running after an interruption accepts the verified copies, but not a different file
+        copy_verified(Path::new(&files[0].source_path), Path::new(&files[0].target_path)).unwrap();
+        fs::write(&files[1].target_path, b"something else").unwrap();
+        assert!(copy_verified(Path::new(&files[1].source_path), Path::new(&files[1].target_path)).is_err());
+
+        remove_moved_files(files.iter().map(|file| file.source_path.as_str()), &source_root).unwrap();
+        assert!(source_root.exists());
+        assert!(!source_root.join("2024").exists());
+
+        fs::remove_dir_all(&base).unwrap();
+    }
+}
@@ -0,0 +1,190 @@
+/*!
+ * FilmVault Library Paths
+ *
+ * Roll and photo paths inside the library are stored relative to the library
+ * root with `/` separators, so the library can be moved, mounted under
+ * another drive letter or opened from another machine. Paths outside the root
+ * stay absolute. `database` resolves stored paths on read and relativizes
+ * them on write, so the rest of the app only sees absolute paths.
+ */
+
+use std::path::{Component, Path, PathBuf};
+use anyhow::Result;
+use sqlx::SqlitePool;
+
+/// The library root relative paths are resolved against
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct LibraryRoot(Option<PathBuf>);
+
+impl LibraryRoot {
+    /// An empty `path` means no library root is configured yet
+    pub fn new(path: &str) -> Self {
+        Self((!path.trim().is_empty()).then(|| PathBuf::from(path.trim())))
+    }
+
+    /// Root from its setting value; a relative value is relative to the database's folder
+    pub fn from_setting(value: &str, database_dir: Option<&Path>) -> Self {
+        match database_dir {
+            Some(dir) if is_relative(value.trim()) => {
+                let mut root = dir.to_path_buf();
+                for part in value.trim().split('/') {
+                    match part {
+                        "" | "." => {}
+                        ".." => {
+                            root.pop();
+                        }
+                        part => root.push(part),
+                    }
+                }
+                Self(Some(root))
+            }
+            _ => Self::new(value),
+        }
+    }
+
+    /// Read the configured library root
+    pub async fn load(pool: &SqlitePool) -> Result<Self> {
+        let root: Option<String> = sqlx::query_scalar("SELECT value FROM settings WHERE key = 'library_root'")
+            .fetch_optional(pool)
+            .await?;
+        let database_dir = database_dir(pool).await?;
+        Ok(Self::from_setting(root.as_deref().unwrap_or_default(), database_dir.as_deref()))
+    }
+
+    /// Absolute path of a stored path
+    pub fn resolve(&self, stored: &str) -> String {
+        match &self.0 {
+            Some(root) if is_relative(stored) => {
+                let mut path = root.clone();
+                path.extend(stored.split('/').filter(|part| !part.is_empty()));
+                path.to_string_lossy().to_string()
+            }
+            _ => stored.to_string(),
+        }
+    }
+
+    pub fn resolve_opt(&self, stored: Option<String>) -> Option<String> {
+        stored.map(|stored| self.resolve(&stored))
+    }
+
+    /// Path to store: relative to the root when inside it, unchanged otherwise
+    pub fn to_stored(&self, path: &str) -> String {
+        let Some(root) = &self.0 else {
+            return path.to_string();
+        };
+        match Path::new(path).strip_prefix(root) {
+            Ok(relative) if relative.components().all(|c| matches!(c, Component::Normal(_))) => {
+                let parts: Vec<_> = relative.components()
+                    .map(|c| c.as_os_str().to_string_lossy().to_string())
+                    .collect();
+                if parts.is_empty() {
+                    path.to_string()
+                } else {
+                    parts.join("/")
+                }
+            }
+            _ => path.to_string(),
+        }
+    }
+
+    pub fn to_stored_opt(&self, path: Option<&str>) -> Option<String> {
+        path.map(|path| self.to_stored(path))
+    }
+}
+
+impl std::fmt::Display for LibraryRoot {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match &self.0 {
+            Some(root) => write!(f, "{}", root.display()),
+            None => Ok(()),
+        }
+    }
+}
+
+/// The open database file (None for in-memory databases)
+pub async fn database_file(pool: &SqlitePool) -> Result<Option<PathBuf>> {
+    let file: Option<String> = sqlx::query_scalar("SELECT file FROM pragma_database_list WHERE name = 'main'")
+        .fetch_optional(pool)
+        .await?;
+    Ok(file.filter(|file| !file.is_empty()).map(PathBuf::from))
+}
+
+/// Folder of the open database file (None for in-memory databases)
+pub async fn database_dir(pool: &SqlitePool) -> Result<Option<PathBuf>> {
+    Ok(database_file(pool).await?.and_then(|file| file.parent().map(Path::to_path_buf)))
+}
+
+/// Setting value for a library root
+///
+/// When the database lives inside the root the value is relative to the
+/// database's folder (`.`, `..`), so a library that carries its own database
+/// keeps working wherever its drive is mounted.
+pub fn root_setting(root: &str, database_dir: Option<&Path>) -> String {
+    if root.trim().is_empty() {
+        return root.to_string();
+    }
+    match database_dir.map(|dir| dir.strip_prefix(root.trim())) {
+        Some(Ok(rest)) => match rest.components().count() {
+            0 => ".".to_string(),
+            depth => vec![".."; depth].join("/"),
+        },
+        _ => root.to_string(),
+    }
+}
+
+/// Whether a stored path is relative to the library root
+///
+/// Checked textually so a library written on Windows (`D:\...`) still reads
+/// as absolute on other platforms and vice versa.
+pub fn is_relative(stored: &str) -> bool {
+    let bytes = stored.as_bytes();
+    let drive = bytes.len() >= 2 && bytes[0].is_ascii_alphabetic() && bytes[1] == b':';
+    !stored.is_empty() && !stored.starts_with('/') && !stored.starts_with('\\') && !drive
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_round_trip() {
+        let root = LibraryRoot::new("/Volumes/Film/Library");
+        let path = "/Volumes/Film/Library/2024/00000001/originals/ROLL_00000001_001.jpg";
+
+        let stored = root.to_stored(path);
+        assert_eq!(stored, "2024/00000001/originals/ROLL_00000001_001.jpg");
+        assert_eq!(root.resolve(&stored), path);
+
+        // Same library mounted somewhere else
+        let moved = LibraryRoot::new("/mnt/film/Library");
+        assert_eq!(moved.resolve(&stored), "/mnt/film/Library/2024/00000001/originals/ROLL_00000001_001.jpg");
+    }
+
+    #[test]
+    fn test_paths_outside_root_stay_absolute() {
+        let root = LibraryRoot::new("/Volumes/Film/Library");
+        assert_eq!(root.to_stored("/Volumes/Film/Other/x.jpg"), "/Volumes/Film/Other/x.jpg");
+        assert_eq!(root.to_stored("/Volumes/Film/Library"), "/Volumes/Film/Library");
+        assert_eq!(root.resolve("/Volumes/Film/Other/x.jpg"), "/Volumes/Film/Other/x.jpg");
+        assert_eq!(root.resolve("D:\\Film\\x.jpg"), "D:\\Film\\x.jpg");
+        assert_eq!(root.resolve(""), "");
+
+        // Without a root nothing is relativized
+        assert_eq!(LibraryRoot::new("").to_stored("/a/b.jpg"), "/a/b.jpg");
+    }
+
+    #[test]
+    fn test_root_relative_to_database() {
+        // Database at the top of the library drive, or in a folder inside it
+        let dir = Path::new("/Volumes/Studio");
+        assert_eq!(root_setting("/Volumes/Studio", Some(dir)), ".");
+        assert_eq!(root_setting("/Volumes/Studio", Some(&dir.join(".filmvault"))), "..");
+        assert_eq!(root_setting("/Volumes/Other", Some(dir)), "/Volumes/Other");
+
+        // Same drive mounted elsewhere
+        let mounted = Path::new("/media/studio/.filmvault");
+        assert_eq!(LibraryRoot::from_setting("..", Some(mounted)), LibraryRoot::new("/media/studio"));
+        assert_eq!(LibraryRoot::from_setting("/Volumes/Other", Some(mounted)), LibraryRoot::new("/Volumes/Other"));
+        assert_eq!(LibraryRoot::from_setting("", Some(mounted)), LibraryRoot::new(""));
+    }
+}
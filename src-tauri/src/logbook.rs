@@ -0,0 +1,597 @@
+/*!
+ * FilmVault Logbook Import
+ *
+ * Parses shot logs exported by phone logbook apps (Lightme Logbook, Exif Notes,
+ * FilmLog, ...) as CSV or JSON and matches the entries to a roll's photos.
+ * Column names differ between apps, so headers are recognised by synonyms.
+ */
+
+use std::collections::HashMap;
+use std::path::Path;
+use serde::{Deserialize, Serialize};
+use anyhow::{anyhow, Result};
+use chrono::NaiveDateTime;
+use regex::Regex;
+
+use crate::database::ShotLogPatch;
+
+/// One exposure read from a logbook file
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct LogbookEntry {
+    /// Position of the entry in the file (0-based)
+    pub index: usize,
+    pub frame_number: Option<i64>,
+    pub aperture: Option<f64>,
+    pub shutter_speed: Option<String>,
+    pub focal_length: Option<f64>,
+    pub exposure_compensation: Option<f64>,
+    pub metering_mode: Option<String>,
+    pub flash: Option<bool>,
+    pub notes: Option<String>,
+    /// Capture time as "YYYY-MM-DD HH:MM:SS"
+    pub shot_at: Option<String>,
+    pub lat: Option<f64>,
+    pub lon: Option<f64>,
+}
+
+impl LogbookEntry {
+    /// Convert to the shot log fields to set on the matched photo; empty fields are left unchanged
+    pub fn to_patch(&self) -> ShotLogPatch {
+        ShotLogPatch {
+            aperture: self.aperture.map(Some),
+            shutter_speed: self.shutter_speed.clone().map(Some),
+            focal_length: self.focal_length.map(Some),
+            exposure_compensation: self.exposure_compensation.map(Some),
+            metering_mode: self.metering_mode.clone().map(Some),
+            flash: self.flash.map(Some),
+            notes: self.notes.clone().map(Some),
+            shot_at: self.shot_at.clone().map(Some),
+        }
+    }
+
+    fn is_empty(&self) -> bool {
+        *self == LogbookEntry { index: self.index, ..Default::default() }
+    }
+}
+
+/// How logbook entries are paired with photos
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum MatchMode {
+    /// n-th entry (by frame number) goes to the n-th photo in roll order
+    FrameOrder,
+    /// Entry frame number matches the photo's frame number, or the `_NNN`
+    /// sequence in its filename when the photo has no numeric frame number
+    FilenameSequence,
+}
+
+/// A proposed pairing of a logbook entry with a photo
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct LogbookMatch {
+    pub photo_id: i64,
+    pub filename: String,
+    pub entry: LogbookEntry,
+}
+
+/// Reviewable result of matching a logbook against a roll
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct MatchPlan {
+    pub matches: Vec<LogbookMatch>,
+    pub unmatched_entries: Vec<LogbookEntry>,
+    pub unmatched_photo_ids: Vec<i64>,
+}
+
+/// Logbook columns we know how to read
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Field {
+    Frame,
+    Aperture,
+    Shutter,
+    FocalLength,
+    ExposureCompensation,
+    Metering,
+    Flash,
+    Notes,
+    DateTime,
+    Date,
+    Time,
+    Latitude,
+    Longitude,
+    Location,
+}
+
+/// Lowercase a header and drop everything but letters and digits
+fn normalize_key(key: &str) -> String {
+    key.chars()
+        .filter(|c| c.is_alphanumeric())
+        .flat_map(char::to_lowercase)
+        .collect()
+}
+
+/// Recognise a column header from the various logbook apps
+fn field_for_key(key: &str) -> Option<Field> {
+    let field = match normalize_key(key).as_str() {
+        "frame" | "framenumber" | "frameno" | "framecount" | "count" | "exposure"
+        | "exposurenumber" | "shot" | "shotnumber" | "no" | "number" => Field::Frame,
+        "aperture" | "fstop" | "fnumber" | "f" => Field::Aperture,
+        "shutter" | "shutterspeed" | "speed" | "exposuretime" => Field::Shutter,
+        "focallength" | "focal" | "focallengthmm" => Field::FocalLength,
+        "exposurecompensation" | "exposurecomp" | "compensation" | "ev" | "evcomp" => Field::ExposureCompensation,
+        "metering" | "meteringmode" => Field::Metering,
+        "flash" | "flashused" | "flashon" => Field::Flash,
+        "note" | "notes" | "comment" | "comments" | "description" | "remark" | "remarks" => Field::Notes,
+        "datetime" | "timestamp" | "taken" | "datetaken" | "capturetime" => Field::DateTime,
+        "date" => Field::Date,
+        "time" => Field::Time,
+        "latitude" | "lat" | "gpslatitude" => Field::Latitude,
+        "longitude" | "lon" | "lng" | "long" | "gpslongitude" => Field::Longitude,
+        "location" | "gps" | "coordinates" | "position" => Field::Location,
+        _ => return None,
+    };
+    Some(field)
+}
+
+/// Parse a decimal or fraction, e.g. "2.8", "+0.7", "-1/3", "-1 1/3"
+fn parse_number(value: &str) -> Option<f64> {
+    let value = value.trim().replace('\u{2212}', "-").replace(',', ".");
+    let (sign, rest) = match value.strip_prefix('-') {
+        Some(rest) => (-1.0, rest.trim()),
+        None => (1.0, value.trim_start_matches('+').trim()),
+    };
+
+    let mut total = 0.0;
+    for part in rest.split_whitespace() {
+        total += match part.split_once('/') {
+            Some((num, den)) => {
+                let den: f64 = den.parse().ok()?;
+                if den == 0.0 {
+                    return None;
+                }
+                num.parse::<f64>().ok()? / den
+            }
+            None => part.parse::<f64>().ok()?,
+        };
+    }
+
+    if rest.is_empty() {
+        return None;
+    }
+    Some(sign * total)
+}
+
+/// Parse a number after stripping a unit prefix/suffix ("f/2.8", "50mm", "+1 EV")
+fn parse_with_unit(value: &str, prefixes: &[&str], suffixes: &[&str]) -> Option<f64> {
+    let mut value = value.trim().to_lowercase();
+    for prefix in prefixes {
+        if let Some(rest) = value.strip_prefix(prefix) {
+            value = rest.trim().to_string();
+        }
+    }
+    for suffix in suffixes {
+        if let Some(rest) = value.strip_suffix(suffix) {
+            value = rest.trim().to_string();
+        }
+    }
+    parse_number(&value)
+}
+
+fn parse_flash(value: &str) -> Option<bool> {
+    match value.trim().to_lowercase().as_str() {
+        "1" | "true" | "yes" | "y" | "on" | "fired" => Some(true),
+        "0" | "false" | "no" | "n" | "off" | "none" | "not fired" => Some(false),
+        _ => None,
+    }
+}
+
+/// Normalise a timestamp to "YYYY-MM-DD HH:MM:SS"
+fn parse_timestamp(value: &str) -> Option<String> {
+    let value = value.trim();
+
+    if let Ok(dt) = chrono::DateTime::parse_from_rfc3339(value) {
+        return Some(dt.naive_local().format("%Y-%m-%d %H:%M:%S").to_string());
+    }
+
+    const FORMATS: &[&str] = &[
+        "%Y-%m-%d %H:%M:%S",
+        "%Y-%m-%dT%H:%M:%S",
+        "%Y-%m-%dT%H:%M:%S%.f",
+        "%Y-%m-%d %H:%M",
+        "%Y-%m-%dT%H:%M",
+        "%Y:%m:%d %H:%M:%S",
+        "%Y/%m/%d %H:%M:%S",
+        "%Y/%m/%d %H:%M",
+        "%d.%m.%Y %H:%M:%S",
+        "%d.%m.%Y %H:%M",
+        "%m/%d/%Y %H:%M:%S",
+        "%m/%d/%Y %H:%M",
+    ];
+
+    FORMATS.iter()
+        .find_map(|format| NaiveDateTime::parse_from_str(value, format).ok())
+        .map(|dt| dt.format("%Y-%m-%d %H:%M:%S").to_string())
+}
+
+/// Parse "lat, lon" (or "lat lon") into coordinates
+fn parse_location(value: &str) -> Option<(f64, f64)> {
+    let parts: Vec<&str> = value
+        .split(|c: char| c == ',' || c == ';' || c.is_whitespace())
+        .filter(|p| !p.is_empty())
+        .collect();
+    if parts.len() != 2 {
+        return None;
+    }
+    let lat = parts[0].parse::<f64>().ok()?;
+    let lon = parts[1].parse::<f64>().ok()?;
+    Some((lat, lon))
+}
+
+/// Collects one entry's raw fields; date and time may arrive in separate columns
+#[derive(Default)]
+struct EntryBuilder {
+    entry: LogbookEntry,
+    date: Option<String>,
+    time: Option<String>,
+}
+
+impl EntryBuilder {
+    fn new(index: usize) -> Self {
+        EntryBuilder {
+            entry: LogbookEntry { index, ..Default::default() },
+            ..Default::default()
+        }
+    }
+
+    fn set(&mut self, field: Field, value: &str) {
+        let value = value.trim();
+        if value.is_empty() {
+            return;
+        }
+
+        let entry = &mut self.entry;
+        match field {
+            Field::Frame => {
+                entry.frame_number = value.trim_start_matches('#').parse::<i64>().ok().or(entry.frame_number);
+            }
+            Field::Aperture => {
+                entry.aperture = parse_with_unit(value, &["f/", "f"], &[]).filter(|a| *a > 0.0);
+            }
+            Field::Shutter => {
+                let shutter = value.trim_end_matches(['s', 'S', '"']).trim().replace(' ', "");
+                if parse_number(&shutter).map(|v| v > 0.0).unwrap_or(false) {
+                    entry.shutter_speed = Some(shutter);
+                }
+            }
+            Field::FocalLength => {
+                entry.focal_length = parse_with_unit(value, &[], &["mm"]).filter(|f| *f > 0.0);
+            }
+            Field::ExposureCompensation => {
+                entry.exposure_compensation = parse_with_unit(value, &[], &["ev"]);
+            }
+            Field::Metering => entry.metering_mode = Some(value.to_string()),
+            Field::Flash => entry.flash = parse_flash(value),
+            Field::Notes => entry.notes = Some(value.to_string()),
+            Field::DateTime => entry.shot_at = parse_timestamp(value),
+            Field::Date => self.date = Some(value.to_string()),
+            Field::Time => self.time = Some(value.to_string()),
+            Field::Latitude => entry.lat = value.parse().ok(),
+            Field::Longitude => entry.lon = value.parse().ok(),
+            Field::Location => {
+                if let Some((lat, lon)) = parse_location(value) {
+                    entry.lat = Some(lat);
+                    entry.lon = Some(lon);
+                }
+            }
+        }
+    }
+
+    fn build(mut self) -> LogbookEntry {
+        if self.entry.shot_at.is_none() {
+            self.entry.shot_at = match (&self.date, &self.time) {
+                (Some(date), Some(time)) => parse_timestamp(&format!("{} {}", date, time)),
+                (Some(date), None) => parse_timestamp(date),
+                _ => None,
+            };
+        }
+        self.entry
+    }
+}
+
+/// Guess the delimiter from the first non-empty line
+fn detect_delimiter(content: &str) -> u8 {
+    let line = content.lines().find(|l| !l.trim().is_empty()).unwrap_or("");
+    [b',', b';', b'\t']
+        .into_iter()
+        .max_by_key(|d| line.bytes().filter(|b| b == d).count())
+        .unwrap_or(b',')
+}
+
+/// Parse a CSV logbook
+///
+/// Some apps put roll metadata above the table, so the header is the first
+/// row with at least two recognised columns.
+pub fn parse_csv(content: &str) -> Result<Vec<LogbookEntry>> {
+    let content = content.trim_start_matches('\u{feff}');
+    let mut reader = csv::ReaderBuilder::new()
+        .delimiter(detect_delimiter(content))
+        .has_headers(false)
+        .flexible(true)
+        .from_reader(content.as_bytes());
+
+    let mut columns: Option<Vec<Option<Field>>> = None;
+    let mut entries = Vec::new();
+
+    for record in reader.records() {
+        let record = record?;
+
+        let Some(ref fields) = columns else {
+            let candidate: Vec<Option<Field>> = record.iter().map(field_for_key).collect();
+            if candidate.iter().filter(|f| f.is_some()).count() >= 2 {
+                columns = Some(candidate);
+            }
+            continue;
+        };
+
+        let mut builder = EntryBuilder::new(entries.len());
+        for (value, field) in record.iter().zip(fields.iter()) {
+            if let Some(field) = field {
+                builder.set(*field, value);
+            }
+        }
+
+        let entry = builder.build();
+        if !entry.is_empty() {
+            entries.push(entry);
+        }
+    }
+
+    if columns.is_none() {
+        return Err(anyhow!("No recognisable header row (frame, aperture, shutter, ...) found"));
+    }
+
+    Ok(entries)
+}
+
+/// Apply the keys of a JSON object (and of nested objects, e.g. "location") to an entry
+fn apply_json_object(builder: &mut EntryBuilder, object: &serde_json::Map<String, serde_json::Value>) {
+    for (key, value) in object {
+        match value {
+            serde_json::Value::Object(nested) => apply_json_object(builder, nested),
+            serde_json::Value::String(s) => {
+                if let Some(field) = field_for_key(key) {
+                    builder.set(field, s);
+                }
+            }
+            serde_json::Value::Number(n) => {
+                if let Some(field) = field_for_key(key) {
+                    builder.set(field, &n.to_string());
+                }
+            }
+            serde_json::Value::Bool(b) => {
+                if let Some(field) = field_for_key(key) {
+                    builder.set(field, if *b { "true" } else { "false" });
+                }
+            }
+            _ => {}
+        }
+    }
+}
+
+/// Find the array of exposures: the top-level array, or the first array of objects
+fn find_entry_array(value: &serde_json::Value) -> Option<&Vec<serde_json::Value>> {
+    match value {
+        serde_json::Value::Array(items) if items.iter().all(|i| i.is_object()) => Some(items),
+        serde_json::Value::Object(object) => object.values().find_map(find_entry_array),
+        _ => None,
+    }
+}
+
+/// Parse a JSON logbook
+pub fn parse_json(content: &str) -> Result<Vec<LogbookEntry>> {
+    let value: serde_json::Value = serde_json::from_str(content.trim_start_matches('\u{feff}'))?;
+    let items = find_entry_array(&value)
+        .ok_or_else(|| anyhow!("No list of exposures found in JSON logbook"))?;
+
+    let mut entries = Vec::new();
+    for item in items {
+        if let Some(object) = item.as_object() {
+            let mut builder = EntryBuilder::new(entries.len());
+            apply_json_object(&mut builder, object);
+            let entry = builder.build();
+            if !entry.is_empty() {
+                entries.push(entry);
+            }
+        }
+    }
+
+    Ok(entries)
+}
+
+/// Parse a logbook file, choosing the format by extension (content sniffing as fallback)
+pub fn parse_logbook_file(path: &Path) -> Result<Vec<LogbookEntry>> {
+    let content = std::fs::read_to_string(path)?;
+    let ext = path.extension()
+        .and_then(|e| e.to_str())
+        .map(|e| e.to_lowercase())
+        .unwrap_or_default();
+
+    match ext.as_str() {
+        "json" => parse_json(&content),
+        "csv" | "tsv" | "txt" => parse_csv(&content),
+        _ if content.trim_start().starts_with(['{', '[']) => parse_json(&content),
+        _ => parse_csv(&content),
+    }
+}
+
+/// Extract the `_NNN` frame sequence from an imported filename (ROLL_0000002A_007.jpg -> 7)
+pub fn filename_sequence(filename: &str) -> Option<i64> {
+    static SEQUENCE: std::sync::OnceLock<Regex> = std::sync::OnceLock::new();
+    let re = SEQUENCE.get_or_init(|| Regex::new(r"_(\d+)\.[A-Za-z0-9]+$").unwrap());
+    re.captures(filename)
+        .and_then(|c| c[1].parse().ok())
+}
+
+/// Frame a photo is matched on: its parsed frame number when numeric, else the filename sequence
+fn photo_frame(filename: &str, frame_number: Option<&str>) -> Option<i64> {
+    frame_number
+        .and_then(|frame| frame.parse().ok())
+        .or_else(|| filename_sequence(filename))
+}
+
+/// Pair logbook entries with photos (given as `(photo_id, filename, frame_number)` in roll order)
+pub fn match_entries(
+    photos: &[(i64, String, Option<String>)],
+    entries: &[LogbookEntry],
+    mode: MatchMode,
+) -> MatchPlan {
+    let mut matches = Vec::new();
+    let mut unmatched_photo_ids = Vec::new();
+    let mut used = vec![false; entries.len()];
+
+    match mode {
+        MatchMode::FrameOrder => {
+            // Entries in frame order when every entry has a frame number, otherwise file order
+            let mut order: Vec<usize> = (0..entries.len()).collect();
+            if entries.iter().all(|e| e.frame_number.is_some()) {
+                order.sort_by_key(|&i| entries[i].frame_number);
+            }
+
+            for (position, (photo_id, filename, _)) in photos.iter().enumerate() {
+                match order.get(position) {
+                    Some(&i) => {
+                        used[i] = true;
+                        matches.push(LogbookMatch {
+                            photo_id: *photo_id,
+                            filename: filename.clone(),
+                            entry: entries[i].clone(),
+                        });
+                    }
+                    None => unmatched_photo_ids.push(*photo_id),
+                }
+            }
+        }
+        MatchMode::FilenameSequence => {
+            // Entries without a frame number count by their position
+            let mut by_frame: HashMap<i64, usize> = HashMap::new();
+            for (i, entry) in entries.iter().enumerate() {
+                by_frame.entry(entry.frame_number.unwrap_or(i as i64 + 1)).or_insert(i);
+            }
+
+            for (photo_id, filename, frame_number) in photos {
+                match photo_frame(filename, frame_number.as_deref()).and_then(|frame| by_frame.get(&frame)) {
+                    Some(&i) if !used[i] => {
+                        used[i] = true;
+                        matches.push(LogbookMatch {
+                            photo_id: *photo_id,
+                            filename: filename.clone(),
+                            entry: entries[i].clone(),
+                        });
+                    }
+                    _ => unmatched_photo_ids.push(*photo_id),
+                }
+            }
+        }
+    }
+
+    let unmatched_entries = entries.iter()
+        .zip(used)
+        .filter(|(_, used)| !used)
+        .map(|(entry, _)| entry.clone())
+        .collect();
+
+    MatchPlan {
+        matches,
+        unmatched_entries,
+        unmatched_photo_ids,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_csv_with_preamble() {
+        let csv = "Roll,Portra 400\nCamera,Nikon FM2\n\nFrame,Aperture,Shutter Speed,Focal Length,Date,Time,Latitude,Longitude,Notes\n1,f/2.8,1/125,50mm,2024-05-01,14:32:10,35.6895,139.6917,backlit\n2,8,1/500s,50,2024-05-01,14:40,,,\n";
+        let entries = parse_csv(csv).unwrap();
+        assert_eq!(entries.len(), 2);
+        assert_eq!(entries[0].frame_number, Some(1));
+        assert_eq!(entries[0].aperture, Some(2.8));
+        assert_eq!(entries[0].shutter_speed.as_deref(), Some("1/125"));
+        assert_eq!(entries[0].focal_length, Some(50.0));
+        assert_eq!(entries[0].shot_at.as_deref(), Some("2024-05-01 14:32:10"));
+        assert_eq!(entries[0].lat, Some(35.6895));
+        assert_eq!(entries[0].notes.as_deref(), Some("backlit"));
+        assert_eq!(entries[1].shutter_speed.as_deref(), Some("1/500"));
+        assert_eq!(entries[1].shot_at.as_deref(), Some("2024-05-01 14:40:00"));
+        assert!(entries[1].lat.is_none());
+    }
+
+    #[test]
+    fn test_parse_csv_semicolon() {
+        let csv = "Count;F-Stop;Exposure Time;EV\n3;5.6;1/60;-1 1/3\n";
+        let entries = parse_csv(csv).unwrap();
+        assert_eq!(entries[0].frame_number, Some(3));
+        assert_eq!(entries[0].aperture, Some(5.6));
+        let ev = entries[0].exposure_compensation.unwrap();
+        assert!((ev + 4.0 / 3.0).abs() < 1e-9);
+    }
+
+    #[test]
+    fn test_parse_json_nested() {
+        let json = r#"{"roll": {"name": "Test", "frames": [
+            {"count": 2, "shutter": "1/250", "aperture": "11", "date": "2024-05-01T09:15:00",
+             "location": {"latitude": 48.85, "longitude": 2.35}, "flashUsed": false},
+            {"count": 1, "shutter": "1/30", "aperture": 2}
+        ]}}"#;
+        let entries = parse_json(json).unwrap();
+        assert_eq!(entries.len(), 2);
+        assert_eq!(entries[0].frame_number, Some(2));
+        assert_eq!(entries[0].aperture, Some(11.0));
+        assert_eq!(entries[0].shot_at.as_deref(), Some("2024-05-01 09:15:00"));
+        assert_eq!(entries[0].lon, Some(2.35));
+        assert_eq!(entries[0].flash, Some(false));
+        assert_eq!(entries[1].aperture, Some(2.0));
+    }
+
+    #[test]
+    fn test_filename_sequence() {
+        assert_eq!(filename_sequence("ROLL_0000002A_007.jpg"), Some(7));
+        assert_eq!(filename_sequence("ROLL_0000002A_123.tif"), Some(123));
+        assert_eq!(filename_sequence("scan.jpg"), None);
+    }
+
+    #[test]
+    fn test_match_entries() {
+        let photos = vec![
+            (10, "ROLL_00000001_001.jpg".to_string(), None),
+            (11, "ROLL_00000001_002.jpg".to_string(), None),
+            (12, "ROLL_00000001_004.jpg".to_string(), None),
+        ];
+        let entries: Vec<LogbookEntry> = [2, 1, 3]
+            .iter()
+            .enumerate()
+            .map(|(i, frame)| LogbookEntry { index: i, frame_number: Some(*frame), ..Default::default() })
+            .collect();
+
+        let plan = match_entries(&photos, &entries, MatchMode::FrameOrder);
+        let frames: Vec<_> = plan.matches.iter().map(|m| (m.photo_id, m.entry.frame_number)).collect();
+        assert_eq!(frames, vec![(10, Some(1)), (11, Some(2)), (12, Some(3))]);
+        assert!(plan.unmatched_entries.is_empty());
+
+        let plan = match_entries(&photos, &entries, MatchMode::FilenameSequence);
+        let frames: Vec<_> = plan.matches.iter().map(|m| (m.photo_id, m.entry.frame_number)).collect();
+        assert_eq!(frames, vec![(10, Some(1)), (11, Some(2))]);
+        assert_eq!(plan.unmatched_photo_ids, vec![12]);
+        assert_eq!(plan.unmatched_entries.len(), 1);
+        assert_eq!(plan.unmatched_entries[0].frame_number, Some(3));
+
+        // A parsed frame number wins over the filename sequence; "12A" is not numeric
+        let photos = vec![
+            (20, "Portra_2024-05-01_001.jpg".to_string(), Some("3".to_string())),
+            (21, "Portra_2024-05-01_002.jpg".to_string(), Some("12A".to_string())),
+        ];
+        let plan = match_entries(&photos, &entries, MatchMode::FilenameSequence);
+        let frames: Vec<_> = plan.matches.iter().map(|m| (m.photo_id, m.entry.frame_number)).collect();
+        assert_eq!(frames, vec![(20, Some(3)), (21, Some(2))]);
+    }
+}
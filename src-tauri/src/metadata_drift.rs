@@ -0,0 +1,473 @@
+/*!
+ * FilmVault Metadata Drift
+ *
+ * Compares what the database says a photo's metadata should be (the same
+ * `PhotoMetadata` a roll write puts in the file) with what ExifTool reads
+ * back, field by field. Used to verify writes and to find files edited in
+ * other tools. A difference is resolved per field: the database wins (the
+ * field is written to the file again) or the file wins (its value is taken
+ * into the photo). Roll fields only take a file's value when the user applies
+ * it to the whole roll. Resolutions are applied by `commands::exif`.
+ */
+
+use serde::{Deserialize, Serialize};
+use anyhow::{anyhow, bail, Result};
+
+use crate::database::{Photo, Roll, ShotLog};
+use crate::exif_tool::{ExifData, PhotoMetadata, photo_metadata_args};
+use crate::film_metadata::FilmMetadata;
+
+/// A metadata field compared between the database and the file
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum DriftField {
+    Camera,
+    Lens,
+    ShootDate,
+    Rating,
+    Description,
+    Location,
+    City,
+    Country,
+    FilmStock,
+    FilmIso,
+    FilmFormat,
+    FilmProcess,
+    PushPull,
+    Lab,
+    Scanner,
+    RollName,
+}
+
+impl DriftField {
+    pub const ALL: [DriftField; 16] = [
+        DriftField::Camera,
+        DriftField::Lens,
+        DriftField::ShootDate,
+        DriftField::Rating,
+        DriftField::Description,
+        DriftField::Location,
+        DriftField::City,
+        DriftField::Country,
+        DriftField::FilmStock,
+        DriftField::FilmIso,
+        DriftField::FilmFormat,
+        DriftField::FilmProcess,
+        DriftField::PushPull,
+        DriftField::Lab,
+        DriftField::Scanner,
+        DriftField::RollName,
+    ];
+
+    /// Fields stored on the roll; taking the file's value changes every photo of the roll
+    pub fn is_roll_field(&self) -> bool {
+        !matches!(
+            self,
+            DriftField::Rating | DriftField::Description | DriftField::Location | DriftField::City | DriftField::Country
+        )
+    }
+
+    /// Film fields are always written (unset ones are deleted), so a value only in the file is drift too
+    fn is_film_field(&self) -> bool {
+        matches!(
+            self,
+            DriftField::FilmStock | DriftField::FilmIso | DriftField::FilmFormat | DriftField::FilmProcess
+                | DriftField::PushPull | DriftField::Lab | DriftField::Scanner | DriftField::RollName
+        )
+    }
+
+    /// Start of the tag assignments `photo_metadata_args` makes for this field
+    fn arg_prefixes(&self) -> &'static [&'static str] {
+        match self {
+            DriftField::Camera => &["-Make=", "-Model="],
+            DriftField::Lens => &["-LensModel=", "-LensMake="],
+            DriftField::ShootDate => &["-DateTimeOriginal=", "-CreateDate="],
+            DriftField::Rating => &["-XMP:Rating="],
+            DriftField::Description => &["-ImageDescription=", "-XMP-dc:Description="],
+            DriftField::Location => &["-GPSLatitude", "-GPSLongitude"],
+            DriftField::City => &["-IPTC:CodedCharacterSet=", "-XMP-photoshop:City=", "-IPTC:City="],
+            DriftField::Country => &["-IPTC:CodedCharacterSet=", "-XMP-photoshop:Country=", "-IPTC:Country-PrimaryLocationName="],
+            DriftField::FilmStock => &["-XMP-filmvault:FilmStock="],
+            DriftField::FilmIso => &["-XMP-filmvault:FilmSpeed="],
+            DriftField::FilmFormat => &["-XMP-filmvault:FilmFormat="],
+            DriftField::FilmProcess => &["-XMP-filmvault:FilmProcess="],
+            DriftField::PushPull => &["-XMP-filmvault:FilmPushPull="],
+            DriftField::Lab => &["-XMP-filmvault:FilmLab="],
+            DriftField::Scanner => &["-XMP-filmvault:FilmScanner="],
+            DriftField::RollName => &["-XMP-filmvault:FilmRollName="],
+        }
+    }
+}
+
+/// One field whose file value differs from the database
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct FieldDrift {
+    pub field: DriftField,
+    pub db_value: Option<String>,
+    pub file_value: Option<String>,
+}
+
+/// The drifted fields of one photo
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct PhotoDrift {
+    pub photo_id: i64,
+    pub roll_id: i64,
+    pub file_path: String,
+    pub fields: Vec<FieldDrift>,
+    pub roll_photo_count: usize, // Photos that taking a roll field from this file would change
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct DriftReport {
+    pub photos_checked: usize,
+    pub drifted: Vec<PhotoDrift>,
+    pub errors: Vec<String>, // Files that could not be read
+}
+
+/// Which side a drifted field is resolved to
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum DriftWinner {
+    DbWins,
+    FileWins,    // Photo fields only
+    ApplyToRoll, // Roll fields only: the file's value becomes the roll's, for every photo of the roll
+}
+
+impl DriftWinner {
+    /// Whether this choice may resolve `field`
+    pub fn allowed_for(&self, field: DriftField) -> bool {
+        match self {
+            DriftWinner::DbWins => true,
+            DriftWinner::FileWins => !field.is_roll_field(),
+            DriftWinner::ApplyToRoll => field.is_roll_field(),
+        }
+    }
+}
+
+/// A resolution the user picked for one field of one photo
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DriftResolution {
+    pub photo_id: i64,
+    pub field: DriftField,
+    pub winner: DriftWinner,
+}
+
+fn non_empty(value: Option<&str>) -> Option<String> {
+    value.map(str::trim).filter(|value| !value.is_empty()).map(String::from)
+}
+
+/// "YYYY:MM:DD HH:MM:SS" -> "YYYY-MM-DD"; the database keeps days only
+fn exif_day(date_time: &str) -> Option<String> {
+    let day = date_time.get(..10)?;
+    Some(day.replace(':', "-"))
+}
+
+fn join_camera(make: Option<&str>, model: Option<&str>) -> Option<String> {
+    let parts: Vec<&str> = [make, model].into_iter().flatten().map(str::trim).filter(|part| !part.is_empty()).collect();
+    (!parts.is_empty()).then(|| parts.join(" "))
+}
+
+fn format_location(lat: f64, lon: f64) -> String {
+    format!("{:.6}, {:.6}", lat, lon)
+}
+
+/// Value the database would write for a field (None: nothing is written)
+fn db_value(field: DriftField, metadata: &PhotoMetadata, film: &FilmMetadata) -> Option<String> {
+    match field {
+        DriftField::Camera => join_camera(Some(&metadata.make), Some(&metadata.model)),
+        DriftField::Lens => non_empty(metadata.lens.as_deref()),
+        DriftField::ShootDate => exif_day(&metadata.date_time_original),
+        DriftField::Rating => metadata.rating.map(|rating| rating.to_string()),
+        DriftField::Description => non_empty(metadata.description.as_deref()),
+        DriftField::Location => metadata.gps.map(|(lat, lon)| format_location(lat, lon)),
+        DriftField::City => non_empty(metadata.city.as_deref()),
+        DriftField::Country => non_empty(metadata.country.as_deref()),
+        DriftField::FilmStock => film.film_stock.clone(),
+        DriftField::FilmIso => film.iso.map(|iso| iso.to_string()),
+        DriftField::FilmFormat => film.format.clone(),
+        DriftField::FilmProcess => film.process.clone(),
+        DriftField::PushPull => film.push_pull.map(|stops| stops.to_string()),
+        DriftField::Lab => film.lab.clone(),
+        DriftField::Scanner => film.scanner.clone(),
+        DriftField::RollName => film.roll_name.clone(),
+    }
+}
+
+/// Value found in the file, in the same form as `db_value`
+fn file_value(field: DriftField, exif: &ExifData) -> Option<String> {
+    let film = exif.film.clone().unwrap_or_default();
+    match field {
+        DriftField::Camera => join_camera(exif.make.as_deref(), exif.model.as_deref()),
+        DriftField::Lens => non_empty(exif.lens_model.as_deref()),
+        DriftField::ShootDate => exif.date_time_original.as_deref().and_then(exif_day),
+        DriftField::Rating => exif.rating.map(|rating| rating.to_string()),
+        DriftField::Description => non_empty(exif.description.as_deref()),
+        DriftField::Location => exif.gps_latitude.zip(exif.gps_longitude).map(|(lat, lon)| format_location(lat, lon)),
+        DriftField::City => non_empty(exif.gps_city.as_deref()),
+        DriftField::Country => non_empty(exif.gps_country.as_deref()),
+        // Falls back to the film stock in an older "Shot on ..." UserComment
+        DriftField::FilmStock => non_empty(exif.film_stock.as_deref()),
+        DriftField::FilmIso => film.iso.map(|iso| iso.to_string()),
+        DriftField::FilmFormat => film.format,
+        DriftField::FilmProcess => film.process,
+        DriftField::PushPull => film.push_pull.map(|stops| stops.to_string()),
+        DriftField::Lab => film.lab,
+        DriftField::Scanner => film.scanner,
+        DriftField::RollName => film.roll_name,
+    }
+}
+
+/// Numbers that went through ExifTool (GPS in degrees/minutes/seconds) only match approximately
+/// An unrated photo matches a file without a rating; line breaks compare as "\n"
+fn values_match(field: DriftField, db: Option<&str>, file: Option<&str>) -> bool {
+    let lines = |value: &str| value.replace("\r\n", "\n").replace('\r', "\n");
+    let numbers = |value: &str| -> Option<Vec<f64>> {
+        value.split(',').map(|part| part.trim().parse::<f64>().ok()).collect()
+    };
+    match (field, db, file) {
+        (DriftField::Rating, Some("0"), None) => true,
+        (DriftField::Location | DriftField::PushPull, Some(db), Some(file)) => match (numbers(db), numbers(file)) {
+            (Some(db), Some(file)) => db.len() == file.len()
+                && db.iter().zip(&file).all(|(a, b)| (a - b).abs() < 1e-5),
+            _ => db == file,
+        },
+        (DriftField::Description, Some(db), Some(file)) => lines(db) == lines(file),
+        _ => db == file,
+    }
+}
+
+/// Fields where the file differs from what a roll write would put there
+///
+/// Fields the database leaves empty are not written, so the file may hold
+/// anything there; film fields are the exception. The date is not compared
+/// when the shot log sets the exact time of the frame.
+pub fn compare(metadata: &PhotoMetadata, shot_log: Option<&ShotLog>, exif: &ExifData) -> Vec<FieldDrift> {
+    let film = metadata.film.clone().unwrap_or_default();
+    let has_shot_time = shot_log.and_then(|log| log.shot_at.as_deref()).is_some_and(|shot_at| !shot_at.is_empty());
+
+    DriftField::ALL
+        .into_iter()
+        .filter(|field| !(*field == DriftField::ShootDate && has_shot_time))
+        .filter(|field| !field.is_film_field() || metadata.film.is_some())
+        .filter_map(|field| {
+            let db_value = db_value(field, metadata, &film);
+            if db_value.is_none() && !field.is_film_field() {
+                return None;
+            }
+            let file_value = file_value(field, exif);
+            (!values_match(field, db_value.as_deref(), file_value.as_deref()))
+                .then_some(FieldDrift { field, db_value, file_value })
+        })
+        .collect()
+}
+
+/// Tag assignments that write the database value of the given fields back to the file
+pub fn db_wins_args(metadata: &PhotoMetadata, fields: &[DriftField]) -> Vec<String> {
+    photo_metadata_args(metadata)
+        .into_iter()
+        .filter(|arg| fields.iter().any(|field| field.arg_prefixes().iter().any(|prefix| arg.starts_with(prefix))))
+        .collect()
+}
+
+fn parse_number<T: std::str::FromStr>(field: DriftField, value: Option<&str>) -> Result<Option<T>> {
+    value
+        .map(|value| value.parse::<T>().map_err(|_| anyhow!("{:?} value '{}' in the file is not a number", field, value)))
+        .transpose()
+}
+
+fn required(field: DriftField, value: Option<&str>) -> Result<String> {
+    value.map(String::from).ok_or_else(|| anyhow!("The file has no {:?}, which the roll requires", field))
+}
+
+/// A photo without its own location takes the roll's, so "none" cannot be taken from the file then
+fn clearable(field: DriftField, value: Option<&str>, roll_value: bool) -> Result<()> {
+    if value.is_none() && roll_value {
+        bail!("The file has no {:?}, but the photo would take the roll's; keep the database value instead", field);
+    }
+    Ok(())
+}
+
+/// Take the file's value of a field into the roll or photo
+pub fn apply_file_value(field: DriftField, value: Option<&str>, roll: &mut Roll, photo: &mut Photo) -> Result<()> {
+    let text = || value.map(String::from);
+    match field {
+        DriftField::Camera => roll.camera = value.unwrap_or_default().to_string(),
+        DriftField::Lens => roll.lens = text(),
+        DriftField::ShootDate => roll.shoot_date = required(field, value)?,
+        DriftField::Rating => photo.rating = parse_number(field, value)?.unwrap_or(0),
+        DriftField::Description => photo.exif_description = text(),
+        DriftField::Location => {
+            clearable(field, value, roll.lat.is_some() && roll.lon.is_some())?;
+            (photo.lat, photo.lon) = match value.map(|value| (value, value.split_once(','))) {
+                Some((_, Some((lat, lon)))) => (parse_number(field, Some(lat.trim()))?, parse_number(field, Some(lon.trim()))?),
+                Some((value, None)) => bail!("Location '{}' in the file is not 'lat, lon'", value),
+                None => (None, None),
+            };
+        }
+        DriftField::City => {
+            clearable(field, value, roll.city.is_some())?;
+            photo.city = text();
+        }
+        DriftField::Country => {
+            clearable(field, value, roll.country.is_some())?;
+            photo.country = text();
+        }
+        DriftField::FilmStock => roll.film_stock = required(field, value)?,
+        DriftField::FilmIso => roll.film_iso = parse_number(field, value)?,
+        DriftField::FilmFormat => roll.film_format = text(),
+        DriftField::FilmProcess => roll.film_process = text(),
+        DriftField::PushPull => roll.push_pull = parse_number(field, value)?,
+        DriftField::Lab => roll.lab_info = text(),
+        DriftField::Scanner => roll.scanner = text(),
+        DriftField::RollName => roll.name = required(field, value)?,
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_compare_and_resolve() {
+        let metadata = PhotoMetadata {
+            make: "Nikon".to_string(),
+            model: "FM2".to_string(),
+            date_time_original: "2024:05:01 12:00:00".to_string(),
+            rating: Some(4),
+            gps: Some((-33.8568, 151.2153)),
+            city: Some("Sydney".to_string()),
+            film: Some(FilmMetadata {
+                film_stock: Some("Kodak Portra 400".to_string()),
+                iso: Some(400),
+                roll_name: Some("Harbour".to_string()),
+                ..Default::default()
+            }),
+            ..Default::default()
+        };
+
+        // What ExifTool reads back after the file was edited elsewhere
+        let exif = ExifData {
+            make: Some("Nikon".to_string()),
+            model: Some("FM2".to_string()),
+            date_time_original: Some("2024:05:01 12:00:00".to_string()),
+            lens_model: Some("Nikkor 50mm f/1.4".to_string()),
+            rating: Some(2),
+            gps_latitude: Some(-33.856800001),
+            gps_longitude: Some(151.2153),
+            gps_city: Some("Sydney".to_string()),
+            film_stock: Some("Kodak Portra 400".to_string()),
+            film: Some(FilmMetadata {
+                film_stock: Some("Kodak Portra 400".to_string()),
+                iso: Some(400),
+                process: Some("C-41".to_string()),
+                roll_name: Some("Harbour".to_string()),
+                ..Default::default()
+            }),
+            ..Default::default()
+        };
+
+        // The lens is not set in the database, so it is not drift
+        let drift = compare(&metadata, None, &exif);
+        let unrated = PhotoMetadata { rating: Some(0), ..metadata.clone() };
+        let no_rating = ExifData { rating: None, ..exif.clone() };
+        assert!(compare(&unrated, None, &no_rating).iter().all(|drift| drift.field != DriftField::Rating));
+        assert!(values_match(DriftField::Description, Some("Harbour\r\nat dusk"), Some("Harbour\nat dusk")));
+        assert_eq!(drift, vec![
+            FieldDrift { field: DriftField::Rating, db_value: Some("4".to_string()), file_value: Some("2".to_string()) },
+            FieldDrift { field: DriftField::FilmProcess, db_value: None, file_value: Some("C-41".to_string()) },
+        ]);
+
+        // A shot log time replaces the roll date, so the date is not compared
+        let log = ShotLog { shot_at: Some("2024-05-02 09:00:00".to_string()), ..Default::default() };
+        let moved = ExifData { date_time_original: Some("2024:05:02 09:00:00".to_string()), ..exif.clone() };
+        assert!(compare(&metadata, Some(&log), &moved).iter().all(|drift| drift.field != DriftField::ShootDate));
+        assert!(compare(&metadata, None, &moved).iter().any(|drift| drift.field == DriftField::ShootDate));
+
+        assert_eq!(
+            db_wins_args(&metadata, &[DriftField::Rating, DriftField::FilmProcess, DriftField::City]),
+            vec![
+                "-XMP:Rating=4",
+                "-IPTC:CodedCharacterSet=UTF8",
+                "-XMP-photoshop:City=Sydney",
+                "-IPTC:City=Sydney",
+                "-XMP-filmvault:FilmProcess=",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_apply_file_value() {
+        let mut roll = Roll {
+            id: 1,
+            name: "Harbour".to_string(),
+            path: "/library/2024/00000001".to_string(),
+            film_stock: "Kodak Portra 400".to_string(),
+            camera: "Nikon FM2".to_string(),
+            lens: None,
+            shoot_date: "2024-05-01".to_string(),
+            lab_info: None,
+            notes: None,
+            city: None,
+            country: None,
+            lat: None,
+            lon: None,
+            film_iso: None,
+            film_format: None,
+            film_process: None,
+            push_pull: None,
+            scanner: None,
+            created_at: String::new(),
+            updated_at: String::new(),
+        };
+        let mut photo = Photo {
+            id: 1,
+            roll_id: 1,
+            filename: "001.jpg".to_string(),
+            file_path: "/library/2024/00000001/originals/001.jpg".to_string(),
+            thumbnail_path: None,
+            preview_path: None,
+            rating: 4,
+            is_cover: false,
+            is_favorite: false,
+            lat: None,
+            lon: None,
+            city: None,
+            country: None,
+            exif_synced: true,
+            created_at: String::new(),
+            exif_written_at: None,
+            exif_data_hash: None,
+            exif_user_comment: None,
+            exif_description: None,
+            content_hash: None,
+            perceptual_hash: None,
+            original_filename: None,
+            frame_number: None,
+            sequence: None,
+        };
+
+        apply_file_value(DriftField::FilmIso, Some("800"), &mut roll, &mut photo).unwrap();
+        apply_file_value(DriftField::Rating, Some("2"), &mut roll, &mut photo).unwrap();
+        apply_file_value(DriftField::Location, Some("-33.856800, 151.215300"), &mut roll, &mut photo).unwrap();
+        assert_eq!(roll.film_iso, Some(800));
+        assert_eq!(photo.rating, 2);
+        assert_eq!((photo.lat, photo.lon), (Some(-33.8568), Some(151.2153)));
+
+        assert!(apply_file_value(DriftField::ShootDate, None, &mut roll, &mut photo).is_err());
+        // Without a city of its own the photo would show the roll's again
+        roll.city = Some("Sydney".to_string());
+        photo.city = Some("Manly".to_string());
+        assert!(apply_file_value(DriftField::City, None, &mut roll, &mut photo).is_err());
+        assert_eq!(photo.city.as_deref(), Some("Manly"));
+        apply_file_value(DriftField::Country, None, &mut roll, &mut photo).unwrap();
+        assert_eq!(photo.country, None);
+        assert!(apply_file_value(DriftField::PushPull, Some("lots"), &mut roll, &mut photo).is_err());
+
+        // One file's camera only replaces the roll's when applied to the roll
+        assert!(!DriftWinner::FileWins.allowed_for(DriftField::Camera));
+        assert!(DriftWinner::ApplyToRoll.allowed_for(DriftField::Camera));
+        assert!(!DriftWinner::ApplyToRoll.allowed_for(DriftField::Rating));
+        assert_eq!(roll.shoot_date, "2024-05-01");
+    }
+}
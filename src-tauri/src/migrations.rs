@@ -0,0 +1,534 @@
+/*!
+ * FilmVault Schema Migrations
+ *
+ * Versioned, transactional migration runner. Applied migrations are recorded in
+ * the `_migrations` table together with a checksum of their SQL, and every
+ * pending migration runs inside a single transaction so a failure never leaves
+ * the schema half-applied.
+ */
+
+use sqlx::SqlitePool;
+use anyhow::{anyhow, Context, Result};
+
+/// A single schema migration embedded in the binary
+pub struct Migration {
+    pub version: i64,
+    pub name: &'static str,
+    pub sql: &'static str,
+}
+
+/// All known migrations, in the order they must be applied
+///
+/// Migration files are kept byte-identical to what shipped. The comments in
+/// 006 still describe the old runner, which ignored failed DROP COLUMNs; this
+/// runner applies it in one transaction, and legacy databases left with some
+/// of its columns are finished by `repair_legacy_photo_columns`.
+pub const MIGRATIONS: &[Migration] = &[
+    Migration { version: 1, name: "initial", sql: include_str!("../migrations/001_initial.sql") },
+    Migration { version: 2, name: "settings", sql: include_str!("../migrations/002_settings.sql") },
+    Migration { version: 3, name: "add_photo_favorite", sql: include_str!("../migrations/003_add_photo_favorite.sql") },
+    Migration { version: 4, name: "exif_write_tracking", sql: include_str!("../migrations/004_exif_write_tracking.sql") },
+    Migration { version: 5, name: "settings_exif", sql: include_str!("../migrations/005_settings_exif.sql") },
+    Migration { version: 6, name: "remove_photo_exif", sql: include_str!("../migrations/006_remove_photo_exif.sql") },
+    Migration { version: 7, name: "add_photo_city", sql: include_str!("../migrations/007_add_photo_city.sql") },
+    Migration { version: 8, name: "add_roll_location", sql: include_str!("../migrations/008_add_roll_location.sql") },
+    Migration { version: 9, name: "film_presets", sql: include_str!("../migrations/009_film_presets.sql") },
+    Migration { version: 10, name: "add_preset_brand", sql: include_str!("../migrations/010_add_preset_brand.sql") },
+    Migration { version: 11, name: "remove_iso", sql: include_str!("../migrations/011_remove_iso.sql") },
+    Migration { version: 12, name: "shot_logs", sql: include_str!("../migrations/012_shot_logs.sql") },
+    Migration { version: 13, name: "shot_log_time", sql: include_str!("../migrations/013_shot_log_time.sql") },
+    Migration { version: 14, name: "photo_content_hash", sql: include_str!("../migrations/014_photo_content_hash.sql") },
+    Migration { version: 15, name: "photo_perceptual_hash", sql: include_str!("../migrations/015_photo_perceptual_hash.sql") },
+    Migration { version: 16, name: "import_jobs", sql: include_str!("../migrations/016_import_jobs.sql") },
+    Migration { version: 17, name: "settings_filename_template", sql: include_str!("../migrations/017_settings_filename_template.sql") },
+    Migration { version: 18, name: "photo_frame_numbers", sql: include_str!("../migrations/018_photo_frame_numbers.sql") },
+    Migration { version: 19, name: "photo_sequence", sql: include_str!("../migrations/019_photo_sequence.sql") },
+    Migration { version: 20, name: "relative_library_paths", sql: include_str!("../migrations/020_relative_library_paths.sql") },
+    Migration { version: 21, name: "library_moves", sql: include_str!("../migrations/021_library_moves.sql") },
+    Migration { version: 22, name: "settings_backups", sql: include_str!("../migrations/022_settings_backups.sql") },
+    Migration { version: 23, name: "settings_metadata_write_mode", sql: include_str!("../migrations/023_settings_metadata_write_mode.sql") },
+    Migration { version: 24, name: "roll_film_details", sql: include_str!("../migrations/024_roll_film_details.sql") },
+    Migration { version: 25, name: "settings_verify_exif_writes", sql: include_str!("../migrations/025_settings_verify_exif_writes.sql") },
+    Migration { version: 26, name: "photo_content_hash_failed", sql: include_str!("../migrations/026_photo_content_hash_failed.sql") },
+    Migration { version: 27, name: "shot_log_frame_from_photo", sql: include_str!("../migrations/027_shot_log_frame_from_photo.sql") },
+];
+
+/// Last migration run by the pre-versioning startup code
+const LAST_LEGACY_VERSION: i64 = 11;
+
+/// Columns dropped by migration 006. Older builds ignored DROP COLUMN failures,
+/// so legacy databases may still carry some of them.
+const LEGACY_PHOTO_EXIF_COLUMNS: &[&str] = &[
+    "exif_iso",
+    "exif_aperture",
+    "exif_shutter_speed",
+    "exif_focal_length",
+    "exif_altitude",
+];
+
+/// Checksum of a migration's SQL text
+pub fn checksum(sql: &str) -> String {
+    format!("{:x}", md5::compute(sql.as_bytes()))
+}
+
+/// Split a SQL script into individual statements
+///
+/// Handles quoted strings/identifiers, `--` and `/* */` comments, and
+/// `CREATE TRIGGER ... BEGIN ... END;` bodies. Comments are dropped and
+/// comment-only fragments are skipped.
+pub fn split_statements(sql: &str) -> Vec<String> {
+    let mut statements = Vec::new();
+    let mut current = String::new();
+    let mut chars = sql.chars().peekable();
+
+    while let Some(c) = chars.next() {
+        match c {
+            '\'' | '"' | '`' => {
+                current.push(c);
+                while let Some(q) = chars.next() {
+                    current.push(q);
+                    if q == c {
+                        // Doubled quote is an escaped quote, keep scanning
+                        if chars.peek() == Some(&c) {
+                            current.push(chars.next().unwrap());
+                        } else {
+                            break;
+                        }
+                    }
+                }
+            }
+            '-' if chars.peek() == Some(&'-') => {
+                for n in chars.by_ref() {
+                    if n == '\n' {
+                        current.push('\n');
+                        break;
+                    }
+                }
+            }
+            '/' if chars.peek() == Some(&'*') => {
+                chars.next();
+                let mut prev = '\0';
+                for n in chars.by_ref() {
+                    if prev == '*' && n == '/' {
+                        break;
+                    }
+                    prev = n;
+                }
+                current.push(' ');
+            }
+            ';' => {
+                if is_unterminated_trigger(&current) {
+                    current.push(c);
+                    continue;
+                }
+                let stmt = current.trim();
+                if !stmt.is_empty() {
+                    statements.push(stmt.to_string());
+                }
+                current.clear();
+            }
+            _ => current.push(c),
+        }
+    }
+
+    let stmt = current.trim();
+    if !stmt.is_empty() {
+        statements.push(stmt.to_string());
+    }
+
+    statements
+}
+
+/// Uppercased words of a statement, skipping quoted strings and identifiers
+fn keywords(statement: &str) -> Vec<String> {
+    let mut words = Vec::new();
+    let mut word = String::new();
+    let mut chars = statement.chars();
+
+    while let Some(c) = chars.next() {
+        if c.is_alphanumeric() || c == '_' {
+            word.push(c.to_ascii_uppercase());
+            continue;
+        }
+        if !word.is_empty() {
+            words.push(std::mem::take(&mut word));
+        }
+        if matches!(c, '\'' | '"' | '`') {
+            // A doubled quote reads as two adjacent quoted parts, which is fine here
+            for q in chars.by_ref() {
+                if q == c {
+                    break;
+                }
+            }
+        }
+    }
+    if !word.is_empty() {
+        words.push(word);
+    }
+
+    words
+}
+
+/// A trigger body contains semicolons; the statement only ends at the `END`
+/// that closes its `BEGIN`, not at the `END` of a `CASE` expression
+fn is_unterminated_trigger(statement: &str) -> bool {
+    let words = keywords(statement);
+    let is_trigger = words.first().map(String::as_str) == Some("CREATE")
+        && words.iter().skip(1).take(2).any(|w| w == "TRIGGER");
+    if !is_trigger {
+        return false;
+    }
+
+    let mut depth = 0;
+    let mut has_body = false;
+    for word in &words {
+        match word.as_str() {
+            "BEGIN" => {
+                has_body = true;
+                depth += 1;
+            }
+            "CASE" => depth += 1,
+            "END" => depth -= 1,
+            _ => {}
+        }
+    }
+
+    !has_body || depth > 0
+}
+
+/// Create the migration bookkeeping table
+async fn ensure_migrations_table(pool: &SqlitePool) -> Result<()> {
+    sqlx::query(
+        r#"
+        CREATE TABLE IF NOT EXISTS _migrations (
+            version INTEGER PRIMARY KEY,
+            name TEXT NOT NULL,
+            checksum TEXT NOT NULL,
+            applied_at DATETIME DEFAULT CURRENT_TIMESTAMP
+        )
+        "#
+    )
+    .execute(pool)
+    .await?;
+
+    Ok(())
+}
+
+async fn table_exists(pool: &SqlitePool, table: &str) -> Result<bool> {
+    let count: i64 = sqlx::query_scalar(
+        "SELECT COUNT(*) FROM sqlite_master WHERE type = 'table' AND name = ?1"
+    )
+    .bind(table)
+    .fetch_one(pool)
+    .await?;
+
+    Ok(count > 0)
+}
+
+async fn column_exists(pool: &SqlitePool, table: &str, column: &str) -> Result<bool> {
+    let count: i64 = sqlx::query_scalar(
+        "SELECT COUNT(*) FROM pragma_table_info(?1) WHERE name = ?2"
+    )
+    .bind(table)
+    .bind(column)
+    .fetch_one(pool)
+    .await?;
+
+    Ok(count > 0)
+}
+
+/// Detect whether a migration was already applied by the pre-versioning runner,
+/// which executed every script on each startup and ignored "already exists" errors
+async fn legacy_migration_applied(pool: &SqlitePool, version: i64) -> Result<bool> {
+    match version {
+        1 => table_exists(pool, "rolls").await,
+        2 => table_exists(pool, "settings").await,
+        3 => column_exists(pool, "photos", "is_favorite").await,
+        4 => column_exists(pool, "photos", "exif_written_at").await,
+        5 => column_exists(pool, "settings", "exif_auto_write").await,
+        // Leftover columns are dropped by repair_legacy_photo_columns
+        6 => column_exists(pool, "photos", "exif_written_at").await,
+        7 => column_exists(pool, "photos", "city").await,
+        8 => column_exists(pool, "rolls", "lat").await,
+        9 => table_exists(pool, "film_presets").await,
+        10 => column_exists(pool, "film_presets", "brand").await,
+        11 => Ok(table_exists(pool, "film_presets").await?
+            && !column_exists(pool, "film_presets", "iso").await?),
+        _ => Ok(false),
+    }
+}
+
+/// Finish a partially applied migration 006 on a legacy database
+async fn repair_legacy_photo_columns(pool: &SqlitePool) -> Result<()> {
+    let mut leftover = Vec::new();
+    for column in LEGACY_PHOTO_EXIF_COLUMNS {
+        if column_exists(pool, "photos", column).await? {
+            leftover.push(*column);
+        }
+    }
+
+    if leftover.is_empty() {
+        return Ok(());
+    }
+
+    eprintln!("[Migrations] Dropping leftover photo columns from migration 006: {:?}", leftover);
+
+    let mut tx = pool.begin().await?;
+    for column in leftover {
+        sqlx::query(&format!("ALTER TABLE photos DROP COLUMN {}", column))
+            .execute(&mut *tx)
+            .await
+            .with_context(|| format!("Failed to drop legacy column photos.{}", column))?;
+    }
+    tx.commit().await?;
+
+    Ok(())
+}
+
+/// Record migrations already present in a database created before `_migrations` existed
+async fn baseline_legacy_database(pool: &SqlitePool) -> Result<()> {
+    let recorded: i64 = sqlx::query_scalar("SELECT COUNT(*) FROM _migrations")
+        .fetch_one(pool)
+        .await?;
+
+    if recorded > 0 || !table_exists(pool, "rolls").await? {
+        return Ok(());
+    }
+
+    eprintln!("[Migrations] Legacy database detected, recording existing schema");
+
+    if legacy_migration_applied(pool, 6).await? {
+        repair_legacy_photo_columns(pool).await?;
+    }
+
+    let mut present = Vec::new();
+    for migration in MIGRATIONS.iter().filter(|m| m.version <= LAST_LEGACY_VERSION) {
+        if legacy_migration_applied(pool, migration.version).await? {
+            present.push(migration);
+        }
+    }
+
+    let mut tx = pool.begin().await?;
+    for migration in present {
+        sqlx::query("INSERT INTO _migrations (version, name, checksum) VALUES (?1, ?2, ?3)")
+            .bind(migration.version)
+            .bind(migration.name)
+            .bind(checksum(migration.sql))
+            .execute(&mut *tx)
+            .await?;
+        eprintln!("[Migrations] Baselined migration {:03}_{}", migration.version, migration.name);
+    }
+    tx.commit().await?;
+
+    Ok(())
+}
+
+/// Apply a single migration and record it, all in one transaction
+async fn apply_migration(pool: &SqlitePool, migration: &Migration) -> Result<()> {
+    let statements = split_statements(migration.sql);
+    let mut tx = pool.begin().await?;
+
+    for (index, statement) in statements.iter().enumerate() {
+        if let Err(e) = sqlx::query(statement).execute(&mut *tx).await {
+            tx.rollback().await?;
+            return Err(anyhow!(
+                "Migration {:03}_{} failed on statement {} of {} ({}): {}",
+                migration.version,
+                migration.name,
+                index + 1,
+                statements.len(),
+                statement,
+                e
+            ));
+        }
+    }
+
+    sqlx::query("INSERT INTO _migrations (version, name, checksum) VALUES (?1, ?2, ?3)")
+        .bind(migration.version)
+        .bind(migration.name)
+        .bind(checksum(migration.sql))
+        .execute(&mut *tx)
+        .await?;
+
+    tx.commit().await?;
+    Ok(())
+}
+
+/// Get the highest applied migration version (0 for an empty database)
+pub async fn current_schema_version(pool: &SqlitePool) -> Result<i64> {
+    if !table_exists(pool, "_migrations").await? {
+        return Ok(0);
+    }
+
+    let version: Option<i64> = sqlx::query_scalar("SELECT MAX(version) FROM _migrations")
+        .fetch_one(pool)
+        .await?;
+
+    Ok(version.unwrap_or(0))
+}
+
+/// Get the schema version this build expects
+pub fn latest_schema_version() -> i64 {
+    MIGRATIONS.last().map(|m| m.version).unwrap_or(0)
+}
+
+/// Run all pending migrations
+///
+/// Fails if an applied migration's checksum no longer matches the embedded SQL,
+/// or if the database was created by a newer build.
+pub async fn run_migrations(pool: &SqlitePool) -> Result<()> {
+    ensure_migrations_table(pool).await?;
+    baseline_legacy_database(pool).await?;
+
+    let applied = sqlx::query_as::<_, (i64, String)>(
+        "SELECT version, checksum FROM _migrations ORDER BY version"
+    )
+    .fetch_all(pool)
+    .await?;
+
+    if let Some((version, _)) = applied.last() {
+        if *version > latest_schema_version() {
+            return Err(anyhow!(
+                "Database schema version {} is newer than this build supports ({})",
+                version,
+                latest_schema_version()
+            ));
+        }
+    }
+
+    for migration in MIGRATIONS {
+        match applied.iter().find(|(version, _)| *version == migration.version) {
+            Some((_, recorded)) => {
+                if *recorded != checksum(migration.sql) {
+                    return Err(anyhow!(
+                        "Checksum mismatch for applied migration {:03}_{}: the migration file was modified after it ran",
+                        migration.version,
+                        migration.name
+                    ));
+                }
+            }
+            None => {
+                eprintln!("[Migrations] Applying {:03}_{}", migration.version, migration.name);
+                apply_migration(pool, migration).await?;
+                eprintln!("[Migrations] Applied {:03}_{}", migration.version, migration.name);
+            }
+        }
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use sqlx::sqlite::SqlitePoolOptions;
+
+    async fn memory_pool() -> SqlitePool {
+        SqlitePoolOptions::new()
+            .max_connections(1)
+            .connect("sqlite::memory:")
+            .await
+            .unwrap()
+    }
+
+    #[test]
+    fn test_split_statements() {
+        let sql = "-- comment; with semicolon\nCREATE TABLE a (x TEXT DEFAULT 'a;b');\n/* block; */\nINSERT INTO a VALUES ('it''s');\n-- trailing comment\n";
+        assert_eq!(
+            split_statements(sql),
+            vec![
+                "CREATE TABLE a (x TEXT DEFAULT 'a;b')".to_string(),
+                "INSERT INTO a VALUES ('it''s')".to_string(),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_split_statements_trigger() {
+        let sql = "CREATE TRIGGER t AFTER INSERT ON a BEGIN UPDATE a SET x = 1; UPDATE a SET x = 2; END; SELECT 1;";
+        let statements = split_statements(sql);
+        assert_eq!(statements.len(), 2);
+        assert!(statements[0].ends_with("END"));
+        assert_eq!(statements[1], "SELECT 1");
+
+        // The END of a CASE inside the body does not close the trigger
+        let sql = "CREATE TEMP TRIGGER t AFTER UPDATE ON a WHEN CASE new.x WHEN 'end;' THEN 0 ELSE 1 END BEGIN UPDATE a SET x = CASE WHEN x > 1 THEN 1 ELSE 2 END; DELETE FROM a WHERE x = 3; END; SELECT 2;";
+        let statements = split_statements(sql);
+        assert_eq!(statements.len(), 2);
+        assert!(statements[0].contains("DELETE FROM a"));
+        assert_eq!(statements[1], "SELECT 2");
+    }
+
+    #[tokio::test]
+    async fn test_run_migrations_fresh_and_idempotent() {
+        let pool = memory_pool().await;
+        run_migrations(&pool).await.unwrap();
+        assert_eq!(current_schema_version(&pool).await.unwrap(), latest_schema_version());
+        assert!(!column_exists(&pool, "photos", "exif_iso").await.unwrap());
+
+        // Second run has nothing to do
+        run_migrations(&pool).await.unwrap();
+        let count: i64 = sqlx::query_scalar("SELECT COUNT(*) FROM _migrations")
+            .fetch_one(&pool)
+            .await
+            .unwrap();
+        assert_eq!(count, MIGRATIONS.len() as i64);
+    }
+
+    #[tokio::test]
+    async fn test_legacy_database_with_partial_006() {
+        let pool = memory_pool().await;
+        // Simulate the old runner: everything applied except two of the 006 drops
+        for migration in MIGRATIONS.iter().filter(|m| m.version <= LAST_LEGACY_VERSION) {
+            for statement in split_statements(migration.sql) {
+                let skipped = statement.contains("exif_focal_length") || statement.contains("exif_altitude");
+                if migration.version == 6 && skipped {
+                    continue;
+                }
+                sqlx::query(&statement).execute(&pool).await.unwrap();
+            }
+        }
+
+        run_migrations(&pool).await.unwrap();
+        assert_eq!(current_schema_version(&pool).await.unwrap(), latest_schema_version());
+        assert!(!column_exists(&pool, "photos", "exif_focal_length").await.unwrap());
+        assert!(!column_exists(&pool, "photos", "exif_altitude").await.unwrap());
+    }
+
+    #[tokio::test]
+    async fn test_relative_library_paths() {
+        let pool = memory_pool().await;
+        run_migrations(&pool).await.unwrap();
+
+        // Rows as written before migration 020
+        sqlx::query("UPDATE settings SET value = '/Volumes/Film/' WHERE key = 'library_root'")
+            .execute(&pool).await.unwrap();
+        sqlx::query("INSERT INTO rolls (id, name, path, film_stock, camera, shoot_date) VALUES (1, 'A', '/Volumes/Film/2024/00000001', '', '', '2024-01-01'), (2, 'B', '/Elsewhere/00000002', '', '', '2024-01-01')")
+            .execute(&pool).await.unwrap();
+        sqlx::query("INSERT INTO photos (roll_id, filename, file_path, thumbnail_path) VALUES (1, 'a.jpg', '/Volumes/Film/2024/00000001/originals/a.jpg', NULL)")
+            .execute(&pool).await.unwrap();
+
+        let migration = MIGRATIONS.iter().find(|m| m.version == 20).unwrap();
+        for statement in split_statements(migration.sql) {
+            sqlx::query(&statement).execute(&pool).await.unwrap();
+        }
+
+        let paths: Vec<String> = sqlx::query_scalar("SELECT path FROM rolls ORDER BY id")
+            .fetch_all(&pool).await.unwrap();
+        assert_eq!(paths, vec!["2024/00000001", "/Elsewhere/00000002"]);
+        let photo: (String, Option<String>) = sqlx::query_as("SELECT file_path, thumbnail_path FROM photos")
+            .fetch_one(&pool).await.unwrap();
+        assert_eq!(photo, ("2024/00000001/originals/a.jpg".to_string(), None));
+    }
+
+    #[tokio::test]
+    async fn test_checksum_mismatch_fails() {
+        let pool = memory_pool().await;
+        run_migrations(&pool).await.unwrap();
+        sqlx::query("UPDATE _migrations SET checksum = 'tampered' WHERE version = 3")
+            .execute(&pool)
+            .await
+            .unwrap();
+        assert!(run_migrations(&pool).await.is_err());
+    }
+}
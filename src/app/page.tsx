@@ -485,7 +485,6 @@ function HomePageContent() {
             open={isImportDialogOpen}
             onOpenChange={setIsImportDialogOpen}
             onImport={handleImport}
-            libraryRoot={libraryRoot}
           />
 
           <EditMetadataForm
@@ -31,11 +31,10 @@ interface ImportDialogProps {
   open: boolean;
   onOpenChange: (open: boolean) => void;
   onImport: (options: ImportOptions) => Promise<void>;
-  libraryRoot: string;
 }
 
 
-export function ImportDialog({ open, onOpenChange, onImport, libraryRoot }: ImportDialogProps) {
+export function ImportDialog({ open, onOpenChange, onImport }: ImportDialogProps) {
   const [sourcePath, setSourcePath] = useState('');
   const [filmStock, setFilmStock] = useState('Kodak Portra 400');
   const [camera, setCamera] = useState('Canon AE-1');
@@ -128,7 +127,6 @@ export function ImportDialog({ open, onOpenChange, onImport, libraryRoot }: Impo
         camera: camera,
         lens: lens || undefined,
         shoot_date: shootDate,
-        library_root: libraryRoot,
         roll_name: rollName || undefined,
         notes: notes || undefined,
         copy_mode: copyMode,
@@ -7,20 +7,54 @@ import type {
   ImportResult,
   UpdateRollRequest,
   AppConfig,
+  LibraryRootCheck,
+  LibraryInfo,
+  CreateLibraryRequest,
+  BackupInfo,
+  MoveLibraryRequest,
+  MoveLibraryResult,
+  LibraryMove,
   DeleteRollRequest,
   DeletePhotosRequest,
   AddPhotosOptions,
+  DuplicateFile,
+  NearDuplicateGroup,
+  ProposedRoll,
+  BatchImportOptions,
+  BatchImportResult,
+  RenameRollFilesRequest,
+  RenameRollFilesResult,
+  ReorderPhotosRequest,
+  ReorderPhotosResult,
 } from '@/types/roll';
 import type {
   ExifData,
   ExifWriteResult,
   WriteRollExifRequest,
   WritePhotoExifRequest,
+  MetadataWriteMode,
+  DriftReport,
+  DriftResolution,
+  ResolveDriftResult,
 } from '@/types/exif';
 import type {
   FilmPreset,
   NewFilmPreset,
 } from '@/types/film-preset';
+import type {
+  ShotLog,
+  UpdateShotLogsRequest,
+  ApplyShotLogFieldsRequest,
+  ShotLogUpdateResult,
+  LogbookMatchMode,
+  LogbookMatchPlan,
+  ApplyLogbookImportRequest,
+} from '@/types/shot-log';
+import type {
+  IntegrityReport,
+  IntegrityFix,
+  RepairLibraryResult,
+} from '@/types/integrity';
 
 /**
  * Get all rolls from database
@@ -71,6 +105,29 @@ export async function previewImportCount(sourcePath: string): Promise<number> {
   return await invoke<number>('preview_import_count', { sourcePath });
 }
 
+/**
+ * Walk a delivery tree and propose one roll per folder that contains images
+ */
+export async function previewBatchImport(sourcePath: string): Promise<ProposedRoll[]> {
+  return await invoke<ProposedRoll[]>('preview_batch_import', { sourcePath });
+}
+
+/**
+ * Import reviewed roll proposals; folders that fail are reported and the rest still imported
+ */
+export async function importBatch(options: BatchImportOptions): Promise<BatchImportResult> {
+  return await invoke<BatchImportResult>('import_batch', { options });
+}
+
+/**
+ * Find images in a source folder that are already in the library
+ * Matches by content hash, so photos whose original has had EXIF written into it are not found
+ * For a .zip/.tar source, archiveFolder limits the check to images directly in that folder
+ */
+export async function findImportDuplicates(sourcePath: string, archiveFolder?: string): Promise<DuplicateFile[]> {
+  return await invoke<DuplicateFile[]>('find_import_duplicates', { sourcePath, archiveFolder });
+}
+
 /**
  * Add photos to an existing roll
  */
@@ -78,6 +135,14 @@ export async function addPhotosToRoll(options: AddPhotosOptions): Promise<Import
   return await invoke<ImportResult>('add_photos_to_roll', { options });
 }
 
+/**
+ * Cancel a running import; files copied so far (and a newly created roll) are removed
+ * Returns false if the job is no longer running
+ */
+export async function cancelImportJob(jobId: number): Promise<boolean> {
+  return await invoke<boolean>('cancel_import_job', { jobId });
+}
+
 /**
  * Set a photo as roll cover
  */
@@ -142,6 +207,157 @@ export async function updateLibraryRoot(path: string): Promise<boolean> {
   return await invoke<boolean>('update_library_root', { path });
 }
 
+/**
+ * Check whether the library's rolls are under a folder (nothing is changed)
+ */
+export async function checkLibraryRoot(path: string): Promise<LibraryRootCheck> {
+  return await invoke<LibraryRootCheck>('check_library_root_command', { path });
+}
+
+/**
+ * Point the library at its new location; switches only when every roll is found
+ */
+export async function relocateLibrary(path: string): Promise<LibraryRootCheck> {
+  return await invoke<LibraryRootCheck>('relocate_library_command', { path });
+}
+
+/**
+ * Get the recently used libraries, most recent first
+ */
+export async function listLibraries(): Promise<LibraryInfo[]> {
+  return await invoke<LibraryInfo[]>('list_libraries_command');
+}
+
+/**
+ * Open an existing library database and switch to it
+ * Fails while imports are running; the app is told through a `library-switched` event
+ */
+export async function openLibrary(dbPath: string): Promise<LibraryInfo> {
+  return await invoke<LibraryInfo>('open_library_command', { dbPath });
+}
+
+/**
+ * Create a new library and switch to it
+ */
+export async function createLibrary(request: CreateLibraryRequest): Promise<LibraryInfo> {
+  return await invoke<LibraryInfo>('create_library_command', { request });
+}
+
+/**
+ * Remove a library from the recent list (its files are kept)
+ */
+export async function forgetLibrary(dbPath: string): Promise<boolean> {
+  return await invoke<boolean>('forget_library_command', { dbPath });
+}
+
+/**
+ * Move (or copy) every roll folder to a new library folder, verifying each file
+ * Resumes the unfinished move to the same folder; progress comes as `library-move-progress` events
+ */
+export async function moveLibrary(request: MoveLibraryRequest): Promise<MoveLibraryResult> {
+  return await invoke<MoveLibraryResult>('move_library_command', { request });
+}
+
+/**
+ * Get the library move that stopped before finishing, if any
+ */
+export async function getUnfinishedLibraryMove(): Promise<LibraryMove | null> {
+  return await invoke<LibraryMove | null>('get_unfinished_library_move_command');
+}
+
+/**
+ * Give up an unfinished library move and remove the copies made so far
+ */
+export async function abandonLibraryMove(moveId: number): Promise<boolean> {
+  return await invoke<boolean>('abandon_library_move_command', { moveId });
+}
+
+/**
+ * Update the filename template for imported originals
+ * Must contain {frame} or {original_name}
+ */
+export async function updateFilenameTemplate(template: string): Promise<boolean> {
+  return await invoke<boolean>('update_filename_template', { template });
+}
+
+/**
+ * Update the regexes used to parse frame numbers from scan filenames
+ */
+export async function updateFramePatterns(patterns: string[]): Promise<boolean> {
+  return await invoke<boolean>('update_frame_patterns', { patterns });
+}
+
+/**
+ * Update how often the database is backed up (0 = off) and how many automatic backups are kept
+ */
+export async function updateBackupSettings(intervalHours: number, keep: number): Promise<boolean> {
+  return await invoke<boolean>('update_backup_settings', { intervalHours, keep });
+}
+
+/**
+ * Choose whether metadata is written into the originals or into XMP sidecars
+ */
+export async function updateMetadataWriteMode(mode: MetadataWriteMode): Promise<boolean> {
+  return await invoke<boolean>('update_metadata_write_mode', { mode });
+}
+
+/**
+ * Choose whether roll EXIF writes are read back and compared with the database
+ */
+export async function updateVerifyExifWrites(enabled: boolean): Promise<boolean> {
+  return await invoke<boolean>('update_verify_exif_writes', { enabled });
+}
+
+/**
+ * Write a consistent backup of the open database to a chosen file
+ */
+export async function backupDatabase(targetPath: string): Promise<BackupInfo> {
+  return await invoke<BackupInfo>('backup_database_command', { targetPath });
+}
+
+/**
+ * List the automatic backups of the open database, newest first
+ */
+export async function listBackups(): Promise<BackupInfo[]> {
+  return await invoke<BackupInfo[]>('list_backups_command');
+}
+
+/**
+ * Check that a backup can be restored (schema version and integrity) without restoring it
+ */
+export async function inspectBackup(backupPath: string): Promise<BackupInfo> {
+  return await invoke<BackupInfo>('inspect_backup_command', { backupPath });
+}
+
+/**
+ * Replace the open database with a backup; the current database is backed up first
+ * The app is told through a `database-restored` event
+ */
+export async function restoreDatabase(backupPath: string): Promise<BackupInfo> {
+  return await invoke<BackupInfo>('restore_database_command', { backupPath });
+}
+
+/**
+ * Parse frame numbers again for a roll's photos; returns how many got one
+ */
+export async function reparseFrameNumbers(rollId: number): Promise<number> {
+  return await invoke<number>('reparse_frame_numbers_command', { rollId });
+}
+
+/**
+ * Rename a roll's originals, thumbnails and previews with a filename template
+ */
+export async function renameRollFiles(request: RenameRollFilesRequest): Promise<RenameRollFilesResult> {
+  return await invoke<RenameRollFilesResult>('rename_roll_files_command', { request });
+}
+
+/**
+ * Put a roll's photos in a new order, optionally renumbering the files
+ */
+export async function reorderRollPhotos(request: ReorderPhotosRequest): Promise<ReorderPhotosResult> {
+  return await invoke<ReorderPhotosResult>('reorder_roll_photos_command', { request });
+}
+
 /**
  * Delete a single photo
  */
@@ -159,6 +375,14 @@ export async function deletePhotos(request: DeletePhotosRequest): Promise<number
   return await invoke<number>('delete_photos_command', { request });
 }
 
+/**
+ * Find groups of visually near-identical photos across the library
+ * @param threshold - Max Hamming distance between perceptual hashes (default 8 of 64 bits)
+ */
+export async function findNearDuplicatePhotos(threshold?: number): Promise<NearDuplicateGroup[]> {
+  return await invoke<NearDuplicateGroup[]>('find_near_duplicate_photos_command', { threshold });
+}
+
 /**
  * Toggle photo favorite status
  */
@@ -198,6 +422,7 @@ export async function checkExifToolAvailable(): Promise<boolean> {
 /**
  * Write roll-level EXIF to all photos in a roll
  * Writes Make, Model, DateTimeOriginal, and UserComment (film stock + location + notes)
+ * Photos whose metadata is unchanged since the last write are skipped unless `force` is set
  */
 export async function writeRollExif(request: WriteRollExifRequest): Promise<ExifWriteResult> {
   return await invoke<ExifWriteResult>('write_roll_exif_command', { request });
@@ -216,7 +441,7 @@ export async function writePhotoExif(request: WritePhotoExifRequest): Promise<bo
 
 /**
  * Clear EXIF from a single photo
- * Removes all EXIF metadata from a photo file
+ * Removes all EXIF metadata from a photo file, or only its XMP sidecar in sidecar mode
  */
 export async function clearPhotoExif(photoId: number): Promise<boolean> {
   return await invoke<boolean>('clear_photo_exif_command', { photoId });
@@ -232,12 +457,28 @@ export async function clearRollExif(rollId: number): Promise<ExifWriteResult> {
 
 /**
  * Read EXIF from a single photo file
- * Extracts all EXIF metadata from photo
+ * Extracts all EXIF metadata from photo; fields in its XMP sidecar win over embedded ones
  */
 export async function readPhotoExif(photoId: number): Promise<ExifData> {
   return await invoke<ExifData>('read_photo_exif_command', { photoId });
 }
 
+/**
+ * Compare the metadata in the files with the database
+ * Scans one roll, or the whole library when rollId is null; photos never written are skipped unless includeUnwritten
+ */
+export async function scanMetadataDrift(rollId: number | null, includeUnwritten = false): Promise<DriftReport> {
+  return await invoke<DriftReport>('scan_metadata_drift_command', { rollId, includeUnwritten });
+}
+
+/**
+ * Resolve drifted fields: db_wins writes the database value to the file, file_wins takes the file value into the photo,
+ * apply_to_roll takes it into the roll (changing every photo of the roll)
+ */
+export async function resolveMetadataDrift(resolutions: DriftResolution[]): Promise<ResolveDriftResult> {
+  return await invoke<ResolveDriftResult>('resolve_metadata_drift_command', { resolutions });
+}
+
 // ==================== Location Functions ====================
 
 /**
@@ -357,3 +598,59 @@ export async function uploadPresetImage(sourcePath: string): Promise<string> {
 export async function readImageAsBase64(path: string): Promise<string> {
   return await invoke<string>('read_image_as_base64', { path });
 }
+
+// ==================== Shot Log Functions ====================
+
+/**
+ * Get shot logs for all photos in a roll
+ */
+export async function getShotLogsByRoll(rollId: number): Promise<ShotLog[]> {
+  return await invoke<ShotLog[]>('get_shot_logs_by_roll_command', { rollId });
+}
+
+/**
+ * Save complete shot logs for one or more photos
+ * Optionally writes FNumber, ExposureTime, FocalLength, ExposureCompensation, ... to EXIF
+ */
+export async function updateShotLogs(request: UpdateShotLogsRequest): Promise<ShotLogUpdateResult> {
+  return await invoke<ShotLogUpdateResult>('update_shot_logs_command', { request });
+}
+
+/**
+ * Set the same shot log fields on many photos
+ */
+export async function applyShotLogFields(request: ApplyShotLogFieldsRequest): Promise<ShotLogUpdateResult> {
+  return await invoke<ShotLogUpdateResult>('apply_shot_log_fields_command', { request });
+}
+
+/**
+ * Parse a logbook export and propose which entry belongs to which photo (nothing is saved)
+ */
+export async function previewLogbookImport(
+  rollId: number,
+  logbookPath: string,
+  mode: LogbookMatchMode
+): Promise<LogbookMatchPlan> {
+  return await invoke<LogbookMatchPlan>('preview_logbook_import_command', { rollId, logbookPath, mode });
+}
+
+/**
+ * Save a reviewed logbook match plan as shot logs (and photo locations)
+ */
+export async function applyLogbookImport(request: ApplyLogbookImportRequest): Promise<ShotLogUpdateResult> {
+  return await invoke<ShotLogUpdateResult>('apply_logbook_import_command', { request });
+}
+
+/**
+ * Compare the database with the files in the library
+ */
+export async function checkLibraryIntegrity(): Promise<IntegrityReport> {
+  return await invoke<IntegrityReport>('check_library_integrity_command');
+}
+
+/**
+ * Apply fixes from an integrity report; failed fixes are listed in the result
+ */
+export async function repairLibrary(fixes: IntegrityFix[]): Promise<RepairLibraryResult> {
+  return await invoke<RepairLibraryResult>('repair_library_command', { fixes });
+}
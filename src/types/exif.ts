@@ -2,9 +2,24 @@
  * FilmVault EXIF Types
  *
  * Type definitions for EXIF metadata operations
- * Simplified version: only Make, Model, DateTimeOriginal, and UserComment
+ * Roll writes cover camera, lens, date, rating, GPS, location, description and keywords
+ * Film details are written as discrete fields in the FilmVault XMP namespace (XMP-filmvault)
  */
 
+/**
+ * Film details of the roll, read from the FilmVault XMP namespace
+ */
+export interface FilmMetadata {
+  film_stock?: string;
+  iso?: number;               // Box speed
+  format?: string;
+  process?: string;
+  push_pull?: number;         // Stops pushed (+) or pulled (-)
+  lab?: string;
+  scanner?: string;
+  roll_name?: string;
+}
+
 /**
  * EXIF data structure for reading EXIF from photos
  */
@@ -18,27 +33,37 @@ export interface ExifData {
 
   // Photo-level fields
   user_comment?: string;      // User comment (notes)
+  gps_latitude?: number;      // Signed decimal degrees (photo location, falls back to roll)
+  gps_longitude?: number;
+  rating?: number;            // XMP:Rating
+  description?: string;       // ImageDescription / XMP:Description
+  gps_city?: string;          // IPTC/XMP city
+  gps_country?: string;       // IPTC/XMP country
 
-  // Legacy fields (kept for reading from existing photos, but not written)
+  // Shot settings (written from the shot log)
   iso?: number;
   aperture?: string;
   shutter_speed?: string;
   focal_length?: string;
-  gps_latitude?: number;
-  gps_longitude?: number;
   gps_altitude?: number;
-  rating?: number;
-  description?: string;
-  gps_city?: string;
-  gps_country?: string;
+  keywords?: string[];
+  film?: FilmMetadata;        // FilmVault XMP fields (film_stock above falls back to the UserComment)
 }
 
+/**
+ * Where metadata writes go
+ * - embedded: into the original files
+ * - sidecar: into .xmp files next to the originals, which are never modified
+ */
+export type MetadataWriteMode = 'embedded' | 'sidecar';
+
 /**
  * Result of EXIF write operation
  * Contains statistics about the write operation
  */
 export interface ExifWriteResult {
   success_count: number;      // Number of successfully written files
+  skipped_count: number;      // Files already up to date (unchanged metadata hash)
   failed_count: number;       // Number of failed files
   failed_files: string[];     // List of failed file paths with error messages
 }
@@ -49,6 +74,7 @@ export interface ExifWriteResult {
 export interface WriteRollExifRequest {
   roll_id: number;            // Roll ID to write EXIF for
   auto_write: boolean;        // Whether to automatically write EXIF
+  force?: boolean;            // Rewrite files even when their metadata is unchanged
 }
 
 /**
@@ -62,3 +88,74 @@ export interface WritePhotoExifRequest {
   photo_id: number;           // Photo ID to write EXIF for
   user_comment?: string;      // User comment (notes) to write
 }
+
+/**
+ * A metadata field compared between the database and the file
+ * Roll fields: camera, lens, shoot_date and the film fields; the rest belong to the photo
+ */
+export type DriftField =
+  | 'camera'
+  | 'lens'
+  | 'shoot_date'
+  | 'rating'
+  | 'description'
+  | 'location'
+  | 'city'
+  | 'country'
+  | 'film_stock'
+  | 'film_iso'
+  | 'film_format'
+  | 'film_process'
+  | 'push_pull'
+  | 'lab'
+  | 'scanner'
+  | 'roll_name';
+
+/**
+ * One field whose file value differs from the database
+ */
+export interface FieldDrift {
+  field: DriftField;
+  db_value?: string;          // What a roll write puts in the file
+  file_value?: string;        // What ExifTool reads from the file
+}
+
+/**
+ * The drifted fields of one photo
+ */
+export interface PhotoDrift {
+  photo_id: number;
+  roll_id: number;
+  file_path: string;
+  fields: FieldDrift[];
+  roll_photo_count: number;   // Photos that taking a roll field from this file would change
+}
+
+/**
+ * Result of a metadata drift scan
+ */
+export interface DriftReport {
+  photos_checked: number;
+  drifted: PhotoDrift[];
+  errors: string[];           // Files that could not be read
+}
+
+/**
+ * Which side a drifted field is resolved to
+ * - db_wins: the database value is written to the file again
+ * - file_wins: the file value is taken into the photo (photo fields only)
+ * - apply_to_roll: the file value becomes the roll's, for every photo of the roll (roll fields only)
+ */
+export type DriftWinner = 'db_wins' | 'file_wins' | 'apply_to_roll';
+
+export interface DriftResolution {
+  photo_id: number;
+  field: DriftField;
+  winner: DriftWinner;
+}
+
+export interface ResolveDriftResult {
+  resolved_count: number;     // Fields that match once compared again
+  roll_photos_changed: number; // Photos of rolls that took a file value; write the roll to update their files
+  errors: string[];           // One message per photo that failed or still differs
+}
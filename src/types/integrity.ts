@@ -0,0 +1,40 @@
+/**
+ * FilmVault Library Integrity Types
+ *
+ * Mismatches between the database and the files in the library,
+ * and the fixes that can be applied to them.
+ */
+
+export type DerivativeKind = 'thumbnail' | 'preview';
+
+/**
+ * One mismatch found by the integrity check
+ */
+export type IntegrityIssue =
+  | { kind: 'missing_roll_folder'; roll_id: number; path: string }
+  | { kind: 'missing_original'; photo_id: number; roll_id: number; path: string; candidate: string | null } // candidate: unlinked file it probably moved to
+  | { kind: 'missing_derivative'; photo_id: number; roll_id: number; derivative: DerivativeKind; path: string | null }
+  | { kind: 'stale_derivative'; photo_id: number; roll_id: number; derivative: DerivativeKind; path: string } // Older than its original
+  | { kind: 'orphan_file'; roll_id: number; path: string } // Image in a roll folder without a photo
+  | { kind: 'orphan_folder'; path: string }; // Roll folder no roll points to
+
+export interface IntegrityReport {
+  rolls_checked: number;
+  photos_checked: number;
+  issues: IntegrityIssue[];
+}
+
+/**
+ * A fix for an integrity issue
+ */
+export type IntegrityFix =
+  | { action: 'regenerate_derivatives'; photo_id: number }
+  | { action: 'relink'; photo_id: number; path: string }
+  | { action: 'adopt'; roll_id: number; path: string }
+  | { action: 'remove_photo'; photo_id: number }
+  | { action: 'remove_roll'; roll_id: number };
+
+export interface RepairLibraryResult {
+  fixed_count: number;
+  errors: string[]; // One message per fix that failed
+}
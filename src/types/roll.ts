@@ -1,3 +1,5 @@
+import type { MetadataWriteMode } from './exif';
+
 export interface Roll {
   id: number;
   name: string;
@@ -12,6 +14,11 @@ export interface Roll {
   country?: string;
   lat?: number;
   lon?: number;
+  film_iso?: number;      // Box speed
+  film_format?: string;   // "35mm", "120", ...
+  film_process?: string;  // "C-41", "E-6", ...
+  push_pull?: number;     // Stops pushed (+) or pulled (-)
+  scanner?: string;
   created_at: string;
   updated_at: string;
 }
@@ -38,6 +45,13 @@ export interface Photo {
   // User-editable metadata (stored in database)
   exif_user_comment?: string;
   exif_description?: string;
+  // BLAKE3 hash of the original as delivered (duplicate detection)
+  content_hash?: string;
+  // dHash of the thumbnail (near-duplicate detection)
+  perceptual_hash?: string;
+  original_filename?: string; // Filename as delivered by the lab/scanner
+  frame_number?: string; // Frame parsed from original_filename ("12", "12A")
+  sequence?: number; // 1-based position in the roll; photos are listed in this order
 }
 
 export interface RollWithPhotos {
@@ -52,21 +66,229 @@ export interface ImportOptions {
   camera: string;
   lens?: string;
   shoot_date: string;
-  library_root: string;
   roll_name?: string;
   notes?: string;
   copy_mode: boolean; // true = copy, false = move
   auto_write_exif?: boolean; // Whether to write EXIF to photos on import
+  duplicate_mode?: DuplicateMode; // Defaults to 'skip'
+  archive_folder?: string; // When source_path is a .zip/.tar archive: only import images directly in this folder
+  replace_roll_ids?: number[]; // Rolls whose photos 'replace' may remove; duplicates in other rolls are skipped
+}
+
+/** A roll proposed for one folder of a delivery tree; edit before importing */
+export interface ProposedRoll {
+  source_path: string; // Folder, or the archive for rolls inside a .zip/.tar delivery
+  archive_folder?: string;
+  roll_name: string;
+  image_count: number;
+  film_stock: string;
+  camera: string;
+  lens?: string;
+  shoot_date: string; // Defaults to the folder's modification date
+  notes?: string;
+}
+
+export interface BatchImportOptions {
+  rolls: ProposedRoll[];
+  copy_mode: boolean; // true = copy, false = move
+  auto_write_exif?: boolean;
+  duplicate_mode?: DuplicateMode;
+  replace_roll_ids?: number[]; // Rolls whose photos 'replace' may remove; duplicates in other rolls are skipped
+}
+
+export interface BatchImportError {
+  source_path: string;
+  roll_name: string;
+  error: string;
+}
+
+export interface BatchImportResult {
+  imported: ImportResult[];
+  failed: BatchImportError[];
+}
+
+/**
+ * How incoming files that are already in the library are handled
+ * - skip: don't import them
+ * - replace: import them and remove the existing library copy, only in rolls confirmed in replace_roll_ids
+ * - import_anyway: import them as new photos
+ */
+export type DuplicateMode = 'skip' | 'replace' | 'import_anyway';
+
+/**
+ * An incoming file whose content is already in the library
+ */
+export interface DuplicateFile {
+  source_path: string;
+  content_hash: string;
+  photo_id?: number | null; // null when the file repeats another file of the same import
+  roll_id?: number | null;
+  existing_path: string;
 }
 
 export interface ImportResult {
+  job_id: number;
   roll_id: number;
   photos_count: number;
   message: string;
+  duplicates: DuplicateFile[];
+  skipped_count: number;
+  replaced_count: number;
+  replaced_roll_ids: number[]; // Rolls that lost photos to 'replace'
+  files: ImportFileReport[];
+}
+
+/** Where an incoming file ended up; library_path is null when the file is (back) at source_path */
+export interface ImportFileReport {
+  source_path: string;
+  library_path: string | null;
+  error: string | null;
+}
+
+/** Phase of an import job, as reported by `import-progress` events */
+export type ImportJobState =
+  | 'pending'
+  | 'copying'
+  | 'generating_derivatives'
+  | 'writing_db'
+  | 'writing_exif'
+  | 'done'
+  | 'cancelled'
+  | 'failed';
+
+/** Payload of the `import-progress` event */
+export interface ImportProgress {
+  jobId: number;
+  phase: ImportJobState;
+  current: number;
+  total: number;
+  filename: string;
+  rollId: number;
 }
 
 export interface AppConfig {
   library_root: string;
+  /** Tokens: {date} {film} {camera} {roll_id} {roll_name} {frame} {original_name} */
+  filename_template: string;
+  /** Regexes with a capture group for the frame, tried before the built-in scanner patterns */
+  frame_patterns: string[];
+  /** Hours between scheduled database backups, 0 = off */
+  backup_interval_hours: number;
+  /** Number of automatic backups kept */
+  backup_keep: number;
+  /** Whether metadata is written into the originals or into XMP sidecars */
+  metadata_write_mode: MetadataWriteMode;
+  /** Whether roll EXIF writes are read back and compared with the database */
+  verify_exif_writes: boolean;
+}
+
+/**
+ * A database backup that passed validation
+ */
+export interface BackupInfo {
+  path: string;
+  schema_version: number;
+  roll_count: number;
+  photo_count: number;
+  size: number;
+  modified_at?: string;
+}
+
+/**
+ * Whether the library's rolls were found under a library root
+ */
+export interface LibraryRootCheck {
+  library_root: string;
+  rolls_found: number;
+  missing_rolls: string[]; // Roll folders (relative to the root) that are not there
+  switched: boolean; // Relocate only switches when no roll is missing
+}
+
+/**
+ * A recently used library: a database plus the library folder it points to
+ */
+export interface LibraryInfo {
+  name: string;
+  db_path: string;
+  last_opened: string;
+  is_current: boolean;
+  available: boolean; // false when the database file cannot be found (e.g. drive not mounted)
+}
+
+export interface CreateLibraryRequest {
+  name: string;
+  library_root: string;
+  db_in_root: boolean; // true = keep the database inside the library folder so it travels with the drive
+}
+
+/**
+ * Payload of the `library-switched` event
+ */
+export interface LibrarySwitched {
+  name: string;
+  dbPath: string;
+  libraryRoot: string;
+}
+
+export interface MoveLibraryRequest {
+  target_root: string;
+  copy_mode: boolean; // true = keep the old folders, false = remove them once the library is switched
+}
+
+export interface MoveLibraryResult {
+  move_id: number;
+  files_copied: number;
+  bytes_copied: number;
+  missing_rolls: string[]; // Roll folders that did not exist and were not moved
+}
+
+export type LibraryMoveState = 'copying' | 'switched' | 'done' | 'cancelled' | 'failed';
+
+/**
+ * A library move that can be resumed (or abandoned while still copying)
+ */
+export interface LibraryMove {
+  id: number;
+  source_root: string;
+  target_root: string;
+  copy_mode: boolean;
+  state: LibraryMoveState;
+  error?: string;
+  created_at: string;
+  updated_at: string;
+}
+
+/**
+ * Payload of the `library-move-progress` event, sent after each verified file
+ */
+export interface LibraryMoveProgress {
+  moveId: number;
+  filesDone: number;
+  filesTotal: number;
+  bytesDone: number;
+  bytesTotal: number;
+  path: string;
+}
+
+export interface RenameRollFilesRequest {
+  roll_id: number;
+  template?: string; // Defaults to the configured filename template
+}
+
+export interface RenameRollFilesResult {
+  renamed_count: number;
+  unchanged_count: number;
+}
+
+export interface ReorderPhotosRequest {
+  roll_id: number;
+  photo_ids: number[]; // Every photo of the roll, in the new order
+  renumber_files: boolean; // Also rename the files so frame numbers follow the new order
+}
+
+export interface ReorderPhotosResult {
+  photos: Photo[];
+  renamed_count: number;
 }
 
 export interface UpdateRollRequest {
@@ -82,6 +304,11 @@ export interface UpdateRollRequest {
   country?: string;
   lat?: number;
   lon?: number;
+  film_iso?: number;
+  film_format?: string;
+  film_process?: string;
+  push_pull?: number;
+  scanner?: string;
 }
 
 export interface DeleteRollRequest {
@@ -95,11 +322,21 @@ export interface DeletePhotosRequest {
   delete_files: boolean;
 }
 
+/**
+ * Visually near-identical photos, possibly from different rolls
+ */
+export interface NearDuplicateGroup {
+  photos: Photo[];
+  max_distance: number; // Largest Hamming distance (of 64 bits) within the group
+}
+
 export interface AddPhotosOptions {
   roll_id: number;
   source_path: string;
   copy_mode: boolean;
   auto_write_exif?: boolean;
+  duplicate_mode?: DuplicateMode;
+  replace_roll_ids?: number[]; // Other rolls whose photos 'replace' may remove; this roll always may
 }
 
 // Film stock color mappings
@@ -0,0 +1,99 @@
+/**
+ * FilmVault Shot Log Types
+ *
+ * Per-frame shooting settings noted while shooting film.
+ * Stored per photo and written to EXIF on demand.
+ */
+
+import type { ExifWriteResult } from './exif';
+
+/**
+ * Shot log for a single photo
+ */
+export interface ShotLog {
+  photo_id: number;
+  frame_number?: number | null;          // The photo's frame number (read-only, not saved with the log)
+  aperture?: number | null;              // f-number (e.g., 2.8)
+  shutter_speed?: string | null;         // e.g., "1/125" or "2"
+  focal_length?: number | null;          // Millimetres
+  exposure_compensation?: number | null; // EV (e.g., -0.7)
+  metering_mode?: string | null;         // e.g., "spot", "center-weighted", "matrix"
+  flash?: boolean | null;
+  notes?: string | null;
+  shot_at?: string | null;               // Capture time, "YYYY-MM-DD HH:MM:SS"
+}
+
+/**
+ * Fields to set on many photos at once; omitted fields are left unchanged, null clears a field
+ */
+export type ShotLogPatch = Omit<ShotLog, 'photo_id' | 'frame_number'>;
+
+/**
+ * Request structure for saving complete shot logs
+ */
+export interface UpdateShotLogsRequest {
+  logs: ShotLog[];
+  write_exif?: boolean;       // Also write the logs to the photo files
+}
+
+/**
+ * Request structure for applying the same fields to many photos
+ */
+export interface ApplyShotLogFieldsRequest {
+  photo_ids: number[];
+  fields: ShotLogPatch;
+  write_exif?: boolean;
+}
+
+/**
+ * Result of a shot log update
+ */
+export interface ShotLogUpdateResult {
+  updated_count: number;
+  exif?: ExifWriteResult | null;  // Present when write_exif was requested
+}
+
+/**
+ * One exposure read from a logbook app export (CSV or JSON)
+ */
+export interface LogbookEntry extends ShotLogPatch {
+  index: number;          // Position in the logbook file
+  frame_number?: number | null; // Also set on matched photos that have no frame number
+  lat?: number | null;
+  lon?: number | null;
+}
+
+/**
+ * How logbook entries are paired with photos
+ * - frame_order: n-th entry (by frame number) to the n-th photo of the roll
+ * - filename_sequence: entry frame number matches the photo's frame number,
+ *   or the _NNN sequence in the filename when the photo has none
+ */
+export type LogbookMatchMode = 'frame_order' | 'filename_sequence';
+
+/**
+ * A proposed pairing of a logbook entry with a photo
+ */
+export interface LogbookMatch {
+  photo_id: number;
+  filename: string;
+  entry: LogbookEntry;
+}
+
+/**
+ * Reviewable result of matching a logbook against a roll
+ */
+export interface LogbookMatchPlan {
+  matches: LogbookMatch[];
+  unmatched_entries: LogbookEntry[];
+  unmatched_photo_ids: number[];
+}
+
+/**
+ * Request structure for applying a reviewed match plan
+ */
+export interface ApplyLogbookImportRequest {
+  roll_id: number;
+  matches: LogbookMatch[];
+  write_exif?: boolean;
+}